@@ -0,0 +1,192 @@
+package handlers
+
+import (
+	"encoding/json"
+	"net/http"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"gorm.io/gorm"
+	"gorm.io/gorm/clause"
+)
+
+func RegisterArcRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/arcs/:id", auth.JWTMiddleware(), ListArcStates)
+	r.PUT("/arcs/:id", auth.JWTMiddleware(), SaveArcState)
+	r.DELETE("/arcs/:id/:fragment", auth.JWTMiddleware(), DeleteArcState)
+	return r
+}
+
+type ArcStateBuilder struct {
+	Fragment      uuid.UUID       `json:"fragment"`
+	Goals         *string         `json:"goals"`
+	Emotion       *string         `json:"emotion"`
+	Valence       *int16          `json:"valence"`
+	Relationships *string         `json:"relationships"`
+	Knowledge     *string         `json:"knowledge"`
+	Custom        json.RawMessage `json:"custom"`
+}
+
+type ArcStateRender struct {
+	Character     uuid.UUID       `json:"character"`
+	Fragment      uuid.UUID       `json:"fragment"`
+	Goals         *string         `json:"goals"`
+	Emotion       *string         `json:"emotion"`
+	Valence       *int16          `json:"valence"`
+	Relationships *string         `json:"relationships"`
+	Knowledge     *string         `json:"knowledge"`
+	Custom        json.RawMessage `json:"custom"`
+}
+
+func renderArcState(state model.CharacterArc) ArcStateRender {
+	return ArcStateRender{
+		Character:     state.Character,
+		Fragment:      state.Fragment,
+		Goals:         state.Goals,
+		Emotion:       state.Emotion,
+		Valence:       state.Valence,
+		Relationships: state.Relationships,
+		Knowledge:     state.Knowledge,
+		Custom:        json.RawMessage(state.Custom),
+	}
+}
+
+/// every annotated state of the character, in no particular order, the frontend orders them by
+/// the fragments of the story being walked
+func ListArcStates(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+
+	var states []model.CharacterArc
+	if err := db.DB.Where(`"character" = ?`, id).Find(&states).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	renders := make([]ArcStateRender, 0, len(states))
+	for _, state := range states {
+		renders = append(renders, renderArcState(state))
+	}
+	c.JSON(http.StatusOK, renders)
+}
+
+/// annotates the character's state at a fragment, replacing any earlier annotation
+func SaveArcState(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var builder ArcStateBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+	if len(builder.Custom) == 0 || string(builder.Custom) == "null" {
+		builder.Custom = json.RawMessage("{}")
+	}
+	if !json.Valid(builder.Custom) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "custom fields aren't valid json"})
+		return
+	}
+	if builder.Valence != nil && (*builder.Valence < -5 || *builder.Valence > 5) {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "valence must be between -5 and 5"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+	access, _, permerr = CheckUserEntityPermission(db.DB, user.ID, builder.Fragment, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to fragment denied"})
+		return
+	}
+
+	state := model.CharacterArc{
+		Character:     id,
+		Fragment:      builder.Fragment,
+		Goals:         builder.Goals,
+		Emotion:       builder.Emotion,
+		Valence:       builder.Valence,
+		Relationships: builder.Relationships,
+		Knowledge:     builder.Knowledge,
+		Custom:        string(builder.Custom),
+	}
+	err = db.DB.Clauses(clause.OnConflict{
+		Columns: []clause.Column{{Name: "character"}, {Name: "fragment"}},
+		DoUpdates: clause.Assignments(map[string]interface{}{
+			"goals":         state.Goals,
+			"emotion":       state.Emotion,
+			"valence":       state.Valence,
+			"relationships": state.Relationships,
+			"knowledge":     state.Knowledge,
+			"custom":        state.Custom,
+			"last_edited":   gorm.Expr("unix_now()"),
+		}),
+	}).Create(&state).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, renderArcState(state))
+}
+
+func DeleteArcState(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	fragment, err := uuid.Parse(c.Param("fragment"))
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "improperly formatted fragment UUID"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+
+	result := db.DB.Where(`"character" = ? AND fragment = ?`, id, fragment).Delete(&model.CharacterArc{})
+	if result.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": result.Error.Error()})
+		return
+	}
+	if result.RowsAffected == 0 {
+		c.JSON(http.StatusNotFound, gin.H{"error": "arc state not found"})
+		return
+	}
+
+	c.JSON(http.StatusOK, gin.H{"character": id, "fragment": fragment})
+}
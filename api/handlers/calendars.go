@@ -0,0 +1,125 @@
+package handlers
+
+import (
+	"encoding/json"
+	"net/http"
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"gorm.io/gorm"
+)
+
+func RegisterCalendarRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/calendars/", auth.JWTMiddleware(), ListCalendars)
+	r.GET("/calendars/:id", auth.JWTMiddleware(), GetCalendar)
+	r.POST("/calendars/", auth.JWTMiddleware(), CreateCalendar)
+	return r
+}
+
+/// the definition is validated by the frontend, the api only checks that it's json
+type CalendarBuilder struct {
+	Name        string          `json:"name"`
+	Description *string         `json:"description"`
+	Definition  json.RawMessage `json:"definition"`
+}
+
+type CalendarRender struct {
+	ID          uuid.UUID       `json:"id"`
+	Name        string          `json:"name"`
+	Description *string         `json:"description"`
+	Definition  json.RawMessage `json:"definition"`
+}
+
+func renderCalendar(calendar model.Calendar) CalendarRender {
+	return CalendarRender{
+		ID:          calendar.ID,
+		Name:        calendar.Name,
+		Description: calendar.Description,
+		Definition:  json.RawMessage(calendar.Definition),
+	}
+}
+
+func ListCalendars(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, uerr)
+		return
+	}
+
+	var calendars []model.Calendar
+	err := db.DB.
+		Where("entities.active = ? AND entities.group_id = ?", true, user.DefaultGroup).
+		Joins("JOIN entities ON entities.id = calendars.id").
+		Order("calendars.name ASC").
+		Find(&calendars).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	renders := make([]CalendarRender, 0, len(calendars))
+	for _, calendar := range calendars {
+		renders = append(renders, renderCalendar(calendar))
+	}
+	c.JSON(http.StatusOK, renders)
+}
+
+func GetCalendar(c *gin.Context) {
+	calendar, err := GetByCtxID[model.Calendar](db.DB, c, "calendars")
+	if err != nil {
+		return
+	}
+	c.JSON(http.StatusOK, renderCalendar(*calendar))
+}
+
+func CreateNewCalendar(tx *gorm.DB, builder CalendarBuilder, userID, groupID uuid.UUID) (model.Calendar, error) {
+	calendar := model.Calendar{
+		ID:          uuid.New(),
+		Name:        builder.Name,
+		Description: builder.Description,
+		Definition:  string(builder.Definition),
+	}
+	if err := tx.Create(&calendar).Error; err != nil {
+		return model.Calendar{}, err
+	}
+	if err := CreateNewEntity(tx, calendar.ID, userID, groupID); err != nil {
+		return model.Calendar{}, err
+	}
+	return calendar, nil
+}
+
+func CreateCalendar(c *gin.Context) {
+	user, err := auth.GetUserFromClaims(db.DB, c)
+	if err != nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "Unauthorized: " + err.Error()})
+		return
+	}
+
+	var builder CalendarBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil || !json.Valid(builder.Definition) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse calendar creation json"})
+		return
+	}
+
+	tx := db.DB.Begin()
+	if tx.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to create transaction"})
+		return
+	}
+
+	calendar, err := CreateNewCalendar(tx, builder, user.ID, user.DefaultGroup)
+	if err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to create calendar: " + err.Error()})
+		return
+	}
+
+	if err := tx.Commit().Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to commit transaction"})
+		return
+	}
+
+	c.JSON(http.StatusOK, renderCalendar(calendar))
+}
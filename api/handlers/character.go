@@ -18,7 +18,6 @@ func RegisterCharacterRoutes(r *gin.Engine) *gin.Engine {
 	r.GET("/characters/:id", auth.JWTMiddleware(), GetCharacter)
 	r.POST("/characters", auth.JWTMiddleware(), CreateCharacter)
 	r.GET("/characters/filter", auth.JWTMiddleware(), FilterCharacters)
-	r.GET("/characters/tree", auth.JWTMiddleware(), GetCharacterTree)
 	//r.PUT("/characters/:id", middleware.RequireOIDC(), UpdateCharacter)
 	r.DELETE("/characters/:id", auth.JWTMiddleware(), DeleteCharacter)
 	return r
@@ -103,6 +102,7 @@ func ListCharacters(c *gin.Context) {
 type CharacterBuilder struct {
 	Name        string  `json:"name"`
 	Description *string `json:"description"`
+	Gender      *string `json:"gender"`
 	Thumbnail   *ImageBuilder
 }
 
@@ -143,6 +143,9 @@ func CreateNewCharacter(tx *gorm.DB, builder *CharacterBuilder, userID, groupID
 		Created:     &now,
 		LastEdited:  &now,
 	}
+	if builder.Gender != nil {
+		character.Gender = *builder.Gender
+	}
 
 	dberr := tx.Create(&character).Error
 	if dberr != nil {
@@ -225,8 +228,38 @@ func UpdateCharacter(c *gin.Context) {
 	c.JSON(http.StatusOK, model.Character{})
 }
 
+/// deletes the character and everything that refers to it, GET /characters/:id/footprint
+/// previews what goes with it
 func DeleteCharacter(c *gin.Context) {
-	c.JSON(http.StatusOK, model.Character{})
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "delete")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+
+	var character model.Character
+	if err := db.DB.First(&character, "id = ?", id).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "character not found"})
+		return
+	}
+	if err := db.DB.Transaction(func(tx *gorm.DB) error {
+		return deleteCharacter(tx, id)
+	}); err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, character)
 }
 
 func FilterCharacters(c *gin.Context) {
@@ -278,68 +311,3 @@ func FilterCharacters(c *gin.Context) {
 
 	c.JSON(http.StatusOK, characterRenders)
 }
-
-func GetCharacterTree(c *gin.Context) {
-	parentID, iderr := uuid.Parse(c.Query("id"))
-	if iderr != nil {
-		c.JSON(http.StatusBadRequest, gin.H{"error": "couldn't parse ID query param"})
-		return
-	}
-	var visited = make(map[uuid.UUID]bool)
-	root, err := FetchCharacterTree(db.DB, parentID, visited)
-	if err != nil {
-		c.JSON(http.StatusInternalServerError, gin.H{"error": err})
-		return
-	}
-	c.JSON(http.StatusOK, root)
-}
-
-type CharacterNode struct {
-	ID          uuid.UUID       `json:"id"`
-	Name        string          `json:"name"`
-	Description *string         `json:"description"`
-	Children    []CharacterNode `json:"children"`
-}
-
-func FetchCharacterTree(db *gorm.DB, parentID uuid.UUID, visited map[uuid.UUID]bool) (CharacterNode, error) {
-	// Prevent cycles
-	if visited[parentID] {
-		return CharacterNode{}, nil
-	}
-	visited[parentID] = true
-
-	var character model.Character
-	err := db.First(&character, "id = ?", parentID).Error
-	if err != nil {
-		return CharacterNode{}, fmt.Errorf("error fetching character: %w", err)
-	}
-
-	node := CharacterNode{
-		ID:          character.ID,
-		Name:        character.Name,
-		Description: character.Description,
-		Children:    []CharacterNode{},
-	}
-
-	// Fetch child IDs
-	var childIDs []uuid.UUID
-	err = db.Model(&model.Relation{}).
-		Where("parent = ? AND child_category = ?", parentID, "characters").
-		Pluck("child", &childIDs).Error
-	if err != nil {
-		return node, fmt.Errorf("error fetching children: %w", err)
-	}
-
-	// Recursively build child nodes
-	for _, childID := range childIDs {
-		childNode, err := FetchCharacterTree(db, childID, visited)
-		if err != nil {
-			return node, err
-		}
-		if childNode.ID != uuid.Nil {
-			node.Children = append(node.Children, childNode)
-		}
-	}
-
-	return node, nil
-}
@@ -227,12 +227,21 @@ func CreateNewRelation(db *gorm.DB, relation *model.Relation) (*model.Relation,
 }
 
 func CreateRelation(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
 	var relation model.Relation
 
 	if err := c.ShouldBindJSON(&relation); err != nil {
 		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
 		return
 	}
+	if err := checkRelationType(db.DB, user.ID, relation.RelationType); err != nil {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "unknown relation type"})
+		return
+	}
 
     _, err := CreateNewRelation(db.DB, &relation)
     if err != nil {
@@ -18,6 +18,7 @@ type FragmentBuilder struct {
 	Content     string          `json:"content"`
 	Name		string			`json:"name"`
 	Tags		[]string		`json:"tags"`
+	Idx			*int32			`json:"idx"`
 }
 
 type FragmentUpdate struct {
@@ -30,6 +31,7 @@ type FragmentUpdate struct {
 type FragmentRender struct {
 	ID		uuid.UUID	`json:"id"`
 	Name	string		`json:"name"`
+	Idx		int32		`json:"idx"`
 	Description	*string	`json:"description"`
 	Content	string		`json:"content"`
 	Images	[]model.Image `json:"images"`
@@ -41,6 +43,7 @@ func RegisterFragmentRoutes(r *gin.Engine) *gin.Engine {
 	
 	r.GET("/fragments/filter", auth.JWTMiddleware(), GetFragmentsByEntity)
 	r.GET("/fragments/:id", auth.JWTMiddleware(), GetFragmentById)
+	r.GET("/fragments/:id/story", auth.JWTMiddleware(), GetFragmentStory)
 	r.POST("/fragments/", auth.JWTMiddleware(), CreateFragment)
 	r.GET("/fragments/", auth.JWTMiddleware(), GetFragments)
 	r.PUT("/fragments/", auth.JWTMiddleware(), EditFragment);
@@ -140,6 +143,7 @@ func selectFragmentsByEntity(db *gorm.DB, parentID uuid.UUID) ([]FragmentRender,
 		render := FragmentRender{
 			ID:          frag.ID,
 			Name:        frag.Name,
+			Idx:         frag.Idx,
 			Description: frag.Description,
 			Content:     frag.Content,
 			Images:      images,
@@ -163,6 +167,45 @@ func GetFragmentById(c *gin.Context) {
 	c.JSON(http.StatusOK, fragment)
 }
 
+/// the story a fragment belongs to, so the fragment can be rendered the way the story is
+func GetFragmentStory(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to fragment denied"})
+		return
+	}
+
+	var story model.Story
+	result := db.DB.Raw(`
+		SELECT stories.* FROM stories
+		JOIN relations ON relations.parent = stories.id AND relations.parent_category = 'stories'
+		JOIN entities ON entities.id = stories.id AND entities.active
+		WHERE relations.child = @fragment AND relations.child_category = 'fragments'
+			AND stories.id IN (`+permittedEntities+`)
+		ORDER BY stories.created
+		LIMIT 1`,
+		map[string]interface{}{"fragment": id, "user": user.ID, "permission": "read"}).Scan(&story)
+	if result.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": result.Error.Error()})
+		return
+	}
+	if result.RowsAffected == 0 {
+		c.JSON(http.StatusNotFound, gin.H{"error": "fragment isn't part of a story"})
+		return
+	}
+	c.JSON(http.StatusOK, story)
+}
+
 func CreateNewFragment(tx *gorm.DB, fragment *FragmentBuilder, userID, groupID uuid.UUID) (model.Fragment, error) {
 	now := time.Now().Unix()
 	fragmentid := uuid.New()
@@ -181,6 +224,9 @@ func CreateNewFragment(tx *gorm.DB, fragment *FragmentBuilder, userID, groupID u
 		LastEdited:	&now,
 		Created:	&now,
 	}
+	if fragment.Idx != nil {
+		newfragment.Idx = *fragment.Idx
+	}
 
 	if fragment.Name != "" {
 		fragmentdberr := tx.Create(&newfragment).Error
@@ -229,6 +229,21 @@ func CheckUserGroupPermission(db *gorm.DB, userID, groupID uuid.UUID, permission
 	return false, nil
 }
 
+// permittedEntities selects the ids of the entities a user holds a permission on, following the
+// same rules as CheckUserEntityPermission so lists can be filtered in a single query. it takes
+// the named arguments @user and @permission.
+const permittedEntities = `
+	SELECT entities.id FROM entities
+	JOIN group_closure gc ON gc.descendant_id = entities.group_id
+	JOIN group_rel gr ON gr.group_id = gc.ancestor_id AND gr.user_id = @user
+	JOIN group_permissions gp ON gp.group_id = entities.group_id AND gp.permission = @permission
+	UNION
+	SELECT eg.entity_id FROM entity_groups eg
+	JOIN group_rel gr ON gr.group_id = eg.group_id AND gr.user_id = @user
+	JOIN group_closure gc ON gc.ancestor_id = gr.group_id AND gc.descendant_id = eg.group_id
+	JOIN group_permissions gp ON gp.group_id = eg.group_id AND gp.permission = @permission
+`
+
 // CheckUserEntityPermission returns whether user has a specific permission on the entity
 // and which groups grant that access (primary or shared).
 func CheckUserEntityPermission(
@@ -0,0 +1,265 @@
+package handlers
+
+import (
+	"net/http"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+)
+
+func RegisterKinshipRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/kinship/family", auth.JWTMiddleware(), GetFamily)
+	r.POST("/kinship", auth.JWTMiddleware(), CreateKinship)
+	r.DELETE("/kinship/:id", auth.JWTMiddleware(), DeleteKinship)
+	return r
+}
+
+var kinshipKinds = map[string]bool{
+	"parent":          true,
+	"adoptive_parent": true,
+	"step_parent":     true,
+	"spouse":          false,
+	"partner":         false,
+	"sibling":         false,
+}
+
+type KinshipBuilder struct {
+	Character uuid.UUID `json:"character"`
+	Relative  uuid.UUID `json:"relative"`
+	Kind      string    `json:"kind"`
+	Begins    *int64    `json:"begins"`
+	Ends      *int64    `json:"ends"`
+}
+
+type FamilyMember struct {
+	ID          uuid.UUID `json:"id"`
+	Name        string    `json:"name"`
+	Description *string   `json:"description"`
+	Gender      string    `json:"gender"`
+}
+
+type Family struct {
+	Members []FamilyMember  `json:"members"`
+	Kinship []model.Kinship `json:"kinship"`
+}
+
+/// every character related to the given one through any chain of kinship the user can read,
+/// along with the kinship between them
+func GetFamily(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	root, iderr := uuid.Parse(c.Query("id"))
+	if iderr != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "couldn't parse ID query param"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, root, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+
+	members := map[uuid.UUID]bool{root: true}
+	order := []uuid.UUID{root}
+	frontier := []uuid.UUID{root}
+	seen := make(map[uuid.UUID]bool)
+	family := Family{Members: []FamilyMember{}, Kinship: []model.Kinship{}}
+	for len(frontier) > 0 {
+		var kinship []model.Kinship
+		err := db.DB.
+			Where(`"character" IN ? OR relative IN ?`, frontier, frontier).
+			Find(&kinship).Error
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+			return
+		}
+		frontier = nil
+		for _, kin := range kinship {
+			if seen[kin.ID] {
+				continue
+			}
+			allowed := true
+			for _, id := range []uuid.UUID{kin.Character, kin.Relative} {
+				if members[id] {
+					continue
+				}
+				access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+				if permerr != nil || !access {
+					allowed = false
+					break
+				}
+				members[id] = true
+				order = append(order, id)
+				frontier = append(frontier, id)
+			}
+			if allowed {
+				seen[kin.ID] = true
+				family.Kinship = append(family.Kinship, kin)
+			}
+		}
+	}
+
+	var characters []model.Character
+	if err := db.DB.Where("id IN ?", order).Find(&characters).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	byID := make(map[uuid.UUID]model.Character, len(characters))
+	for _, character := range characters {
+		byID[character.ID] = character
+	}
+	for _, id := range order {
+		if character, ok := byID[id]; ok {
+			family.Members = append(family.Members, FamilyMember{
+				ID:          character.ID,
+				Name:        character.Name,
+				Description: character.Description,
+				Gender:      character.Gender,
+			})
+		}
+	}
+
+	c.JSON(http.StatusOK, family)
+}
+
+/// whether ancestor is a parent of character, or a parent of one of its parents and so on
+func isAncestor(ancestor, character uuid.UUID) (bool, error) {
+	visited := map[uuid.UUID]bool{character: true}
+	frontier := []uuid.UUID{character}
+	for len(frontier) > 0 {
+		var parents []uuid.UUID
+		err := db.DB.Model(&model.Kinship{}).
+			Where("relative IN ? AND kind IN ?", frontier, []string{"parent", "adoptive_parent", "step_parent"}).
+			Pluck(`"character"`, &parents).Error
+		if err != nil {
+			return false, err
+		}
+		frontier = nil
+		for _, parent := range parents {
+			if parent == ancestor {
+				return true, nil
+			}
+			if !visited[parent] {
+				visited[parent] = true
+				frontier = append(frontier, parent)
+			}
+		}
+	}
+	return false, nil
+}
+
+func CreateKinship(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+
+	var builder KinshipBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+	directed, known := kinshipKinds[builder.Kind]
+	if !known {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "unknown kind of kinship: " + builder.Kind})
+		return
+	}
+	if builder.Character == builder.Relative {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a character can't be their own relative"})
+		return
+	}
+	if builder.Begins != nil && builder.Ends != nil && *builder.Ends < *builder.Begins {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "kinship can't end before it begins"})
+		return
+	}
+	for _, id := range []uuid.UUID{builder.Character, builder.Relative} {
+		access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+		if permerr != nil || !access {
+			c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+			return
+		}
+	}
+
+	// symmetric kinship is the same whichever way round it was given
+	query := db.DB.Where("kind = ?", builder.Kind)
+	if directed {
+		query = query.Where(`"character" = ? AND relative = ?`, builder.Character, builder.Relative)
+	} else {
+		query = query.Where(
+			`(("character" = ? AND relative = ?) OR ("character" = ? AND relative = ?))`,
+			builder.Character, builder.Relative, builder.Relative, builder.Character,
+		)
+	}
+	var existing []model.Kinship
+	if err := query.Limit(1).Find(&existing).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	if len(existing) > 0 {
+		c.JSON(http.StatusConflict, gin.H{"error": "the characters are already related this way"})
+		return
+	}
+	if directed {
+		cycle, err := isAncestor(builder.Relative, builder.Character)
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+			return
+		}
+		if cycle {
+			c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a character can't be a parent of their own ancestor"})
+			return
+		}
+	}
+
+	kinship := model.Kinship{
+		ID:        uuid.New(),
+		Character: builder.Character,
+		Relative:  builder.Relative,
+		Kind:      builder.Kind,
+		Begins:    builder.Begins,
+		Ends:      builder.Ends,
+	}
+	if err := db.DB.Create(&kinship).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, kinship)
+}
+
+func DeleteKinship(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var kinship model.Kinship
+	if err := db.DB.First(&kinship, "id = ?", id).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "kinship not found"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, kinship.Character, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+	if err := db.DB.Delete(&kinship).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, kinship)
+}
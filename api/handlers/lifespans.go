@@ -0,0 +1,104 @@
+package handlers
+
+import (
+	"net/http"
+	"github.com/gin-gonic/gin"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"github.com/vivianlazaras/storyteller/auth"
+	"gorm.io/gorm/clause"
+)
+
+func RegisterLifespanRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/lifespans", auth.JWTMiddleware(), ListLifespans)
+	r.GET("/lifespans/:id", auth.JWTMiddleware(), GetLifespan)
+	r.PUT("/lifespans", auth.JWTMiddleware(), SetLifespan)
+	return r
+}
+
+/// returns the lifespans of every active entity the user can read, the continuity checker needs
+/// all of them at once
+func ListLifespans(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, uerr)
+		return
+	}
+
+	var lifespans []model.Lifespan
+	err := db.DB.
+		Where("entities.active = ?", true).
+		Where("lifespans.entity IN ("+permittedEntities+")", map[string]interface{}{
+			"user":       user.ID,
+			"permission": "read",
+		}).
+		Joins("JOIN entities ON entities.id = lifespans.entity").
+		Find(&lifespans).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, lifespans)
+}
+
+/// an entity without a lifespan gets an open one rather than a 404
+func GetLifespan(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, uerr)
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to entity denied"})
+		return
+	}
+
+	var lifespans []model.Lifespan
+	if err := db.DB.Where("entity = ?", id).Limit(1).Find(&lifespans).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	if len(lifespans) == 0 {
+		c.JSON(http.StatusOK, model.Lifespan{Entity: id})
+		return
+	}
+	c.JSON(http.StatusOK, lifespans[0])
+}
+
+func SetLifespan(c *gin.Context) {
+	user, err := auth.GetUserFromClaims(db.DB, c)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Internal Server Error: " + err.Error()})
+		return
+	}
+
+	var lifespan model.Lifespan
+	if err := c.ShouldBindJSON(&lifespan); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, lifespan.Entity, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to entity denied"})
+		return
+	}
+
+	err = db.DB.Clauses(clause.OnConflict{
+		Columns:   []clause.Column{{Name: "entity"}},
+		DoUpdates: clause.AssignmentColumns([]string{"begins", "ends"}),
+	}).Create(&lifespan).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, lifespan)
+}
@@ -1,6 +1,7 @@
 package handlers
 
 import (
+	"encoding/json"
 	"net/http"
 	"time"
 	"fmt"
@@ -18,6 +19,12 @@ func RegisterLocationRoutes(r *gin.Engine) *gin.Engine {
 	r.GET("/locations/:id", auth.JWTMiddleware(), GetLocation)
 	r.POST("/locations/", auth.JWTMiddleware(), CreateLocation)
 	r.GET("/locations/filter", auth.JWTMiddleware(), GetLocationsByStory)
+	r.PUT("/locations/:id/coordinates", auth.JWTMiddleware(), SetLocationCoordinates)
+	r.PUT("/locations/:id/parent", auth.JWTMiddleware(), SetLocationParent)
+	r.GET("/locations/:id/within", auth.JWTMiddleware(), GetFragmentsWithin)
+	r.GET("/locations/:id/links", auth.JWTMiddleware(), GetLocationLinks)
+	r.PUT("/locations/:id", auth.JWTMiddleware(), UpdateLocation)
+	r.DELETE("/locations/:id", auth.JWTMiddleware(), DeleteLocation)
 	return r
 }
 
@@ -27,6 +34,10 @@ type LocationBuilder struct {
 	Description		*string			`json:"description"`
 	Tags			[]string		`json:"tags"`
 	Thumbnail		*ImageBuilder	`json:"thumbnail"`
+	Latitude		*float64		`json:"latitude"`
+	Longitude		*float64		`json:"longitude"`
+	Geometry		json.RawMessage	`json:"geometry"`
+	Parent			*uuid.UUID		`json:"parent"`
 }
 
 type LocationRender struct {
@@ -37,6 +48,38 @@ type LocationRender struct {
 	Images			[]model.Image	`json:"images"`
 	Tags			[]model.Tag		`json:"tags"`
 	Created			int64			`json:"created"`
+	Latitude		*float64		`json:"latitude"`
+	Longitude		*float64		`json:"longitude"`
+	Geometry		json.RawMessage	`json:"geometry"`
+	Parent			*uuid.UUID		`json:"parent"`
+}
+
+/// where a location is, a geometry without coordinates is rejected, the frontend sets both
+type LocationCoordinates struct {
+	Latitude		*float64		`json:"latitude"`
+	Longitude		*float64		`json:"longitude"`
+	Geometry		json.RawMessage	`json:"geometry"`
+}
+
+/// checks the coordinates are paired and in range, and returns the geometry to store
+func (coordinates LocationCoordinates) validate() (*string, error) {
+	if (coordinates.Latitude == nil) != (coordinates.Longitude == nil) {
+		return nil, fmt.Errorf("a location needs both a latitude and a longitude")
+	}
+	if coordinates.Latitude != nil && (*coordinates.Latitude < -90 || *coordinates.Latitude > 90 || *coordinates.Longitude < -180 || *coordinates.Longitude > 180) {
+		return nil, fmt.Errorf("coordinates out of range")
+	}
+	if len(coordinates.Geometry) == 0 || string(coordinates.Geometry) == "null" {
+		return nil, nil
+	}
+	if coordinates.Latitude == nil {
+		return nil, fmt.Errorf("a location with a geometry needs coordinates")
+	}
+	if !json.Valid(coordinates.Geometry) {
+		return nil, fmt.Errorf("geometry isn't valid json")
+	}
+	geometry := string(coordinates.Geometry)
+	return &geometry, nil
 }
 
 func GetLocations(c *gin.Context) {
@@ -92,6 +135,11 @@ func RenderLocation(tx *gorm.DB, location model.Location, userID uuid.UUID) (Loc
 		return LocationRender{}, imgerr
 	}
 
+	var geometry json.RawMessage
+	if location.Geometry != nil {
+		geometry = json.RawMessage(*location.Geometry)
+	}
+
 	return LocationRender{
 		Thumbnail: thumbnail,
 		Tags:  tags,
@@ -100,6 +148,10 @@ func RenderLocation(tx *gorm.DB, location model.Location, userID uuid.UUID) (Loc
 		Description: location.Description,
 		Images: images,
 		Created: location.Created,
+		Latitude: location.Latitude,
+		Longitude: location.Longitude,
+		Geometry: geometry,
+		Parent: location.Parent,
 	}, nil
 }
 
@@ -119,8 +171,12 @@ func GetLocation(c *gin.Context) {
 		return
 	}
 
+	// deleted locations are only deactivated, they're gone as far as readers are concerned
 	var location model.Location
-	if err := db.DB.First(&location, "id = ?", locationID).Error; err != nil {
+	if err := db.DB.
+		Joins("JOIN entities ON entities.id = locations.id").
+		Where("entities.active = ?", true).
+		First(&location, "locations.id = ?", locationID).Error; err != nil {
 		c.JSON(http.StatusNotFound, gin.H{"error": "location not found"})
 		return
 	}
@@ -137,15 +193,32 @@ func GetLocation(c *gin.Context) {
 func CreateNewLocation(tx *gorm.DB, builder LocationBuilder, userID, groupID uuid.UUID) (model.Location, error) {
 	now := time.Now().Unix()
 
+	coordinates := LocationCoordinates{Latitude: builder.Latitude, Longitude: builder.Longitude, Geometry: builder.Geometry}
+	geometry, err := coordinates.validate()
+	if err != nil {
+		return model.Location{}, err
+	}
+
+	if builder.Parent != nil {
+		access, _, permerr := CheckUserEntityPermission(tx, userID, *builder.Parent, "update")
+		if permerr != nil || !access {
+			return model.Location{}, fmt.Errorf("access to parent location denied")
+		}
+	}
+
 	var location = model.Location {
 		ID: uuid.New(),
 		Name: builder.Name,
 		Description: builder.Description,
 		Created: now,
 		LastEdited: &now,
+		Latitude: builder.Latitude,
+		Longitude: builder.Longitude,
+		Geometry: geometry,
+		Parent: builder.Parent,
 	}
 
-	err := tx.Create(&location).Error;
+	err = tx.Create(&location).Error;
 	if err != nil {
 		//tx.Rollback()
 		fmt.Printf("location error: %s", err)
@@ -272,9 +345,369 @@ func GetLocationsByStory(c *gin.Context) {
 			Name:        loc.Name,
 			Description: loc.Description,
 			Thumbnail:   thumbnail,
+			Latitude:    loc.Latitude,
+			Longitude:   loc.Longitude,
+			Parent:      loc.Parent,
+		}
+		if loc.Geometry != nil {
+			render.Geometry = json.RawMessage(*loc.Geometry)
 		}
 		locationRenders = append(locationRenders, render)
 	}
 
 	c.JSON(http.StatusOK, locationRenders)
-}
\ No newline at end of file
+}
+
+/// places a location, or clears its coordinates and geometry when both are null
+func SetLocationCoordinates(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var coordinates LocationCoordinates
+	if err := c.ShouldBindJSON(&coordinates); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse coordinates json"})
+		return
+	}
+	geometry, err := coordinates.validate()
+	if err != nil {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to location denied"})
+		return
+	}
+
+	result := db.DB.Model(&model.Location{}).Where("id = ?", id).Updates(map[string]interface{}{
+		"latitude":    coordinates.Latitude,
+		"longitude":   coordinates.Longitude,
+		"geometry":    geometry,
+		"last_edited": gorm.Expr("unix_now()"),
+	})
+	if result.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": result.Error.Error()})
+		return
+	}
+	if result.RowsAffected == 0 {
+		c.JSON(http.StatusNotFound, gin.H{"error": "location not found"})
+		return
+	}
+
+	var location model.Location
+	if err := db.DB.First(&location, "id = ?", id).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	render, err := RenderLocation(db.DB, location, user.ID)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, render)
+}
+
+type LocationParent struct {
+	Parent *uuid.UUID `json:"parent"`
+}
+
+/// whether `location` is `other` or contains it, however deeply
+func locationContains(tx *gorm.DB, location, other uuid.UUID) (bool, error) {
+	var contains bool
+	err := tx.Raw(`
+		WITH RECURSIVE inside AS (
+			SELECT id FROM locations WHERE id = @location
+			UNION
+			SELECT locations.id FROM locations JOIN inside ON locations.parent = inside.id
+		)
+		SELECT EXISTS (SELECT 1 FROM inside WHERE id = @other)`,
+		map[string]interface{}{"location": location, "other": other}).Scan(&contains).Error
+	return contains, err
+}
+
+/// moves a location inside another, or to the top level when the parent is null
+func SetLocationParent(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var body LocationParent
+	if err := c.ShouldBindJSON(&body); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse parent json"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to location denied"})
+		return
+	}
+	if body.Parent != nil {
+		// nesting changes what the parent contains, so reading it isn't enough
+		access, _, permerr = CheckUserEntityPermission(db.DB, user.ID, *body.Parent, "update")
+		if permerr != nil || !access {
+			c.JSON(http.StatusForbidden, gin.H{"error": "access to parent location denied"})
+			return
+		}
+		contains, err := locationContains(db.DB, id, *body.Parent)
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+			return
+		}
+		if contains {
+			c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a location can't be inside itself or a location it contains"})
+			return
+		}
+	}
+
+	result := db.DB.Model(&model.Location{}).Where("id = ?", id).Updates(map[string]interface{}{
+		"parent":      body.Parent,
+		"last_edited": gorm.Expr("unix_now()"),
+	})
+	if result.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": result.Error.Error()})
+		return
+	}
+	if result.RowsAffected == 0 {
+		c.JSON(http.StatusNotFound, gin.H{"error": "location not found"})
+		return
+	}
+	c.JSON(http.StatusOK, gin.H{"location": id, "parent": body.Parent})
+}
+
+/// a fragment related to a location or to any location inside it
+type FragmentWithin struct {
+	ID           uuid.UUID `json:"id"`
+	Name         string    `json:"name"`
+	Location     uuid.UUID `json:"location"`
+	LocationName string    `json:"location_name"`
+}
+
+/// every fragment set anywhere inside a location, relations in either direction count
+func GetFragmentsWithin(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to location denied"})
+		return
+	}
+
+	fragments := []FragmentWithin{}
+	err = db.DB.Raw(`
+		WITH RECURSIVE inside AS (
+			SELECT id, name FROM locations WHERE id = @location
+			UNION
+			SELECT locations.id, locations.name FROM locations JOIN inside ON locations.parent = inside.id
+		)
+		SELECT DISTINCT fragments.id, fragments.name, inside.id AS location, inside.name AS location_name
+		FROM inside
+		JOIN relations ON (relations.parent = inside.id AND relations.child_category = 'fragments')
+			OR (relations.child = inside.id AND relations.parent_category = 'fragments')
+		JOIN fragments ON fragments.id = CASE WHEN relations.parent = inside.id THEN relations.child ELSE relations.parent END
+		JOIN entities ON entities.id = fragments.id AND entities.active
+		WHERE inside.id IN (`+permittedEntities+`)
+			AND fragments.id IN (`+permittedEntities+`)
+		ORDER BY inside.name, fragments.name`,
+		map[string]interface{}{"location": id, "user": user.ID, "permission": "read"}).Scan(&fragments).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, fragments)
+}
+
+/// what the edit page sets, the tags replace the location's tags. images are uploaded through the
+/// images api with the location as their parent, removing one only unlinks it from the location
+type LocationUpdate struct {
+	Name			string			`json:"name"`
+	Description		*string			`json:"description"`
+	Tags			[]string		`json:"tags"`
+	Thumbnail		*uuid.UUID		`json:"thumbnail"`
+	RemoveImages	[]uuid.UUID		`json:"remove_images"`
+	Parent			*uuid.UUID		`json:"parent"`
+}
+
+func UpdateLocation(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var update LocationUpdate
+	if err := c.ShouldBindJSON(&update); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse location update json"})
+		return
+	}
+	if update.Name == "" {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a location needs a name"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to location denied"})
+		return
+	}
+	if update.Parent != nil {
+		access, _, permerr = CheckUserEntityPermission(db.DB, user.ID, *update.Parent, "update")
+		if permerr != nil || !access {
+			c.JSON(http.StatusForbidden, gin.H{"error": "access to parent location denied"})
+			return
+		}
+		contains, err := locationContains(db.DB, id, *update.Parent)
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+			return
+		}
+		if contains {
+			c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a location can't be inside itself or a location it contains"})
+			return
+		}
+	}
+
+	var location model.Location
+	err = db.DB.Transaction(func(tx *gorm.DB) error {
+		if len(update.RemoveImages) > 0 {
+			if err := tx.Where("parent = ? AND child IN ? AND child_category = ?", id, update.RemoveImages, "images").
+				Delete(&model.Relation{}).Error; err != nil {
+				return err
+			}
+		}
+		if update.Thumbnail != nil {
+			images, err := GetImagesByParentID(tx, id)
+			if err != nil {
+				return err
+			}
+			found := false
+			for _, image := range images {
+				found = found || image.ID == *update.Thumbnail
+			}
+			if !found {
+				return fmt.Errorf("the primary image must be one of the location's images")
+			}
+		}
+		if err := tx.Model(&model.Location{}).Where("id = ?", id).Updates(map[string]interface{}{
+			"name":        update.Name,
+			"description": update.Description,
+			"thumbnail":   update.Thumbnail,
+			"parent":      update.Parent,
+			"last_edited": gorm.Expr("unix_now()"),
+		}).Error; err != nil {
+			return err
+		}
+		if err := tx.Where("entity = ?", id).Delete(&model.Tag{}).Error; err != nil {
+			return err
+		}
+		if err := InsertTagsForEntity(tx, id, update.Tags); err != nil {
+			return err
+		}
+		return tx.First(&location, "id = ?", id).Error
+	})
+	if err != nil {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": err.Error()})
+		return
+	}
+
+	render, err := RenderLocation(db.DB, location, user.ID)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, render)
+}
+
+/// the relations of a location, previewed before it's deleted
+func GetLocationLinks(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to location denied"})
+		return
+	}
+
+	links, err := entityLinks(db.DB, id)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, links)
+}
+
+/// soft deletes a location, its relations, tags and images are kept with it. the locations inside
+/// it move up to the location that contained it
+func DeleteLocation(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "delete")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to location denied"})
+		return
+	}
+
+	var location model.Location
+	if err := db.DB.First(&location, "id = ?", id).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "location not found"})
+		return
+	}
+	err = db.DB.Transaction(func(tx *gorm.DB) error {
+		if err := tx.Model(&model.Location{}).Where("parent = ?", id).
+			Update("parent", location.Parent).Error; err != nil {
+			return err
+		}
+		if err := tx.Model(&model.Location{}).Where("id = ?", id).
+			Updates(map[string]interface{}{"parent": nil, "last_edited": gorm.Expr("unix_now()")}).Error; err != nil {
+			return err
+		}
+		return tx.Model(&model.Entity{}).Where("id = ?", id).Update("active", false).Error
+	})
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, location)
+}
@@ -0,0 +1,359 @@
+package handlers
+
+import (
+	"encoding/json"
+	"net/http"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"gorm.io/gorm"
+)
+
+func RegisterMapRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/maps/", auth.JWTMiddleware(), ListMaps)
+	r.GET("/maps/:id", auth.JWTMiddleware(), GetMap)
+	r.POST("/maps/", auth.JWTMiddleware(), CreateMap)
+	r.POST("/maps/:id/features", auth.JWTMiddleware(), CreateMapFeature)
+	r.DELETE("/maps/:id/features/:feature", auth.JWTMiddleware(), DeleteMapFeature)
+	return r
+}
+
+type MapBounds struct {
+	North float64 `json:"north"`
+	South float64 `json:"south"`
+	East  float64 `json:"east"`
+	West  float64 `json:"west"`
+}
+
+/// the image is uploaded through the images api first, width and height are its size in pixels
+type MapBuilder struct {
+	Name        string     `json:"name"`
+	Description *string    `json:"description"`
+	Image       uuid.UUID  `json:"image"`
+	Width       int32      `json:"width"`
+	Height      int32      `json:"height"`
+	Projection  string     `json:"projection"`
+	Scale       *float64   `json:"scale"`
+	Unit        *string    `json:"unit"`
+	Bounds      *MapBounds `json:"bounds"`
+	Location    *uuid.UUID `json:"location"`
+}
+
+type MapFeatureBuilder struct {
+	Location uuid.UUID       `json:"location"`
+	Kind     string          `json:"kind"`
+	Points   json.RawMessage `json:"points"`
+	Label    *string         `json:"label"`
+	Color    *string         `json:"color"`
+}
+
+type MapFeatureRender struct {
+	ID           uuid.UUID       `json:"id"`
+	Location     uuid.UUID       `json:"location"`
+	LocationName string          `json:"location_name"`
+	Kind         string          `json:"kind"`
+	Points       json.RawMessage `json:"points"`
+	Label        *string         `json:"label"`
+	Color        *string         `json:"color"`
+}
+
+type MapRender struct {
+	ID          uuid.UUID          `json:"id"`
+	Name        string             `json:"name"`
+	Description *string            `json:"description"`
+	Image       *model.Image       `json:"image"`
+	Width       int32              `json:"width"`
+	Height      int32              `json:"height"`
+	Projection  string             `json:"projection"`
+	Scale       *float64           `json:"scale"`
+	Unit        *string            `json:"unit"`
+	Bounds      *MapBounds         `json:"bounds"`
+	Location    *uuid.UUID         `json:"location"`
+	Features    []MapFeatureRender `json:"features"`
+}
+
+/// the map without its features, which are only loaded for a single map
+func renderMap(tx *gorm.DB, m model.Map) (MapRender, error) {
+	render := MapRender{
+		ID:          m.ID,
+		Name:        m.Name,
+		Description: m.Description,
+		Width:       m.Width,
+		Height:      m.Height,
+		Projection:  m.Projection,
+		Scale:       m.Scale,
+		Unit:        m.Unit,
+		Location:    m.Location,
+		Features:    []MapFeatureRender{},
+	}
+	if m.North != nil && m.South != nil && m.East != nil && m.West != nil {
+		render.Bounds = &MapBounds{North: *m.North, South: *m.South, East: *m.East, West: *m.West}
+	}
+	if m.Image != nil {
+		var image model.Image
+		if err := tx.First(&image, "id = ?", *m.Image).Error; err != nil {
+			return MapRender{}, err
+		}
+		render.Image = &image
+	}
+	return render, nil
+}
+
+func ListMaps(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, uerr)
+		return
+	}
+
+	var maps []model.Map
+	err := db.DB.
+		Where("entities.active = ? AND entities.group_id = ?", true, user.DefaultGroup).
+		Joins("JOIN entities ON entities.id = maps.id").
+		Order("maps.name ASC").
+		Find(&maps).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	renders := make([]MapRender, 0, len(maps))
+	for _, m := range maps {
+		render, err := renderMap(db.DB, m)
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+			return
+		}
+		renders = append(renders, render)
+	}
+	c.JSON(http.StatusOK, renders)
+}
+
+/// the map with every location pinned or drawn on it
+func GetMap(c *gin.Context) {
+	m, err := GetByCtxID[model.Map](db.DB, c, "maps")
+	if err != nil {
+		return
+	}
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	render, err := renderMap(db.DB, *m)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	var features []struct {
+		model.MapFeature
+		LocationName string `gorm:"column:location_name"`
+	}
+	err = db.DB.
+		Table("map_features").
+		Select("map_features.*, locations.name AS location_name").
+		Joins("JOIN locations ON locations.id = map_features.location").
+		Where("map_features.map = ?", m.ID).
+		// features of locations the viewer can't read would give their names away
+		Where("map_features.location IN ("+permittedEntities+")", map[string]interface{}{
+			"user":       user.ID,
+			"permission": "read",
+		}).
+		Order("map_features.kind DESC, locations.name ASC").
+		Scan(&features).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	for _, feature := range features {
+		render.Features = append(render.Features, MapFeatureRender{
+			ID:           feature.ID,
+			Location:     feature.Location,
+			LocationName: feature.LocationName,
+			Kind:         feature.Kind,
+			Points:       json.RawMessage(feature.Points),
+			Label:        feature.Label,
+			Color:        feature.Color,
+		})
+	}
+	c.JSON(http.StatusOK, render)
+}
+
+func CreateNewMap(tx *gorm.DB, builder MapBuilder, userID, groupID uuid.UUID) (model.Map, error) {
+	m := model.Map{
+		ID:          uuid.New(),
+		Name:        builder.Name,
+		Description: builder.Description,
+		Image:       &builder.Image,
+		Width:       builder.Width,
+		Height:      builder.Height,
+		Projection:  builder.Projection,
+		Scale:       builder.Scale,
+		Unit:        builder.Unit,
+		Location:    builder.Location,
+	}
+	if builder.Bounds != nil {
+		m.North = &builder.Bounds.North
+		m.South = &builder.Bounds.South
+		m.East = &builder.Bounds.East
+		m.West = &builder.Bounds.West
+	}
+	if err := tx.Create(&m).Error; err != nil {
+		return model.Map{}, err
+	}
+	if err := CreateNewEntity(tx, m.ID, userID, groupID); err != nil {
+		return model.Map{}, err
+	}
+	return m, nil
+}
+
+func CreateMap(c *gin.Context) {
+	user, err := auth.GetUserFromClaims(db.DB, c)
+	if err != nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "Unauthorized: " + err.Error()})
+		return
+	}
+
+	var builder MapBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse map creation json"})
+		return
+	}
+	if builder.Projection == "" {
+		builder.Projection = "pixels"
+	}
+	if builder.Projection != "pixels" && builder.Projection != "geographic" {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "unknown projection " + builder.Projection})
+		return
+	}
+	if builder.Projection == "geographic" && builder.Bounds == nil {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a geographic map needs the bounds of the area it shows"})
+		return
+	}
+
+	tx := db.DB.Begin()
+	if tx.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to create transaction"})
+		return
+	}
+
+	m, err := CreateNewMap(tx, builder, user.ID, user.DefaultGroup)
+	if err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to create map: " + err.Error()})
+		return
+	}
+
+	if err := tx.Commit().Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to commit transaction"})
+		return
+	}
+
+	render, err := renderMap(db.DB, m)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, render)
+}
+
+/// pins a location to the map or draws it as a polygon, the frontend checks the points fit the
+/// kind and lie on the map
+func CreateMapFeature(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var builder MapFeatureBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil || !json.Valid(builder.Points) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse map feature json"})
+		return
+	}
+	if builder.Kind != "pin" && builder.Kind != "polygon" {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "unknown feature kind " + builder.Kind})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to map denied"})
+		return
+	}
+	access, _, permerr = CheckUserEntityPermission(db.DB, user.ID, builder.Location, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to location denied"})
+		return
+	}
+
+	feature := model.MapFeature{
+		ID:       uuid.New(),
+		Map:      id,
+		Location: builder.Location,
+		Kind:     builder.Kind,
+		Points:   string(builder.Points),
+		Label:    builder.Label,
+		Color:    builder.Color,
+	}
+	if err := db.DB.Create(&feature).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	db.DB.Model(&model.Map{}).Where("id = ?", id).Update("last_edited", gorm.Expr("unix_now()"))
+
+	var location model.Location
+	db.DB.Select("name").First(&location, "id = ?", feature.Location)
+	c.JSON(http.StatusOK, MapFeatureRender{
+		ID:           feature.ID,
+		Location:     feature.Location,
+		LocationName: location.Name,
+		Kind:         feature.Kind,
+		Points:       builder.Points,
+		Label:        feature.Label,
+		Color:        feature.Color,
+	})
+}
+
+func DeleteMapFeature(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	feature, err := uuid.Parse(c.Param("feature"))
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "improperly formatted feature UUID"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to map denied"})
+		return
+	}
+
+	result := db.DB.Where("id = ? AND map = ?", feature, id).Delete(&model.MapFeature{})
+	if result.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": result.Error.Error()})
+		return
+	}
+	if result.RowsAffected == 0 {
+		c.JSON(http.StatusNotFound, gin.H{"error": "map feature not found"})
+		return
+	}
+
+	c.JSON(http.StatusOK, gin.H{"map": id, "feature": feature})
+}
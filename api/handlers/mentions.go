@@ -0,0 +1,85 @@
+package handlers
+
+import (
+	"net/http"
+	"github.com/gin-gonic/gin"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/google/uuid"
+	"gorm.io/gorm/clause"
+)
+
+func RegisterMentionRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/mentions/dismissed", auth.JWTMiddleware(), ListDismissedMentions)
+	r.POST("/mentions/dismissed", auth.JWTMiddleware(), DismissMention)
+	return r
+}
+
+type MentionBuilder struct {
+	Fragment	uuid.UUID	`json:"fragment"`
+	Entity		uuid.UUID	`json:"entity"`
+}
+
+/// returns the ids of the entities dismissed as suggestions for the fragment
+func ListDismissedMentions(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	fragmentID, err := uuid.Parse(c.Query("fragment"))
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse fragment as UUID"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, fragmentID, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to fragment denied"})
+		return
+	}
+
+	var entities []uuid.UUID
+	err = db.DB.
+		Model(&model.DismissedMention{}).
+		Where("fragment = ?", fragmentID).
+		Pluck("entity", &entities).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, entities)
+}
+
+func DismissMention(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+
+	var builder MentionBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, builder.Fragment, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to fragment denied"})
+		return
+	}
+
+	mention := model.DismissedMention{
+		Fragment:    builder.Fragment,
+		Entity:      builder.Entity,
+		DismissedBy: user.ID,
+	}
+	// dismissing twice is harmless
+	if err := db.DB.Clauses(clause.OnConflict{DoNothing: true}).Create(&mention).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, mention)
+}
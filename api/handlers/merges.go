@@ -0,0 +1,337 @@
+package handlers
+
+import (
+	"errors"
+	"net/http"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"gorm.io/gorm"
+)
+
+func RegisterMergeRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/characters/:id/footprint", auth.JWTMiddleware(), GetCharacterFootprint)
+	r.POST("/characters/:id/merge/:other", auth.JWTMiddleware(), MergeCharacters)
+	r.GET("/characters/merges/:id", auth.JWTMiddleware(), GetCharacterMerge)
+	return r
+}
+
+/// a relation seen from the character's side, parent is whether the character is the parent
+type CharacterLink struct {
+	Entity      uuid.UUID `json:"entity"`
+	Name        *string   `json:"name"`
+	Category    string    `json:"category"`
+	Description *string   `json:"description"`
+	Parent      bool      `json:"parent"`
+}
+
+/// everything that refers to a character, what a delete removes and a merge moves
+type CharacterFootprint struct {
+	ID          uuid.UUID       `json:"id"`
+	Name        string          `json:"name"`
+	Description *string         `json:"description"`
+	Gender      string          `json:"gender"`
+	Thumbnail   *uuid.UUID      `json:"thumbnail"`
+	Links       []CharacterLink `json:"links"`
+	Tags        []string        `json:"tags"`
+	Kinship     []model.Kinship `json:"kinship"`
+	Sheets      []uuid.UUID     `json:"sheets"`
+	Arcs        []uuid.UUID     `json:"arcs"`
+	Lifespan    *model.Lifespan `json:"lifespan"`
+}
+
+type MergeBuilder struct {
+	Description *string `json:"description"`
+	Gender      *string `json:"gender"`
+}
+
+func characterFootprint(tx *gorm.DB, id uuid.UUID) (CharacterFootprint, error) {
+	var character model.Character
+	if err := tx.First(&character, "id = ?", id).Error; err != nil {
+		return CharacterFootprint{}, err
+	}
+	footprint := CharacterFootprint{
+		ID:          character.ID,
+		Name:        character.Name,
+		Description: character.Description,
+		Gender:      character.Gender,
+		Thumbnail:   character.Thumbnail,
+		Links:       []CharacterLink{},
+		Tags:        []string{},
+		Kinship:     []model.Kinship{},
+		Sheets:      []uuid.UUID{},
+		Arcs:        []uuid.UUID{},
+	}
+
+	links, err := entityLinks(tx, id)
+	if err != nil {
+		return CharacterFootprint{}, err
+	}
+	footprint.Links = links
+	if err := tx.Model(&model.Tag{}).Where("entity = ?", id).Pluck("value", &footprint.Tags).Error; err != nil {
+		return CharacterFootprint{}, err
+	}
+	if err := tx.Where(`"character" = ? OR relative = ?`, id, id).Find(&footprint.Kinship).Error; err != nil {
+		return CharacterFootprint{}, err
+	}
+	if err := tx.Model(&model.CharacterSheet{}).Where(`"character" = ?`, id).Pluck("schema", &footprint.Sheets).Error; err != nil {
+		return CharacterFootprint{}, err
+	}
+	if err := tx.Model(&model.CharacterArc{}).Where(`"character" = ?`, id).Pluck("fragment", &footprint.Arcs).Error; err != nil {
+		return CharacterFootprint{}, err
+	}
+	var lifespan model.Lifespan
+	err = tx.First(&lifespan, "entity = ?", id).Error
+	if err == nil {
+		footprint.Lifespan = &lifespan
+	} else if !errors.Is(err, gorm.ErrRecordNotFound) {
+		return CharacterFootprint{}, err
+	}
+	return footprint, nil
+}
+
+/// every relation of an entity seen from its side, with the name of what it links to
+func entityLinks(tx *gorm.DB, id uuid.UUID) ([]CharacterLink, error) {
+	links := []CharacterLink{}
+	err := tx.Raw(`
+		SELECT r.entity, COALESCE(ch.name, s.name, l.name, f.name) AS name, r.category, r.description, r.parent
+		FROM (
+			SELECT CASE WHEN parent = @id THEN child ELSE parent END AS entity,
+				CASE WHEN parent = @id THEN child_category ELSE parent_category END AS category,
+				description, parent = @id AS parent
+			FROM relations
+			WHERE parent = @id OR child = @id
+		) r
+		LEFT JOIN characters ch ON ch.id = r.entity
+		LEFT JOIN stories s ON s.id = r.entity
+		LEFT JOIN locations l ON l.id = r.entity
+		LEFT JOIN fragments f ON f.id = r.entity`,
+		map[string]interface{}{"id": id},
+	).Scan(&links).Error
+	return links, err
+}
+
+/// removes a character along with everything that refers to it. kinship, sheets, arcs and the
+/// merge records pointing at it go with the characters row, lifespans and dismissed mentions with
+/// the entities row
+func deleteCharacter(tx *gorm.DB, id uuid.UUID) error {
+	if err := tx.Where("parent = ? OR child = ?", id, id).Delete(&model.Relation{}).Error; err != nil {
+		return err
+	}
+	if err := tx.Where("entity = ?", id).Delete(&model.Tag{}).Error; err != nil {
+		return err
+	}
+	if err := tx.Where("entity_id = ?", id).Delete(&model.EntityGroup{}).Error; err != nil {
+		return err
+	}
+	if err := tx.Where("id = ?", id).Delete(&model.Character{}).Error; err != nil {
+		return err
+	}
+	return tx.Where("id = ?", id).Delete(&model.Entity{}).Error
+}
+
+/// what refers to the character, the frontend previews a delete or merge from it
+func GetCharacterFootprint(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+
+	footprint, err := characterFootprint(db.DB, id)
+	if errors.Is(err, gorm.ErrRecordNotFound) {
+		c.JSON(http.StatusNotFound, gin.H{"error": "character not found"})
+		return
+	} else if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, footprint)
+}
+
+/// merges other into the character at id, the survivor. everything referring to other is moved
+/// onto the survivor, where both have the same thing the survivor's is kept, then other is deleted
+/// and where it went is recorded
+func MergeCharacters(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	survivor, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	merged, err := uuid.Parse(c.Param("other"))
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "improperly formatted character UUID"})
+		return
+	}
+	if survivor == merged {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a character can't be merged into itself"})
+		return
+	}
+	var builder MergeBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, survivor, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+	access, _, permerr = CheckUserEntityPermission(db.DB, user.ID, merged, "delete")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to merged character denied"})
+		return
+	}
+
+	var kept, gone model.Character
+	if err := db.DB.First(&kept, "id = ?", survivor).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "character not found"})
+		return
+	}
+	if err := db.DB.First(&gone, "id = ?", merged).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "merged character not found"})
+		return
+	}
+
+	ids := map[string]interface{}{"survivor": survivor, "merged": merged}
+	statements := []string{
+		// relations, dropping the ones between the two
+		`INSERT INTO relations (parent, child, description, parent_category, child_category, root, relation_type)
+		SELECT @survivor, child, description, parent_category, child_category, root, relation_type
+		FROM relations WHERE parent = @merged AND child <> @survivor
+		ON CONFLICT DO NOTHING`,
+		`INSERT INTO relations (parent, child, description, parent_category, child_category, root, relation_type)
+		SELECT parent, @survivor, description, parent_category, child_category, root, relation_type
+		FROM relations WHERE child = @merged AND parent <> @survivor
+		ON CONFLICT DO NOTHING`,
+		`DELETE FROM relations WHERE parent = @merged OR child = @merged`,
+		`INSERT INTO tags (value, entity)
+		SELECT t.value, @survivor FROM tags t
+		WHERE t.entity = @merged
+		AND NOT EXISTS (SELECT 1 FROM tags s WHERE s.entity = @survivor AND s.value = t.value)`,
+		`DELETE FROM tags WHERE entity = @merged`,
+		// kinship between the two would point at itself, and kinship the survivor already has is
+		// a duplicate, symmetric kinds are stored once in either direction
+		`DELETE FROM kinship
+		WHERE ("character" = @merged AND relative = @survivor) OR ("character" = @survivor AND relative = @merged)`,
+		`DELETE FROM kinship m
+		WHERE (m."character" = @merged OR m.relative = @merged)
+		AND EXISTS (
+			SELECT 1 FROM kinship s
+			WHERE s.kind = m.kind AND (
+				(m."character" = @merged AND s."character" = @survivor AND s.relative = m.relative)
+				OR (m.relative = @merged AND s.relative = @survivor AND s."character" = m."character")
+				OR (m.kind IN ('spouse', 'partner', 'sibling') AND (
+					(m."character" = @merged AND s.relative = @survivor AND s."character" = m.relative)
+					OR (m.relative = @merged AND s."character" = @survivor AND s.relative = m."character")
+				))
+			)
+		)`,
+		`UPDATE kinship SET "character" = @survivor WHERE "character" = @merged`,
+		`UPDATE kinship SET relative = @survivor WHERE relative = @merged`,
+		`DELETE FROM character_sheets
+		WHERE "character" = @merged
+		AND schema IN (SELECT schema FROM character_sheets WHERE "character" = @survivor)`,
+		`UPDATE character_sheets SET "character" = @survivor WHERE "character" = @merged`,
+		`DELETE FROM character_arcs
+		WHERE "character" = @merged
+		AND fragment IN (SELECT fragment FROM character_arcs WHERE "character" = @survivor)`,
+		`UPDATE character_arcs SET "character" = @survivor WHERE "character" = @merged`,
+		// the merged lifespan only fills in what the survivor's leaves open
+		`INSERT INTO lifespans (entity, begins, ends)
+		SELECT @survivor, begins, ends FROM lifespans WHERE entity = @merged
+		ON CONFLICT (entity) DO UPDATE SET
+			begins = COALESCE(lifespans.begins, EXCLUDED.begins),
+			ends = COALESCE(lifespans.ends, EXCLUDED.ends)`,
+		`INSERT INTO dismissed_mentions (fragment, entity, dismissed_by)
+		SELECT fragment, @survivor, dismissed_by FROM dismissed_mentions WHERE entity = @merged
+		ON CONFLICT DO NOTHING`,
+		`UPDATE character_merges SET survivor = @survivor WHERE survivor = @merged`,
+	}
+
+	updates := map[string]interface{}{"last_edited": gorm.Expr("unix_now()")}
+	if builder.Description != nil {
+		updates["description"] = *builder.Description
+	}
+	if builder.Gender != nil {
+		updates["gender"] = *builder.Gender
+	}
+	if kept.Thumbnail == nil && gone.Thumbnail != nil {
+		updates["thumbnail"] = *gone.Thumbnail
+	}
+
+	err = db.DB.Transaction(func(tx *gorm.DB) error {
+		for _, statement := range statements {
+			if err := tx.Exec(statement, ids).Error; err != nil {
+				return err
+			}
+		}
+		if err := tx.Model(&model.Character{}).Where("id = ?", survivor).Updates(updates).Error; err != nil {
+			return err
+		}
+		if err := deleteCharacter(tx, merged); err != nil {
+			return err
+		}
+		return tx.Create(&model.CharacterMerge{
+			Merged:   merged,
+			Survivor: survivor,
+			Name:     gone.Name,
+			MergedBy: &user.ID,
+		}).Error
+	})
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	footprint, err := characterFootprint(db.DB, survivor)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, footprint)
+}
+
+/// where a merged character went
+func GetCharacterMerge(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var merge model.CharacterMerge
+	if err := db.DB.First(&merge, "merged = ?", id).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "character wasn't merged"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, merge.Survivor, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+	c.JSON(http.StatusOK, merge)
+}
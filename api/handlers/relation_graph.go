@@ -0,0 +1,79 @@
+package handlers
+
+import (
+	"net/http"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+)
+
+func RegisterRelationGraphRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/relations/graph", auth.JWTMiddleware(), GetRelationGraph)
+	return r
+}
+
+type GraphEntity struct {
+	ID       uuid.UUID `json:"id"`
+	Name     string    `json:"name"`
+	Category string    `json:"category"`
+}
+
+type RelationGraph struct {
+	Entities  []GraphEntity   `json:"entities"`
+	Relations []TypedRelation `json:"relations"`
+}
+
+/// the active stories, fragments, characters and locations the user can read, by the same rule
+/// as CheckUserEntityPermission
+const visibleEntities = `
+	SELECT DISTINCT named.id, named.name, named.category
+	FROM (
+		SELECT id, name, 'stories' AS category FROM stories
+		UNION ALL SELECT id, name, 'fragments' FROM fragments
+		UNION ALL SELECT id, name, 'characters' FROM characters
+		UNION ALL SELECT id, name, 'locations' FROM locations
+	) named
+	JOIN entities ON entities.id = named.id AND entities.active
+	WHERE named.id IN (`+permittedEntities+`)
+`
+
+/// every entity the user can see and every relation between two of them, for finding how
+/// entities are connected
+func GetRelationGraph(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	args := map[string]interface{}{"user": user.ID, "permission": "read"}
+
+	graph := RelationGraph{Entities: []GraphEntity{}, Relations: []TypedRelation{}}
+	if err := db.DB.Raw(visibleEntities+" ORDER BY named.name ASC", args).Scan(&graph.Entities).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	var relations []model.Relation
+	err := db.DB.Raw(`
+		WITH visible AS (`+visibleEntities+`)
+		SELECT relations.*
+		FROM relations
+		JOIN visible parents ON parents.id = relations.parent
+		JOIN visible children ON children.id = relations.child
+		WHERE relations.parent <> relations.child
+	`, args).Scan(&relations).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	typed, err := typeRelations(db.DB, relations)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	graph.Relations = typed
+	c.JSON(http.StatusOK, graph)
+}
@@ -0,0 +1,200 @@
+package handlers
+
+import (
+	"net/http"
+	"strings"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"gorm.io/gorm"
+)
+
+func RegisterRelationTypeRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/relations/types/", auth.JWTMiddleware(), ListRelationTypes)
+	r.POST("/relations/types/", auth.JWTMiddleware(), CreateRelationType)
+	r.GET("/relations/typed/:id", auth.JWTMiddleware(), GetTypedRelations)
+	return r
+}
+
+var relationStyles = map[string]bool{
+	"solid":  true,
+	"dashed": true,
+	"dotted": true,
+	"bold":   true,
+}
+
+type RelationTypeBuilder struct {
+	Name     string  `json:"name"`
+	Inverse  *string `json:"inverse"`
+	Directed bool    `json:"directed"`
+	Style    string  `json:"style"`
+}
+
+/// a relation of an entity along with its type, if it has one
+type TypedRelation struct {
+	Parent         uuid.UUID           `json:"parent"`
+	Child          uuid.UUID           `json:"child"`
+	ParentCategory string              `json:"parent_category"`
+	ChildCategory  string              `json:"child_category"`
+	Description    *string             `json:"description"`
+	RelationType   *model.RelationType `json:"relation_type"`
+}
+
+/// the built in types and the ones the user made
+func visibleRelationTypes(tx *gorm.DB, userID uuid.UUID) *gorm.DB {
+	return tx.Model(&model.RelationType{}).Where("creator IS NULL OR creator = ?", userID)
+}
+
+func ListRelationTypes(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+
+	var types []model.RelationType
+	if err := visibleRelationTypes(db.DB, user.ID).Order("creator NULLS FIRST, name ASC").Find(&types).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, types)
+}
+
+func CreateRelationType(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+
+	var builder RelationTypeBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse relation type json"})
+		return
+	}
+	builder.Name = strings.TrimSpace(builder.Name)
+	if builder.Name == "" {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "a relation type needs a name"})
+		return
+	}
+	if builder.Style == "" {
+		builder.Style = "solid"
+	}
+	if !relationStyles[builder.Style] {
+		c.JSON(http.StatusUnprocessableEntity, gin.H{"error": "unknown relation style " + builder.Style})
+		return
+	}
+	if builder.Inverse != nil && (!builder.Directed || strings.TrimSpace(*builder.Inverse) == "") {
+		builder.Inverse = nil
+	}
+
+	var clashes int64
+	if err := visibleRelationTypes(db.DB, user.ID).Where("lower(name) = lower(?)", builder.Name).Count(&clashes).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	if clashes > 0 {
+		c.JSON(http.StatusConflict, gin.H{"error": "there's already a relation type called " + builder.Name})
+		return
+	}
+
+	relationType := model.RelationType{
+		ID:       uuid.New(),
+		Name:     builder.Name,
+		Inverse:  builder.Inverse,
+		Directed: builder.Directed,
+		Style:    builder.Style,
+		Creator:  &user.ID,
+	}
+	if err := db.DB.Create(&relationType).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, relationType)
+}
+
+/// checks a relation's type is one the user can use
+func checkRelationType(tx *gorm.DB, userID uuid.UUID, id *uuid.UUID) error {
+	if id == nil {
+		return nil
+	}
+	var relationType model.RelationType
+	return visibleRelationTypes(tx, userID).First(&relationType, "id = ?", *id).Error
+}
+
+/// every relation the entity is either end of with the type of each, for labeling graph edges
+func GetTypedRelations(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to entity denied"})
+		return
+	}
+
+	// the other end has to be readable too, or its id and the description would leak
+	var relations []model.Relation
+	err = db.DB.
+		Where("(parent = @id AND child IN ("+permittedEntities+")) OR (child = @id AND parent IN ("+permittedEntities+"))",
+			map[string]interface{}{"id": id, "user": user.ID, "permission": "read"}).
+		Find(&relations).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	typed, err := typeRelations(db.DB, relations)
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	c.JSON(http.StatusOK, typed)
+}
+
+/// the relations along with their types
+func typeRelations(tx *gorm.DB, relations []model.Relation) ([]TypedRelation, error) {
+	ids := []uuid.UUID{}
+	for _, relation := range relations {
+		if relation.RelationType != nil {
+			ids = append(ids, *relation.RelationType)
+		}
+	}
+	var types []model.RelationType
+	if len(ids) > 0 {
+		if err := tx.Where("id IN ?", ids).Find(&types).Error; err != nil {
+			return nil, err
+		}
+	}
+	byID := make(map[uuid.UUID]model.RelationType, len(types))
+	for _, relationType := range types {
+		byID[relationType.ID] = relationType
+	}
+
+	typed := make([]TypedRelation, 0, len(relations))
+	for _, relation := range relations {
+		render := TypedRelation{
+			Parent:         relation.Parent,
+			Child:          relation.Child,
+			ParentCategory: relation.ParentCategory,
+			ChildCategory:  relation.ChildCategory,
+			Description:    relation.Description,
+		}
+		if relation.RelationType != nil {
+			if relationType, ok := byID[*relation.RelationType]; ok {
+				render.RelationType = &relationType
+			}
+		}
+		typed = append(typed, render)
+	}
+	return typed, nil
+}
@@ -0,0 +1,273 @@
+package handlers
+
+import (
+	"encoding/json"
+	"net/http"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+	"github.com/vivianlazaras/storyteller/auth"
+	"github.com/vivianlazaras/storyteller/db"
+	"github.com/vivianlazaras/storyteller/model"
+	"gorm.io/gorm"
+	"gorm.io/gorm/clause"
+)
+
+func RegisterSheetRoutes(r *gin.Engine) *gin.Engine {
+	r.GET("/sheets/schemas/", auth.JWTMiddleware(), ListSheetSchemas)
+	r.GET("/sheets/schemas/:id", auth.JWTMiddleware(), GetSheetSchema)
+	r.POST("/sheets/schemas/", auth.JWTMiddleware(), CreateSheetSchema)
+	r.GET("/sheets/characters/:id", auth.JWTMiddleware(), ListCharacterSheets)
+	r.PUT("/sheets/characters/:id", auth.JWTMiddleware(), SaveCharacterSheet)
+	r.DELETE("/sheets/characters/:id/:schema", auth.JWTMiddleware(), DeleteCharacterSheet)
+	return r
+}
+
+/// the definition and sheet values are validated by the frontend, the api only checks that
+/// they're json
+type SheetSchemaBuilder struct {
+	Name        string          `json:"name"`
+	System      *string         `json:"system"`
+	Description *string         `json:"description"`
+	Definition  json.RawMessage `json:"definition"`
+}
+
+type SheetSchemaRender struct {
+	ID          uuid.UUID       `json:"id"`
+	Name        string          `json:"name"`
+	System      *string         `json:"system"`
+	Description *string         `json:"description"`
+	Definition  json.RawMessage `json:"definition"`
+}
+
+type CharacterSheetBuilder struct {
+	Schema uuid.UUID       `json:"schema"`
+	Data   json.RawMessage `json:"data"`
+}
+
+type CharacterSheetRender struct {
+	Character uuid.UUID       `json:"character"`
+	Schema    uuid.UUID       `json:"schema"`
+	Data      json.RawMessage `json:"data"`
+}
+
+func renderSheetSchema(schema model.SheetSchema) SheetSchemaRender {
+	return SheetSchemaRender{
+		ID:          schema.ID,
+		Name:        schema.Name,
+		System:      schema.System,
+		Description: schema.Description,
+		Definition:  json.RawMessage(schema.Definition),
+	}
+}
+
+func renderCharacterSheet(sheet model.CharacterSheet) CharacterSheetRender {
+	return CharacterSheetRender{
+		Character: sheet.Character,
+		Schema:    sheet.Schema,
+		Data:      json.RawMessage(sheet.Data),
+	}
+}
+
+func ListSheetSchemas(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, uerr)
+		return
+	}
+
+	var schemas []model.SheetSchema
+	err := db.DB.
+		Where("entities.active = ? AND entities.group_id = ?", true, user.DefaultGroup).
+		Joins("JOIN entities ON entities.id = sheet_schemas.id").
+		Order("sheet_schemas.name ASC").
+		Find(&schemas).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	renders := make([]SheetSchemaRender, 0, len(schemas))
+	for _, schema := range schemas {
+		renders = append(renders, renderSheetSchema(schema))
+	}
+	c.JSON(http.StatusOK, renders)
+}
+
+func GetSheetSchema(c *gin.Context) {
+	schema, err := GetByCtxID[model.SheetSchema](db.DB, c, "sheet_schemas")
+	if err != nil {
+		return
+	}
+	c.JSON(http.StatusOK, renderSheetSchema(*schema))
+}
+
+func CreateNewSheetSchema(tx *gorm.DB, builder SheetSchemaBuilder, userID, groupID uuid.UUID) (model.SheetSchema, error) {
+	schema := model.SheetSchema{
+		ID:          uuid.New(),
+		Name:        builder.Name,
+		System:      builder.System,
+		Description: builder.Description,
+		Definition:  string(builder.Definition),
+	}
+	if err := tx.Create(&schema).Error; err != nil {
+		return model.SheetSchema{}, err
+	}
+	if err := CreateNewEntity(tx, schema.ID, userID, groupID); err != nil {
+		return model.SheetSchema{}, err
+	}
+	return schema, nil
+}
+
+func CreateSheetSchema(c *gin.Context) {
+	user, err := auth.GetUserFromClaims(db.DB, c)
+	if err != nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "Unauthorized: " + err.Error()})
+		return
+	}
+
+	var builder SheetSchemaBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil || !json.Valid(builder.Definition) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "failed to parse sheet schema creation json"})
+		return
+	}
+
+	tx := db.DB.Begin()
+	if tx.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to create transaction"})
+		return
+	}
+
+	schema, err := CreateNewSheetSchema(tx, builder, user.ID, user.DefaultGroup)
+	if err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to create sheet schema: " + err.Error()})
+		return
+	}
+
+	if err := tx.Commit().Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to commit transaction"})
+		return
+	}
+
+	c.JSON(http.StatusOK, renderSheetSchema(schema))
+}
+
+/// every sheet attached to the character
+func ListCharacterSheets(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+
+	var sheets []model.CharacterSheet
+	if err := db.DB.Where(`"character" = ?`, id).Find(&sheets).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	renders := make([]CharacterSheetRender, 0, len(sheets))
+	for _, sheet := range sheets {
+		renders = append(renders, renderCharacterSheet(sheet))
+	}
+	c.JSON(http.StatusOK, renders)
+}
+
+/// attaches a schema to the character, or replaces the values of one already attached
+func SaveCharacterSheet(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var builder CharacterSheetBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+	if len(builder.Data) == 0 {
+		builder.Data = json.RawMessage("{}")
+	}
+	if !json.Valid(builder.Data) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "sheet values aren't valid json"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+	access, _, permerr = CheckUserEntityPermission(db.DB, user.ID, builder.Schema, "read")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to sheet schema denied"})
+		return
+	}
+
+	sheet := model.CharacterSheet{
+		Character: id,
+		Schema:    builder.Schema,
+		Data:      string(builder.Data),
+	}
+	err = db.DB.Clauses(clause.OnConflict{
+		Columns:   []clause.Column{{Name: "character"}, {Name: "schema"}},
+		DoUpdates: clause.Assignments(map[string]interface{}{"data": sheet.Data, "last_edited": gorm.Expr("unix_now()")}),
+	}).Create(&sheet).Error
+	if err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, renderCharacterSheet(sheet))
+}
+
+func DeleteCharacterSheet(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+	schema, err := uuid.Parse(c.Param("schema"))
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "improperly formatted schema UUID"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, id, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to character denied"})
+		return
+	}
+
+	result := db.DB.Where(`"character" = ? AND schema = ?`, id, schema).Delete(&model.CharacterSheet{})
+	if result.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": result.Error.Error()})
+		return
+	}
+	if result.RowsAffected == 0 {
+		c.JSON(http.StatusNotFound, gin.H{"error": "sheet not found"})
+		return
+	}
+
+	c.JSON(http.StatusOK, gin.H{"character": id, "schema": schema})
+}
@@ -2,6 +2,7 @@ package handlers
 
 import (
 	"net/http"
+	"sort"
 	"time"
 	"fmt"
 	"encoding/json"
@@ -25,6 +26,22 @@ type FullMoment struct {
 	Timeline	uuid.UUID `json:"timeline"`
 	Fragment	model.Fragment	`json:"fragment"`
 	Idx			int64	`json:"idx"`
+	Day			*int64	`json:"day"`
+	Calendar	*uuid.UUID	`json:"calendar"`
+	Until		*int64	`json:"until"`
+	Duration	*int64	`json:"duration"`
+	Lane		*string	`json:"lane"`
+	Branch		*uuid.UUID	`json:"branch"`
+}
+
+/// days since 1970-01-01, every frontend calendar converts from this
+/// until is the last day covered, duration is used for moments without an end date
+type MomentDate struct {
+	Day			*int64		`json:"day"`
+	Calendar	*uuid.UUID	`json:"calendar"`
+	Until		*int64		`json:"until"`
+	Duration	*int64		`json:"duration"`
+	Lane		*string		`json:"lane"`
 }
 
 type FullTimeline struct {
@@ -33,15 +50,51 @@ type FullTimeline struct {
 	Description	*string	`json:"description"`
 	Created		*int64	`json:"created"`
 	Moments		[]FullMoment	`json:"moments"`
+	Branches	[]model.Branch	`json:"branches"`
 	Graph		*string	`json:"graph"`
 }
 
+/// adds a fragment to a branch, the trunk when nil, before the moments at position or last
+type MomentBuilder struct {
+	Timeline	uuid.UUID	`json:"timeline"`
+	Fragment	uuid.UUID	`json:"fragment"`
+	Branch		*uuid.UUID	`json:"branch"`
+	Position	*int64		`json:"position"`
+}
+
+type MomentOrder struct {
+	ID			uuid.UUID	`json:"id"`
+	Position	int64		`json:"position"`
+}
+
+/// the new order of every moment of a branch, moments sharing a position happen together
+type TimelineOrder struct {
+	Timeline	uuid.UUID		`json:"timeline"`
+	Branch		*uuid.UUID		`json:"branch"`
+	Moments		[]MomentOrder	`json:"moments"`
+}
+
+/// forks a branch off parent, the trunk when nil, after the fork moment and continues it with
+/// the given fragments in order
+type BranchBuilder struct {
+	Timeline	uuid.UUID	`json:"timeline"`
+	Name		string		`json:"name"`
+	Parent		*uuid.UUID	`json:"parent"`
+	Fork		*uuid.UUID	`json:"fork"`
+	Fragments	[]uuid.UUID	`json:"fragments"`
+}
+
 func RegisterTimelineRoutes(r *gin.Engine) *gin.Engine {
 	// after testing I may not expose these, opting rather to handle interaction
 	// with timelines through stories, or characters
 	r.GET("/timelines", auth.JWTMiddleware(), ListTimelines)
     r.GET("/timelines/:id", auth.JWTMiddleware(), GetTimeline)
 	r.POST("/timelines", auth.JWTMiddleware(), CreateTimeline)
+	r.PUT("/timelines/moments/:id", auth.JWTMiddleware(), SetMomentDate)
+	r.POST("/timelines/branches", auth.JWTMiddleware(), CreateBranch)
+	r.POST("/timelines/moments", auth.JWTMiddleware(), AddMoment)
+	r.DELETE("/timelines/moments/:id", auth.JWTMiddleware(), DeleteMoment)
+	r.PUT("/timelines/order", auth.JWTMiddleware(), ReorderMoments)
 
 	return r
 }
@@ -85,9 +138,23 @@ func GetFullTimeline(db *gorm.DB, timeline model.Timeline) (FullTimeline, error)
 			Timeline: moment.Timeline,
 			Fragment: fragment,
 			Idx:      moment.Idx,
+			Day:      moment.Day,
+			Calendar: moment.Calendar,
+			Until:    moment.Until,
+			Duration: moment.Duration,
+			Lane:     moment.Lane,
+			Branch:   moment.Branch,
 		})
 	}
 
+	var branches []model.Branch
+	if err := db.
+		Where("timeline = ?", timeline.ID).
+		Order("created ASC").
+		Find(&branches).Error; err != nil {
+		return FullTimeline{}, err
+	}
+
 	// Attempt to fetch the graph, if any
 	var graph model.Graph
 	var graphStr *string
@@ -105,6 +172,7 @@ func GetFullTimeline(db *gorm.DB, timeline model.Timeline) (FullTimeline, error)
 		Description: timeline.Description,
 		Created:     timeline.Created,
 		Moments:     fullMoments,
+		Branches:    branches,
 		Graph:       graphStr,
 	}
 
@@ -133,6 +201,526 @@ func createDefaultTimeline(metadata string) (model.Timeline, error) {
 	return timeline, err
 }*/
 
+func SetMomentDate(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var date MomentDate
+	if err := c.ShouldBindJSON(&date); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+
+	var moment model.Moment
+	if err := db.DB.First(&moment, "id = ?", id).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "moment not found"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, moment.Timeline, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to timeline denied"})
+		return
+	}
+
+	if date.Until != nil && (date.Day == nil || *date.Until < *date.Day) {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "a moment can't end before it begins"})
+		return
+	}
+	if date.Duration != nil && *date.Duration < 1 {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "a moment lasts at least one day"})
+		return
+	}
+
+	moment.Day = date.Day
+	moment.Calendar = date.Calendar
+	moment.Until = date.Until
+	moment.Duration = date.Duration
+	moment.Lane = date.Lane
+	if err := db.DB.Model(&moment).Select("day", "calendar", "until", "duration", "lane").Updates(&moment).Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	c.JSON(http.StatusOK, moment)
+}
+
+func CreateBranch(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+
+	var builder BranchBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+	if builder.Name == "" {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "a branch needs a name"})
+		return
+	}
+
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, builder.Timeline, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to timeline denied"})
+		return
+	}
+
+	if builder.Parent != nil {
+		var parent model.Branch
+		if err := db.DB.First(&parent, "id = ? AND timeline = ?", *builder.Parent, builder.Timeline).Error; err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "parent branch isn't part of this timeline"})
+			return
+		}
+	}
+
+	// the branch's own moments continue on from the fork moment
+	var start int64 = 0
+	if builder.Fork != nil {
+		var fork model.Moment
+		if err := db.DB.First(&fork, "id = ? AND timeline = ?", *builder.Fork, builder.Timeline).Error; err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "fork moment isn't part of this timeline"})
+			return
+		}
+		inSequence, err := onBranch(db.DB, builder.Parent, fork)
+		if err != nil {
+			c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+			return
+		}
+		if !inSequence {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "fork moment isn't part of the parent branch"})
+			return
+		}
+		start = fork.Idx + 8
+	}
+
+	for _, fragment := range builder.Fragments {
+		readable, _, rerr := CheckUserEntityPermission(db.DB, user.ID, fragment, "read")
+		if rerr != nil || !readable {
+			c.JSON(http.StatusForbidden, gin.H{"error": "access to fragment denied"})
+			return
+		}
+	}
+
+	tx := db.DB.Begin()
+	if tx.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to create transaction"})
+		return
+	}
+
+	branch := model.Branch{
+		ID:       uuid.New(),
+		Timeline: builder.Timeline,
+		Name:     builder.Name,
+		Parent:   builder.Parent,
+		Fork:     builder.Fork,
+		Created:  time.Now().Unix(),
+	}
+	if err := tx.Create(&branch).Error; err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to create branch"})
+		return
+	}
+
+	for idx, fragment := range builder.Fragments {
+		fragment := fragment
+		moment := model.Moment{
+			ID:       uuid.New(),
+			Timeline: builder.Timeline,
+			Fragment: &fragment,
+			Idx:      start + int64(idx)*8,
+			Branch:   &branch.ID,
+		}
+		if err := tx.Create(&moment).Error; err != nil {
+			tx.Rollback()
+			c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to create moment"})
+			return
+		}
+	}
+
+	if err := tx.Commit().Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to commit transaction"})
+		return
+	}
+
+	c.JSON(http.StatusOK, branch)
+}
+
+/// the moments belonging to a branch itself, the trunk when branch is nil, in order
+func branchMoments(tx *gorm.DB, timeline uuid.UUID, branch *uuid.UUID) ([]model.Moment, error) {
+	var moments []model.Moment
+	query := tx.Where("timeline = ?", timeline)
+	if branch == nil {
+		query = query.Where("branch IS NULL")
+	} else {
+		query = query.Where("branch = ?", *branch)
+	}
+	err := query.Order("idx ASC").Find(&moments).Error
+	return moments, err
+}
+
+/// whether a moment is part of the sequence seen from a branch, the trunk when branch is nil:
+/// the branch's own moments and its parent's sequence up to the fork
+func onBranch(tx *gorm.DB, branch *uuid.UUID, moment model.Moment) (bool, error) {
+	var cut *int64
+	// visited stops the walk up the parents if the branches ever form a cycle
+	visited := map[uuid.UUID]bool{}
+	for {
+		if sameBranch(moment.Branch, branch) {
+			return cut == nil || moment.Idx <= *cut, nil
+		}
+		if branch == nil || visited[*branch] {
+			return false, nil
+		}
+		visited[*branch] = true
+
+		var b model.Branch
+		if err := tx.First(&b, "id = ?", *branch).Error; err != nil {
+			return false, err
+		}
+		// a branch without a fork doesn't inherit any of its parent's moments
+		if b.Fork == nil {
+			return false, nil
+		}
+		var fork model.Moment
+		if err := tx.First(&fork, "id = ?", *b.Fork).Error; err != nil {
+			return false, err
+		}
+		if cut == nil || fork.Idx < *cut {
+			cut = &fork.Idx
+		}
+		branch = b.Parent
+	}
+}
+
+func sameBranch(a, b *uuid.UUID) bool {
+	if a == nil || b == nil {
+		return a == nil && b == nil
+	}
+	return *a == *b
+}
+
+/// the idx a branch's own moments start from, just past its fork
+func branchBase(tx *gorm.DB, branch *uuid.UUID) (int64, error) {
+	if branch == nil {
+		return 0, nil
+	}
+	var b model.Branch
+	if err := tx.First(&b, "id = ?", *branch).Error; err != nil {
+		return 0, err
+	}
+	if b.Fork == nil {
+		return 0, nil
+	}
+	var fork model.Moment
+	if err := tx.First(&fork, "id = ?", *b.Fork).Error; err != nil {
+		return 0, err
+	}
+	return fork.Idx + 8, nil
+}
+
+/// spaces the moments of a branch out by 8 from its base, moments with equal positions keep
+/// an equal idx. branches forking off it are re-based so their moments stay after their forks
+func renumberMoments(tx *gorm.DB, timeline uuid.UUID, branch *uuid.UUID, moments []model.Moment, positions []int64) error {
+	base, err := branchBase(tx, branch)
+	if err != nil {
+		return err
+	}
+	idx := base
+	for i, moment := range moments {
+		if i > 0 && positions[i] != positions[i-1] {
+			idx += 8
+		}
+		if err := tx.Model(&model.Moment{}).Where("id = ?", moment.ID).Update("idx", idx).Error; err != nil {
+			return err
+		}
+	}
+
+	var children []model.Branch
+	query := tx.Where("timeline = ?", timeline)
+	if branch == nil {
+		query = query.Where("parent IS NULL")
+	} else {
+		query = query.Where("parent = ?", *branch)
+	}
+	if err := query.Find(&children).Error; err != nil {
+		return err
+	}
+	for _, child := range children {
+		child := child
+		own, err := branchMoments(tx, timeline, &child.ID)
+		if err != nil {
+			return err
+		}
+		positions := make([]int64, len(own))
+		for i, moment := range own {
+			positions[i] = moment.Idx
+		}
+		if err := renumberMoments(tx, timeline, &child.ID, own, positions); err != nil {
+			return err
+		}
+	}
+	return nil
+}
+
+/// the current idx of each moment as its position, so simultaneous moments stay together
+func positionsOf(moments []model.Moment) []int64 {
+	positions := make([]int64, len(moments))
+	for i, moment := range moments {
+		positions[i] = moment.Idx
+	}
+	return positions
+}
+
+func AddMoment(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+
+	var builder MomentBuilder
+	if err := c.ShouldBindJSON(&builder); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, builder.Timeline, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to timeline denied"})
+		return
+	}
+	readable, _, rerr := CheckUserEntityPermission(db.DB, user.ID, builder.Fragment, "read")
+	if rerr != nil || !readable {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to fragment denied"})
+		return
+	}
+	if builder.Branch != nil {
+		var branch model.Branch
+		if err := db.DB.First(&branch, "id = ? AND timeline = ?", *builder.Branch, builder.Timeline).Error; err != nil {
+			c.JSON(http.StatusBadRequest, gin.H{"error": "branch isn't part of this timeline"})
+			return
+		}
+	}
+
+	tx := db.DB.Begin()
+	if tx.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to create transaction"})
+		return
+	}
+
+	existing, err := branchMoments(tx, builder.Timeline, builder.Branch)
+	if err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	moment := model.Moment{
+		ID:       uuid.New(),
+		Timeline: builder.Timeline,
+		Fragment: &builder.Fragment,
+		Branch:   builder.Branch,
+	}
+	if err := tx.Create(&moment).Error; err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to create moment"})
+		return
+	}
+
+	// existing groups get even positions so the new moment can sit between them
+	positions := make([]int64, 0, len(existing)+1)
+	moments := make([]model.Moment, 0, len(existing)+1)
+	group := int64(-1)
+	inserted := false
+	for i, other := range existing {
+		if i == 0 || other.Idx != existing[i-1].Idx {
+			group++
+		}
+		if !inserted && builder.Position != nil && *builder.Position <= group {
+			moments = append(moments, moment)
+			positions = append(positions, group*2-1)
+			inserted = true
+		}
+		moments = append(moments, other)
+		positions = append(positions, group*2)
+	}
+	if !inserted {
+		moments = append(moments, moment)
+		positions = append(positions, group*2+1)
+	}
+
+	if err := renumberMoments(tx, builder.Timeline, builder.Branch, moments, positions); err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	if err := tx.Commit().Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to commit transaction"})
+		return
+	}
+
+	c.JSON(http.StatusOK, moment)
+}
+
+func DeleteMoment(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+	id, err := GetID(c)
+	if err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": err.Error()})
+		return
+	}
+
+	var moment model.Moment
+	if err := db.DB.First(&moment, "id = ?", id).Error; err != nil {
+		c.JSON(http.StatusNotFound, gin.H{"error": "moment not found"})
+		return
+	}
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, moment.Timeline, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to timeline denied"})
+		return
+	}
+
+	tx := db.DB.Begin()
+	if tx.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to create transaction"})
+		return
+	}
+
+	// branches forking at this moment fork at the one before it, or where its branch forks
+	var newFork *uuid.UUID
+	var previous model.Moment
+	query := tx.Where("timeline = ? AND idx < ?", moment.Timeline, moment.Idx)
+	if moment.Branch == nil {
+		query = query.Where("branch IS NULL")
+	} else {
+		query = query.Where("branch = ?", *moment.Branch)
+	}
+	if err := query.Order("idx DESC").First(&previous).Error; err == nil {
+		newFork = &previous.ID
+	} else if moment.Branch != nil {
+		var branch model.Branch
+		if err := tx.First(&branch, "id = ?", *moment.Branch).Error; err == nil {
+			newFork = branch.Fork
+		}
+	}
+	if err := tx.Model(&model.Branch{}).Where("fork = ?", moment.ID).Update("fork", newFork).Error; err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	if err := tx.Delete(&moment).Error; err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "failed to delete moment"})
+		return
+	}
+
+	remaining, err := branchMoments(tx, moment.Timeline, moment.Branch)
+	if err == nil {
+		err = renumberMoments(tx, moment.Timeline, moment.Branch, remaining, positionsOf(remaining))
+	}
+	if err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	if err := tx.Commit().Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to commit transaction"})
+		return
+	}
+
+	c.JSON(http.StatusOK, gin.H{"deleted": moment.ID})
+}
+
+func ReorderMoments(c *gin.Context) {
+	user, uerr := auth.GetUserFromClaims(db.DB, c)
+	if uerr != nil || user == nil {
+		c.JSON(http.StatusUnauthorized, gin.H{"error": "unauthorized"})
+		return
+	}
+
+	var order TimelineOrder
+	if err := c.ShouldBindJSON(&order); err != nil {
+		c.JSON(http.StatusBadRequest, gin.H{"error": "Invalid request: " + err.Error()})
+		return
+	}
+
+	access, _, permerr := CheckUserEntityPermission(db.DB, user.ID, order.Timeline, "update")
+	if permerr != nil || !access {
+		c.JSON(http.StatusForbidden, gin.H{"error": "access to timeline denied"})
+		return
+	}
+
+	tx := db.DB.Begin()
+	if tx.Error != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to create transaction"})
+		return
+	}
+
+	existing, err := branchMoments(tx, order.Timeline, order.Branch)
+	if err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+	byID := make(map[uuid.UUID]model.Moment, len(existing))
+	for _, moment := range existing {
+		byID[moment.ID] = moment
+	}
+	if len(order.Moments) != len(existing) {
+		tx.Rollback()
+		c.JSON(http.StatusBadRequest, gin.H{"error": "the order must list every moment of the branch"})
+		return
+	}
+
+	sort.SliceStable(order.Moments, func(i, j int) bool {
+		return order.Moments[i].Position < order.Moments[j].Position
+	})
+	moments := make([]model.Moment, 0, len(order.Moments))
+	positions := make([]int64, 0, len(order.Moments))
+	for _, entry := range order.Moments {
+		moment, ok := byID[entry.ID]
+		if !ok {
+			tx.Rollback()
+			c.JSON(http.StatusBadRequest, gin.H{"error": "moment " + entry.ID.String() + " isn't part of this branch"})
+			return
+		}
+		delete(byID, entry.ID)
+		moments = append(moments, moment)
+		positions = append(positions, entry.Position)
+	}
+
+	if err := renumberMoments(tx, order.Timeline, order.Branch, moments, positions); err != nil {
+		tx.Rollback()
+		c.JSON(http.StatusInternalServerError, gin.H{"error": err.Error()})
+		return
+	}
+
+	if err := tx.Commit().Error; err != nil {
+		c.JSON(http.StatusInternalServerError, gin.H{"error": "Failed to commit transaction"})
+		return
+	}
+
+	c.JSON(http.StatusOK, gin.H{"reordered": len(moments)})
+}
+
 func UpdateTimeline(c *gin.Context) {
 
 }
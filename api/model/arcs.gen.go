@@ -0,0 +1,29 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameCharacterArc = "character_arcs"
+
+// CharacterArc mapped from table <character_arcs>
+type CharacterArc struct {
+	Character     uuid.UUID `gorm:"column:character;primaryKey" json:"character"`
+	Fragment      uuid.UUID `gorm:"column:fragment;primaryKey" json:"fragment"`
+	Goals         *string   `gorm:"column:goals" json:"goals"`
+	Emotion       *string   `gorm:"column:emotion" json:"emotion"`
+	Valence       *int16    `gorm:"column:valence" json:"valence"`
+	Relationships *string   `gorm:"column:relationships" json:"relationships"`
+	Knowledge     *string   `gorm:"column:knowledge" json:"knowledge"`
+	Custom        string    `gorm:"column:custom;not null;default:'{}'::jsonb" json:"custom"`
+	LastEdited    *int64    `gorm:"column:last_edited;default:unix_now()" json:"last_edited"`
+}
+
+// TableName CharacterArc's table name
+func (*CharacterArc) TableName() string {
+	return TableNameCharacterArc
+}
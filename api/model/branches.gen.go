@@ -0,0 +1,26 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameBranch = "branches"
+
+// Branch mapped from table <branches>
+type Branch struct {
+	ID       uuid.UUID  `gorm:"column:id;primaryKey;default:gen_random_uuid()" json:"id"`
+	Timeline uuid.UUID  `gorm:"column:timeline;not null" json:"timeline"`
+	Name     string     `gorm:"column:name;not null" json:"name"`
+	Parent   *uuid.UUID `gorm:"column:parent" json:"parent"`
+	Fork     *uuid.UUID `gorm:"column:fork" json:"fork"`
+	Created  int64      `gorm:"column:created;not null;default:unix_now()" json:"created"`
+}
+
+// TableName Branch's table name
+func (*Branch) TableName() string {
+	return TableNameBranch
+}
@@ -0,0 +1,26 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameCalendar = "calendars"
+
+// Calendar mapped from table <calendars>
+type Calendar struct {
+	ID          uuid.UUID `gorm:"column:id;primaryKey;default:gen_random_uuid()" json:"id"`
+	Name        string    `gorm:"column:name;not null" json:"name"`
+	Description *string   `gorm:"column:description" json:"description"`
+	Definition  string    `gorm:"column:definition;not null" json:"definition"`
+	Created     int64     `gorm:"column:created;not null;default:unix_now()" json:"created"`
+	LastEdited  *int64    `gorm:"column:last_edited;default:unix_now()" json:"last_edited"`
+}
+
+// TableName Calendar's table name
+func (*Calendar) TableName() string {
+	return TableNameCalendar
+}
@@ -0,0 +1,23 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameDismissedMention = "dismissed_mentions"
+
+// DismissedMention mapped from table <dismissed_mentions>
+type DismissedMention struct {
+	Fragment    uuid.UUID `gorm:"column:fragment;primaryKey" json:"fragment"`
+	Entity      uuid.UUID `gorm:"column:entity;primaryKey" json:"entity"`
+	DismissedBy uuid.UUID `gorm:"column:dismissed_by;not null" json:"dismissed_by"`
+}
+
+// TableName DismissedMention's table name
+func (*DismissedMention) TableName() string {
+	return TableNameDismissedMention
+}
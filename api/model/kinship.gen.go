@@ -0,0 +1,26 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameKinship = "kinship"
+
+// Kinship mapped from table <kinship>
+type Kinship struct {
+	ID        uuid.UUID `gorm:"column:id;primaryKey;default:gen_random_uuid()" json:"id"`
+	Character uuid.UUID `gorm:"column:character;not null" json:"character"`
+	Relative  uuid.UUID `gorm:"column:relative;not null" json:"relative"`
+	Kind      string    `gorm:"column:kind;not null" json:"kind"`
+	Begins    *int64    `gorm:"column:begins" json:"begins"`
+	Ends      *int64    `gorm:"column:ends" json:"ends"`
+}
+
+// TableName Kinship's table name
+func (*Kinship) TableName() string {
+	return TableNameKinship
+}
@@ -0,0 +1,23 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameLifespan = "lifespans"
+
+// Lifespan mapped from table <lifespans>
+type Lifespan struct {
+	Entity uuid.UUID  `gorm:"column:entity;primaryKey" json:"entity"`
+	Begins *uuid.UUID `gorm:"column:begins" json:"begins"`
+	Ends   *uuid.UUID `gorm:"column:ends" json:"ends"`
+}
+
+// TableName Lifespan's table name
+func (*Lifespan) TableName() string {
+	return TableNameLifespan
+}
@@ -18,6 +18,10 @@ type Location struct {
 	Created     int64      `gorm:"column:created;not null;default:unix_now()" json:"created"`
 	Thumbnail   *uuid.UUID `gorm:"column:thumbnail" json:"thumbnail"`
 	LastEdited  *int64     `gorm:"column:last_edited;default:unix_now()" json:"last_edited"`
+	Latitude    *float64   `gorm:"column:latitude" json:"latitude"`
+	Longitude   *float64   `gorm:"column:longitude" json:"longitude"`
+	Geometry    *string    `gorm:"column:geometry;type:jsonb" json:"geometry"`
+	Parent      *uuid.UUID `gorm:"column:parent" json:"parent"`
 }
 
 // TableName Location's table name
@@ -12,14 +12,46 @@ const TableNameMap = "maps"
 
 // Map mapped from table <maps>
 type Map struct {
-	ID        uuid.UUID  `gorm:"column:id;primaryKey" json:"id"`
-	Location  *uuid.UUID `gorm:"column:location" json:"location"`
-	Latitude  *int64     `gorm:"column:latitude" json:"latitude"`
-	Longitude *int64     `gorm:"column:longitude" json:"longitude"`
-	Radius    *int64     `gorm:"column:radius" json:"radius"`
+	ID          uuid.UUID  `gorm:"column:id;primaryKey" json:"id"`
+	Location    *uuid.UUID `gorm:"column:location" json:"location"`
+	Latitude    *int64     `gorm:"column:latitude" json:"latitude"`
+	Longitude   *int64     `gorm:"column:longitude" json:"longitude"`
+	Radius      *int64     `gorm:"column:radius" json:"radius"`
+	Name        string     `gorm:"column:name;not null" json:"name"`
+	Description *string    `gorm:"column:description" json:"description"`
+	Image       *uuid.UUID `gorm:"column:image" json:"image"`
+	Width       int32      `gorm:"column:width;not null" json:"width"`
+	Height      int32      `gorm:"column:height;not null" json:"height"`
+	Projection  string     `gorm:"column:projection;not null;default:pixels" json:"projection"`
+	Scale       *float64   `gorm:"column:scale" json:"scale"`
+	Unit        *string    `gorm:"column:unit" json:"unit"`
+	North       *float64   `gorm:"column:north" json:"north"`
+	South       *float64   `gorm:"column:south" json:"south"`
+	East        *float64   `gorm:"column:east" json:"east"`
+	West        *float64   `gorm:"column:west" json:"west"`
+	Created     int64      `gorm:"column:created;not null;default:unix_now()" json:"created"`
+	LastEdited  *int64     `gorm:"column:last_edited;default:unix_now()" json:"last_edited"`
 }
 
 // TableName Map's table name
 func (*Map) TableName() string {
 	return TableNameMap
 }
+
+const TableNameMapFeature = "map_features"
+
+// MapFeature mapped from table <map_features>
+type MapFeature struct {
+	ID       uuid.UUID `gorm:"column:id;primaryKey;default:gen_random_uuid()" json:"id"`
+	Map      uuid.UUID `gorm:"column:map;not null" json:"map"`
+	Location uuid.UUID `gorm:"column:location;not null" json:"location"`
+	Kind     string    `gorm:"column:kind;not null" json:"kind"`
+	Points   string    `gorm:"column:points;not null" json:"points"`
+	Label    *string   `gorm:"column:label" json:"label"`
+	Color    *string   `gorm:"column:color" json:"color"`
+}
+
+// TableName MapFeature's table name
+func (*MapFeature) TableName() string {
+	return TableNameMapFeature
+}
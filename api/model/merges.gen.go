@@ -0,0 +1,25 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameCharacterMerge = "character_merges"
+
+// CharacterMerge mapped from table <character_merges>
+type CharacterMerge struct {
+	Merged   uuid.UUID  `gorm:"column:merged;primaryKey" json:"merged"`
+	Survivor uuid.UUID  `gorm:"column:survivor;not null" json:"survivor"`
+	Name     string     `gorm:"column:name;not null" json:"name"`
+	MergedBy *uuid.UUID `gorm:"column:merged_by" json:"merged_by"`
+	MergedAt int64      `gorm:"column:merged_at;not null;default:unix_now()" json:"merged_at"`
+}
+
+// TableName CharacterMerge's table name
+func (*CharacterMerge) TableName() string {
+	return TableNameCharacterMerge
+}
@@ -16,6 +16,12 @@ type Moment struct {
 	Timeline uuid.UUID  `gorm:"column:timeline;not null" json:"timeline"`
 	Idx      int64      `gorm:"column:idx;not null" json:"idx"`
 	Fragment *uuid.UUID `gorm:"column:fragment" json:"fragment"`
+	Day      *int64     `gorm:"column:day" json:"day"`
+	Calendar *uuid.UUID `gorm:"column:calendar" json:"calendar"`
+	Until    *int64     `gorm:"column:until" json:"until"`
+	Duration *int64     `gorm:"column:duration" json:"duration"`
+	Lane     *string    `gorm:"column:lane" json:"lane"`
+	Branch   *uuid.UUID `gorm:"column:branch" json:"branch"`
 }
 
 // TableName Moment's table name
@@ -0,0 +1,26 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameRelationType = "relation_types"
+
+// RelationType mapped from table <relation_types>
+type RelationType struct {
+	ID       uuid.UUID  `gorm:"column:id;primaryKey;default:gen_random_uuid()" json:"id"`
+	Name     string     `gorm:"column:name;not null" json:"name"`
+	Inverse  *string    `gorm:"column:inverse" json:"inverse"`
+	Directed bool       `gorm:"column:directed;not null;default:true" json:"directed"`
+	Style    string     `gorm:"column:style;not null;default:solid" json:"style"`
+	Creator  *uuid.UUID `gorm:"column:creator" json:"creator"`
+}
+
+// TableName RelationType's table name
+func (*RelationType) TableName() string {
+	return TableNameRelationType
+}
@@ -18,6 +18,7 @@ type Relation struct {
 	ParentCategory string     `gorm:"column:parent_category;primaryKey" json:"parent_category"`
 	ChildCategory  string     `gorm:"column:child_category;primaryKey" json:"child_category"`
 	Root           *uuid.UUID `gorm:"column:root" json:"root"`
+	RelationType   *uuid.UUID `gorm:"column:relation_type" json:"relation_type"`
 }
 
 // TableName Relation's table name
@@ -0,0 +1,42 @@
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+// Code generated by gorm.io/gen. DO NOT EDIT.
+
+package model
+
+import (
+	"github.com/google/uuid"
+)
+
+const TableNameSheetSchema = "sheet_schemas"
+
+// SheetSchema mapped from table <sheet_schemas>
+type SheetSchema struct {
+	ID          uuid.UUID `gorm:"column:id;primaryKey;default:gen_random_uuid()" json:"id"`
+	Name        string    `gorm:"column:name;not null" json:"name"`
+	System      *string   `gorm:"column:system" json:"system"`
+	Description *string   `gorm:"column:description" json:"description"`
+	Definition  string    `gorm:"column:definition;not null" json:"definition"`
+	Created     int64     `gorm:"column:created;not null;default:unix_now()" json:"created"`
+	LastEdited  *int64    `gorm:"column:last_edited;default:unix_now()" json:"last_edited"`
+}
+
+// TableName SheetSchema's table name
+func (*SheetSchema) TableName() string {
+	return TableNameSheetSchema
+}
+
+const TableNameCharacterSheet = "character_sheets"
+
+// CharacterSheet mapped from table <character_sheets>
+type CharacterSheet struct {
+	Character  uuid.UUID `gorm:"column:character;primaryKey" json:"character"`
+	Schema     uuid.UUID `gorm:"column:schema;primaryKey" json:"schema"`
+	Data       string    `gorm:"column:data;not null;default:'{}'::jsonb" json:"data"`
+	LastEdited *int64    `gorm:"column:last_edited;default:unix_now()" json:"last_edited"`
+}
+
+// TableName CharacterSheet's table name
+func (*CharacterSheet) TableName() string {
+	return TableNameCharacterSheet
+}
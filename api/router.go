@@ -26,8 +26,18 @@ func SetupRouter(config *config.Config) (*gin.Engine, error) {
     handlers.RegisterLocationRoutes(r)
     handlers.RegisterTagRoutes(r)
     handlers.RegisterTimelineRoutes(r)
+    handlers.RegisterCalendarRoutes(r)
     handlers.RegisterFragmentRoutes(r)
+    handlers.RegisterMentionRoutes(r)
+    handlers.RegisterLifespanRoutes(r)
+    handlers.RegisterKinshipRoutes(r)
+    handlers.RegisterSheetRoutes(r)
+    handlers.RegisterArcRoutes(r)
+    handlers.RegisterMergeRoutes(r)
+    handlers.RegisterMapRoutes(r)
     handlers.RegisterEntityRoutes(r)
+    handlers.RegisterRelationTypeRoutes(r)
+    handlers.RegisterRelationGraphRoutes(r)
     handlers.RegisterNoteRoutes(r)
     handlers.RegisterImageRoutes(r)
     handlers.RegisterProfileRoutes(r)
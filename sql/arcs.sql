@@ -0,0 +1,17 @@
+-- a character's state at one fragment, the arc page walks a story's fragments in idx order.
+-- valence is how the character feels, from -5 for despair to 5 for elation, and is what the
+-- arc chart plots
+CREATE TABLE character_arcs (
+    "character" UUID NOT NULL REFERENCES characters(id) ON DELETE CASCADE,
+    fragment UUID NOT NULL REFERENCES fragments(id) ON DELETE CASCADE,
+    goals TEXT,
+    emotion TEXT,
+    valence SMALLINT CHECK (valence BETWEEN -5 AND 5),
+    relationships TEXT,
+    knowledge TEXT,
+    custom JSONB NOT NULL DEFAULT '{}'::jsonb,
+    last_edited BIGINT DEFAULT unix_now(),
+    PRIMARY KEY ("character", fragment)
+);
+
+CREATE INDEX character_arcs_fragment_idx ON character_arcs(fragment);
@@ -0,0 +1,14 @@
+-- alternate branches of a timeline. a branch shares the moments of its parent, the trunk when
+-- parent is null, up to and including the fork moment and continues with its own moments
+CREATE TABLE branches (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    timeline UUID NOT NULL REFERENCES timelines(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    parent UUID REFERENCES branches(id) ON DELETE CASCADE,
+    fork UUID REFERENCES moments(id) ON DELETE SET NULL,
+    created BIGINT NOT NULL DEFAULT unix_now()
+);
+
+-- moments on the trunk have no branch
+ALTER TABLE moments
+    ADD COLUMN branch UUID REFERENCES branches(id) ON DELETE CASCADE;
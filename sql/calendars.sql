@@ -0,0 +1,20 @@
+-- user defined calendar systems, the definition holds months, weekdays, eras, leap rules and
+-- moons as json so the frontend can evolve the format without schema changes
+CREATE TABLE calendars (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    description TEXT,
+    definition JSONB NOT NULL,
+    created BIGINT NOT NULL DEFAULT unix_now(),
+    last_edited BIGINT DEFAULT unix_now()
+);
+
+CREATE TRIGGER calendars_insert_entity
+BEFORE INSERT ON public.calendars
+FOR EACH ROW EXECUTE FUNCTION public.insert_into_entities();
+
+-- moments carry a date as a day number, days since 1970-01-01, which every calendar converts
+-- from. calendar is the one the date was entered in, null for gregorian
+ALTER TABLE moments
+    ADD COLUMN day BIGINT,
+    ADD COLUMN calendar UUID REFERENCES calendars(id) ON DELETE SET NULL;
@@ -0,0 +1,16 @@
+-- typed family relations between characters. the parent kinds point from the parent to the
+-- child, spouse, partner and sibling are symmetric and stored once. begins and ends are day
+-- numbers counted from 1970-01-01, for spouses and partners when they got together and parted.
+CREATE TABLE kinship (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    character UUID NOT NULL REFERENCES characters(id) ON DELETE CASCADE,
+    relative UUID NOT NULL REFERENCES characters(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('parent', 'adoptive_parent', 'step_parent', 'spouse', 'partner', 'sibling')),
+    begins BIGINT,
+    ends BIGINT,
+    CONSTRAINT kinship_not_self CHECK (character <> relative),
+    CONSTRAINT kinship_ends_after_begins CHECK (begins IS NULL OR ends IS NULL OR ends >= begins)
+);
+
+CREATE INDEX kinship_character_idx ON kinship (character);
+CREATE INDEX kinship_relative_idx ON kinship (relative);
@@ -0,0 +1,33 @@
+-- copies the relations between characters, which drew family trees before kinship existed, into
+-- kinship as parents. the parent of the relation is the parent and the child the child, the
+-- relations themselves are kept. relations that are part of a cycle can't be parentage and are
+-- left out, as are pairs kinship already has.
+WITH RECURSIVE candidates AS (
+    SELECT DISTINCT relations.parent, relations.child
+    FROM relations
+    JOIN characters parents ON parents.id = relations.parent
+    JOIN characters children ON children.id = relations.child
+    WHERE relations.parent_category = 'characters'
+      AND relations.child_category = 'characters'
+      AND relations.parent <> relations.child
+),
+descendants (ancestor, descendant, path) AS (
+    SELECT parent, child, ARRAY[parent, child] FROM candidates
+    UNION ALL
+    SELECT descendants.ancestor, candidates.child, descendants.path || candidates.child
+    FROM descendants
+    JOIN candidates ON candidates.parent = descendants.descendant
+    WHERE NOT candidates.child = ANY(descendants.path)
+)
+INSERT INTO kinship (character, relative, kind)
+SELECT candidates.parent, candidates.child, 'parent'
+FROM candidates
+WHERE NOT EXISTS (
+    SELECT 1 FROM descendants
+    WHERE descendants.ancestor = candidates.child AND descendants.descendant = candidates.parent
+)
+AND NOT EXISTS (
+    SELECT 1 FROM kinship
+    WHERE (kinship.character = candidates.parent AND kinship.relative = candidates.child)
+       OR (kinship.character = candidates.child AND kinship.relative = candidates.parent)
+);
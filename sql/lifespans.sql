@@ -0,0 +1,11 @@
+-- when a character or location exists, measured in fragments so continuity can be checked
+-- against any timeline the anchors appear on. begins is a birth or founding, ends a death or
+-- destruction, either may be left open.
+CREATE TABLE lifespans (
+    entity UUID PRIMARY KEY,
+    begins UUID,
+    ends UUID,
+    FOREIGN KEY (entity) REFERENCES entities(id) ON DELETE CASCADE,
+    FOREIGN KEY (begins) REFERENCES fragments(id) ON DELETE SET NULL,
+    FOREIGN KEY (ends) REFERENCES fragments(id) ON DELETE SET NULL
+);
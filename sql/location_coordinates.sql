@@ -0,0 +1,6 @@
+-- real world locations can be placed on earth, in decimal degrees with north and east positive.
+-- photo imports match locations by distance from these coordinates
+ALTER TABLE locations
+    ADD COLUMN latitude DOUBLE PRECISION CHECK (latitude BETWEEN -90 AND 90),
+    ADD COLUMN longitude DOUBLE PRECISION CHECK (longitude BETWEEN -180 AND 180),
+    ADD CONSTRAINT locations_coordinates_paired CHECK ((latitude IS NULL) = (longitude IS NULL));
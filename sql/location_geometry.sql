@@ -0,0 +1,6 @@
+-- a location can also have a shape, a GeoJSON geometry in the same degrees as its coordinates.
+-- latitude and longitude stay the point the location is found by, the centre of the shape
+ALTER TABLE locations
+    ADD COLUMN geometry JSONB CHECK (
+        geometry IS NULL OR geometry->>'type' IN ('Point', 'LineString', 'Polygon', 'MultiPolygon')
+    );
@@ -0,0 +1,29 @@
+-- locations contain one another, the tavern in the harbour district of the capital. a location
+-- outlives the one containing it and becomes top level when that is deleted
+ALTER TABLE locations
+    ADD COLUMN parent UUID REFERENCES locations(id) ON DELETE SET NULL,
+    ADD CONSTRAINT locations_parent_not_self CHECK (parent <> id);
+
+CREATE INDEX IF NOT EXISTS locations_parent_idx ON locations(parent);
+
+-- a location can't be moved inside one of the locations it contains
+CREATE OR REPLACE FUNCTION locations_prevent_cycle() RETURNS trigger AS $$
+BEGIN
+    IF NEW.parent IS NOT NULL AND EXISTS (
+        WITH RECURSIVE above AS (
+            SELECT id, parent FROM locations WHERE id = NEW.parent
+            UNION
+            SELECT locations.id, locations.parent FROM locations JOIN above ON locations.id = above.parent
+        )
+        SELECT 1 FROM above WHERE id = NEW.id
+    ) THEN
+        RAISE EXCEPTION 'location % would contain itself', NEW.id;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS locations_prevent_cycle ON locations;
+CREATE TRIGGER locations_prevent_cycle
+    BEFORE INSERT OR UPDATE OF parent ON locations
+    FOR EACH ROW EXECUTE FUNCTION locations_prevent_cycle();
@@ -0,0 +1,50 @@
+-- maps are uploaded images with a coordinate system. positions on a map are image pixels from the
+-- top left corner, scale is how far a pixel is in unit, and a geographic map also has the bounds
+-- of the area it shows so locations with coordinates can be placed on it
+CREATE TABLE IF NOT EXISTS maps (
+    id UUID PRIMARY KEY,
+    location UUID,
+    latitude BIGINT,
+    longitude BIGINT,
+    radius BIGINT
+);
+
+ALTER TABLE maps
+    ADD COLUMN name TEXT NOT NULL DEFAULT '',
+    ADD COLUMN description TEXT,
+    ADD COLUMN image UUID REFERENCES images(id) ON DELETE SET NULL,
+    ADD COLUMN width INTEGER NOT NULL DEFAULT 0 CHECK (width >= 0),
+    ADD COLUMN height INTEGER NOT NULL DEFAULT 0 CHECK (height >= 0),
+    ADD COLUMN projection TEXT NOT NULL DEFAULT 'pixels' CHECK (projection IN ('pixels', 'geographic')),
+    ADD COLUMN scale DOUBLE PRECISION CHECK (scale > 0),
+    ADD COLUMN unit TEXT,
+    ADD COLUMN north DOUBLE PRECISION CHECK (north BETWEEN -90 AND 90),
+    ADD COLUMN south DOUBLE PRECISION CHECK (south BETWEEN -90 AND 90),
+    ADD COLUMN east DOUBLE PRECISION CHECK (east BETWEEN -180 AND 180),
+    ADD COLUMN west DOUBLE PRECISION CHECK (west BETWEEN -180 AND 180),
+    ADD COLUMN created BIGINT NOT NULL DEFAULT unix_now(),
+    ADD COLUMN last_edited BIGINT DEFAULT unix_now(),
+    ADD CONSTRAINT maps_geographic_bounds CHECK (
+        projection <> 'geographic'
+        OR (north IS NOT NULL AND south IS NOT NULL AND east IS NOT NULL AND west IS NOT NULL
+            AND north > south AND east > west)
+    );
+
+CREATE TRIGGER maps_insert_entity
+BEFORE INSERT ON public.maps
+FOR EACH ROW EXECUTE FUNCTION public.insert_into_entities();
+
+-- a location pinned to a point on a map or drawn as a polygon around its area, points is a json
+-- array of {x, y} in the map's pixels
+CREATE TABLE map_features (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    map UUID NOT NULL REFERENCES maps(id) ON DELETE CASCADE,
+    location UUID NOT NULL REFERENCES locations(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('pin', 'polygon')),
+    points JSONB NOT NULL,
+    label TEXT,
+    color TEXT
+);
+
+CREATE INDEX map_features_map_idx ON map_features(map);
+CREATE INDEX map_features_location_idx ON map_features(location);
@@ -0,0 +1,10 @@
+-- mentions the user rejected as link suggestions, so the analyzer stops offering them
+CREATE TABLE dismissed_mentions (
+    fragment UUID NOT NULL,
+    entity UUID NOT NULL,
+    dismissed_by UUID NOT NULL,
+    PRIMARY KEY (fragment, entity),
+    FOREIGN KEY (fragment) REFERENCES fragments(id) ON DELETE CASCADE,
+    FOREIGN KEY (entity) REFERENCES entities(id) ON DELETE CASCADE,
+    FOREIGN KEY (dismissed_by) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+-- where a merged character went. the merged character is deleted, so merged has no foreign key,
+-- and when a survivor is itself merged away its records are pointed at the new survivor so a
+-- lookup never takes more than one hop
+CREATE TABLE character_merges (
+    merged UUID PRIMARY KEY,
+    survivor UUID NOT NULL REFERENCES characters(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    merged_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    merged_at BIGINT NOT NULL DEFAULT unix_now()
+);
+
+CREATE INDEX character_merges_survivor_idx ON character_merges(survivor);
@@ -0,0 +1,7 @@
+-- moments can cover a span of days, either up to an inclusive end day or for a number of days,
+-- and belong to a named sub-plot which the timeline chart draws as its own lane
+ALTER TABLE moments
+    ADD COLUMN until BIGINT,
+    ADD COLUMN duration BIGINT CHECK (duration > 0),
+    ADD COLUMN lane TEXT,
+    ADD CONSTRAINT moments_until_after_day CHECK (until IS NULL OR (day IS NOT NULL AND until >= day));
@@ -0,0 +1,26 @@
+-- the kinds of relation between entities, drawn as edge labels and styles in graphs. built in
+-- types have no creator and are shared by everyone, the rest belong to the user who made them.
+-- a directed type reads from the parent of a relation to its child and the inverse the other way
+-- round, an undirected type reads the same both ways and has no inverse.
+CREATE TABLE relation_types (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    inverse TEXT,
+    directed BOOLEAN NOT NULL DEFAULT true,
+    style TEXT NOT NULL DEFAULT 'solid' CHECK (style IN ('solid', 'dashed', 'dotted', 'bold')),
+    creator UUID REFERENCES users(id) ON DELETE CASCADE,
+    CONSTRAINT relation_types_inverse_directed CHECK (directed OR inverse IS NULL)
+);
+
+CREATE UNIQUE INDEX relation_types_name_idx
+    ON relation_types (COALESCE(creator, '00000000-0000-0000-0000-000000000000'::uuid), lower(name));
+
+INSERT INTO relation_types (name, inverse, directed, style) VALUES
+    ('appears in', 'features', true, 'solid'),
+    ('located in', 'location of', true, 'dotted'),
+    ('rival of', NULL, false, 'dashed'),
+    ('ally of', NULL, false, 'solid'),
+    ('member of', 'has member', true, 'solid'),
+    ('owns', 'owned by', true, 'bold');
+
+ALTER TABLE relations ADD COLUMN relation_type UUID REFERENCES relation_types(id) ON DELETE SET NULL;
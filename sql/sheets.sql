@@ -0,0 +1,26 @@
+-- user defined character sheet schemas for tabletop game systems, the definition holds the typed
+-- attributes as json and is validated by the frontend
+CREATE TABLE sheet_schemas (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL,
+    system TEXT,
+    description TEXT,
+    definition JSONB NOT NULL,
+    created BIGINT NOT NULL DEFAULT unix_now(),
+    last_edited BIGINT DEFAULT unix_now()
+);
+
+CREATE TRIGGER sheet_schemas_insert_entity
+BEFORE INSERT ON public.sheet_schemas
+FOR EACH ROW EXECUTE FUNCTION public.insert_into_entities();
+
+-- a character's values for one schema, computed attributes aren't stored
+CREATE TABLE character_sheets (
+    "character" UUID NOT NULL REFERENCES characters(id) ON DELETE CASCADE,
+    schema UUID NOT NULL REFERENCES sheet_schemas(id) ON DELETE CASCADE,
+    data JSONB NOT NULL DEFAULT '{}'::jsonb,
+    last_edited BIGINT DEFAULT unix_now(),
+    PRIMARY KEY ("character", schema)
+);
+
+CREATE INDEX character_sheets_schema_idx ON character_sheets(schema);
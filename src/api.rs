@@ -1,3 +1,4 @@
+use crate::calendars::api::Calendar;
 use crate::errors::*;
 use base64::Engine;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
@@ -336,7 +337,11 @@ pub fn normalize_newlines(input: &str) -> String {
     input.replace("\r\n", "\n").replace('\r', "\n")
 }
 
-pub(crate) fn epoch_to_human(epoch: i64) -> String {
+/// formats a unix timestamp in `calendar`, or as local gregorian time when there is none.
+pub(crate) fn epoch_to_human(epoch: i64, calendar: Option<&Calendar>) -> String {
+    if let Some(calendar) = calendar {
+        return calendar.format_epoch(epoch);
+    }
     match OffsetDateTime::from_unix_timestamp(epoch) {
         Ok(utc_dt) => match UtcOffset::local_offset_at(utc_dt) {
             Ok(local_offset) => {
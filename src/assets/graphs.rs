@@ -5,6 +5,7 @@ use crate::errors::ApiError;
 use crate::errors::LazyError;
 use crate::fragments::frontend::FragmentRender;
 use crate::locations::LocationRender;
+use crate::relations::{EdgeLabel, TypedRelation};
 use crate::stories::StoryRender;
 use petgraph::{dot::Config, dot::Dot, graph::DiGraph};
 use regex::Regex;
@@ -39,7 +40,21 @@ pub fn strip_svg_dimensions(svg: &str) -> String {
     svg_no_height.to_string().replace("&#45;", "-")
 }
 
-fn render_str(graph_str: &str) -> (String, String) {
+pub(crate) fn render_str(graph_str: &str) -> (String, String) {
+    render_layout(graph_str, Layout::Neato)
+}
+
+/// renders graphs drawn as nested clusters, which neato doesn't draw but dot does.
+pub(crate) fn render_clusters(graph_str: &str) -> (String, String) {
+    render_layout(graph_str, Layout::Dot)
+}
+
+/// renders graphs laid out in ranks, like the paths between two entities.
+pub(crate) fn render_ranked(graph_str: &str) -> (String, String) {
+    render_layout(graph_str, Layout::Dot)
+}
+
+fn render_layout(graph_str: &str, layout: Layout) -> (String, String) {
     let context = Context::new();
     let mut gvGraph = Graph::new(graph_str, &context);
     /*for (idx, id) in idmap.into_iter() {
@@ -48,7 +63,7 @@ fn render_str(graph_str: &str) -> (String, String) {
         gvGraph.set_attr_on_node(&node_id, NodeAttr::Style(NodeStyle::Filled)).unwrap();
         gvGraph.set_attr_on_node(&node_id, NodeAttr::FillColor(Color::CORAL)).unwrap();
     }*/
-    gvGraph.set_layout(Layout::Neato).unwrap();
+    gvGraph.set_layout(layout).unwrap();
     let dot_back = gvGraph.to_dot().unwrap();
     let svg_slice = context.render(&gvGraph, OutputFormat::Svg).unwrap();
     let svg = String::from_utf8_lossy(&svg_slice);
@@ -206,6 +221,8 @@ where
     // Uses default methods above; override in specific impls if desired.
 }
 
+/// adds `children` to the graph with an edge from the parent to each, labeled with the type of
+/// the relation between them. `label` is the category of the children.
 pub(crate) async fn render_children<T, G>(
     parent_id: &Uuid,
     children: &[T],
@@ -219,9 +236,12 @@ where
     T: Renderable<G, Err = LazyError> + Sync + Entity,
     G: CompatGraph<Edge = REdge>,
 {
+    if children.is_empty() {
+        return Ok(());
+    }
+    let relations = TypedRelation::of(api, access_token, *parent_id).await?;
     for child in children {
         let child_id = child.id();
-        let name = child.name();
         let child_id_str = child_id.to_string();
         let parent_id_str = parent_id.to_string();
 
@@ -229,20 +249,30 @@ where
             child.render(api, access_token, graph, visited).await?;
         }
 
-        // Create an edge from the story to the child
-        let edge_id = format!("{}->{}", parent_id, child_id);
-        //graph.new_edge(label, &parent_id.to_string(), &child_id_str)?;
         debug_assert_ne!(*parent_id, child_id);
-        println!("parent: {}, child: {}", parent_id, child_id);
-        let edge = REdge::create(parent_id_str, child_id_str);
-        graph.add_edge(edge);
-        /*if parent_id_str != child_id_str {
-            graph.new_edge(edge_id, label, parent_id_str, child_id_str);
-        }*/
+        let relation = TypedRelation::between(&relations, *parent_id, child_id);
+        graph.add_edge(relation_edge(
+            parent_id_str,
+            child_id_str,
+            &EdgeLabel::new(relation, *parent_id, label),
+        ));
     }
     Ok(())
 }
 
+/// an edge from `tail` to `head` drawn as `label` describes.
+pub(crate) fn relation_edge(tail: String, head: String, label: &EdgeLabel) -> REdge {
+    let mut edge = REdge::create(tail, head);
+    if let Some(text) = &label.text {
+        edge.set_attr(CommonAttr::Label(text.clone()));
+    }
+    if let Some(tooltip) = &label.tooltip {
+        edge.set_attr(CommonAttr::Tooltip(tooltip.clone()));
+    }
+    edge.set_attr(CommonAttr::Class(label.class.clone()));
+    edge
+}
+
 // for now just render for stories, I will add routes to handle other entites later.
 #[get("/generate/<id>")]
 async fn generate(guard: Guard, api: &State<ApiClient>, id: Uuid) -> RawHtml<Template> {
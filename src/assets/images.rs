@@ -41,6 +41,36 @@ pub struct ImageRender {
     tags: Vec<String>,
 }
 
+/// the codes of the tags the api names, for reading the GPS position and time of an image back
+/// from it.
+const EXIF_CODES: [(&str, u16); 6] = [
+    ("GPSLatitudeRef", 0x0001),
+    ("GPSLatitude", 0x0002),
+    ("GPSLongitudeRef", 0x0003),
+    ("GPSLongitude", 0x0004),
+    ("DateTime", 0x0132),
+    ("DateTimeOriginal", 0x9003),
+];
+
+impl ImageRender {
+    pub async fn fetch(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Self, ApiError> {
+        let url = format!("/assets/images/{}", id);
+        api.get_protected(url, access_token, None).await
+    }
+
+    /// the tags kept when the image was uploaded, the image file itself no longer has them.
+    /// only the GPS and time tags are known by name.
+    pub fn exif_tags(&self) -> Vec<ExifTag> {
+        self.exif_tags
+            .iter()
+            .filter_map(|tag| {
+                let (_, code) = EXIF_CODES.iter().find(|(name, _)| *name == tag.tag)?;
+                Some(ExifTag::new(*code, tag.value.clone()))
+            })
+            .collect()
+    }
+}
+
 #[rocket::async_trait]
 pub trait ImageForm<'r>: Sized + Send {
     fn description(&self) -> Option<&str>;
@@ -77,6 +107,33 @@ impl ExifTag {
     pub fn new(tag: u16, value: String) -> Self {
         Self { tag, value }
     }
+
+    pub fn tag(&self) -> u16 {
+        self.tag
+    }
+
+    /// the value as formatted by `nom_exif`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// every EXIF tag of an image, empty for images without EXIF data.
+pub fn exif_tags(bytes: &[u8]) -> Result<Vec<ExifTag>, ApiError> {
+    let mut exif_tags = Vec::new();
+    let exif_source = MediaSource::seekable(Cursor::new(bytes))?;
+    if exif_source.has_exif() {
+        let mut parser = MediaParser::new();
+        if let Ok(tags) = parser.parse(exif_source) {
+            let taglist: ExifIter = tags;
+            for tag in taglist {
+                if let Some(value) = tag.get_value() {
+                    exif_tags.push(ExifTag::new(tag.tag_code(), value.to_string()));
+                }
+            }
+        }
+    }
+    Ok(exif_tags)
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -116,6 +173,11 @@ impl ImageBuilder {
     }
 }
 
+/// extracts the image id from urls of the form `/assets/images/<id>`.
+pub fn image_id(url: &str) -> Option<Uuid> {
+    url.rsplit('/').next().and_then(|id| Uuid::parse_str(id).ok())
+}
+
 pub struct ImageData<'r> {
     pub images: &'r Vec<TempFile<'r>>,
     pub tags: &'r [String],
@@ -159,39 +221,10 @@ impl ImageProcessor {
             if image.name().is_none() {
                 continue;
             }
-            let name = Uuid::new_v4().to_string();
-            let save_path = self.image_dir.join(&name);
-
             let mut bytes = Vec::new();
             let mut temp = image.open().await?;
             temp.read_to_end(&mut bytes).await?;
-
-            let mut exif_tags = Vec::new();
-            let exif_source = MediaSource::seekable(Cursor::new(&bytes))?;
-            if exif_source.has_exif() {
-                let mut parser = MediaParser::new();
-                if let Ok(tags) = parser.parse(exif_source) {
-                    let taglist: ExifIter = tags;
-                    for tag in taglist {
-                        if let Some(value) = tag.get_value() {
-                            exif_tags.push(ExifTag::new(tag.tag_code(), value.to_string()));
-                        }
-                    }
-                }
-            }
-
-            let reader = ImageReader::new(Cursor::new(&bytes)).with_guessed_format()?;
-            let format = reader.format().unwrap_or(ImageFormat::Png);
-            let img = reader.decode()?;
-
-            let mut output = Vec::new();
-            img.write_to(&mut Cursor::new(&mut output), format)?;
-
-            let mut file = File::create(&save_path).await?;
-            file.write_all(&output).await?;
-
-            let url = format!("/assets/images/{}", name);
-            entries.push(ImageEntry { url, exif_tags })
+            entries.push(self.store(&bytes).await?);
         }
         Ok(Some(ImageBuilder::new(
             entries,
@@ -202,6 +235,26 @@ impl ImageProcessor {
         )))
     }
 
+    /// saves an image under a new name, keeping its EXIF tags for the api.
+    pub async fn store(&self, bytes: &[u8]) -> Result<ImageEntry, ApiError> {
+        let name = Uuid::new_v4().to_string();
+        let save_path = self.image_dir.join(&name);
+        let exif_tags = exif_tags(bytes)?;
+
+        let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+        let format = reader.format().unwrap_or(ImageFormat::Png);
+        let img = reader.decode()?;
+
+        let mut output = Vec::new();
+        img.write_to(&mut Cursor::new(&mut output), format)?;
+
+        let mut file = File::create(&save_path).await?;
+        file.write_all(&output).await?;
+
+        let url = format!("/assets/images/{}", name);
+        Ok(ImageEntry { url, exif_tags })
+    }
+
     pub async fn get_image(&self, id: Uuid) -> Result<(DynamicImage, ImageFormat), ImageError> {
         let path = self.image_dir.join(id.to_string());
         Self::load_image_from_path(path).await
@@ -222,6 +275,12 @@ impl ImageProcessor {
         Ok((img, format))
     }
 
+    /// loads an image from one of the urls handed out by [`ImageProcessor::process`].
+    pub async fn load_url(&self, url: &str) -> Option<DynamicImage> {
+        let id = image_id(url)?;
+        self.get_image(id).await.ok().map(|(img, _)| img)
+    }
+
     pub fn get_image_url(&self, id: Uuid) -> PathBuf {
         self.image_dir.join(id.to_string())
     }
@@ -0,0 +1,466 @@
+//! user defined calendar systems.
+//!
+//! every calendar maps dates to and from an absolute day number, the count of days since
+//! 1970-01-01 in the gregorian calendar. dates are stored as day numbers, so converting between
+//! calendars is a round trip through the day number and a timeline can be shown in whichever
+//! calendar the reader picks.
+use crate::ApiClient;
+use crate::errors::ApiError;
+use uuid::Uuid;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+/// the largest leap year divisor, the multiples of all three still fit comfortably in an i64.
+const MAX_DIVISOR: u32 = 1_000_000;
+/// the most days a leap year can have.
+const MAX_YEAR_LENGTH: i64 = 1_000_000;
+/// dates can be at most this many years either side of year one.
+pub const MAX_YEAR: i64 = 1_000_000_000;
+/// day numbers beyond this many days either side of 1970 are treated as the furthest day.
+pub const MAX_DAY: i64 = MAX_YEAR * MAX_YEAR_LENGTH;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Month {
+    pub name: String,
+    pub days: u32,
+    /// extra days the month gains in a leap year.
+    #[serde(default)]
+    pub leap_days: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Era {
+    pub name: String,
+    pub abbreviation: String,
+    /// the year, counted from year one of the calendar, the era begins in.
+    pub start: i64,
+    /// years count down towards the start of the next era, like BCE.
+    #[serde(default)]
+    pub reverse: bool,
+}
+
+/// a year is a leap year when it is divisible by `every`, unless it is also divisible by
+/// `except` without being divisible by `unless`. the gregorian rule is 4, 100 and 400.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LeapRule {
+    pub every: Option<u32>,
+    pub except: Option<u32>,
+    pub unless: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Moon {
+    pub name: String,
+    /// length of a full cycle in days.
+    pub cycle: f64,
+    /// how many days into its cycle the moon is on day zero.
+    #[serde(default)]
+    pub offset: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalendarDefinition {
+    pub months: Vec<Month>,
+    #[serde(default)]
+    pub weekdays: Vec<String>,
+    #[serde(default)]
+    pub eras: Vec<Era>,
+    #[serde(default)]
+    pub leap: LeapRule,
+    #[serde(default)]
+    pub moons: Vec<Moon>,
+    /// the day number of the first day of year one.
+    pub epoch: i64,
+    /// the weekday of day zero, as an index into `weekdays`.
+    #[serde(default)]
+    pub weekday_offset: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Calendar {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub definition: CalendarDefinition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarDate {
+    pub year: i64,
+    /// one based index into the months of the calendar.
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoonPhase {
+    pub moon: String,
+    pub phase: &'static str,
+    /// how far through the cycle the moon is, from 0 up to 1.
+    pub fraction: f64,
+}
+
+const PHASES: [&str; 8] = [
+    "new",
+    "waxing crescent",
+    "first quarter",
+    "waxing gibbous",
+    "full",
+    "waning gibbous",
+    "last quarter",
+    "waning crescent",
+];
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// `None` when the multiple doesn't fit, no year is that far out.
+fn lcm(a: i64, b: i64) -> Option<i64> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+/// multiples of `n` in `1..year`, negative counts cover `year..=0` for years before one.
+fn multiples_before(year: i64, n: Option<i64>) -> i64 {
+    n.map_or(0, |n| year.saturating_sub(1).div_euclid(n))
+}
+
+impl LeapRule {
+    pub fn is_leap(&self, year: i64) -> bool {
+        let divides = |n: Option<u32>| n.is_some_and(|n| n > 0 && year.rem_euclid(n as i64) == 0);
+        divides(self.every) && (!divides(self.except) || divides(self.unless))
+    }
+
+    /// leap years in `1..year`, counted by inclusion and exclusion over the three divisors.
+    fn leaps_before(&self, year: i64) -> i64 {
+        let Some(every) = self.every.filter(|&n| n > 0).map(i64::from) else {
+            return 0;
+        };
+        let mut count = multiples_before(year, Some(every));
+        if let Some(except) = self.except.filter(|&n| n > 0).map(i64::from) {
+            let except = lcm(every, except);
+            count -= multiples_before(year, except);
+            if let Some(unless) = self.unless.filter(|&n| n > 0).map(i64::from) {
+                count += multiples_before(year, except.and_then(|except| lcm(except, unless)));
+            }
+        }
+        count
+    }
+
+    /// the share of years that are leap years.
+    fn frequency(&self) -> f64 {
+        let span = 1_000_000;
+        self.leaps_before(span + 1) as f64 / span as f64
+    }
+}
+
+impl CalendarDefinition {
+    /// the proleptic gregorian calendar with the common era, weeks start on monday.
+    pub fn gregorian() -> Self {
+        let months = [
+            ("January", 31, 0),
+            ("February", 28, 1),
+            ("March", 31, 0),
+            ("April", 30, 0),
+            ("May", 31, 0),
+            ("June", 30, 0),
+            ("July", 31, 0),
+            ("August", 31, 0),
+            ("September", 30, 0),
+            ("October", 31, 0),
+            ("November", 30, 0),
+            ("December", 31, 0),
+        ];
+        let weekdays = [
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+            "Sunday",
+        ];
+        Self {
+            months: months
+                .iter()
+                .map(|&(name, days, leap_days)| Month {
+                    name: name.to_string(),
+                    days,
+                    leap_days,
+                })
+                .collect(),
+            weekdays: weekdays.iter().map(|day| day.to_string()).collect(),
+            eras: vec![
+                Era {
+                    name: "Before Common Era".to_string(),
+                    abbreviation: "BCE".to_string(),
+                    start: i64::MIN,
+                    reverse: true,
+                },
+                Era {
+                    name: "Common Era".to_string(),
+                    abbreviation: "CE".to_string(),
+                    start: 1,
+                    reverse: false,
+                },
+            ],
+            leap: LeapRule {
+                every: Some(4),
+                except: Some(100),
+                unless: Some(400),
+            },
+            moons: vec![Moon {
+                name: "Moon".to_string(),
+                cycle: 29.530588,
+                // the new moon of 1970-01-07
+                offset: 29.530588 - 6.0,
+            }],
+            epoch: -719_162,
+            // 1970-01-01 was a thursday
+            weekday_offset: 3,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let invalid = |message: &str| Err(ApiError::UnprocessableEntity(message.to_string()));
+        if self.months.is_empty() {
+            return invalid("a calendar needs at least one month");
+        }
+        if self.months.iter().any(|month| month.days == 0 && month.leap_days == 0) {
+            return invalid("every month needs at least one day");
+        }
+        if self.common_year() == 0 {
+            return invalid("a common year needs at least one day");
+        }
+        if self.leap.every == Some(0) || self.leap.except == Some(0) || self.leap.unless == Some(0) {
+            return invalid("leap year divisors must be positive");
+        }
+        let divisors = [self.leap.every, self.leap.except, self.leap.unless];
+        if divisors.iter().flatten().any(|&n| n > MAX_DIVISOR) {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "leap year divisors can't be larger than {}",
+                MAX_DIVISOR
+            )));
+        }
+        if self.common_year() + self.leap_extra() > MAX_YEAR_LENGTH {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "a year can't be longer than {} days",
+                MAX_YEAR_LENGTH
+            )));
+        }
+        if !(-MAX_DAY..=MAX_DAY).contains(&self.epoch) {
+            return invalid("the first day of year one is too far from 1970");
+        }
+        if self.moons.iter().any(|moon| moon.cycle.is_nan() || moon.cycle <= 0.0) {
+            return invalid("moon cycles must be longer than zero days");
+        }
+        if !self.weekdays.is_empty() && self.weekday_offset >= self.weekdays.len() {
+            return invalid("the weekday of day zero must be one of the weekdays");
+        }
+        Ok(())
+    }
+
+    fn common_year(&self) -> i64 {
+        self.months.iter().map(|month| month.days as i64).sum()
+    }
+
+    fn leap_extra(&self) -> i64 {
+        self.months.iter().map(|month| month.leap_days as i64).sum()
+    }
+
+    pub fn year_length(&self, year: i64) -> i64 {
+        self.common_year() + if self.leap.is_leap(year) { self.leap_extra() } else { 0 }
+    }
+
+    pub fn month_length(&self, year: i64, month: u32) -> Option<u32> {
+        let month = self.months.get((month as usize).checked_sub(1)?)?;
+        Some(month.days + if self.leap.is_leap(year) { month.leap_days } else { 0 })
+    }
+
+    /// days from the first day of year one to the first day of `year`, saturating for calendars
+    /// saved before their size was limited.
+    fn days_before_year(&self, year: i64) -> i64 {
+        year.saturating_sub(1)
+            .saturating_mul(self.common_year())
+            .saturating_add(self.leap.leaps_before(year).saturating_mul(self.leap_extra()))
+    }
+
+    pub fn to_day(&self, date: CalendarDate) -> Result<i64, ApiError> {
+        if !(-MAX_YEAR..=MAX_YEAR).contains(&date.year) {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "years must be between {} and {}",
+                -MAX_YEAR, MAX_YEAR
+            )));
+        }
+        let length = self.month_length(date.year, date.month).ok_or_else(|| {
+            ApiError::UnprocessableEntity(format!("there is no month {}", date.month))
+        })?;
+        if date.day == 0 || date.day > length {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "{} only has {} days in year {}",
+                self.months[date.month as usize - 1].name,
+                length,
+                date.year
+            )));
+        }
+        let before_month: i64 = (1..date.month)
+            .filter_map(|month| self.month_length(date.year, month))
+            .map(i64::from)
+            .sum();
+        Ok(self
+            .epoch
+            .saturating_add(self.days_before_year(date.year))
+            .saturating_add(before_month + date.day as i64 - 1))
+    }
+
+    pub fn from_day(&self, day: i64) -> CalendarDate {
+        let offset = day.clamp(-MAX_DAY, MAX_DAY).saturating_sub(self.epoch);
+        let mean = self.common_year() as f64 + self.leap_extra() as f64 * self.leap.frequency();
+        let mut year = (offset as f64 / mean).floor() as i64 + 1;
+        while self.days_before_year(year) > offset {
+            year -= 1;
+        }
+        while self.days_before_year(year + 1) <= offset {
+            year += 1;
+        }
+
+        let mut remaining = offset - self.days_before_year(year);
+        let mut month = 1;
+        while let Some(length) = self.month_length(year, month) {
+            if remaining < length as i64 || month as usize == self.months.len() {
+                break;
+            }
+            remaining -= length as i64;
+            month += 1;
+        }
+        CalendarDate {
+            year,
+            month,
+            day: remaining as u32 + 1,
+        }
+    }
+
+    pub fn weekday(&self, day: i64) -> Option<&str> {
+        if self.weekdays.is_empty() {
+            return None;
+        }
+        let index = (day + self.weekday_offset as i64).rem_euclid(self.weekdays.len() as i64);
+        Some(&self.weekdays[index as usize])
+    }
+
+    /// the era a year falls in and the year as numbered within it.
+    pub fn era(&self, year: i64) -> (Option<&Era>, i64) {
+        let mut eras: Vec<&Era> = self.eras.iter().collect();
+        eras.sort_by_key(|era| era.start);
+        let Some(index) = eras.iter().rposition(|era| era.start <= year) else {
+            return (None, year);
+        };
+        let era = eras[index];
+        match eras.get(index + 1) {
+            Some(next) if era.reverse => (Some(era), next.start - year),
+            _ => (Some(era), year.saturating_sub(era.start).saturating_add(1)),
+        }
+    }
+
+    pub fn moon_phases(&self, day: i64) -> Vec<MoonPhase> {
+        self.moons
+            .iter()
+            .map(|moon| {
+                let fraction = ((day as f64 + moon.offset) / moon.cycle).rem_euclid(1.0);
+                let index = ((fraction * 8.0).round() as usize) % PHASES.len();
+                MoonPhase {
+                    moon: moon.name.clone(),
+                    phase: PHASES[index],
+                    fraction,
+                }
+            })
+            .collect()
+    }
+
+    /// a year as numbered in its era, such as `44 BCE`.
+    pub fn year_label(&self, year: i64) -> String {
+        match self.era(year) {
+            (Some(era), year) if !era.abbreviation.is_empty() => {
+                format!("{} {}", year, era.abbreviation)
+            }
+            (_, year) => year.to_string(),
+        }
+    }
+
+    /// a date such as `Thursday, 1 January 1970 CE`.
+    pub fn format(&self, day: i64) -> String {
+        let date = self.from_day(day);
+        let month = &self.months[date.month as usize - 1].name;
+        let text = format!("{} {} {}", date.day, month, self.year_label(date.year));
+        match self.weekday(day) {
+            Some(weekday) => format!("{}, {}", weekday, text),
+            None => text,
+        }
+    }
+
+    /// a unix timestamp as a date and a utc time of day.
+    pub fn format_epoch(&self, epoch: i64) -> String {
+        let seconds = epoch.rem_euclid(SECONDS_PER_DAY);
+        format!(
+            "{} {:02}:{:02}",
+            self.format(epoch.div_euclid(SECONDS_PER_DAY)),
+            seconds / 3600,
+            seconds % 3600 / 60
+        )
+    }
+}
+
+impl Calendar {
+    pub fn gregorian() -> Self {
+        Self {
+            id: Uuid::nil(),
+            name: "Gregorian".to_string(),
+            description: Some("the proleptic gregorian calendar".to_string()),
+            definition: CalendarDefinition::gregorian(),
+        }
+    }
+
+    /// the built in gregorian calendar followed by the user's calendars.
+    pub async fn list(api: &ApiClient, access_token: &str) -> Result<Vec<Self>, ApiError> {
+        let calendars: Option<Vec<Calendar>> =
+            api.get_protected("/calendars/", access_token, None).await?;
+        let mut list = vec![Self::gregorian()];
+        list.extend(calendars.unwrap_or_default());
+        Ok(list)
+    }
+
+    /// looks up a calendar, the nil id is the built in gregorian calendar.
+    pub async fn fetch(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Self, ApiError> {
+        if id.is_nil() {
+            return Ok(Self::gregorian());
+        }
+        let url = format!("/calendars/{}", id);
+        api.get_protected(&url, access_token, None).await
+    }
+
+    /// converts a date in this calendar into the same day in `other`.
+    pub fn convert(&self, date: CalendarDate, other: &Calendar) -> Result<CalendarDate, ApiError> {
+        Ok(other.definition.from_day(self.definition.to_day(date)?))
+    }
+
+    pub fn format(&self, day: i64) -> String {
+        self.definition.format(day)
+    }
+
+    pub fn format_epoch(&self, epoch: i64) -> String {
+        self.definition.format_epoch(epoch)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarBuilder {
+    pub name: String,
+    pub description: Option<String>,
+    pub definition: CalendarDefinition,
+}
+
+impl CalendarBuilder {
+    pub async fn build(&self, api: &ApiClient, access_token: &str) -> Result<Calendar, ApiError> {
+        self.definition.validate()?;
+        api.post("/calendars/", access_token, None, &self).await
+    }
+}
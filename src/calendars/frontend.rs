@@ -0,0 +1,233 @@
+use super::api::*;
+use crate::ApiClient;
+use crate::auth::Guard;
+use crate::errors::ApiError;
+use rocket::http::{Cookie, CookieJar};
+use rocket::response::{Redirect, content::RawHtml};
+use rocket::{FromForm, Route, State, form::Form, get, post, routes};
+use rocket_dyn_templates::{Template, context};
+use uuid::Uuid;
+
+/// the cookie holding the id of the calendar dates are shown in.
+pub const CALENDAR_COOKIE: &str = "calendar";
+
+/// the calendar the reader picked, gregorian when none was picked or it can't be loaded.
+pub async fn preferred(api: &ApiClient, access_token: &str, jar: &CookieJar<'_>) -> Calendar {
+    let id = jar
+        .get(CALENDAR_COOKIE)
+        .and_then(|cookie| cookie.value().parse::<Uuid>().ok());
+    match id {
+        Some(id) => Calendar::fetch(api, access_token, id)
+            .await
+            .unwrap_or_else(|_| Calendar::gregorian()),
+        None => Calendar::gregorian(),
+    }
+}
+
+#[derive(Debug, Clone, FromForm)]
+pub struct CalendarForm {
+    name: String,
+    description: Option<String>,
+    /// one month per line as `name, days` or `name, days, leap days`.
+    months: String,
+    /// one weekday per line.
+    weekdays: Option<String>,
+    /// one era per line as `name, abbreviation, start year` with an optional `, reverse`.
+    eras: Option<String>,
+    /// one moon per line as `name, cycle in days, offset in days`.
+    moons: Option<String>,
+    leap_every: Option<u32>,
+    leap_except: Option<u32>,
+    leap_unless: Option<u32>,
+    /// the gregorian date of the first day of year one.
+    epoch_year: i64,
+    epoch_month: u32,
+    epoch_day: u32,
+    /// the weekday of the first day of year one, one based.
+    #[field(default = 1)]
+    first_weekday: usize,
+}
+
+/// non empty lines split on commas, each with its line number for error messages.
+fn rows(text: Option<&str>) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    text.unwrap_or_default()
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| (idx + 1, line.split(',').map(str::trim).collect()))
+}
+
+fn number<T: std::str::FromStr>(field: &str, value: Option<&&str>, line: usize) -> Result<T, ApiError> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("{} on line {} isn't a number", field, line)))
+}
+
+impl CalendarForm {
+    pub fn into_builder(self) -> Result<CalendarBuilder, ApiError> {
+        let mut months = Vec::new();
+        for (line, row) in rows(Some(&self.months)) {
+            months.push(Month {
+                name: row[0].to_string(),
+                days: number("month length", row.get(1), line)?,
+                leap_days: match row.get(2) {
+                    Some(_) => number("leap days", row.get(2), line)?,
+                    None => 0,
+                },
+            });
+        }
+        let weekdays: Vec<String> = rows(self.weekdays.as_deref())
+            .map(|(_, row)| row.join(", "))
+            .collect();
+        let mut eras = Vec::new();
+        for (line, row) in rows(self.eras.as_deref()) {
+            eras.push(Era {
+                name: row[0].to_string(),
+                abbreviation: row.get(1).map(|abbr| abbr.to_string()).unwrap_or_default(),
+                start: number("era start", row.get(2), line)?,
+                reverse: row.get(3).is_some_and(|flag| flag.eq_ignore_ascii_case("reverse")),
+            });
+        }
+        let mut moons = Vec::new();
+        for (line, row) in rows(self.moons.as_deref()) {
+            moons.push(Moon {
+                name: row[0].to_string(),
+                cycle: number("moon cycle", row.get(1), line)?,
+                offset: match row.get(2) {
+                    Some(_) => number("moon offset", row.get(2), line)?,
+                    None => 0.0,
+                },
+            });
+        }
+
+        let epoch = CalendarDefinition::gregorian().to_day(CalendarDate {
+            year: self.epoch_year,
+            month: self.epoch_month,
+            day: self.epoch_day,
+        })?;
+        let weekday_offset = match weekdays.len() {
+            0 => 0,
+            n => (self.first_weekday.saturating_sub(1) as i64 - epoch).rem_euclid(n as i64) as usize,
+        };
+        let definition = CalendarDefinition {
+            months,
+            weekdays,
+            eras,
+            leap: LeapRule {
+                every: self.leap_every,
+                except: self.leap_except,
+                unless: self.leap_unless,
+            },
+            moons,
+            epoch,
+            weekday_offset,
+        };
+        definition.validate()?;
+        Ok(CalendarBuilder {
+            name: self.name,
+            description: self.description.filter(|description| !description.trim().is_empty()),
+            definition,
+        })
+    }
+}
+
+#[get("/")]
+async fn list_calendars(
+    guard: Guard,
+    api: &State<ApiClient>,
+    jar: &CookieJar<'_>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let calendars = Calendar::list(api, guard.access_token()).await?;
+    let preferred = preferred(api, guard.access_token(), jar).await;
+    let today = time::OffsetDateTime::now_utc().unix_timestamp();
+    let today: Vec<(Uuid, String)> = calendars
+        .iter()
+        .map(|calendar| (calendar.id, calendar.format_epoch(today)))
+        .collect();
+    Ok(RawHtml(Template::render(
+        "calendars/index",
+        context! { title: "calendars", calendars, today, preferred: preferred.id },
+    )))
+}
+
+#[get("/create")]
+async fn create_calendar_html(guard: Guard) -> RawHtml<Template> {
+    RawHtml(Template::render(
+        "calendars/create",
+        context! { title: "create calendar" },
+    ))
+}
+
+#[post("/", data = "<form>")]
+async fn create_calendar(
+    guard: Guard,
+    api: &State<ApiClient>,
+    form: Form<CalendarForm>,
+) -> Result<Redirect, ApiError> {
+    let builder = form.into_inner().into_builder()?;
+    let calendar = builder.build(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!("/calendars/{}", calendar.id)))
+}
+
+/// a calendar and, when a date is given, the same day in every other calendar.
+#[get("/<id>?<year>&<month>&<day>")]
+async fn get_calendar(
+    guard: Guard,
+    id: Uuid,
+    year: Option<i64>,
+    month: Option<u32>,
+    day: Option<u32>,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let calendar = Calendar::fetch(api, guard.access_token(), id).await?;
+    let calendars = Calendar::list(api, guard.access_token()).await?;
+    let date = match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => Some(CalendarDate { year, month, day }),
+        _ => None,
+    };
+    let absolute = date
+        .map(|date| calendar.definition.to_day(date))
+        .transpose()?;
+    let conversions: Vec<(String, String)> = match absolute {
+        Some(absolute) => calendars
+            .iter()
+            .map(|other| (other.name.clone(), other.format(absolute)))
+            .collect(),
+        None => Vec::new(),
+    };
+    let moons = absolute
+        .map(|absolute| calendar.definition.moon_phases(absolute))
+        .unwrap_or_default();
+    Ok(RawHtml(Template::render(
+        "calendars/calendar",
+        context! { title: calendar.name.clone(), calendar, date, conversions, moons },
+    )))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct PreferForm {
+    id: Uuid,
+    redirect: Option<String>,
+}
+
+/// shows dates in the chosen calendar from now on.
+#[post("/prefer", data = "<form>")]
+async fn prefer_calendar(guard: Guard, jar: &CookieJar<'_>, form: Form<PreferForm>) -> Redirect {
+    let form = form.into_inner();
+    jar.add(Cookie::build((CALENDAR_COOKIE, form.id.to_string())).path("/"));
+    let redirect = form
+        .redirect
+        .filter(|redirect| redirect.starts_with('/') && !redirect.starts_with("//"))
+        .unwrap_or_else(|| "/calendars/".to_string());
+    Redirect::to(redirect)
+}
+
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        list_calendars,
+        create_calendar_html,
+        create_calendar,
+        get_calendar,
+        prefer_calendar
+    ]
+}
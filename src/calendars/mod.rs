@@ -0,0 +1,3 @@
+pub mod api;
+pub mod frontend;
+pub use frontend::get_routes;
@@ -21,11 +21,8 @@ use crate::{
     assets::images::{ImageBuilder, ImageData, ImageProcessor},
     model::{Character, Image},
 };
-use petgraph::graph::{DiGraph, NodeIndex};
-use petgraph::visit::EdgeRef;
-use petgraph::*;
+use super::kinship::Family;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 /// A builder struct for creating new `Character` instances.
@@ -37,6 +34,8 @@ pub struct CharacterBuilder {
     pub description: Option<String>,
     /// Optional list of tags associated with the character.
     pub tags: Option<Vec<String>>,
+    /// such as `male`, `female` or `unknown`, the api's default when not given.
+    pub gender: Option<String>,
     pub thumbnail: Option<ImageBuilder>,
 }
 
@@ -47,25 +46,13 @@ use wrappedviz::style::*;
 use wrappedviz::*;
 
 impl Character {
-    /// # Note
-    /// This is an expensive function, the api server has to recursively build the tree
-    /// then the rust code has to traverse the tree and create appropriate links
+    /// everyone related to the character through kinship, see [`Family`].
     pub async fn family_tree(
         id: Uuid,
         api: &ApiClient,
         access_token: &str,
-    ) -> Result<(DiGraph<String, &'static str>, HashMap<Uuid, NodeIndex>), ApiError> {
-        let mut index_map = HashMap::new();
-        let mut seen = HashSet::new();
-        let mut graph = DiGraph::new();
-        let mut params = HashMap::new();
-        let id_str = id.to_string();
-        params.insert("id", id_str.as_str());
-        let root: CharacterNode = api
-            .get_protected("/characters/tree", access_token, Some(params))
-            .await?;
-        build_family_tree(&root, &mut graph, &mut index_map, &mut seen);
-        Ok((graph, index_map))
+    ) -> Result<Family, ApiError> {
+        Family::fetch(api, access_token, id).await
     }
 
     /// Converts a `Character` into a `CharacterRender` with the given image and tags.
@@ -117,19 +104,12 @@ impl CharacterBuilder {
             name: name.to_string(),
             description: description.map(|s| s.to_string()),
             tags,
+            gender: None,
             thumbnail,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CharacterNode {
-    pub id: Uuid,
-    pub name: String,
-    pub description: String,
-    pub children: Vec<CharacterNode>,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CharacterRender {
     pub thumbnail: Option<Image>,
@@ -230,30 +210,3 @@ impl<G: CompatGraph<Node = Node, Edge = Edge> + Send> Renderable<G> for Characte
         Ok(())
     }
 }
-
-fn build_family_tree(
-    root: &CharacterNode,
-    graph: &mut DiGraph<String, &'static str>,
-    index_map: &mut HashMap<Uuid, NodeIndex>,
-    seen: &mut HashSet<Uuid>,
-) -> NodeIndex {
-    if let Some(&idx) = index_map.get(&root.id) {
-        return idx;
-    }
-
-    // Add current node
-    let node_index = graph.add_node(root.name.clone());
-    index_map.insert(root.id, node_index);
-    seen.insert(root.id);
-
-    for child in &root.children {
-        let child_index = build_family_tree(child, graph, index_map, seen);
-
-        // Prevent duplicate edges or cycles
-        if !graph.edges(node_index).any(|e| e.target() == child_index) {
-            graph.add_edge(node_index, child_index, "parent_of");
-        }
-    }
-
-    node_index
-}
@@ -0,0 +1,340 @@
+//! how a character changes over the course of a story.
+//!
+//! authors annotate a character's [`ArcState`] at a fragment: their goals, emotional state and a
+//! valence for how they feel, their relationships, what they know, and any custom fields. a
+//! [`CharacterArc`] walks a story's fragments in `idx` order with the state at each, noting what
+//! changed since the last annotated fragment, and flags fragments the character is related to
+//! that haven't been annotated.
+use crate::ApiClient;
+use crate::assets::graphs::EntityExt;
+use crate::characters::api::CharacterRender;
+use crate::errors::ApiError;
+use crate::render::escape_html;
+use crate::stories::StoryRender;
+use crate::timelines::continuity::FragmentRef;
+use reqwest::Method;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+use uuid::Uuid;
+
+/// from despair to elation.
+pub const VALENCE: RangeInclusive<i16> = -5..=5;
+
+const STEP: f64 = 60.0;
+const LEVEL: f64 = 16.0;
+const MARGIN: f64 = 30.0;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArcState {
+    pub character: Uuid,
+    pub fragment: Uuid,
+    pub goals: Option<String>,
+    pub emotion: Option<String>,
+    pub valence: Option<i16>,
+    pub relationships: Option<String>,
+    pub knowledge: Option<String>,
+    #[serde(default)]
+    pub custom: BTreeMap<String, String>,
+}
+
+impl ArcState {
+    pub async fn list(
+        api: &ApiClient,
+        access_token: &str,
+        character: Uuid,
+    ) -> Result<Vec<Self>, ApiError> {
+        let url = format!("/arcs/{}", character);
+        let states: Option<Vec<Self>> = api.get_protected(&url, access_token, None).await?;
+        Ok(states.unwrap_or_default())
+    }
+
+    /// annotates the fragment, replacing an earlier annotation of it.
+    pub async fn save(&self, api: &ApiClient, access_token: &str) -> Result<Self, ApiError> {
+        if let Some(valence) = self.valence.filter(|valence| !VALENCE.contains(valence)) {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "valence {} isn't between {} and {}",
+                valence,
+                VALENCE.start(),
+                VALENCE.end()
+            )));
+        }
+        let url = format!("/arcs/{}", self.character);
+        api.put(&url, access_token, self).await
+    }
+
+    pub async fn remove(
+        api: &ApiClient,
+        access_token: &str,
+        character: Uuid,
+        fragment: Uuid,
+    ) -> Result<(), ApiError> {
+        let url = format!("/arcs/{}/{}", character, fragment);
+        let _: serde_json::Value = api
+            .empty_request()
+            .method(Method::DELETE)
+            .access_token(access_token)
+            .route(&url)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// the fields that differ from an earlier state, custom fields by name.
+    pub fn changes(&self, previous: &Self) -> Vec<String> {
+        let mut changes: Vec<String> = [
+            ("goals", self.goals != previous.goals),
+            ("emotion", self.emotion != previous.emotion),
+            ("valence", self.valence != previous.valence),
+            (
+                "relationships",
+                self.relationships != previous.relationships,
+            ),
+            ("knowledge", self.knowledge != previous.knowledge),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(field, _)| field.to_string())
+        .collect();
+        let names: HashSet<&String> = self.custom.keys().chain(previous.custom.keys()).collect();
+        let mut custom: Vec<String> = names
+            .into_iter()
+            .filter(|name| self.custom.get(*name) != previous.custom.get(*name))
+            .cloned()
+            .collect();
+        custom.sort();
+        changes.extend(custom);
+        changes
+    }
+}
+
+/// custom fields written one per line as `name: value`.
+pub fn parse_custom(text: &str) -> Result<BTreeMap<String, String>, ApiError> {
+    let mut custom = BTreeMap::new();
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.split_once(':') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                custom.insert(name.trim().to_string(), value.trim().to_string());
+            }
+            _ => {
+                return Err(ApiError::UnprocessableEntity(format!(
+                    "custom field on line {} should be written as name: value",
+                    idx + 1
+                )));
+            }
+        }
+    }
+    Ok(custom)
+}
+
+/// the custom fields as the form takes them.
+pub fn custom_text(custom: &BTreeMap<String, String>) -> String {
+    custom
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// a fragment of the story, with the character's state there when it was annotated.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArcBeat {
+    /// one based position in the story.
+    pub position: usize,
+    pub fragment: FragmentRef,
+    pub state: Option<ArcState>,
+    /// whether the character is related to the fragment.
+    pub appears: bool,
+    /// the fields that changed since the previous annotated fragment.
+    pub changes: Vec<String>,
+    /// the custom fields as the form takes them.
+    pub custom: String,
+}
+
+impl ArcBeat {
+    pub fn missing(&self) -> bool {
+        self.appears && self.state.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CharacterArc {
+    pub beats: Vec<ArcBeat>,
+    /// fragments the character is related to that haven't been annotated.
+    pub missing: Vec<FragmentRef>,
+}
+
+impl CharacterArc {
+    /// `fragments` in story order. states for fragments outside the story are left out.
+    pub fn new(
+        fragments: Vec<FragmentRef>,
+        appearances: &HashSet<Uuid>,
+        states: Vec<ArcState>,
+    ) -> Self {
+        let mut states: HashMap<Uuid, ArcState> = states
+            .into_iter()
+            .map(|state| (state.fragment, state))
+            .collect();
+        let mut previous: Option<ArcState> = None;
+        let mut beats = Vec::new();
+        for (idx, fragment) in fragments.into_iter().enumerate() {
+            let state = states.remove(&fragment.id);
+            let changes = match (&state, &previous) {
+                (Some(state), Some(previous)) => state.changes(previous),
+                _ => Vec::new(),
+            };
+            if state.is_some() {
+                previous = state.clone();
+            }
+            beats.push(ArcBeat {
+                position: idx + 1,
+                appears: appearances.contains(&fragment.id),
+                custom: state
+                    .as_ref()
+                    .map(|state| custom_text(&state.custom))
+                    .unwrap_or_default(),
+                fragment,
+                state,
+                changes,
+            });
+        }
+        let missing = beats
+            .iter()
+            .filter(|beat| beat.missing())
+            .map(|beat| beat.fragment.clone())
+            .collect();
+        Self { beats, missing }
+    }
+
+    /// the character's arc over the story's fragments in `idx` order.
+    pub async fn fetch(
+        api: &ApiClient,
+        access_token: &str,
+        character: &CharacterRender,
+        story: &StoryRender,
+    ) -> Result<Self, ApiError> {
+        let mut fragments = story.fragments(story.request(api, access_token)).await?;
+        fragments.sort_by_key(|fragment| fragment.idx);
+        let appearances: HashSet<Uuid> = character
+            .fragments(character.request(api, access_token))
+            .await?
+            .into_iter()
+            .map(|fragment| fragment.id)
+            .collect();
+        let states = ArcState::list(api, access_token, character.id).await?;
+        let fragments = fragments
+            .into_iter()
+            .map(|fragment| FragmentRef {
+                id: fragment.id,
+                name: fragment.name,
+            })
+            .collect();
+        Ok(Self::new(fragments, &appearances, states))
+    }
+
+    /// the valence at every annotated fragment as a line chart, with fragments that are missing
+    /// an annotation marked along the bottom. points link to the fragment's entry on the page.
+    pub fn svg(&self) -> String {
+        let levels = (VALENCE.end() - VALENCE.start()) as f64;
+        let width = MARGIN * 2.0 + self.beats.len().max(1) as f64 * STEP;
+        let height = MARGIN * 2.0 + levels * LEVEL + LEVEL;
+        let x = |position: usize| MARGIN + (position as f64 - 0.5) * STEP;
+        let y = |valence: i16| MARGIN + (VALENCE.end() - valence) as f64 * LEVEL;
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" id="svg" class="arc-chart" viewBox="0 0 {} {}">"#,
+            width, height
+        );
+        svg.push_str(concat!(
+            "<style>",
+            ".axis{stroke:#bbb;stroke-width:1}.zero{stroke:#888}",
+            ".arc{fill:none;stroke:#e8896b;stroke-width:2}",
+            ".state{fill:#e8896b;stroke:#9c4a31}.unrated{fill:#fff;stroke:#9c4a31}",
+            ".missing{fill:#c0392b}",
+            "text{font-family:sans-serif;font-size:10px;fill:#333}",
+            "</style>"
+        ));
+        for valence in [*VALENCE.end(), 0, *VALENCE.start()] {
+            let _ = write!(
+                svg,
+                r#"<line class="axis{}" x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}"/><text x="4" y="{:.1}">{:+}</text>"#,
+                if valence == 0 { " zero" } else { "" },
+                MARGIN,
+                y(valence),
+                width - MARGIN,
+                y(valence),
+                y(valence) + 3.0,
+                valence
+            );
+        }
+
+        let points: Vec<String> = self
+            .beats
+            .iter()
+            .filter_map(|beat| {
+                let valence = beat.state.as_ref()?.valence?;
+                Some(format!("{:.1},{:.1}", x(beat.position), y(valence)))
+            })
+            .collect();
+        if points.len() > 1 {
+            let _ = write!(
+                svg,
+                r#"<polyline class="arc" points="{}"/>"#,
+                points.join(" ")
+            );
+        }
+
+        let bottom = height - MARGIN / 2.0;
+        for beat in &self.beats {
+            let name = escape_html(&beat.fragment.name);
+            let _ = write!(
+                svg,
+                r##"<a href="#fragment-{}"><text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"##,
+                beat.fragment.id,
+                x(beat.position),
+                bottom,
+                beat.position
+            );
+            match &beat.state {
+                Some(state) => {
+                    let (class, valence) = match state.valence {
+                        Some(valence) => ("state", valence),
+                        None => ("unrated", 0),
+                    };
+                    let emotion = state
+                        .emotion
+                        .as_deref()
+                        .map(escape_html)
+                        .unwrap_or_default();
+                    let _ = write!(
+                        svg,
+                        r#"<circle class="{}" cx="{:.1}" cy="{:.1}" r="5"><title>{}: {}</title></circle>"#,
+                        class,
+                        x(beat.position),
+                        y(valence),
+                        name,
+                        emotion
+                    );
+                }
+                None if beat.appears => {
+                    let _ = write!(
+                        svg,
+                        r#"<rect class="missing" x="{:.1}" y="{:.1}" width="8" height="8"><title>{} isn't annotated</title></rect>"#,
+                        x(beat.position) - 4.0,
+                        y(*VALENCE.start()) + 4.0,
+                        name
+                    );
+                }
+                None => {}
+            }
+            svg.push_str("</a>");
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
@@ -1,15 +1,28 @@
 use super::api::*;
-use crate::assets::graphs::render_graph;
+use super::arcs::{self, ArcState, CharacterArc};
+use super::gedcom::{self, Gedcom};
+use super::kinship::{self, Role};
+use super::merge::{self, Footprint, MergeBuilder, MergePreview, MergeRecord};
+use super::sheets::{self, CharacterSheet, SchemaBuilder, SchemaDefinition, SheetSchema};
+use crate::assets::graphs::EntityExt;
+use crate::calendars::api::{CalendarDate, CalendarDefinition};
+use crate::calendars::frontend::preferred;
 use crate::assets::images::{ImageBuilder, ImageData, ImageForm};
 use crate::get_access_token;
 use crate::{ApiClient, assets::images::ImageProcessor, auth::Guard, model::Character};
 use crate::errors::ApiError;
+use crate::model::Story;
+use crate::render::SupportedRender;
+use crate::stories::StoryRender;
+use crate::render::pdf::{
+    PdfBlock, PdfExport, PdfOptions, PdfRenderer, PdfSection, content_blocks,
+};
 use rocket::{
-    Route, State,
+    Either, Route, State,
     form::{Form, FromForm},
     fs::TempFile,
     get,
-    http::CookieJar,
+    http::{ContentType, CookieJar},
     post,
     response::{Redirect, content::RawHtml},
     routes,
@@ -17,6 +30,7 @@ use rocket::{
 use rocket_dyn_templates::{Template, context};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use tokio::io::AsyncReadExt;
 use uuid::Uuid;
 
 #[post("/", data = "<form>")]
@@ -39,19 +53,84 @@ async fn create_character<'f>(
     Ok(Redirect::to("/characters/"))
 }
 
+/// characters that were merged into another redirect to the one they were merged into.
 #[get("/<id>")]
 async fn get_character(
     guard: Guard,
     id: Uuid,
     api: &State<ApiClient>,
-) -> Result<RawHtml<Template>, ApiError> {
-    let render: CharacterRender = api
+) -> Result<Either<Redirect, RawHtml<Template>>, ApiError> {
+    let render: CharacterRender = match api
         .get_protected(&format!("/characters/{}", id), guard.access_token(), None)
-        .await?;
-    Ok(RawHtml(Template::render(
+        .await
+    {
+        Ok(render) => render,
+        Err(err) => {
+            return match MergeRecord::find(api, guard.access_token(), id).await? {
+                Some(record) => Ok(Either::Left(Redirect::to(format!(
+                    "/characters/{}",
+                    record.survivor
+                )))),
+                None => Err(err),
+            };
+        }
+    };
+    let attached = CharacterSheet::list(api, guard.access_token(), id).await?;
+    let (sheets, schemas): (Vec<SheetSchema>, Vec<SheetSchema>) =
+        SheetSchema::list(api, guard.access_token())
+            .await?
+            .into_iter()
+            .partition(|schema| attached.iter().any(|sheet| sheet.schema == schema.id));
+    Ok(Either::Right(RawHtml(Template::render(
         "characters/character",
-        context! { title: render.name.clone(), character: render },
-    )))
+        context! { title: render.name.clone(), character: render, sheets, schemas },
+    ))))
+}
+
+/// a printable character sheet for tabletop sessions.
+#[get("/<id>/sheet.pdf?<options..>")]
+async fn character_sheet(
+    guard: Guard,
+    id: Uuid,
+    options: PdfOptions,
+    api: &State<ApiClient>,
+    processor: &State<ImageProcessor>,
+    pdf: &State<PdfRenderer>,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let character: CharacterRender = api
+        .get_protected(&format!("/characters/{}", id), guard.access_token(), None)
+        .await?;
+    let request = character.request(api, guard.access_token());
+    let fragments = character.fragments(request.clone()).await?;
+    let locations = character.locations(request).await?;
+
+    let thumbnail = match &character.thumbnail {
+        Some(image) => processor.load_url(&image.url).await,
+        None => None,
+    };
+    let mut section = PdfSection::new(&character.name).image(thumbnail);
+    if let Some(tags) = character.tags.as_ref().filter(|tags| !tags.is_empty()) {
+        section = section.field("Tags", &tags.join(", "));
+    }
+    let description = character.description.as_deref().unwrap_or("");
+    section = section.blocks(content_blocks(SupportedRender::Text, description));
+    if !fragments.is_empty() {
+        let names: Vec<&str> = fragments.iter().map(|fragment| fragment.name.as_str()).collect();
+        section = section.field("Appears In", &names.join(", "));
+    }
+    if !locations.is_empty() {
+        let names: Vec<&str> = locations.iter().map(|location| location.name.as_str()).collect();
+        section = section.field("Locations", &names.join(", "));
+    }
+
+    let mut export = PdfExport::new(&character.name)
+        .title_page(false)
+        .section(section);
+    for sheet in CharacterSheet::list(api, guard.access_token(), id).await? {
+        let schema = SheetSchema::fetch(api, guard.access_token(), sheet.schema).await?;
+        export = export.section(sheet_section(&character.name, &schema, &sheet));
+    }
+    Ok((ContentType::PDF, pdf.render(export, options).await?))
 }
 
 #[get("/create")]
@@ -85,14 +164,408 @@ async fn list_characters(
 }
 
 #[get("/trees/<id>")]
-async fn get_tree(guard: Guard, id: Uuid, api: &State<ApiClient>) -> Result<RawHtml<Template>, ApiError> {
-    let url = format!("/characters/{}", id);
-    let character: Character = api.get_protected(url, guard.access_token(), None).await?;
-    let (graph, index_map) = Character::family_tree(id, &api, guard.access_token()).await?;
-    let svg = render_graph(graph, HashMap::new());
+async fn get_tree(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    jar: &CookieJar<'_>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let family = Character::family_tree(id, api, guard.access_token()).await?;
+    let name = match family.member(id) {
+        Some(member) => member.name.clone(),
+        None => return Err(ApiError::NotFound(format!("character {}", id))),
+    };
+    let calendar = preferred(api, guard.access_token(), jar).await;
+    let tree = family.svg(id, &calendar);
+    let relatives = family.relatives(id, &calendar);
+    let characters: Option<Vec<CharacterRender>> = api
+        .get_protected("/characters", guard.access_token(), None)
+        .await?;
+    let characters: Vec<CharacterRender> = characters
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|character| character.id != id)
+        .collect();
+    let roles: Vec<(&str, &str)> = Role::ALL
+        .iter()
+        .map(|role| (role.as_str(), role.label()))
+        .collect();
     Ok(RawHtml(Template::render(
         "characters/tree",
-        context! { title: character.name, tree: svg },
+        context! { title: name.clone(), id, name, tree, relatives, characters, roles },
+    )))
+}
+
+#[derive(Debug, FromForm)]
+struct KinshipForm {
+    relative: Uuid,
+    /// what the relative is to the character whose tree it is.
+    role: Role,
+    /// `yyyy-mm-dd`, for spouses and partners.
+    begins: Option<String>,
+    ends: Option<String>,
+}
+
+/// a day from a date input, which is always gregorian.
+fn form_day(value: Option<&str>) -> Result<Option<i64>, ApiError> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+    let invalid = || ApiError::UnprocessableEntity(format!("{} is not a valid date", value));
+    let mut parts = value.rsplitn(3, '-');
+    let (Some(day), Some(month), Some(year)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let date = CalendarDate {
+        year: year.parse().map_err(|_| invalid())?,
+        month: month.parse().map_err(|_| invalid())?,
+        day: day.parse().map_err(|_| invalid())?,
+    };
+    CalendarDefinition::gregorian().to_day(date).map(Some)
+}
+
+#[post("/trees/<id>/kinship", data = "<form>")]
+async fn add_kinship(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<KinshipForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let begins = form_day(form.begins.as_deref())?;
+    let ends = form_day(form.ends.as_deref())?;
+    let builder = form.role.builder(id, form.relative, begins, ends);
+    builder.build(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!("/characters/trees/{}", id)))
+}
+
+#[post("/trees/<id>/kinship/<kinship>/remove")]
+async fn remove_kinship(
+    guard: Guard,
+    id: Uuid,
+    kinship: Uuid,
+    api: &State<ApiClient>,
+) -> Result<Redirect, ApiError> {
+    kinship::remove(api, guard.access_token(), kinship).await?;
+    Ok(Redirect::to(format!("/characters/trees/{}", id)))
+}
+
+/// the family of a character as a GEDCOM 5.5.1 file.
+#[get("/trees/<id>/export.ged")]
+async fn export_gedcom(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+) -> Result<(ContentType, String), ApiError> {
+    let family = Character::family_tree(id, api, guard.access_token()).await?;
+    let content_type = ContentType::new("text", "vnd.familysearch.gedcom");
+    Ok((content_type, gedcom::write(&family)))
+}
+
+#[derive(Debug, FromForm)]
+pub struct GedcomForm<'r> {
+    /// a `.ged` file exported from a genealogy program.
+    file: TempFile<'r>,
+}
+
+#[get("/trees/import")]
+async fn import_gedcom_html(guard: Guard) -> RawHtml<Template> {
+    RawHtml(Template::render(
+        "characters/import",
+        context! { title: "import a family" },
+    ))
+}
+
+/// creates a character for every individual of an uploaded GEDCOM file.
+#[post("/trees/import", data = "<form>")]
+async fn import_gedcom<'r>(
+    guard: Guard,
+    api: &State<ApiClient>,
+    form: Form<GedcomForm<'r>>,
+) -> Result<Either<Redirect, RawHtml<Template>>, ApiError> {
+    let form = form.into_inner();
+    let mut bytes = Vec::new();
+    form.file.open().await?.read_to_end(&mut bytes).await?;
+    let gedcom = Gedcom::parse(&String::from_utf8_lossy(&bytes))?;
+    let report = gedcom.import(api, guard.access_token()).await?;
+    if !report.skipped.is_empty() {
+        return Ok(Either::Right(RawHtml(Template::render(
+            "characters/import_report",
+            context! { title: "family import", report },
+        ))));
+    }
+    let redirect = match report.created.first() {
+        Some(id) => Redirect::to(format!("/characters/trees/{}", id)),
+        None => Redirect::to("/characters/"),
+    };
+    Ok(Either::Left(redirect))
+}
+
+/// the stat block of one game system, a field per attribute under its section heading.
+fn sheet_section(name: &str, schema: &SheetSchema, sheet: &CharacterSheet) -> PdfSection {
+    let title = match &schema.system {
+        Some(system) => format!("{} ({})", name, system),
+        None => format!("{} ({})", name, schema.name),
+    };
+    let mut section = PdfSection::new(&title);
+    for part in schema.definition.sections(&sheet.data) {
+        if let Some(heading) = &part.name {
+            section = section.block(PdfBlock::Heading(heading.clone()));
+        }
+        for field in part.fields {
+            let value = field.display.unwrap_or_else(|| "—".to_string());
+            section = section.field(&field.label, &value);
+        }
+    }
+    section
+}
+
+#[get("/sheets")]
+async fn list_sheet_schemas(
+    guard: Guard,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let schemas = SheetSchema::list(api, guard.access_token()).await?;
+    Ok(RawHtml(Template::render(
+        "characters/sheets/index",
+        context! { title: "character sheets", schemas, templates: &sheets::TEMPLATES },
+    )))
+}
+
+/// the create form, filled in from one of the built in templates when one is given.
+#[get("/sheets/create?<template>")]
+async fn create_sheet_schema_html(guard: Guard, template: Option<&str>) -> RawHtml<Template> {
+    let template = template.and_then(sheets::template);
+    RawHtml(Template::render(
+        "characters/sheets/create",
+        context! { title: "create sheet schema", template, templates: &sheets::TEMPLATES },
+    ))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct SchemaForm {
+    name: String,
+    system: Option<String>,
+    description: Option<String>,
+    /// one attribute per line as `key, label, type` and what the type needs.
+    attributes: String,
+}
+
+#[post("/sheets", data = "<form>")]
+async fn create_sheet_schema(
+    guard: Guard,
+    api: &State<ApiClient>,
+    form: Form<SchemaForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let builder = SchemaBuilder {
+        name: form.name,
+        system: form.system.filter(|system| !system.trim().is_empty()),
+        description: form
+            .description
+            .filter(|description| !description.trim().is_empty()),
+        definition: SchemaDefinition::parse(&form.attributes)?,
+    };
+    builder.build(api, guard.access_token()).await?;
+    Ok(Redirect::to("/characters/sheets"))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct AttachForm {
+    schema: Uuid,
+}
+
+#[post("/sheets/<id>", data = "<form>")]
+async fn attach_sheet(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<AttachForm>,
+) -> Result<Redirect, ApiError> {
+    let sheet = CharacterSheet {
+        character: id,
+        schema: form.schema,
+        data: Default::default(),
+    };
+    sheet.save(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!(
+        "/characters/sheets/{}/{}",
+        id, form.schema
+    )))
+}
+
+/// a form generated from the schema, with the computed attributes worked out.
+#[get("/sheets/<id>/<schema>")]
+async fn get_sheet(
+    guard: Guard,
+    id: Uuid,
+    schema: Uuid,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let character: CharacterRender = api
+        .get_protected(&format!("/characters/{}", id), guard.access_token(), None)
+        .await?;
+    let schema = SheetSchema::fetch(api, guard.access_token(), schema).await?;
+    let sheet = CharacterSheet::list(api, guard.access_token(), id)
+        .await?
+        .into_iter()
+        .find(|sheet| sheet.schema == schema.id)
+        .ok_or_else(|| {
+            ApiError::NotFound(format!("{} sheet for {}", schema.name, character.name))
+        })?;
+    let sections = schema.definition.sections(&sheet.data);
+    Ok(RawHtml(Template::render(
+        "characters/sheet",
+        context! {
+            title: format!("{} - {}", character.name, schema.name),
+            character,
+            schema,
+            sections,
+        },
+    )))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct SheetForm {
+    values: HashMap<String, String>,
+}
+
+#[post("/sheets/<id>/<schema>", data = "<form>")]
+async fn save_sheet(
+    guard: Guard,
+    id: Uuid,
+    schema: Uuid,
+    api: &State<ApiClient>,
+    form: Form<SheetForm>,
+) -> Result<Redirect, ApiError> {
+    let schema = SheetSchema::fetch(api, guard.access_token(), schema).await?;
+    let sheet = CharacterSheet {
+        character: id,
+        schema: schema.id,
+        data: schema.definition.values(&form.values)?,
+    };
+    sheet.save(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!(
+        "/characters/sheets/{}/{}",
+        id, schema.id
+    )))
+}
+
+#[post("/sheets/<id>/<schema>/remove")]
+async fn remove_sheet(
+    guard: Guard,
+    id: Uuid,
+    schema: Uuid,
+    api: &State<ApiClient>,
+) -> Result<Redirect, ApiError> {
+    CharacterSheet::remove(api, guard.access_token(), id, schema).await?;
+    Ok(Redirect::to(format!("/characters/{}", id)))
+}
+
+/// the stories whose fragments the character's arc can be walked through.
+#[get("/arcs/<id>")]
+async fn list_arcs(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let character: CharacterRender = api
+        .get_protected(&format!("/characters/{}", id), guard.access_token(), None)
+        .await?;
+    let stories: Vec<Story> = api
+        .get_protected("/stories", guard.access_token(), None)
+        .await?;
+    Ok(RawHtml(Template::render(
+        "characters/arcs",
+        context! { title: format!("arcs of {}", character.name), character, stories },
+    )))
+}
+
+/// the character's state at each of the story's fragments in `idx` order.
+#[get("/arcs/<id>/<story>")]
+async fn get_arc(
+    guard: Guard,
+    id: Uuid,
+    story: Uuid,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let character: CharacterRender = api
+        .get_protected(&format!("/characters/{}", id), guard.access_token(), None)
+        .await?;
+    let story: StoryRender = api
+        .get_protected(&format!("/stories/{}", story), guard.access_token(), None)
+        .await?;
+    let arc = CharacterArc::fetch(api, guard.access_token(), &character, &story).await?;
+    let chart = arc.svg();
+    Ok(RawHtml(Template::render(
+        "characters/arc",
+        context! {
+            title: format!("{} in {}", character.name, story.name),
+            character,
+            story,
+            arc,
+            chart,
+            valence: arcs::VALENCE.collect::<Vec<i16>>(),
+        },
+    )))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct ArcForm {
+    fragment: Uuid,
+    goals: Option<String>,
+    emotion: Option<String>,
+    /// from -5 for despair to 5 for elation.
+    valence: Option<i16>,
+    relationships: Option<String>,
+    knowledge: Option<String>,
+    /// one per line as `name: value`.
+    custom: Option<String>,
+}
+
+/// text fields left empty on the form aren't part of the state.
+fn filled(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+#[post("/arcs/<id>/<story>", data = "<form>")]
+async fn save_arc_state(
+    guard: Guard,
+    id: Uuid,
+    story: Uuid,
+    api: &State<ApiClient>,
+    form: Form<ArcForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let state = ArcState {
+        character: id,
+        fragment: form.fragment,
+        goals: filled(form.goals),
+        emotion: filled(form.emotion),
+        valence: form.valence,
+        relationships: filled(form.relationships),
+        knowledge: filled(form.knowledge),
+        custom: arcs::parse_custom(form.custom.as_deref().unwrap_or_default())?,
+    };
+    state.save(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!(
+        "/characters/arcs/{}/{}#fragment-{}",
+        id, story, form.fragment
+    )))
+}
+
+#[post("/arcs/<id>/<story>/<fragment>/remove")]
+async fn remove_arc_state(
+    guard: Guard,
+    id: Uuid,
+    story: Uuid,
+    fragment: Uuid,
+    api: &State<ApiClient>,
+) -> Result<Redirect, ApiError> {
+    ArcState::remove(api, guard.access_token(), id, fragment).await?;
+    Ok(Redirect::to(format!(
+        "/characters/arcs/{}/{}#fragment-{}",
+        id, story, fragment
     )))
 }
 
@@ -101,9 +574,105 @@ pub struct DeleteRequest {
     id: Uuid,
 }
 
+/// what deleting the character takes with it, confirmed by posting to `/characters/delete`.
+#[get("/delete/<id>")]
+async fn delete_character_html(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let character = Footprint::fetch(api, guard.access_token(), id).await?;
+    let cascade = character.cascade();
+    Ok(RawHtml(Template::render(
+        "characters/delete",
+        context! { title: format!("Delete {}", character.name), character, cascade },
+    )))
+}
+
 // this method could be the same for all entities
 #[post("/delete", data = "<form>")]
-async fn delete_entity(guard: Guard, form: Form<DeleteRequest>, api: &State<ApiClient>) {}
+async fn delete_entity(
+    guard: Guard,
+    form: Form<DeleteRequest>,
+    api: &State<ApiClient>,
+) -> Result<Redirect, ApiError> {
+    Footprint::delete(api, guard.access_token(), form.id).await?;
+    Ok(Redirect::to("/characters/"))
+}
+
+/// picks a duplicate to merge into the character, then previews the merge.
+#[get("/merge/<id>?<other>")]
+async fn merge_character_html(
+    guard: Guard,
+    id: Uuid,
+    other: Option<Uuid>,
+    api: &State<ApiClient>,
+    jar: &CookieJar<'_>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let survivor = Footprint::fetch(api, guard.access_token(), id).await?;
+    let Some(other) = other else {
+        let characters: Option<Vec<CharacterRender>> = api
+            .get_protected("/characters", guard.access_token(), None)
+            .await?;
+        let (duplicates, characters): (Vec<CharacterRender>, Vec<CharacterRender>) = characters
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|character| character.id != id)
+            .partition(|character| merge::likely_duplicate(&survivor.name, &character.name));
+        return Ok(RawHtml(Template::render(
+            "characters/merge",
+            context! {
+                title: format!("Merge into {}", survivor.name),
+                survivor,
+                duplicates,
+                characters,
+            },
+        )));
+    };
+    let merged = Footprint::fetch(api, guard.access_token(), other).await?;
+    let mut names: HashMap<Uuid, String> = SheetSchema::list(api, guard.access_token())
+        .await?
+        .into_iter()
+        .map(|schema| (schema.id, schema.name))
+        .collect();
+    for link in survivor.links.iter().chain(&merged.links) {
+        if let Some(name) = &link.name {
+            names.insert(link.entity, name.clone());
+        }
+    }
+    let calendar = preferred(api, guard.access_token(), jar).await;
+    let preview = MergePreview::new(survivor, merged, &names, &calendar);
+    Ok(RawHtml(Template::render(
+        "characters/merge_preview",
+        context! {
+            title: format!("Merge {} into {}", preview.merged.name, preview.survivor.name),
+            preview,
+        },
+    )))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct MergeForm {
+    description: Option<String>,
+    gender: Option<String>,
+}
+
+#[post("/merge/<id>/<other>", data = "<form>")]
+async fn merge_character(
+    guard: Guard,
+    id: Uuid,
+    other: Uuid,
+    api: &State<ApiClient>,
+    form: Form<MergeForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let builder = MergeBuilder {
+        description: form.description.filter(|description| !description.trim().is_empty()),
+        gender: form.gender.filter(|gender| !gender.is_empty()),
+    };
+    builder.build(api, guard.access_token(), id, other).await?;
+    Ok(Redirect::to(format!("/characters/{}", id)))
+}
 
 #[derive(FromForm, Debug)]
 pub struct CharacterBuilderForm<'r> {
@@ -147,6 +716,7 @@ impl<'r> CharacterBuilderForm<'r> {
             name,
             description,
             tags,
+            gender: None,
             thumbnail,
         })
     }
@@ -158,6 +728,27 @@ pub fn get_routes() -> Vec<Route> {
         list_characters,
         get_character,
         create_character,
-        get_tree
+        get_tree,
+        add_kinship,
+        remove_kinship,
+        export_gedcom,
+        import_gedcom_html,
+        import_gedcom,
+        character_sheet,
+        list_sheet_schemas,
+        create_sheet_schema_html,
+        create_sheet_schema,
+        attach_sheet,
+        get_sheet,
+        save_sheet,
+        remove_sheet,
+        list_arcs,
+        get_arc,
+        save_arc_state,
+        remove_arc_state,
+        delete_character_html,
+        delete_entity,
+        merge_character_html,
+        merge_character
     ]
 }
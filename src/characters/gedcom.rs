@@ -0,0 +1,656 @@
+//! GEDCOM 5.5.1 import and export of families.
+//!
+//! individuals become characters and families become kinship. the husband and wife of a family
+//! are spouses when it records a marriage and partners otherwise, and every child of the family
+//! is a child of both. adoptions are read from the `PEDI` of a child's `FAMC` link and from the
+//! `_FREL` and `_MREL` tags most genealogy programs put under a family's `CHIL`, which is also
+//! where step children are written. siblings without a parent in common are written as a family
+//! with children and no husband or wife.
+//!
+//! of an individual only the name, sex and notes are kept, births and deaths become lines of the
+//! character's description.
+use crate::ApiClient;
+use crate::calendars::api::{CalendarDate, CalendarDefinition};
+use crate::characters::api::CharacterBuilder;
+use crate::characters::kinship::{Family, KinshipBuilder, KinshipKind};
+use crate::errors::ApiError;
+use crate::model::Character;
+use std::collections::HashMap;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+/// text longer than this many characters is split over `CONC` lines.
+const LINE_LIMIT: usize = 200;
+/// the gender of individuals whose sex is missing or `U`, so the api doesn't pick its default.
+pub const UNKNOWN_GENDER: &str = "unknown";
+
+/// a line of a GEDCOM file and the lines nested under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub xref: Option<String>,
+    pub tag: String,
+    pub value: String,
+    pub children: Vec<Record>,
+}
+
+impl Record {
+    fn find(&self, tag: &str) -> Option<&Record> {
+        self.children.iter().find(|child| child.tag == tag)
+    }
+
+    fn all<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Record> + 'a {
+        self.children.iter().filter(move |child| child.tag == tag)
+    }
+
+    /// the value with its `CONT` and `CONC` lines put back together.
+    fn text(&self) -> String {
+        let mut text = self.value.clone();
+        for child in self.children.iter() {
+            match child.tag.as_str() {
+                "CONT" => {
+                    text.push('\n');
+                    text.push_str(&child.value);
+                }
+                "CONC" => text.push_str(&child.value),
+                _ => {}
+            }
+        }
+        text.replace("@@", "@")
+    }
+}
+
+/// the records of a file, level zero lines with everything under them.
+pub fn records(source: &str) -> Result<Vec<Record>, ApiError> {
+    let source = crate::normalize_newlines(source.trim_start_matches('\u{feff}'));
+    // records still being read, with their level.
+    let mut open: Vec<(usize, Record)> = Vec::new();
+    let mut records = Vec::new();
+    let close = |open: &mut Vec<(usize, Record)>, records: &mut Vec<Record>| {
+        let (_, record) = open.pop()?;
+        match open.last_mut() {
+            Some((_, parent)) => parent.children.push(record),
+            None => records.push(record),
+        }
+        Some(())
+    };
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim_start();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || ApiError::UnprocessableEntity(format!("line {} isn't GEDCOM", number + 1));
+        let (level, rest) = line.split_once(' ').ok_or_else(invalid)?;
+        let level: usize = level.parse().map_err(|_| invalid())?;
+        let (xref, rest) = match rest.strip_prefix('@') {
+            Some(rest) => {
+                let (xref, rest) = rest.split_once("@ ").ok_or_else(invalid)?;
+                (Some(format!("@{}@", xref)), rest)
+            }
+            None => (None, rest),
+        };
+        let (tag, value) = rest.split_once(' ').unwrap_or((rest, ""));
+        if tag.is_empty() || level > open.last().map_or(0, |(level, _)| level + 1) {
+            return Err(invalid());
+        }
+        while open.last().is_some_and(|(open, _)| *open >= level) {
+            close(&mut open, &mut records);
+        }
+        let record = Record {
+            xref,
+            tag: tag.to_ascii_uppercase(),
+            value: value.to_string(),
+            children: Vec::new(),
+        };
+        open.push((level, record));
+    }
+    while close(&mut open, &mut records).is_some() {}
+    Ok(records)
+}
+
+/// days since 1970-01-01 from a GEDCOM date such as `12 JUN 1850`, `ABT 1850` or
+/// `BET JUN 1850 AND 1851`. only the first date of a range is used, missing days and months
+/// are taken to be the first.
+pub fn parse_date(text: &str) -> Option<i64> {
+    let text = text.to_ascii_uppercase().replace("@#DGREGORIAN@", "");
+    let text = match text.split_once('(') {
+        Some((date, _)) => date.to_string(),
+        None => text,
+    };
+    let qualifiers = ["ABT", "CAL", "EST", "BEF", "AFT", "FROM", "BET", "INT"];
+    let tokens: Vec<&str> = text
+        .split_whitespace()
+        .skip_while(|token| qualifiers.contains(token))
+        .take_while(|token| !["AND", "TO"].contains(token))
+        .collect();
+    let mut numbers_before = Vec::new();
+    let mut numbers_after = Vec::new();
+    let mut month = None;
+    let mut before_christ = false;
+    for token in tokens {
+        if let Some(index) = MONTHS.iter().position(|name| *name == token) {
+            month = Some(index as u32 + 1);
+        } else if matches!(token, "B.C." | "BC" | "BCE") {
+            before_christ = true;
+        } else {
+            // dual years like 1750/51 are given in the year before the slash.
+            let number: i64 = token.split('/').next()?.parse().ok()?;
+            match month {
+                Some(_) => numbers_after.push(number),
+                None => numbers_before.push(number),
+            }
+        }
+    }
+    let (day, year) = match (month, &numbers_before[..], &numbers_after[..]) {
+        (Some(_), [], [year]) => (1, *year),
+        (Some(_), [day], [year]) => (*day, *year),
+        (None, [year], []) => (1, *year),
+        _ => return None,
+    };
+    let year = if before_christ { 1 - year } else { year };
+    let date = CalendarDate {
+        year,
+        month: month.unwrap_or(1),
+        day: u32::try_from(day).ok()?,
+    };
+    CalendarDefinition::gregorian().to_day(date).ok()
+}
+
+/// a day as a GEDCOM date, `12 JUN 1850` or `15 MAR 44 B.C.`.
+pub fn format_date(day: i64) -> String {
+    let date = CalendarDefinition::gregorian().from_day(day);
+    let month = MONTHS[date.month as usize - 1];
+    if date.year < 1 {
+        format!("{} {} {} B.C.", date.day, month, 1 - date.year)
+    } else {
+        format!("{} {} {}", date.day, month, date.year)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Individual {
+    pub xref: String,
+    pub name: String,
+    /// `male` or `female`, as characters store it.
+    pub gender: Option<String>,
+    pub description: Option<String>,
+}
+
+/// kinship between two individuals, by their cross references.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub character: String,
+    pub relative: String,
+    pub kind: KinshipKind,
+    pub begins: Option<i64>,
+    pub ends: Option<i64>,
+}
+
+/// a link the api refused, such as one that would make a character their own ancestor. the
+/// import carries on without it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedLink {
+    pub character: String,
+    pub relative: String,
+    pub kind: KinshipKind,
+    pub error: String,
+}
+
+/// what [`Gedcom::import`] created.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GedcomImport {
+    /// the characters created, in the order of the file.
+    pub created: Vec<Uuid>,
+    pub skipped: Vec<SkippedLink>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Gedcom {
+    pub individuals: Vec<Individual>,
+    pub links: Vec<Link>,
+}
+
+fn pedigree(value: &str) -> KinshipKind {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "adopted" | "foster" => KinshipKind::AdoptiveParent,
+        "step" => KinshipKind::StepParent,
+        _ => KinshipKind::Parent,
+    }
+}
+
+impl Gedcom {
+    pub fn parse(source: &str) -> Result<Self, ApiError> {
+        let records = records(source)?;
+        let notes: HashMap<&str, String> = records
+            .iter()
+            .filter(|record| record.tag == "NOTE")
+            .filter_map(|record| Some((record.xref.as_deref()?, record.text())))
+            .collect();
+
+        let mut gedcom = Self::default();
+        // how each child is linked to each of its families, from the child's side.
+        let mut pedigrees: HashMap<(&str, &str), KinshipKind> = HashMap::new();
+        for record in records.iter().filter(|record| record.tag == "INDI") {
+            let Some(xref) = record.xref.as_deref() else {
+                continue;
+            };
+            let name = record
+                .find("NAME")
+                .map(|name| name.value.replace('/', " "))
+                .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| "Unnamed".to_string());
+            let gender = match record.find("SEX").map(|sex| sex.value.trim()) {
+                Some("M") => Some("male".to_string()),
+                Some("F") => Some("female".to_string()),
+                _ => None,
+            };
+            let mut paragraphs = Vec::new();
+            for (tag, verb) in [("BIRT", "Born"), ("DEAT", "Died")] {
+                let Some(event) = record.find(tag) else {
+                    continue;
+                };
+                let mut line = verb.to_string();
+                if let Some(date) = event.find("DATE") {
+                    let _ = write!(line, " {}", date.value.trim());
+                }
+                if let Some(place) = event.find("PLAC") {
+                    let _ = write!(line, " in {}", place.value.trim());
+                }
+                if line != verb {
+                    paragraphs.push(format!("{}.", line));
+                }
+            }
+            for note in record.all("NOTE") {
+                let text = match notes.get(note.value.trim()) {
+                    Some(text) => text.clone(),
+                    None => note.text(),
+                };
+                if !text.trim().is_empty() {
+                    paragraphs.push(text);
+                }
+            }
+            for family in record.all("FAMC") {
+                let kind = family.find("PEDI").map(|pedi| pedigree(&pedi.value));
+                if let Some(kind) = kind {
+                    pedigrees.insert((xref, family.value.trim()), kind);
+                }
+            }
+            gedcom.individuals.push(Individual {
+                xref: xref.to_string(),
+                name,
+                gender,
+                description: (!paragraphs.is_empty()).then(|| paragraphs.join("\n\n")),
+            });
+        }
+
+        let known = |xref: &str| gedcom.individuals.iter().any(|person| person.xref == xref);
+        let mut links = Vec::new();
+        for record in records.iter().filter(|record| record.tag == "FAM") {
+            let Some(xref) = record.xref.as_deref() else {
+                continue;
+            };
+            let date = |tag: &str| {
+                record
+                    .find(tag)
+                    .and_then(|event| event.find("DATE"))
+                    .and_then(|date| parse_date(&date.value))
+            };
+            let husband = record.find("HUSB").map(|husband| husband.value.trim());
+            let wife = record.find("WIFE").map(|wife| wife.value.trim());
+            let parents: Vec<(&str, &str)> = [(husband, "_FREL"), (wife, "_MREL")]
+                .into_iter()
+                .filter_map(|(parent, tag)| Some((parent.filter(|xref| known(xref))?, tag)))
+                .collect();
+            if let [(husband, _), (wife, _)] = parents[..] {
+                let married = record.find("MARR").is_some();
+                links.push(Link {
+                    character: husband.to_string(),
+                    relative: wife.to_string(),
+                    kind: if married {
+                        KinshipKind::Spouse
+                    } else {
+                        KinshipKind::Partner
+                    },
+                    begins: date("MARR"),
+                    ends: date("DIV"),
+                });
+            }
+            let children: Vec<&Record> = record
+                .all("CHIL")
+                .filter(|child| known(child.value.trim()))
+                .collect();
+            for child in children.iter() {
+                let xref_child = child.value.trim();
+                for (parent, tag) in parents.iter() {
+                    let kind = child
+                        .find(tag)
+                        .map(|relation| pedigree(&relation.value))
+                        .or_else(|| pedigrees.get(&(xref_child, xref)).copied())
+                        .unwrap_or(KinshipKind::Parent);
+                    links.push(Link {
+                        character: parent.to_string(),
+                        relative: xref_child.to_string(),
+                        kind,
+                        begins: None,
+                        ends: None,
+                    });
+                }
+            }
+            if parents.is_empty() {
+                for (index, first) in children.iter().enumerate() {
+                    for second in children[index + 1..].iter() {
+                        links.push(Link {
+                            character: first.value.trim().to_string(),
+                            relative: second.value.trim().to_string(),
+                            kind: KinshipKind::Sibling,
+                            begins: None,
+                            ends: None,
+                        });
+                    }
+                }
+            }
+        }
+        for link in links {
+            // spouses, partners and siblings are stored once whichever way round they're written.
+            let duplicate = gedcom.links.iter().any(|other| {
+                let same = other.character == link.character && other.relative == link.relative;
+                let reversed = other.character == link.relative && other.relative == link.character;
+                other.kind == link.kind && (same || reversed && !link.kind.is_parent())
+            });
+            if !duplicate && link.character != link.relative {
+                gedcom.links.push(link);
+            }
+        }
+        Ok(gedcom)
+    }
+
+    /// the name of the individual with the cross reference, for reporting.
+    fn name_of<'g>(&'g self, xref: &'g str) -> &'g str {
+        self.individuals
+            .iter()
+            .find(|individual| individual.xref == xref)
+            .map(|individual| individual.name.as_str())
+            .unwrap_or(xref)
+    }
+
+    /// creates a character for every individual and the kinship between them. links the api
+    /// refuses are skipped and reported, since the characters already exist by then.
+    pub async fn import(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+    ) -> Result<GedcomImport, ApiError> {
+        let mut ids = HashMap::new();
+        let mut report = GedcomImport::default();
+        for individual in self.individuals.iter() {
+            let mut builder = CharacterBuilder::new(
+                &individual.name,
+                individual.description.as_deref(),
+                None,
+                None,
+            );
+            let gender = individual.gender.as_deref().unwrap_or(UNKNOWN_GENDER);
+            builder.gender = Some(gender.to_string());
+            let character: Character = builder.build(api, access_token).await?;
+            ids.insert(individual.xref.as_str(), character.id);
+            report.created.push(character.id);
+        }
+        for link in self.links.iter() {
+            let (Some(character), Some(relative)) = (
+                ids.get(link.character.as_str()),
+                ids.get(link.relative.as_str()),
+            ) else {
+                continue;
+            };
+            let builder = KinshipBuilder {
+                character: *character,
+                relative: *relative,
+                kind: link.kind,
+                begins: link.begins,
+                ends: link.ends,
+            };
+            if let Err(err) = builder.build(api, access_token).await {
+                report.skipped.push(SkippedLink {
+                    character: self.name_of(&link.character).to_string(),
+                    relative: self.name_of(&link.relative).to_string(),
+                    kind: link.kind,
+                    error: err.to_string(),
+                });
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// writes `text` as a `tag` line at `level`, continued over `CONT` and `CONC` lines.
+fn push_text(out: &mut String, level: usize, tag: &str, text: &str) {
+    let text = crate::normalize_newlines(text).replace('@', "@@");
+    for (index, line) in text.split('\n').enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let mut chunks = chars
+            .chunks(LINE_LIMIT)
+            .map(|chunk| chunk.iter().collect::<String>());
+        let first = chunks.next().unwrap_or_default();
+        match index {
+            0 => push_line(out, level, tag, &first),
+            _ => push_line(out, level + 1, "CONT", &first),
+        }
+        for chunk in chunks {
+            push_line(out, level + 1, "CONC", &chunk);
+        }
+    }
+}
+
+fn push_line(out: &mut String, level: usize, tag: &str, value: &str) {
+    if value.is_empty() {
+        let _ = writeln!(out, "{} {}", level, tag);
+    } else {
+        let _ = writeln!(out, "{} {} {}", level, tag, value);
+    }
+}
+
+/// a GEDCOM family, the parents sharing it and its children.
+struct Household {
+    partners: Vec<Uuid>,
+    marriage: Option<(Option<i64>, Option<i64>)>,
+    children: Vec<(Uuid, KinshipKind)>,
+}
+
+/// `family` as a GEDCOM 5.5.1 file.
+pub fn write(family: &Family) -> String {
+    let mut households: Vec<Household> = Vec::new();
+    let find = |households: &Vec<Household>, partners: &[Uuid]| {
+        households.iter().position(|household| {
+            household.partners.len() == partners.len()
+                && partners.iter().all(|id| household.partners.contains(id))
+        })
+    };
+    for kinship in family
+        .kinship
+        .iter()
+        .filter(|kinship| kinship.kind.is_couple())
+    {
+        let partners = [kinship.character, kinship.relative];
+        if find(&households, &partners).is_none() {
+            let married = kinship.kind == KinshipKind::Spouse;
+            households.push(Household {
+                partners: partners.to_vec(),
+                marriage: married.then_some((kinship.begins, kinship.ends)),
+                children: Vec::new(),
+            });
+        }
+    }
+    for member in family.members.iter() {
+        let parents = family.parents(member.id);
+        for kind in [
+            KinshipKind::Parent,
+            KinshipKind::AdoptiveParent,
+            KinshipKind::StepParent,
+        ] {
+            let group: Vec<Uuid> = parents
+                .iter()
+                .filter(|(_, parent_kind)| *parent_kind == kind)
+                .map(|(parent, _)| *parent)
+                .collect();
+            for partners in group.chunks(2) {
+                let index = match find(&households, partners) {
+                    Some(index) => index,
+                    None => {
+                        households.push(Household {
+                            partners: partners.to_vec(),
+                            marriage: None,
+                            children: Vec::new(),
+                        });
+                        households.len() - 1
+                    }
+                };
+                households[index].children.push((member.id, kind));
+            }
+        }
+    }
+    // siblings already sharing a parent are siblings in the file without saying so.
+    let mut broods: Vec<Vec<Uuid>> = Vec::new();
+    for kinship in family.kinship.iter() {
+        if kinship.kind != KinshipKind::Sibling {
+            continue;
+        }
+        let first = family.parents(kinship.character);
+        let second = family.parents(kinship.relative);
+        if first
+            .iter()
+            .any(|(parent, _)| second.iter().any(|(other, _)| other == parent))
+        {
+            continue;
+        }
+        let pair = [kinship.character, kinship.relative];
+        let joined: Vec<usize> = (0..broods.len())
+            .filter(|index| pair.iter().any(|id| broods[*index].contains(id)))
+            .collect();
+        let mut brood: Vec<Uuid> = Vec::new();
+        for index in joined.into_iter().rev() {
+            brood.extend(broods.remove(index));
+        }
+        for id in pair {
+            if !brood.contains(&id) {
+                brood.push(id);
+            }
+        }
+        broods.push(brood);
+    }
+    for brood in broods {
+        households.push(Household {
+            partners: Vec::new(),
+            marriage: None,
+            children: brood
+                .into_iter()
+                .map(|id| (id, KinshipKind::Parent))
+                .collect(),
+        });
+    }
+
+    let individual = |id: Uuid| {
+        let index = family.members.iter().position(|member| member.id == id);
+        index.map(|index| format!("@I{}@", index + 1))
+    };
+    let female = |id: Uuid| {
+        family
+            .member(id)
+            .is_some_and(|member| member.gender.eq_ignore_ascii_case("female"))
+    };
+
+    let mut out = String::new();
+    push_line(&mut out, 0, "HEAD", "");
+    push_line(&mut out, 1, "SOUR", "STORYTELLER");
+    push_line(&mut out, 2, "NAME", "storyteller");
+    push_line(&mut out, 1, "SUBM", "@U1@");
+    push_line(&mut out, 1, "GEDC", "");
+    push_line(&mut out, 2, "VERS", "5.5.1");
+    push_line(&mut out, 2, "FORM", "LINEAGE-LINKED");
+    push_line(&mut out, 1, "CHAR", "UTF-8");
+    push_line(&mut out, 0, "@U1@ SUBM", "");
+    push_line(&mut out, 1, "NAME", "storyteller");
+
+    for (index, member) in family.members.iter().enumerate() {
+        push_line(&mut out, 0, &format!("@I{}@ INDI", index + 1), "");
+        push_text(&mut out, 1, "NAME", &member.name.replace('\n', " "));
+        let sex = match member.gender.to_ascii_lowercase().as_str() {
+            "male" => "M",
+            "female" => "F",
+            _ => "U",
+        };
+        push_line(&mut out, 1, "SEX", sex);
+        if let Some(description) = member
+            .description
+            .as_deref()
+            .filter(|text| !text.is_empty())
+        {
+            push_text(&mut out, 1, "NOTE", description);
+        }
+        for (number, household) in households.iter().enumerate() {
+            let reference = format!("@F{}@", number + 1);
+            for (child, kind) in household.children.iter() {
+                if *child == member.id {
+                    push_line(&mut out, 1, "FAMC", &reference);
+                    if *kind == KinshipKind::AdoptiveParent {
+                        push_line(&mut out, 2, "PEDI", "adopted");
+                    }
+                }
+            }
+            if household.partners.contains(&member.id) {
+                push_line(&mut out, 1, "FAMS", &reference);
+            }
+        }
+    }
+
+    for (number, household) in households.iter().enumerate() {
+        push_line(&mut out, 0, &format!("@F{}@ FAM", number + 1), "");
+        let mut partners = household.partners.clone();
+        if partners.len() == 2 && female(partners[0]) && !female(partners[1]) {
+            partners.swap(0, 1);
+        }
+        // a lone parent is a wife when she's a woman and a husband otherwise.
+        let slots: Vec<(&str, &str)> = match partners.len() {
+            1 if female(partners[0]) => vec![("WIFE", "_MREL")],
+            _ => vec![("HUSB", "_FREL"), ("WIFE", "_MREL")],
+        };
+        for (partner, (tag, _)) in partners.iter().zip(slots.iter()) {
+            if let Some(reference) = individual(*partner) {
+                push_line(&mut out, 1, tag, &reference);
+            }
+        }
+        if let Some((married, divorced)) = household.marriage {
+            push_line(
+                &mut out,
+                1,
+                "MARR",
+                if married.is_none() { "Y" } else { "" },
+            );
+            if let Some(day) = married {
+                push_line(&mut out, 2, "DATE", &format_date(day));
+            }
+            if let Some(day) = divorced {
+                push_line(&mut out, 1, "DIV", "");
+                push_line(&mut out, 2, "DATE", &format_date(day));
+            }
+        }
+        for (child, kind) in household.children.iter() {
+            let Some(reference) = individual(*child) else {
+                continue;
+            };
+            push_line(&mut out, 1, "CHIL", &reference);
+            let relation = match kind {
+                KinshipKind::AdoptiveParent => "Adopted",
+                KinshipKind::StepParent => "Step",
+                _ => continue,
+            };
+            for (_, (_, tag)) in partners.iter().zip(slots.iter()) {
+                push_line(&mut out, 2, tag, relation);
+            }
+        }
+    }
+    push_line(&mut out, 0, "TRLR", "");
+    out
+}
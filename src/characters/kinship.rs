@@ -0,0 +1,579 @@
+//! typed family relations between characters and the family trees drawn from them.
+//!
+//! kinship is stored as an edge between two characters. the parent kinds point from the parent
+//! to the child, spouse, partner and sibling read the same either way round. a [`Family`] is
+//! every character reachable from one through kinship, drawn a generation to a row.
+use crate::ApiClient;
+use crate::calendars::api::Calendar;
+use crate::errors::ApiError;
+use crate::render::escape_html;
+use crate::timelines::lanes::truncate;
+use reqwest::Method;
+use rocket::FromFormField;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use uuid::Uuid;
+
+const BOX_WIDTH: f64 = 140.0;
+const BOX_HEIGHT: f64 = 36.0;
+const COLUMN_GAP: f64 = 30.0;
+const ROW_GAP: f64 = 70.0;
+const MARGIN: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KinshipKind {
+    Parent,
+    AdoptiveParent,
+    StepParent,
+    Spouse,
+    Partner,
+    Sibling,
+}
+
+impl KinshipKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Parent => "parent",
+            Self::AdoptiveParent => "adoptive_parent",
+            Self::StepParent => "step_parent",
+            Self::Spouse => "spouse",
+            Self::Partner => "partner",
+            Self::Sibling => "sibling",
+        }
+    }
+
+    /// whether the kinship points from a parent to a child.
+    pub fn is_parent(&self) -> bool {
+        matches!(self, Self::Parent | Self::AdoptiveParent | Self::StepParent)
+    }
+
+    /// whether the two characters are a couple.
+    pub fn is_couple(&self) -> bool {
+        matches!(self, Self::Spouse | Self::Partner)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Kinship {
+    pub id: Uuid,
+    /// the parent for the parent kinds.
+    pub character: Uuid,
+    pub relative: Uuid,
+    pub kind: KinshipKind,
+    /// days since 1970-01-01, a wedding or the start of a partnership.
+    pub begins: Option<i64>,
+    /// days since 1970-01-01, a divorce or a separation.
+    pub ends: Option<i64>,
+}
+
+impl Kinship {
+    /// when the kinship began and ended, such as `from 1 June 1850 until 3 March 1860`.
+    pub fn dates(&self, calendar: &Calendar) -> Option<String> {
+        let mut dates = Vec::new();
+        if let Some(begins) = self.begins {
+            dates.push(format!("from {}", calendar.format(begins)));
+        }
+        if let Some(ends) = self.ends {
+            dates.push(format!("until {}", calendar.format(ends)));
+        }
+        (!dates.is_empty()).then(|| dates.join(" "))
+    }
+
+    /// the other character of the kinship, `None` when `id` isn't part of it.
+    pub fn other(&self, id: Uuid) -> Option<Uuid> {
+        if self.character == id {
+            Some(self.relative)
+        } else if self.relative == id {
+            Some(self.character)
+        } else {
+            None
+        }
+    }
+}
+
+/// the body of `POST /kinship`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KinshipBuilder {
+    pub character: Uuid,
+    pub relative: Uuid,
+    pub kind: KinshipKind,
+    pub begins: Option<i64>,
+    pub ends: Option<i64>,
+}
+
+impl KinshipBuilder {
+    pub async fn build(&self, api: &ApiClient, access_token: &str) -> Result<Kinship, ApiError> {
+        api.post("/kinship", access_token, None, self).await
+    }
+}
+
+pub async fn remove(api: &ApiClient, access_token: &str, kinship: Uuid) -> Result<(), ApiError> {
+    let url = format!("/kinship/{}", kinship);
+    let _: serde_json::Value = api
+        .empty_request()
+        .method(Method::DELETE)
+        .access_token(access_token)
+        .route(&url)
+        .send()
+        .await?;
+    Ok(())
+}
+
+/// what a relative is to a character, kinship as seen from one of its two sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Parent,
+    Child,
+    #[field(value = "adoptive_parent")]
+    AdoptiveParent,
+    #[field(value = "adopted_child")]
+    AdoptedChild,
+    #[field(value = "step_parent")]
+    StepParent,
+    #[field(value = "step_child")]
+    StepChild,
+    Spouse,
+    Partner,
+    Sibling,
+}
+
+impl Role {
+    pub const ALL: [Role; 9] = [
+        Self::Parent,
+        Self::Child,
+        Self::AdoptiveParent,
+        Self::AdoptedChild,
+        Self::StepParent,
+        Self::StepChild,
+        Self::Spouse,
+        Self::Partner,
+        Self::Sibling,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Parent => "parent",
+            Self::Child => "child",
+            Self::AdoptiveParent => "adoptive_parent",
+            Self::AdoptedChild => "adopted_child",
+            Self::StepParent => "step_parent",
+            Self::StepChild => "step_child",
+            Self::Spouse => "spouse",
+            Self::Partner => "partner",
+            Self::Sibling => "sibling",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Parent => "parent",
+            Self::Child => "child",
+            Self::AdoptiveParent => "adoptive parent",
+            Self::AdoptedChild => "adopted child",
+            Self::StepParent => "step parent",
+            Self::StepChild => "step child",
+            Self::Spouse => "spouse",
+            Self::Partner => "partner",
+            Self::Sibling => "sibling",
+        }
+    }
+
+    pub fn kind(&self) -> KinshipKind {
+        match self {
+            Self::Parent | Self::Child => KinshipKind::Parent,
+            Self::AdoptiveParent | Self::AdoptedChild => KinshipKind::AdoptiveParent,
+            Self::StepParent | Self::StepChild => KinshipKind::StepParent,
+            Self::Spouse => KinshipKind::Spouse,
+            Self::Partner => KinshipKind::Partner,
+            Self::Sibling => KinshipKind::Sibling,
+        }
+    }
+
+    /// whether the relative is the parent side of the kinship.
+    fn is_elder(&self) -> bool {
+        matches!(self, Self::Parent | Self::AdoptiveParent | Self::StepParent)
+    }
+
+    /// the kinship that makes `relative` this to `character`.
+    pub fn builder(
+        &self,
+        character: Uuid,
+        relative: Uuid,
+        begins: Option<i64>,
+        ends: Option<i64>,
+    ) -> KinshipBuilder {
+        let (character, relative) = if self.is_elder() {
+            (relative, character)
+        } else {
+            (character, relative)
+        };
+        KinshipBuilder {
+            character,
+            relative,
+            kind: self.kind(),
+            begins,
+            ends,
+        }
+    }
+
+    /// what `kinship` makes its other character to `id`.
+    pub fn of(kinship: &Kinship, id: Uuid) -> Option<Self> {
+        kinship.other(id)?;
+        let elder = kinship.relative == id;
+        Self::ALL.into_iter().find(|role| {
+            role.kind() == kinship.kind && (!kinship.kind.is_parent() || role.is_elder() == elder)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FamilyMember {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub gender: String,
+}
+
+/// a relative of one member of a family, for listing next to the tree.
+#[derive(Debug, Clone, Serialize)]
+pub struct Relative<'a> {
+    pub role: Role,
+    pub label: &'static str,
+    pub member: &'a FamilyMember,
+    pub kinship: &'a Kinship,
+    pub dates: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Family {
+    pub members: Vec<FamilyMember>,
+    pub kinship: Vec<Kinship>,
+}
+
+impl Family {
+    /// everyone related to `id` through kinship, `id` included.
+    pub async fn fetch(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Self, ApiError> {
+        let mut params = HashMap::new();
+        let id = id.to_string();
+        params.insert("id", id.as_str());
+        api.get_protected("/kinship/family", access_token, Some(params))
+            .await
+    }
+
+    pub fn member(&self, id: Uuid) -> Option<&FamilyMember> {
+        self.members.iter().find(|member| member.id == id)
+    }
+
+    /// the direct relatives of `id`, parents first, dates are shown in `calendar`.
+    pub fn relatives(&self, id: Uuid, calendar: &Calendar) -> Vec<Relative<'_>> {
+        let mut relatives: Vec<Relative> = self
+            .kinship
+            .iter()
+            .filter_map(|kinship| {
+                let role = Role::of(kinship, id)?;
+                let member = self.member(kinship.other(id)?)?;
+                Some(Relative {
+                    role,
+                    label: role.label(),
+                    member,
+                    kinship,
+                    dates: kinship.dates(calendar),
+                })
+            })
+            .collect();
+        relatives.sort_by_key(|relative| {
+            Role::ALL
+                .iter()
+                .position(|role| *role == relative.role)
+                .unwrap_or(Role::ALL.len())
+        });
+        relatives
+    }
+
+    /// the parents of `id` and how they're its parents.
+    pub fn parents(&self, id: Uuid) -> Vec<(Uuid, KinshipKind)> {
+        self.kinship
+            .iter()
+            .filter(|kinship| kinship.kind.is_parent() && kinship.relative == id)
+            .map(|kinship| (kinship.character, kinship.kind))
+            .collect()
+    }
+
+    fn partners(&self, id: Uuid) -> impl Iterator<Item = Uuid> + '_ {
+        self.kinship
+            .iter()
+            .filter(|kinship| kinship.kind.is_couple())
+            .filter_map(move |kinship| kinship.other(id))
+    }
+
+    /// the generation of every member counting down from the eldest, who are generation zero.
+    /// partners and siblings share a generation and children are one below their parents. when
+    /// two paths disagree, someone partnering across generations, the one closer to `root` wins.
+    pub fn generations(&self, root: Uuid) -> HashMap<Uuid, i64> {
+        let mut generations = HashMap::new();
+        let starts = std::iter::once(root).chain(self.members.iter().map(|member| member.id));
+        for start in starts {
+            if generations.contains_key(&start) {
+                continue;
+            }
+            generations.insert(start, 0);
+            let mut queue = VecDeque::from([start]);
+            while let Some(id) = queue.pop_front() {
+                let generation = generations[&id];
+                for kinship in self.kinship.iter() {
+                    let Some(other) = kinship.other(id) else {
+                        continue;
+                    };
+                    let step = match kinship.kind.is_parent() {
+                        true if kinship.character == id => 1,
+                        true => -1,
+                        false => 0,
+                    };
+                    if let Entry::Vacant(entry) = generations.entry(other) {
+                        entry.insert(generation + step);
+                        queue.push_back(other);
+                    }
+                }
+            }
+        }
+        let eldest = generations.values().copied().min().unwrap_or(0);
+        for generation in generations.values_mut() {
+            *generation -= eldest;
+        }
+        generations
+    }
+
+    /// the members a row per generation, eldest first. children sit under their parents and
+    /// couples next to each other.
+    pub fn rows(&self, root: Uuid) -> Vec<Vec<Uuid>> {
+        let generations = self.generations(root);
+        let depth = generations.values().copied().max().map_or(0, |max| max + 1);
+        let mut rows: Vec<Vec<Uuid>> = vec![Vec::new(); depth as usize];
+        for member in self.members.iter() {
+            if let Some(generation) = generations.get(&member.id) {
+                rows[*generation as usize].push(member.id);
+            }
+        }
+
+        for index in 0..rows.len() {
+            if index > 0 {
+                let above: HashMap<Uuid, usize> = rows[index - 1]
+                    .iter()
+                    .enumerate()
+                    .map(|(column, id)| (*id, column))
+                    .collect();
+                let mut keys: HashMap<Uuid, f64> = HashMap::new();
+                for id in rows[index].iter() {
+                    let columns: Vec<usize> = self
+                        .parents(*id)
+                        .iter()
+                        .filter_map(|(parent, _)| above.get(parent).copied())
+                        .collect();
+                    if !columns.is_empty() {
+                        let mean = columns.iter().sum::<usize>() as f64 / columns.len() as f64;
+                        keys.insert(*id, mean);
+                    }
+                }
+                // someone who married into the family stands with their partner.
+                for id in rows[index].iter() {
+                    if !keys.contains_key(id) {
+                        let key = self
+                            .partners(*id)
+                            .find_map(|other| keys.get(&other).copied());
+                        keys.insert(*id, key.unwrap_or(f64::INFINITY));
+                    }
+                }
+                rows[index].sort_by(|a, b| keys[a].total_cmp(&keys[b]));
+            }
+
+            let row = std::mem::take(&mut rows[index]);
+            let members: HashSet<Uuid> = row.iter().copied().collect();
+            let mut placed = HashSet::new();
+            for id in row {
+                if !placed.insert(id) {
+                    continue;
+                }
+                rows[index].push(id);
+                for partner in self.partners(id) {
+                    if members.contains(&partner) && placed.insert(partner) {
+                        rows[index].push(partner);
+                    }
+                }
+            }
+        }
+        rows
+    }
+
+    /// the family tree as a standalone svg document, dates are shown in `calendar`.
+    pub fn svg(&self, root: Uuid, calendar: &Calendar) -> String {
+        let rows = self.rows(root);
+        let widest = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let pitch = BOX_WIDTH + COLUMN_GAP;
+        let width = MARGIN * 2.0 + widest as f64 * pitch - COLUMN_GAP;
+        let height = MARGIN * 2.0 + rows.len().max(1) as f64 * (BOX_HEIGHT + ROW_GAP) - ROW_GAP;
+
+        // the top left corner of every member's box.
+        let mut boxes: HashMap<Uuid, (f64, f64)> = HashMap::new();
+        for (index, row) in rows.iter().enumerate() {
+            let offset = (widest - row.len()) as f64 * pitch / 2.0;
+            for (column, id) in row.iter().enumerate() {
+                let x = MARGIN + offset + column as f64 * pitch;
+                let y = MARGIN + index as f64 * (BOX_HEIGHT + ROW_GAP);
+                boxes.insert(*id, (x, y));
+            }
+        }
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" id="svg" class="family-tree" viewBox="0 0 {} {}">"#,
+            width, height
+        );
+        svg.push_str(concat!(
+            "<style>",
+            ".member{fill:#f4f1ec;stroke:#9c8f7a}.member.root{fill:#e8896b;stroke:#9c4a31}",
+            ".couple,.descent,.sibling{fill:none;stroke:#555;stroke-width:1.5}",
+            ".partner,.sibling{stroke-dasharray:6 3}.ended{stroke:#aaa}",
+            ".adoptive_parent{stroke-dasharray:2 3}.step_parent{stroke:#5b2a9c;stroke-dasharray:6 3}",
+            "text{font-family:sans-serif;font-size:11px;fill:#333}",
+            "</style>"
+        ));
+
+        // where the line down to a couple's children starts.
+        let mut couples: HashMap<(Uuid, Uuid), (f64, f64)> = HashMap::new();
+        for kinship in self.kinship.iter() {
+            let class = match kinship.kind {
+                KinshipKind::Spouse | KinshipKind::Partner => "couple",
+                KinshipKind::Sibling => "sibling",
+                _ => continue,
+            };
+            let (Some(from), Some(to)) =
+                (boxes.get(&kinship.character), boxes.get(&kinship.relative))
+            else {
+                continue;
+            };
+            let (left, right) = if from.0 <= to.0 {
+                (from, to)
+            } else {
+                (to, from)
+            };
+            let y = left.1 + BOX_HEIGHT / 2.0;
+            let adjacent = left.1 == right.1 && right.0 - left.0 <= pitch + 0.5;
+            let path = if adjacent {
+                format!("M{:.1},{:.1} H{:.1}", left.0 + BOX_WIDTH, y, right.0)
+            } else {
+                // arcs over the members in between.
+                let top = left.1.min(right.1) - ROW_GAP / 3.0;
+                format!(
+                    "M{:.1},{:.1} C{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}",
+                    left.0 + BOX_WIDTH / 2.0,
+                    left.1,
+                    left.0 + BOX_WIDTH / 2.0,
+                    top,
+                    right.0 + BOX_WIDTH / 2.0,
+                    top,
+                    right.0 + BOX_WIDTH / 2.0,
+                    right.1
+                )
+            };
+            let ended = if kinship.ends.is_some() { " ended" } else { "" };
+            let title = match kinship.dates(calendar) {
+                Some(dates) => format!("{} {}", kinship.kind.as_str(), dates),
+                None => kinship.kind.as_str().to_string(),
+            };
+            let _ = write!(
+                svg,
+                r#"<path class="{} {}{}" d="{}"><title>{}</title></path>"#,
+                class,
+                kinship.kind.as_str(),
+                ended,
+                path,
+                escape_html(&title)
+            );
+            if kinship.kind.is_couple() {
+                let start = match adjacent {
+                    true => ((left.0 + BOX_WIDTH + right.0) / 2.0, y),
+                    false => ((left.0 + right.0 + BOX_WIDTH) / 2.0, y + BOX_HEIGHT / 2.0),
+                };
+                couples.insert((kinship.character, kinship.relative), start);
+                couples.insert((kinship.relative, kinship.character), start);
+            }
+        }
+
+        for member in self.members.iter() {
+            let Some((x, y)) = boxes.get(&member.id).copied() else {
+                continue;
+            };
+            let parents = self.parents(member.id);
+            let mut drawn = HashSet::new();
+            for (parent, kind) in parents.iter() {
+                if !drawn.insert(*parent) {
+                    continue;
+                }
+                let Some(from) = boxes.get(parent) else {
+                    continue;
+                };
+                // both parents of the same kind draw a single line from between them.
+                let couple = parents.iter().find_map(|(other, other_kind)| {
+                    (other != parent && other_kind == kind)
+                        .then(|| {
+                            couples
+                                .get(&(*parent, *other))
+                                .map(|start| (*other, *start))
+                        })
+                        .flatten()
+                });
+                let start = match couple {
+                    Some((other, start)) => {
+                        drawn.insert(other);
+                        start
+                    }
+                    None => (from.0 + BOX_WIDTH / 2.0, from.1 + BOX_HEIGHT),
+                };
+                let middle = y - ROW_GAP / 2.0;
+                let _ = write!(
+                    svg,
+                    r#"<path class="descent {}" d="M{:.1},{:.1} V{:.1} H{:.1} V{:.1}"/>"#,
+                    kind.as_str(),
+                    start.0,
+                    start.1,
+                    middle,
+                    x + BOX_WIDTH / 2.0,
+                    y
+                );
+            }
+        }
+
+        for member in self.members.iter() {
+            let Some((x, y)) = boxes.get(&member.id).copied() else {
+                continue;
+            };
+            let class = if member.id == root {
+                "member root"
+            } else {
+                "member"
+            };
+            let title = member.description.as_deref().unwrap_or(&member.name);
+            let _ = write!(
+                svg,
+                r#"<a href="/characters/trees/{}"><rect class="{}" x="{:.1}" y="{:.1}" width="{}" height="{}" rx="4"><title>{}</title></rect><text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text></a>"#,
+                member.id,
+                class,
+                x,
+                y,
+                BOX_WIDTH,
+                BOX_HEIGHT,
+                escape_html(title),
+                x + BOX_WIDTH / 2.0,
+                y + BOX_HEIGHT / 2.0 + 4.0,
+                escape_html(&truncate(&member.name, BOX_WIDTH - 12.0))
+            );
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
@@ -0,0 +1,405 @@
+//! deleting characters and merging duplicates of one another.
+//!
+//! a [`Footprint`] is everything that refers to a character: its relations to stories,
+//! fragments, locations, images and other characters, its tags, kinship, sheets, arc annotations
+//! and lifespan. deleting a character removes all of it, which the delete page previews as
+//! [`Cascade`]s. merging moves all of it onto a survivor, keeping the survivor's where both have
+//! the same thing; a [`MergePreview`] lists those conflicts before anything changes. the merged
+//! character is then deleted and a [`MergeRecord`] remembers where it went.
+use super::kinship::Kinship;
+use crate::ApiClient;
+use crate::calendars::api::Calendar;
+use crate::errors::ApiError;
+use crate::timelines::continuity::Lifespan;
+use reqwest::Method;
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// a relation seen from the character's side.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    pub entity: Uuid,
+    pub name: Option<String>,
+    /// the category of the linked entity, such as `stories` or `images`.
+    pub category: String,
+    pub description: Option<String>,
+    /// whether the character is the parent of the relation.
+    pub parent: bool,
+}
+
+impl Link {
+    fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self.entity.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Footprint {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub gender: String,
+    pub thumbnail: Option<Uuid>,
+    pub links: Vec<Link>,
+    pub tags: Vec<String>,
+    pub kinship: Vec<Kinship>,
+    /// the schemas the character has a sheet for.
+    pub sheets: Vec<Uuid>,
+    /// the fragments the character's arc is annotated at.
+    pub arcs: Vec<Uuid>,
+    pub lifespan: Option<Lifespan>,
+}
+
+/// one kind of thing a delete removes or a merge moves.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cascade {
+    pub label: String,
+    pub count: usize,
+    /// what's affected, where it has a name.
+    pub names: Vec<String>,
+}
+
+impl Footprint {
+    pub async fn fetch(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Self, ApiError> {
+        let url = format!("/characters/{}/footprint", id);
+        api.empty_request()
+            .access_token(access_token)
+            .route(&url)
+            .send()
+            .await
+    }
+
+    /// deletes the character and everything in its footprint.
+    pub async fn delete(api: &ApiClient, access_token: &str, id: Uuid) -> Result<(), ApiError> {
+        let url = format!("/characters/{}", id);
+        let _: serde_json::Value = api
+            .empty_request()
+            .method(Method::DELETE)
+            .access_token(access_token)
+            .route(&url)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// what deleting the character removes, relations grouped by what they link to.
+    pub fn cascade(&self) -> Vec<Cascade> {
+        let mut cascade = cascade_links(&self.links);
+        let counts = [
+            ("tags", self.tags.len(), self.tags.clone()),
+            ("kinship", self.kinship.len(), Vec::new()),
+            ("sheets", self.sheets.len(), Vec::new()),
+            ("arc annotations", self.arcs.len(), Vec::new()),
+            ("lifespan", usize::from(self.lifespan.is_some()), Vec::new()),
+        ];
+        cascade.extend(counts.into_iter().filter(|(_, count, _)| *count > 0).map(
+            |(label, count, names)| Cascade {
+                label: label.to_string(),
+                count,
+                names,
+            },
+        ));
+        cascade
+    }
+}
+
+/// relations grouped by the category of what they link to.
+pub fn cascade_links(links: &[Link]) -> Vec<Cascade> {
+    let mut grouped: BTreeMap<&str, Vec<&Link>> = BTreeMap::new();
+    for link in links {
+        grouped
+            .entry(link.category.as_str())
+            .or_default()
+            .push(link);
+    }
+    grouped
+        .into_iter()
+        .map(|(category, links)| {
+            let mut names: Vec<String> =
+                links.iter().filter_map(|link| link.name.clone()).collect();
+            names.sort();
+            names.dedup();
+            Cascade {
+                label: format!("links to {}", category),
+                count: links.len(),
+                names,
+            }
+        })
+        .collect()
+}
+
+/// something both characters have where only the survivor's is kept, or that is dropped.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Conflict {
+    pub field: String,
+    pub survivor: String,
+    pub merged: String,
+    pub resolution: &'static str,
+}
+
+/// the descriptions of two duplicates as one, without repeating a description the other
+/// already contains.
+pub fn combine_descriptions(survivor: Option<&str>, merged: Option<&str>) -> Option<String> {
+    let survivor = survivor.map(str::trim).filter(|text| !text.is_empty());
+    let merged = merged.map(str::trim).filter(|text| !text.is_empty());
+    match (survivor, merged) {
+        (Some(survivor), Some(merged)) if survivor.contains(merged) => Some(survivor.to_string()),
+        (Some(survivor), Some(merged)) if merged.contains(survivor) => Some(merged.to_string()),
+        (Some(survivor), Some(merged)) => Some(format!("{}\n\n{}", survivor, merged)),
+        (survivor, merged) => survivor.or(merged).map(str::to_string),
+    }
+}
+
+/// a name folded so duplicates typed slightly differently compare equal.
+fn folded(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// whether two characters are probably the same one, going by their names.
+pub fn likely_duplicate(name: &str, other: &str) -> bool {
+    let (name, other) = (folded(name), folded(other));
+    !name.is_empty() && name == other
+}
+
+/// what merging one character into another does, worked out before anything changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergePreview {
+    pub survivor: Footprint,
+    pub merged: Footprint,
+    /// what moves from the merged character onto the survivor.
+    pub moved: Vec<Cascade>,
+    pub conflicts: Vec<Conflict>,
+    /// the survivor's description after the merge, unless it's edited.
+    pub description: Option<String>,
+}
+
+impl MergePreview {
+    /// `names` names the sheet schemas and fragments the footprints refer to by id, kinship dates
+    /// are written in `calendar`.
+    pub fn new(
+        survivor: Footprint,
+        merged: Footprint,
+        names: &HashMap<Uuid, String>,
+        calendar: &Calendar,
+    ) -> Self {
+        let name = |id: &Uuid| names.get(id).cloned().unwrap_or_else(|| id.to_string());
+        let mut conflicts = Vec::new();
+        let mut conflict = |field: String, survivor: String, merged: String, resolution| {
+            conflicts.push(Conflict {
+                field,
+                survivor,
+                merged,
+                resolution,
+            })
+        };
+
+        let description = combine_descriptions(
+            survivor.description.as_deref(),
+            merged.description.as_deref(),
+        );
+        if let (Some(kept), Some(other)) = (&survivor.description, &merged.description)
+            && description.as_deref() != Some(kept.trim())
+            && description.as_deref() != Some(other.trim())
+        {
+            conflict(
+                "description".to_string(),
+                kept.clone(),
+                other.clone(),
+                "both are kept, one after the other",
+            );
+        }
+        if survivor.gender != merged.gender {
+            conflict(
+                "gender".to_string(),
+                survivor.gender.clone(),
+                merged.gender.clone(),
+                "the one picked below is kept",
+            );
+        }
+        if let (Some(kept), Some(other)) = (survivor.thumbnail, merged.thumbnail)
+            && kept != other
+        {
+            conflict(
+                "thumbnail".to_string(),
+                kept.to_string(),
+                other.to_string(),
+                "the survivor's is kept",
+            );
+        }
+
+        for link in &merged.links {
+            if link.entity == survivor.id {
+                conflict(
+                    format!("relation between {} and {}", survivor.name, merged.name),
+                    String::new(),
+                    link.description.clone().unwrap_or_default(),
+                    "dropped, it would relate the survivor to itself",
+                );
+                continue;
+            }
+            let kept = survivor.links.iter().find(|kept| {
+                kept.entity == link.entity
+                    && kept.category == link.category
+                    && kept.parent == link.parent
+            });
+            if let Some(kept) = kept
+                && kept.description != link.description
+            {
+                conflict(
+                    format!("relation to {}", link.label()),
+                    kept.description.clone().unwrap_or_default(),
+                    link.description.clone().unwrap_or_default(),
+                    "the survivor's description is kept",
+                );
+            }
+        }
+
+        for kinship in &merged.kinship {
+            let Some(relative) = kinship.other(merged.id) else {
+                continue;
+            };
+            if relative == survivor.id {
+                conflict(
+                    format!("{} kinship between the two", kinship.kind.as_str()),
+                    String::new(),
+                    String::new(),
+                    "dropped, it would relate the survivor to itself",
+                );
+                continue;
+            }
+            let kept = survivor.kinship.iter().find(|kept| {
+                kept.kind == kinship.kind
+                    && kept.other(survivor.id) == Some(relative)
+                    && (!kinship.kind.is_parent()
+                        || (kept.character == survivor.id) == (kinship.character == merged.id))
+            });
+            if let Some(kept) = kept
+                && (kept.begins, kept.ends) != (kinship.begins, kinship.ends)
+            {
+                conflict(
+                    format!("{} kinship dates", kinship.kind.as_str()),
+                    kept.dates(calendar).unwrap_or_default(),
+                    kinship.dates(calendar).unwrap_or_default(),
+                    "the survivor's are kept",
+                );
+            }
+        }
+
+        for schema in merged
+            .sheets
+            .iter()
+            .filter(|schema| survivor.sheets.contains(schema))
+        {
+            conflict(
+                format!("{} sheet", name(schema)),
+                "filled in".to_string(),
+                "filled in".to_string(),
+                "the survivor's is kept",
+            );
+        }
+        for fragment in merged
+            .arcs
+            .iter()
+            .filter(|fragment| survivor.arcs.contains(fragment))
+        {
+            conflict(
+                format!("arc at {}", name(fragment)),
+                "annotated".to_string(),
+                "annotated".to_string(),
+                "the survivor's is kept",
+            );
+        }
+        if let (Some(kept), Some(other)) = (&survivor.lifespan, &merged.lifespan) {
+            let ends = [
+                ("begins", kept.begins, other.begins),
+                ("ends", kept.ends, other.ends),
+            ];
+            for (end, kept, other) in ends {
+                if let (Some(kept), Some(other)) = (kept, other)
+                    && kept != other
+                {
+                    conflict(
+                        format!("lifespan {}", end),
+                        name(&kept),
+                        name(&other),
+                        "the survivor's is kept",
+                    );
+                }
+            }
+        }
+
+        Self {
+            moved: merged.cascade(),
+            survivor,
+            merged,
+            conflicts,
+            description,
+        }
+    }
+}
+
+/// the body of `POST /characters/{survivor}/merge/{merged}`, what to set on the survivor.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeBuilder {
+    pub description: Option<String>,
+    pub gender: Option<String>,
+}
+
+impl MergeBuilder {
+    /// merges `merged` into `survivor`, returning the survivor's footprint afterwards.
+    pub async fn build(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+        survivor: Uuid,
+        merged: Uuid,
+    ) -> Result<Footprint, ApiError> {
+        if survivor == merged {
+            return Err(ApiError::UnprocessableEntity(
+                "a character can't be merged into itself".to_string(),
+            ));
+        }
+        let url = format!("/characters/{}/merge/{}", survivor, merged);
+        api.post(&url, access_token, None, self).await
+    }
+}
+
+/// where a merged character went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeRecord {
+    pub merged: Uuid,
+    pub survivor: Uuid,
+    /// the merged character's name.
+    pub name: String,
+    pub merged_by: Option<Uuid>,
+    pub merged_at: i64,
+}
+
+impl MergeRecord {
+    /// `None` when the character was never merged.
+    pub async fn find(
+        api: &ApiClient,
+        access_token: &str,
+        id: Uuid,
+    ) -> Result<Option<Self>, ApiError> {
+        let url = format!("/characters/merges/{}", id);
+        match api
+            .empty_request()
+            .access_token(access_token)
+            .route(&url)
+            .send()
+            .await
+        {
+            Ok(record) => Ok(Some(record)),
+            Err(ApiError::NotFound(_)) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
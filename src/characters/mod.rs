@@ -1,3 +1,9 @@
 pub mod api;
+pub mod arcs;
 pub(crate) mod frontend;
+pub mod gedcom;
+pub mod kinship;
+pub mod merge;
+pub mod network;
+pub mod sheets;
 pub use frontend::get_routes;
@@ -0,0 +1,450 @@
+//! the relationship network between the characters of a story.
+//!
+//! characters are nodes, and two characters are tied by the fragments they appear in together
+//! and by explicit relations between them, a relation counting for [`RELATION_WEIGHT`] shared
+//! fragments. a story's sub-stories can be included so a series or a whole universe is analysed
+//! as one network. the network is ranked by degree and betweenness centrality, split into
+//! communities by label propagation, and drawn through graphviz like the other graphs.
+use crate::ApiClient;
+use crate::assets::graphs::EntityExt;
+use crate::characters::api::CharacterRender;
+use crate::errors::ApiError;
+use crate::stories::StoryRender;
+use petgraph::dot::{Config, Dot};
+use petgraph::graph::{NodeIndex, UnGraph};
+use petgraph::visit::EdgeRef;
+use rocket::FromFormField;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use uuid::Uuid;
+
+/// how many shared fragments an explicit relation counts for.
+pub const RELATION_WEIGHT: f64 = 2.0;
+/// communities past this many share colours, the network template styles this many classes.
+pub const PALETTE: usize = 8;
+/// label propagation stops after this many rounds even when labels are still changing.
+const MAX_ROUNDS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Member {
+    pub id: Uuid,
+    pub name: String,
+    /// the fragments the character appears in.
+    pub appearances: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Tie {
+    pub coappearances: usize,
+    pub related: bool,
+}
+
+impl Tie {
+    pub fn weight(&self) -> f64 {
+        self.coappearances as f64 + if self.related { RELATION_WEIGHT } else { 0.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Name,
+    Appearances,
+    Degree,
+    #[default]
+    Strength,
+    Betweenness,
+    Community,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 6] = [
+        Self::Name,
+        Self::Appearances,
+        Self::Degree,
+        Self::Strength,
+        Self::Betweenness,
+        Self::Community,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Appearances => "appearances",
+            Self::Degree => "degree",
+            Self::Strength => "strength",
+            Self::Betweenness => "betweenness",
+            Self::Community => "community",
+        }
+    }
+}
+
+/// a character's place in the network.
+#[derive(Debug, Clone, Serialize)]
+pub struct Centrality {
+    pub id: Uuid,
+    pub name: String,
+    pub appearances: usize,
+    /// how many characters they're tied to.
+    pub degree: usize,
+    /// the summed weight of their ties.
+    pub strength: f64,
+    /// the share of shortest paths between other characters that pass through them, from 0 to 1.
+    pub betweenness: f64,
+    pub community: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Community {
+    pub index: usize,
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    graph: UnGraph<Member, Tie>,
+}
+
+impl Network {
+    /// `scenes` are the characters of each fragment and `relations` the explicit relations
+    /// between characters, either way round. characters that aren't members are ignored.
+    pub fn new(members: Vec<Member>, scenes: &[Vec<Uuid>], relations: &[(Uuid, Uuid)]) -> Self {
+        let mut graph = UnGraph::new_undirected();
+        let mut nodes: HashMap<Uuid, NodeIndex> = HashMap::new();
+        for member in members {
+            nodes
+                .entry(member.id)
+                .or_insert_with(|| graph.add_node(member));
+        }
+        let mut ties: BTreeMap<(NodeIndex, NodeIndex), Tie> = BTreeMap::new();
+        let pair = |a: NodeIndex, b: NodeIndex| if a < b { (a, b) } else { (b, a) };
+        for scene in scenes {
+            let mut present: Vec<NodeIndex> = scene
+                .iter()
+                .filter_map(|id| nodes.get(id).copied())
+                .collect();
+            present.sort();
+            present.dedup();
+            for node in &present {
+                graph[*node].appearances += 1;
+            }
+            for (idx, a) in present.iter().enumerate() {
+                for b in &present[idx + 1..] {
+                    ties.entry(pair(*a, *b)).or_default().coappearances += 1;
+                }
+            }
+        }
+        for (a, b) in relations {
+            if let (Some(a), Some(b)) = (nodes.get(a), nodes.get(b))
+                && a != b
+            {
+                ties.entry(pair(*a, *b)).or_default().related = true;
+            }
+        }
+        for ((a, b), tie) in ties {
+            graph.add_edge(a, b, tie);
+        }
+        Self { graph }
+    }
+
+    /// the characters of the story's fragments, and of every sub-story's when `universe` is set,
+    /// along with the characters related to the story itself.
+    pub async fn fetch(
+        api: &ApiClient,
+        access_token: &str,
+        story: &StoryRender,
+        universe: bool,
+    ) -> Result<Self, ApiError> {
+        let mut fragments = Vec::new();
+        let mut characters: HashMap<Uuid, CharacterRender> = HashMap::new();
+        let mut visited = HashSet::from([story.id]);
+        let mut pending = vec![story.clone()];
+        while let Some(story) = pending.pop() {
+            let request = story.request(api, access_token);
+            fragments.extend(story.fragments(request.clone()).await?);
+            for character in story.characters(request.clone()).await? {
+                characters.insert(character.id, character);
+            }
+            if universe {
+                for substory in story.stories(request).await? {
+                    if visited.insert(substory.id) {
+                        pending.push(substory);
+                    }
+                }
+            }
+        }
+
+        let mut scenes = Vec::new();
+        for fragment in fragments {
+            let present = fragment
+                .characters(fragment.request(api, access_token))
+                .await?;
+            scenes.push(present.iter().map(|character| character.id).collect());
+            for character in present {
+                characters.insert(character.id, character);
+            }
+        }
+        let mut relations = Vec::new();
+        for character in characters.values() {
+            let related = character
+                .characters(character.request(api, access_token))
+                .await?;
+            relations.extend(related.into_iter().map(|other| (character.id, other.id)));
+        }
+
+        let mut members: Vec<Member> = characters
+            .into_values()
+            .map(|character| Member {
+                id: character.id,
+                name: character.name,
+                appearances: 0,
+            })
+            .collect();
+        members.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        Ok(Self::new(members, &scenes, &relations))
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &Member> {
+        self.graph.node_weights()
+    }
+
+    pub fn tie(&self, a: Uuid, b: Uuid) -> Option<&Tie> {
+        let node = |id: Uuid| {
+            self.graph
+                .node_indices()
+                .find(|node| self.graph[*node].id == id)
+        };
+        let edge = self.graph.find_edge(node(a)?, node(b)?)?;
+        self.graph.edge_weight(edge)
+    }
+
+    /// betweenness centrality by brandes' algorithm over unweighted shortest paths, normalised
+    /// by the number of pairs of other characters.
+    pub fn betweenness(&self) -> Vec<f64> {
+        let count = self.graph.node_count();
+        let mut centrality = vec![0.0; count];
+        for source in self.graph.node_indices() {
+            let mut stack = Vec::new();
+            let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); count];
+            let mut paths = vec![0.0; count];
+            let mut distance: Vec<Option<usize>> = vec![None; count];
+            paths[source.index()] = 1.0;
+            distance[source.index()] = Some(0);
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                stack.push(node.index());
+                let here = distance[node.index()].unwrap_or_default();
+                for neighbour in self.graph.neighbors(node) {
+                    let next = neighbour.index();
+                    if distance[next].is_none() {
+                        distance[next] = Some(here + 1);
+                        queue.push_back(neighbour);
+                    }
+                    if distance[next] == Some(here + 1) {
+                        paths[next] += paths[node.index()];
+                        predecessors[next].push(node.index());
+                    }
+                }
+            }
+            let mut dependency = vec![0.0; count];
+            while let Some(node) = stack.pop() {
+                for &previous in &predecessors[node] {
+                    dependency[previous] +=
+                        paths[previous] / paths[node] * (1.0 + dependency[node]);
+                }
+                if node != source.index() {
+                    centrality[node] += dependency[node];
+                }
+            }
+        }
+        // every path was counted from both of its ends
+        let pairs = if count > 2 {
+            ((count - 1) * (count - 2)) as f64
+        } else {
+            1.0
+        };
+        centrality.into_iter().map(|value| value / pairs).collect()
+    }
+
+    /// a community index for every character by weighted label propagation, visiting characters
+    /// in a fixed order so the result doesn't change between page loads. communities are
+    /// numbered from the largest.
+    pub fn communities(&self) -> Vec<usize> {
+        let count = self.graph.node_count();
+        let mut labels: Vec<usize> = (0..count).collect();
+        for _ in 0..MAX_ROUNDS {
+            let mut changed = false;
+            for node in self.graph.node_indices() {
+                let mut weights: BTreeMap<usize, f64> = BTreeMap::new();
+                for edge in self.graph.edges(node) {
+                    let other = if edge.source() == node {
+                        edge.target()
+                    } else {
+                        edge.source()
+                    };
+                    *weights.entry(labels[other.index()]).or_default() += edge.weight().weight();
+                }
+                let Some(best) = weights.values().copied().reduce(f64::max) else {
+                    continue;
+                };
+                let current = labels[node.index()];
+                if weights.get(&current) == Some(&best) {
+                    continue;
+                }
+                // the lowest label among the heaviest, the map iterates in label order
+                if let Some((label, _)) = weights.into_iter().find(|(_, weight)| *weight == best) {
+                    labels[node.index()] = label;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut sizes: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+        for (node, label) in labels.iter().enumerate() {
+            let entry = sizes.entry(*label).or_insert((0, node));
+            entry.0 += 1;
+        }
+        let mut order: Vec<(usize, (usize, usize))> = sizes.into_iter().collect();
+        order.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.1.1.cmp(&b.1.1)));
+        let renumber: HashMap<usize, usize> = order
+            .into_iter()
+            .enumerate()
+            .map(|(index, (label, _))| (label, index))
+            .collect();
+        labels.into_iter().map(|label| renumber[&label]).collect()
+    }
+
+    /// how much more tightly the communities are tied within than chance would have it, from
+    /// -0.5 to 1.
+    pub fn modularity(&self, communities: &[usize]) -> f64 {
+        let total: f64 = self.graph.edge_weights().map(Tie::weight).sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+        let mut inside: HashMap<usize, f64> = HashMap::new();
+        let mut degree: HashMap<usize, f64> = HashMap::new();
+        for edge in self.graph.edge_references() {
+            let (a, b) = (
+                communities[edge.source().index()],
+                communities[edge.target().index()],
+            );
+            let weight = edge.weight().weight();
+            if a == b {
+                *inside.entry(a).or_default() += weight;
+            }
+            *degree.entry(a).or_default() += weight;
+            *degree.entry(b).or_default() += weight;
+        }
+        degree
+            .iter()
+            .map(|(community, degree)| {
+                inside.get(community).copied().unwrap_or_default() / total
+                    - (degree / (2.0 * total)).powi(2)
+            })
+            .sum()
+    }
+
+    /// every character's centrality, sorted by `metric`. numbers sort from the highest,
+    /// names and communities from the first.
+    pub fn table(&self, metric: Metric) -> Vec<Centrality> {
+        let betweenness = self.betweenness();
+        let communities = self.communities();
+        let mut table: Vec<Centrality> = self
+            .graph
+            .node_indices()
+            .map(|node| {
+                let member = &self.graph[node];
+                Centrality {
+                    id: member.id,
+                    name: member.name.clone(),
+                    appearances: member.appearances,
+                    degree: self.graph.neighbors(node).count(),
+                    strength: self
+                        .graph
+                        .edges(node)
+                        .map(|edge| edge.weight().weight())
+                        .sum(),
+                    betweenness: betweenness[node.index()],
+                    community: communities[node.index()],
+                }
+            })
+            .collect();
+        table.sort_by(|a, b| {
+            let order = match metric {
+                Metric::Name => a.name.cmp(&b.name),
+                Metric::Appearances => b.appearances.cmp(&a.appearances),
+                Metric::Degree => b.degree.cmp(&a.degree),
+                Metric::Strength => b.strength.total_cmp(&a.strength),
+                Metric::Betweenness => b.betweenness.total_cmp(&a.betweenness),
+                Metric::Community => a.community.cmp(&b.community),
+            };
+            order.then_with(|| a.name.cmp(&b.name))
+        });
+        table
+    }
+
+    /// the `count` characters with the weakest ties, the unconnected ones first.
+    pub fn isolated(&self, count: usize) -> Vec<Centrality> {
+        let mut table = self.table(Metric::Name);
+        table.sort_by(|a, b| {
+            a.strength
+                .total_cmp(&b.strength)
+                .then(a.degree.cmp(&b.degree))
+                .then(a.betweenness.total_cmp(&b.betweenness))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        table.truncate(count);
+        table
+    }
+
+    pub fn community_members(&self, communities: &[usize]) -> Vec<Community> {
+        let mut grouped: BTreeMap<usize, Vec<Member>> = BTreeMap::new();
+        for node in self.graph.node_indices() {
+            grouped
+                .entry(communities[node.index()])
+                .or_default()
+                .push(self.graph[node].clone());
+        }
+        grouped
+            .into_iter()
+            .map(|(index, members)| Community { index, members })
+            .collect()
+    }
+
+    /// the network in dot, characters classed by community and ties as thick as their weight.
+    pub fn dot(&self, communities: &[usize]) -> String {
+        let quote = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let edge = |_, edge: petgraph::graph::EdgeReference<'_, Tie>| {
+            let tie = edge.weight();
+            let mut tooltip = format!("{} shared fragments", tie.coappearances);
+            if tie.related {
+                tooltip.push_str(", related");
+            }
+            format!(
+                "penwidth={:.1} weight={:.1} tooltip=\"{}\"{}",
+                1.0 + tie.weight().min(8.0) / 2.0,
+                tie.weight(),
+                tooltip,
+                if tie.related { " style=bold" } else { "" }
+            )
+        };
+        let node = |_, (index, member): (NodeIndex, &Member)| {
+            format!(
+                "label=\"{}\" URL=\"/characters/{}\" tooltip=\"{} appearances\" class=\"character community-{}\"",
+                quote(&member.name),
+                member.id,
+                member.appearances,
+                communities[index.index()] % PALETTE
+            )
+        };
+        let dot = Dot::with_attr_getters(
+            &self.graph,
+            &[Config::NodeNoLabel, Config::EdgeNoLabel],
+            &edge,
+            &node,
+        );
+        format!("{:?}", dot)
+    }
+}
@@ -0,0 +1,1047 @@
+//! character sheets for tabletop games.
+//!
+//! a [`SheetSchema`] lists the attributes a game system gives its characters, grouped into
+//! sections. attributes are typed: numbers within optional bounds, free text, one of a fixed set
+//! of options, dice expressions such as `1d8 + dex_mod`, lists, and computed values such as
+//! ability modifiers, worked out from the other attributes by a formula. a character holds one
+//! [`CharacterSheet`] per schema, with values for everything but the computed attributes.
+//!
+//! schemas are written one attribute per line as `key, label, type` followed by what the type
+//! needs, and a line starting with `#` begins a new section:
+//!
+//! ```text
+//! # Abilities
+//! strength, Strength, number, 1, 30
+//! str_mod, Strength Modifier, modifier, floor((strength - 10) / 2)
+//! class, Class, enum, Fighter | Rogue | Wizard
+//! hit_dice, Hit Dice, dice
+//! equipment, Equipment, list
+//! ```
+use crate::ApiClient;
+use crate::errors::ApiError;
+use reqwest::Method;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use uuid::Uuid;
+
+const FUNCTIONS: [&str; 6] = ["floor", "ceil", "round", "abs", "min", "max"];
+/// the most tokens in a formula, longer formulas are rejected before they're parsed.
+const MAX_TOKENS: usize = 256;
+/// how deeply parentheses, calls and signs can nest in a formula.
+const MAX_DEPTH: usize = 32;
+/// the most dice and the most sides of a single roll in a dice expression.
+const MAX_DICE: u32 = 1000;
+const MAX_SIDES: u32 = 1000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AttributeKind {
+    Number {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    Text,
+    Enum {
+        options: Vec<String>,
+    },
+    Dice,
+    List,
+    /// worked out from other numbers, `signed` values are shown with a leading `+`.
+    Computed {
+        formula: String,
+        #[serde(default)]
+        signed: bool,
+    },
+}
+
+impl AttributeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Number { .. } => "number",
+            Self::Text => "text",
+            Self::Enum { .. } => "enum",
+            Self::Dice => "dice",
+            Self::List => "list",
+            Self::Computed { .. } => "computed",
+        }
+    }
+
+    /// whether formulas and dice may refer to the attribute.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Number { .. } | Self::Computed { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attribute {
+    pub key: String,
+    pub label: String,
+    pub kind: AttributeKind,
+    #[serde(default)]
+    pub section: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchemaDefinition {
+    pub attributes: Vec<Attribute>,
+}
+
+/// a value entered on a sheet, numbers for number attributes, lists for list attributes and
+/// text for the rest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SheetValue {
+    Number(f64),
+    Text(String),
+    List(Vec<String>),
+}
+
+pub type SheetValues = BTreeMap<String, SheetValue>;
+
+/// an attribute ready to be shown on the sheet form or printed.
+#[derive(Debug, Clone, Serialize)]
+pub struct SheetField {
+    pub key: String,
+    pub label: String,
+    pub kind: &'static str,
+    /// the value as it goes back into the form, lists a line per item.
+    pub input: String,
+    /// the value as it's read on the sheet, `None` when it hasn't been filled in.
+    pub display: Option<String>,
+    pub options: Vec<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SheetSection {
+    pub name: Option<String>,
+    pub fields: Vec<SheetField>,
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_lowercase() || first == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// a number without a trailing `.0` when it's whole.
+pub fn number_text(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+impl SchemaDefinition {
+    /// reads the one attribute per line format described in the module docs.
+    pub fn parse(text: &str) -> Result<Self, ApiError> {
+        let mut attributes = Vec::new();
+        let mut section = None;
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let number = idx + 1;
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('#') {
+                section = Some(name.trim().to_string()).filter(|name| !name.is_empty());
+                continue;
+            }
+            let row: Vec<&str> = line.splitn(4, ',').map(str::trim).collect();
+            let (Some(key), Some(kind)) = (row.first(), row.get(2)) else {
+                return Err(ApiError::UnprocessableEntity(format!(
+                    "line {} needs a key, a label and a type",
+                    number
+                )));
+            };
+            let extra = row.get(3).copied().unwrap_or_default();
+            let bound = |value: Option<&str>| -> Result<Option<f64>, ApiError> {
+                match value.map(str::trim).filter(|value| !value.is_empty()) {
+                    Some(value) => value.parse().map(Some).map_err(|_| {
+                        ApiError::UnprocessableEntity(format!(
+                            "bound {} on line {} isn't a number",
+                            value, number
+                        ))
+                    }),
+                    None => Ok(None),
+                }
+            };
+            let kind = match kind.to_ascii_lowercase().as_str() {
+                "number" => {
+                    let mut bounds = extra.split(',');
+                    AttributeKind::Number {
+                        min: bound(bounds.next())?,
+                        max: bound(bounds.next())?,
+                    }
+                }
+                "text" => AttributeKind::Text,
+                "enum" => AttributeKind::Enum {
+                    options: extra
+                        .split('|')
+                        .map(str::trim)
+                        .filter(|option| !option.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                },
+                "dice" => AttributeKind::Dice,
+                "list" => AttributeKind::List,
+                "computed" | "modifier" => AttributeKind::Computed {
+                    formula: extra.to_string(),
+                    signed: kind.eq_ignore_ascii_case("modifier"),
+                },
+                other => {
+                    return Err(ApiError::UnprocessableEntity(format!(
+                        "unknown attribute type {} on line {}",
+                        other, number
+                    )));
+                }
+            };
+            let label = row.get(1).copied().filter(|label| !label.is_empty());
+            attributes.push(Attribute {
+                key: key.to_string(),
+                label: label.unwrap_or(key).to_string(),
+                kind,
+                section: section.clone(),
+            });
+        }
+        Ok(Self { attributes })
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.key == key)
+    }
+
+    /// checks the keys are unique identifiers, the bounds and options make sense, and every
+    /// formula parses and refers only to numbers, without depending on itself.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let invalid = |message: String| Err(ApiError::UnprocessableEntity(message));
+        if self.attributes.is_empty() {
+            return invalid("a sheet schema needs at least one attribute".to_string());
+        }
+        let mut keys = HashSet::new();
+        for attribute in &self.attributes {
+            if !is_identifier(&attribute.key) || FUNCTIONS.contains(&attribute.key.as_str()) {
+                return invalid(format!(
+                    "{} isn't a valid key, use lowercase letters, digits and underscores",
+                    attribute.key
+                ));
+            }
+            if !keys.insert(attribute.key.as_str()) {
+                return invalid(format!(
+                    "{} is used by more than one attribute",
+                    attribute.key
+                ));
+            }
+        }
+        for attribute in &self.attributes {
+            match &attribute.kind {
+                AttributeKind::Number {
+                    min: Some(min),
+                    max: Some(max),
+                } if min > max => {
+                    return invalid(format!(
+                        "{} has a minimum above its maximum",
+                        attribute.label
+                    ));
+                }
+                AttributeKind::Enum { options } if options.is_empty() => {
+                    return invalid(format!("{} has no options to choose from", attribute.label));
+                }
+                AttributeKind::Computed { formula, .. } => {
+                    for reference in Formula::parse(formula)?.references() {
+                        match self.attribute(reference) {
+                            Some(other) if other.kind.is_numeric() => {}
+                            Some(_) => {
+                                return invalid(format!(
+                                    "{} refers to {}, which isn't a number",
+                                    attribute.label, reference
+                                ));
+                            }
+                            None => {
+                                return invalid(format!(
+                                    "{} refers to {}, which isn't an attribute",
+                                    attribute.label, reference
+                                ));
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let mut done = HashSet::new();
+        for attribute in &self.attributes {
+            self.check_cycle(&attribute.key, &mut Vec::new(), &mut done)?;
+        }
+        Ok(())
+    }
+
+    fn check_cycle<'a>(
+        &'a self,
+        key: &'a str,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Result<(), ApiError> {
+        if done.contains(key) {
+            return Ok(());
+        }
+        if path.contains(&key) {
+            path.push(key);
+            return Err(ApiError::UnprocessableEntity(format!(
+                "formulas depend on themselves: {}",
+                path.join(" -> ")
+            )));
+        }
+        if let Some(Attribute {
+            kind: AttributeKind::Computed { formula, .. },
+            ..
+        }) = self.attribute(key)
+        {
+            path.push(key);
+            let formula = Formula::parse(formula)?;
+            for reference in formula.references() {
+                if let Some(attribute) = self.attribute(reference) {
+                    self.check_cycle(&attribute.key, path, done)?;
+                }
+            }
+            path.pop();
+        }
+        done.insert(key);
+        Ok(())
+    }
+
+    /// the values of a submitted sheet form, keyed by attribute. empty fields are left out and
+    /// anything that doesn't fit its attribute is refused.
+    pub fn values(&self, form: &HashMap<String, String>) -> Result<SheetValues, ApiError> {
+        let mut values = SheetValues::new();
+        for attribute in &self.attributes {
+            let Some(raw) = form.get(&attribute.key).map(|raw| raw.trim()) else {
+                continue;
+            };
+            if raw.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                Err(ApiError::UnprocessableEntity(format!(
+                    "{} {}",
+                    attribute.label, message
+                )))
+            };
+            let value = match &attribute.kind {
+                AttributeKind::Number { min, max } => {
+                    let number: f64 = match raw.parse() {
+                        Ok(number) if f64::is_finite(number) => number,
+                        _ => return invalid("must be a number"),
+                    };
+                    if let Some(min) = min.filter(|min| number < *min) {
+                        return invalid(&format!("must be at least {}", number_text(min)));
+                    }
+                    if let Some(max) = max.filter(|max| number > *max) {
+                        return invalid(&format!("must be at most {}", number_text(max)));
+                    }
+                    SheetValue::Number(number)
+                }
+                AttributeKind::Text => SheetValue::Text(raw.to_string()),
+                AttributeKind::Enum { options } => {
+                    match options.iter().find(|option| *option == raw) {
+                        Some(option) => SheetValue::Text(option.clone()),
+                        None => return invalid(&format!("must be one of {}", options.join(", "))),
+                    }
+                }
+                AttributeKind::Dice => {
+                    let dice = Dice::parse(raw)?;
+                    for reference in dice.references() {
+                        if !self
+                            .attribute(reference)
+                            .is_some_and(|other| other.kind.is_numeric())
+                        {
+                            return invalid(&format!(
+                                "refers to {}, which isn't a number",
+                                reference
+                            ));
+                        }
+                    }
+                    SheetValue::Text(dice.to_string())
+                }
+                AttributeKind::List => SheetValue::List(
+                    raw.lines()
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                ),
+                AttributeKind::Computed { .. } => continue,
+            };
+            values.insert(attribute.key.clone(), value);
+        }
+        Ok(values)
+    }
+
+    /// every number on the sheet, the entered ones and the computed ones that could be worked
+    /// out. a formula that refers to an empty number is left out.
+    pub fn numbers(&self, values: &SheetValues) -> HashMap<String, f64> {
+        let mut numbers: HashMap<String, f64> = values
+            .iter()
+            .filter_map(|(key, value)| match value {
+                SheetValue::Number(number) => Some((key.clone(), *number)),
+                _ => None,
+            })
+            .collect();
+        // computed attributes can depend on each other in any order, validation rules out
+        // cycles so a pass per attribute is always enough
+        let formulas: Vec<(&str, Formula)> = self
+            .attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.kind {
+                AttributeKind::Computed { formula, .. } => Formula::parse(formula)
+                    .ok()
+                    .map(|formula| (attribute.key.as_str(), formula)),
+                _ => None,
+            })
+            .collect();
+        for _ in 0..formulas.len() {
+            let mut progress = false;
+            for (key, formula) in &formulas {
+                if numbers.contains_key(*key) {
+                    continue;
+                }
+                if let Some(value) = formula.eval(&|name| numbers.get(name).copied()) {
+                    numbers.insert(key.to_string(), value);
+                    progress = true;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+        numbers
+    }
+
+    /// the attributes with their values, a section at a time in schema order.
+    pub fn sections(&self, values: &SheetValues) -> Vec<SheetSection> {
+        let numbers = self.numbers(values);
+        let mut sections: Vec<SheetSection> = Vec::new();
+        for attribute in &self.attributes {
+            let value = values.get(&attribute.key);
+            let input = match value {
+                Some(SheetValue::Number(number)) => number_text(*number),
+                Some(SheetValue::Text(text)) => text.clone(),
+                Some(SheetValue::List(items)) => items.join("\n"),
+                None => String::new(),
+            };
+            let display = match &attribute.kind {
+                AttributeKind::Computed { signed, .. } => {
+                    numbers.get(&attribute.key).map(|number| match signed {
+                        true if *number >= 0.0 => format!("+{}", number_text(*number)),
+                        _ => number_text(*number),
+                    })
+                }
+                AttributeKind::Dice => value.and_then(|_| Dice::parse(&input).ok()).map(|dice| {
+                    let (low, high) = dice.range(&numbers);
+                    format!("{} ({}–{})", dice.resolve(&numbers), low, high)
+                }),
+                AttributeKind::List => Some(input.replace('\n', ", ")).filter(|_| value.is_some()),
+                _ => Some(input.clone()).filter(|_| value.is_some()),
+            };
+            let (options, min, max) = match &attribute.kind {
+                AttributeKind::Enum { options } => (options.clone(), None, None),
+                AttributeKind::Number { min, max } => (Vec::new(), *min, *max),
+                _ => (Vec::new(), None, None),
+            };
+            let field = SheetField {
+                key: attribute.key.clone(),
+                label: attribute.label.clone(),
+                kind: attribute.kind.as_str(),
+                input,
+                display,
+                options,
+                min,
+                max,
+            };
+            match sections.last_mut() {
+                Some(section) if section.name == attribute.section => section.fields.push(field),
+                _ => sections.push(SheetSection {
+                    name: attribute.section.clone(),
+                    fields: vec![field],
+                }),
+            }
+        }
+        sections
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Attribute(String),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, char, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+fn tokens(source: &str) -> Result<Vec<Token>, ApiError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut text = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                text.push(c);
+                chars.next();
+            }
+            let number = text.parse().map_err(|_| {
+                ApiError::UnprocessableEntity(format!("{} in {} isn't a number", text, source))
+            })?;
+            tokens.push(Token::Number(number));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut text = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+            {
+                text.push(c.to_ascii_lowercase());
+                chars.next();
+            }
+            tokens.push(Token::Name(text));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "unexpected {} in {}",
+                c, source
+            )));
+        }
+    }
+    Ok(tokens)
+}
+
+/// a formula for a computed attribute: numbers, attribute keys, `+ - * /`, parentheses and the
+/// functions `floor`, `ceil`, `round`, `abs`, `min` and `max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    expr: Expr,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ApiError {
+        ApiError::UnprocessableEntity(format!("{} in formula {}", message, self.source))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, ApiError> {
+        let mut expr = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ('+' | '-'))) => *op,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ApiError> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol(op @ ('*' | '/'))) => *op,
+                _ => return Ok(expr),
+            };
+            self.position += 1;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.unary()?));
+        }
+    }
+
+    /// every sign, parenthesis and call nests through here, so this is where depth is counted.
+    fn unary(&mut self) -> Result<Expr, ApiError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        let expr = if self.eat('-') {
+            self.unary().map(|expr| Expr::Negate(Box::new(expr)))
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.primary()
+        };
+        self.depth -= 1;
+        expr
+    }
+
+    fn primary(&mut self) -> Result<Expr, ApiError> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Name(name)) if self.eat('(') => {
+                if !FUNCTIONS.contains(&name.as_str()) {
+                    return Err(self.error(&format!("unknown function {}", name)));
+                }
+                let mut args = vec![self.sum()?];
+                while self.eat(',') {
+                    args.push(self.sum()?);
+                }
+                if !self.eat(')') {
+                    return Err(self.error("missing )"));
+                }
+                let arity = match name.as_str() {
+                    "min" | "max" => args.len() >= 2,
+                    _ => args.len() == 1,
+                };
+                if !arity {
+                    return Err(self.error(&format!("wrong number of arguments to {}", name)));
+                }
+                Ok(Expr::Call(name, args))
+            }
+            Some(Token::Name(name)) => Ok(Expr::Attribute(name)),
+            Some(Token::Symbol('(')) => {
+                let expr = self.sum()?;
+                if !self.eat(')') {
+                    return Err(self.error("missing )"));
+                }
+                Ok(expr)
+            }
+            Some(Token::Symbol(c)) => Err(self.error(&format!("unexpected {}", c))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+}
+
+impl Expr {
+    fn references<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Self::Number(_) => {}
+            Self::Attribute(name) => {
+                if !out.contains(&name.as_str()) {
+                    out.push(name)
+                }
+            }
+            Self::Negate(expr) => expr.references(out),
+            Self::Binary(left, _, right) => {
+                left.references(out);
+                right.references(out);
+            }
+            Self::Call(_, args) => args.iter().for_each(|arg| arg.references(out)),
+        }
+    }
+
+    fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Option<f64> {
+        Some(match self {
+            Self::Number(number) => *number,
+            Self::Attribute(name) => lookup(name)?,
+            Self::Negate(expr) => -expr.eval(lookup)?,
+            Self::Binary(left, op, right) => {
+                let (left, right) = (left.eval(lookup)?, right.eval(lookup)?);
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    _ if right == 0.0 => return None,
+                    _ => left / right,
+                }
+            }
+            Self::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.eval(lookup))
+                    .collect::<Option<Vec<f64>>>()?;
+                match name.as_str() {
+                    "floor" => args[0].floor(),
+                    "ceil" => args[0].ceil(),
+                    "round" => args[0].round(),
+                    "abs" => args[0].abs(),
+                    "min" => args.into_iter().fold(f64::INFINITY, f64::min),
+                    _ => args.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                }
+            }
+        })
+    }
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Self, ApiError> {
+        let mut parser = Parser {
+            source,
+            tokens: tokens(source)?,
+            position: 0,
+            depth: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Err(ApiError::UnprocessableEntity(
+                "a formula can't be empty".to_string(),
+            ));
+        }
+        if parser.tokens.len() > MAX_TOKENS {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "a formula can't be longer than {} terms",
+                MAX_TOKENS
+            )));
+        }
+        let expr = parser.sum()?;
+        if parser.position < parser.tokens.len() {
+            return Err(parser.error("unexpected text after the end"));
+        }
+        Ok(Self { expr })
+    }
+
+    /// the attribute keys the formula refers to.
+    pub fn references(&self) -> Vec<&str> {
+        let mut out = Vec::new();
+        self.expr.references(&mut out);
+        out
+    }
+
+    /// `None` when an attribute it refers to has no value or it divides by zero.
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Option<f64> {
+        self.expr.eval(lookup).filter(|value| value.is_finite())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum DiceTerm {
+    Roll { count: u32, sides: u32 },
+    Flat(i64),
+    Attribute(String),
+}
+
+/// a dice expression such as `2d6 + 3` or `1d20 + dex_mod`, terms added or subtracted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dice {
+    terms: Vec<(i64, DiceTerm)>,
+}
+
+impl Dice {
+    pub fn parse(source: &str) -> Result<Self, ApiError> {
+        let invalid = |term: &str| {
+            ApiError::UnprocessableEntity(format!(
+                "{} in {} isn't a roll like 2d6, a number or an attribute",
+                term, source
+            ))
+        };
+        let compact: String = source
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        if compact.is_empty() {
+            return Err(ApiError::UnprocessableEntity(
+                "a dice expression can't be empty".to_string(),
+            ));
+        }
+        let (mut sign, rest) = match compact.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, compact.as_str()),
+        };
+        let mut terms = Vec::new();
+        let mut start = 0;
+        let ends = rest
+            .char_indices()
+            .chain(std::iter::once((rest.len(), '+')));
+        for (idx, c) in ends.filter(|(_, c)| *c == '+' || *c == '-') {
+            let term = &rest[start..idx];
+            let roll = term.split_once('d').filter(|(count, sides)| {
+                count.chars().all(|c| c.is_ascii_digit())
+                    && (*sides == "%"
+                        || !sides.is_empty() && sides.chars().all(|c| c.is_ascii_digit()))
+            });
+            let parsed = match roll {
+                Some((count, sides)) => {
+                    let count = match count {
+                        "" => 1,
+                        count => count.parse().map_err(|_| invalid(term))?,
+                    };
+                    let sides = match sides {
+                        "%" => 100,
+                        sides => sides.parse().map_err(|_| invalid(term))?,
+                    };
+                    if count == 0 || count > MAX_DICE || sides == 0 || sides > MAX_SIDES {
+                        return Err(invalid(term));
+                    }
+                    DiceTerm::Roll { count, sides }
+                }
+                None if is_identifier(term) => DiceTerm::Attribute(term.to_string()),
+                None => DiceTerm::Flat(term.parse().map_err(|_| invalid(term))?),
+            };
+            terms.push((sign, parsed));
+            sign = if c == '-' { -1 } else { 1 };
+            start = idx + 1;
+        }
+        Ok(Self { terms })
+    }
+
+    pub fn references(&self) -> Vec<&str> {
+        self.terms
+            .iter()
+            .filter_map(|(_, term)| match term {
+                DiceTerm::Attribute(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the dice with attributes replaced by their values and the flat numbers added up.
+    pub fn resolve(&self, numbers: &HashMap<String, f64>) -> String {
+        let mut flat = 0;
+        let mut out = String::new();
+        for (sign, term) in &self.terms {
+            match term {
+                DiceTerm::Roll { count, sides } => {
+                    let op = match (sign, out.is_empty()) {
+                        (-1, true) => "-",
+                        (-1, false) => " - ",
+                        (_, true) => "",
+                        _ => " + ",
+                    };
+                    out.push_str(&format!("{}{}d{}", op, count, sides));
+                }
+                DiceTerm::Flat(value) => flat = sign.saturating_mul(*value).saturating_add(flat),
+                DiceTerm::Attribute(name) => {
+                    let value = numbers.get(name).copied().unwrap_or_default().round() as i64;
+                    flat = sign.saturating_mul(value).saturating_add(flat);
+                }
+            }
+        }
+        match (flat, out.is_empty()) {
+            (_, true) => flat.to_string(),
+            (0, false) => out,
+            (flat, false) if flat < 0 => format!("{} - {}", out, flat.unsigned_abs()),
+            (flat, false) => format!("{} + {}", out, flat),
+        }
+    }
+
+    /// the lowest and highest total the dice can roll.
+    pub fn range(&self, numbers: &HashMap<String, f64>) -> (i64, i64) {
+        let (mut low, mut high) = (0i64, 0i64);
+        for (sign, term) in &self.terms {
+            let (a, b) = match term {
+                DiceTerm::Roll { count, sides } => (*count as i64, *count as i64 * *sides as i64),
+                DiceTerm::Flat(value) => (*value, *value),
+                DiceTerm::Attribute(name) => {
+                    let value = numbers.get(name).copied().unwrap_or_default().round() as i64;
+                    (value, value)
+                }
+            };
+            if *sign < 0 {
+                low = low.saturating_sub(b);
+                high = high.saturating_sub(a);
+            } else {
+                low = low.saturating_add(a);
+                high = high.saturating_add(b);
+            }
+        }
+        (low, high)
+    }
+}
+
+impl std::fmt::Display for Dice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, (sign, term)) in self.terms.iter().enumerate() {
+            match (idx, *sign < 0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            match term {
+                DiceTerm::Roll { count, sides } => write!(f, "{}d{}", count, sides)?,
+                DiceTerm::Flat(value) => write!(f, "{}", value)?,
+                DiceTerm::Attribute(name) => write!(f, "{}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// a schema that ships with storyteller, offered as a starting point when creating one.
+#[derive(Debug, Serialize)]
+pub struct SchemaTemplate {
+    pub slug: &'static str,
+    pub name: &'static str,
+    pub system: &'static str,
+    pub description: &'static str,
+    pub attributes: &'static str,
+}
+
+pub const TEMPLATES: [SchemaTemplate; 2] = [
+    SchemaTemplate {
+        slug: "dnd5e",
+        name: "Fifth Edition Adventurer",
+        system: "D&D 5e",
+        description: "ability scores with their modifiers, combat statistics and equipment",
+        attributes: "# Character
+class, Class, enum, Barbarian | Bard | Cleric | Druid | Fighter | Monk | Paladin | Ranger | Rogue | Sorcerer | Warlock | Wizard
+level, Level, number, 1, 20
+race, Race, text
+background, Background, text
+alignment, Alignment, enum, Lawful Good | Neutral Good | Chaotic Good | Lawful Neutral | True Neutral | Chaotic Neutral | Lawful Evil | Neutral Evil | Chaotic Evil
+proficiency, Proficiency Bonus, modifier, 2 + floor((level - 1) / 4)
+# Ability Scores
+strength, Strength, number, 1, 30
+str_mod, Strength Modifier, modifier, floor((strength - 10) / 2)
+dexterity, Dexterity, number, 1, 30
+dex_mod, Dexterity Modifier, modifier, floor((dexterity - 10) / 2)
+constitution, Constitution, number, 1, 30
+con_mod, Constitution Modifier, modifier, floor((constitution - 10) / 2)
+intelligence, Intelligence, number, 1, 30
+int_mod, Intelligence Modifier, modifier, floor((intelligence - 10) / 2)
+wisdom, Wisdom, number, 1, 30
+wis_mod, Wisdom Modifier, modifier, floor((wisdom - 10) / 2)
+charisma, Charisma, number, 1, 30
+cha_mod, Charisma Modifier, modifier, floor((charisma - 10) / 2)
+# Combat
+armor_class, Armor Class, number, 0,
+initiative, Initiative, modifier, dex_mod
+speed, Speed, number, 0,
+max_hp, Hit Point Maximum, number, 1,
+hit_dice, Hit Dice, dice
+passive_perception, Passive Perception, computed, 10 + wis_mod
+attacks, Attacks, list
+# Equipment
+equipment, Equipment, list
+features, Features & Traits, list",
+    },
+    SchemaTemplate {
+        slug: "generic",
+        name: "Generic Character",
+        system: "Generic",
+        description: "four broad attributes, health, skills and gear for any game",
+        attributes: "# Attributes
+body, Body, number, 0, 10
+agility, Agility, number, 0, 10
+mind, Mind, number, 0, 10
+spirit, Spirit, number, 0, 10
+# Condition
+max_health, Maximum Health, computed, 10 + body * 2
+health, Health, number, 0,
+damage, Weapon Damage, dice
+# Background
+concept, Concept, text
+skills, Skills, list
+gear, Gear, list",
+    },
+];
+
+pub fn template(slug: &str) -> Option<&'static SchemaTemplate> {
+    TEMPLATES.iter().find(|template| template.slug == slug)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SheetSchema {
+    pub id: Uuid,
+    pub name: String,
+    pub system: Option<String>,
+    pub description: Option<String>,
+    pub definition: SchemaDefinition,
+}
+
+impl SheetSchema {
+    pub async fn list(api: &ApiClient, access_token: &str) -> Result<Vec<Self>, ApiError> {
+        let schemas: Option<Vec<Self>> = api
+            .get_protected("/sheets/schemas/", access_token, None)
+            .await?;
+        Ok(schemas.unwrap_or_default())
+    }
+
+    pub async fn fetch(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Self, ApiError> {
+        let url = format!("/sheets/schemas/{}", id);
+        api.get_protected(&url, access_token, None).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaBuilder {
+    pub name: String,
+    pub system: Option<String>,
+    pub description: Option<String>,
+    pub definition: SchemaDefinition,
+}
+
+impl SchemaBuilder {
+    pub async fn build(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+    ) -> Result<SheetSchema, ApiError> {
+        self.definition.validate()?;
+        api.post("/sheets/schemas/", access_token, None, &self)
+            .await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CharacterSheet {
+    pub character: Uuid,
+    pub schema: Uuid,
+    #[serde(default)]
+    pub data: SheetValues,
+}
+
+impl CharacterSheet {
+    pub async fn list(
+        api: &ApiClient,
+        access_token: &str,
+        character: Uuid,
+    ) -> Result<Vec<Self>, ApiError> {
+        let url = format!("/sheets/characters/{}", character);
+        let sheets: Option<Vec<Self>> = api.get_protected(&url, access_token, None).await?;
+        Ok(sheets.unwrap_or_default())
+    }
+
+    /// attaches the schema to the character, or replaces its values when already attached.
+    pub async fn save(&self, api: &ApiClient, access_token: &str) -> Result<Self, ApiError> {
+        let url = format!("/sheets/characters/{}", self.character);
+        api.put(&url, access_token, self).await
+    }
+
+    pub async fn remove(
+        api: &ApiClient,
+        access_token: &str,
+        character: Uuid,
+        schema: Uuid,
+    ) -> Result<(), ApiError> {
+        let url = format!("/sheets/characters/{}/{}", character, schema);
+        let _: serde_json::Value = api
+            .empty_request()
+            .method(Method::DELETE)
+            .access_token(access_token)
+            .route(&url)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
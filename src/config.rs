@@ -100,6 +100,10 @@ pub struct Config {
     /// the directory in which to store image files
     pub images: PathBuf,
     pub uploadLimit: String,
+    /// directory containing the pdfium shared library used for pdf export,
+    /// the system library is used when this isn't set.
+    #[serde(default)]
+    pub pdfium: Option<PathBuf>,
 }
 
 impl Config {
@@ -136,6 +140,7 @@ impl Default for Config {
             api: APIConfig::default(),
             images,
             uploadLimit: "50MiB".to_string(),
+            pdfium: None,
         }
     }
 }
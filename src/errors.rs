@@ -71,7 +71,9 @@ server_error!(
     url::ParseError,
     image::ImageError,
     nom_exif::Error,
-    reqwest::Error
+    reqwest::Error,
+    zip::result::ZipError,
+    pdfium_render::prelude::PdfiumError
 );
 
 init_error!(jsonwebtoken::errors::Error, base64::DecodeError);
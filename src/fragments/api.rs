@@ -12,6 +12,9 @@ pub struct FragmentBuilder<'a> {
     pub name: &'a str,
     pub content: &'a str,
     pub tags: &'a Vec<String>,
+    /// position of the fragment within its parent story, defaults to 0 when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idx: Option<i32>,
 }
 
 impl<'a> FragmentBuilder<'a> {
@@ -36,7 +39,7 @@ impl StoryFragment {
             id: self.id,
             name: self.name,
             content: self.content,
-            created: crate::epoch_to_human(self.created),
+            created: crate::epoch_to_human(self.created, None),
             // I'll handle last edited when I have edit's implemented
             last_edited: String::from("unimplemented"),
         }
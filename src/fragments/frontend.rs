@@ -1,10 +1,18 @@
 use super::api::*;
 use crate::ApiClient;
 use crate::auth::Guard;
-use crate::errors::LazyError;
+use crate::errors::{ApiError, LazyError};
 use crate::model::*;
+use crate::characters::api::CharacterRender;
+use crate::locations::LocationRender;
+use super::mentions::{Mention, MentionAnalyzer};
+use crate::relations::Relation;
+use crate::render::links::{EntityIndex, LinkTarget, LinkedRenderer};
+use crate::render::{Renderer, SupportedRender};
+use crate::render::pdf::{PdfExport, PdfOptions, PdfRenderer, PdfSection, content_blocks};
+use crate::stories::StoryRender;
 use rocket::fs::TempFile;
-use rocket::http::CookieJar;
+use rocket::http::{ContentType, CookieJar};
 use rocket::response::Redirect;
 use rocket::response::content::RawHtml;
 use rocket::{FromForm, Route, State, form::Form, get, post, routes};
@@ -42,6 +50,7 @@ impl<'r> EditFragmentForm<'r> {
                 name: self.name.as_str(),
                 content: self.content.as_str(),
                 tags: &self.tags,
+                idx: None,
             }
     }
 }
@@ -67,6 +76,7 @@ impl<'r> CreateFragmentForm<'r> {
             name: self.name.as_str(),
             content: self.content.as_str(),
             tags: &self.tags,
+            idx: None,
         }
     }
 }
@@ -75,13 +85,23 @@ impl<'r> CreateFragmentForm<'r> {
 pub struct FragmentRender {
     pub id: Uuid,
     pub name: String,
+    /// position of the fragment within its parent story
+    #[serde(default)]
+    pub idx: i32,
     pub content: String,
     pub images: Option<Vec<Image>>,
     pub created: String,
     pub last_edited: String,
+    /// `content` rendered with the parent story's renderer, filled in before templating.
+    #[serde(default, skip_deserializing)]
+    pub html: String,
 }
 
 impl FragmentRender {
+    pub fn render_content(&mut self, renderer: &impl Renderer) {
+        self.html = renderer.render(&self.content);
+    }
+
     pub fn build_node(&self) -> Node {
         let mut node = Node::new(self.id.to_string(), self.name.clone());
         node.set_attr(NodeAttr::Shape(NodeShape::Box));
@@ -164,7 +184,7 @@ impl<G: CompatGraph<Node = Node, Edge = Edge> + Send> Renderable<G> for Fragment
     }
 }
 
-async fn fetch_fragment(guard: Guard, id: Uuid, api: &State<ApiClient>) -> Result<StoryFragment, LazyError> {
+async fn fetch_fragment(guard: &Guard, id: Uuid, api: &State<ApiClient>) -> Result<StoryFragment, LazyError> {
     let url = format!("/fragments/{}", id);
     let fragment: StoryFragment = api
         .get_protected(&url, guard.access_token(), None)
@@ -172,6 +192,91 @@ async fn fetch_fragment(guard: Guard, id: Uuid, api: &State<ApiClient>) -> Resul
     Ok(fragment)
 }
 
+/// ids of the characters and locations the fragment is already related to.
+async fn related_ids(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Vec<Uuid>, ApiError> {
+    let request = api
+        .empty_request()
+        .access_token(access_token)
+        .set_param("parent", id.to_string())
+        .set_param("category", "fragments".to_string());
+    let characters: Option<Vec<CharacterRender>> =
+        request.clone().route("/characters/filter").send().await?;
+    let locations: Option<Vec<LocationRender>> =
+        request.route("/locations/filter").send().await?;
+    Ok(characters
+        .unwrap_or_default()
+        .iter()
+        .map(|character| character.id)
+        .chain(locations.unwrap_or_default().iter().map(|location| location.id))
+        .collect())
+}
+
+/// entities the content references with wiki-links that the fragment isn't related to yet.
+async fn link_suggestions(
+    api: &ApiClient,
+    access_token: &str,
+    index: &EntityIndex,
+    id: Uuid,
+    content: &str,
+) -> Result<Vec<LinkTarget>, ApiError> {
+    let mut suggestions = index.referenced(content);
+    if suggestions.is_empty() {
+        return Ok(suggestions);
+    }
+    let related = related_ids(api, access_token, id).await?;
+    suggestions.retain(|target| !related.contains(&target.id));
+    Ok(suggestions)
+}
+
+/// entities mentioned in the prose that aren't related to the fragment or dismissed yet.
+async fn mention_suggestions(
+    api: &ApiClient,
+    access_token: &str,
+    index: &EntityIndex,
+    renderer: SupportedRender,
+    fragment: &StoryFragment,
+) -> Result<Vec<Mention>, ApiError> {
+    let mut mentions = MentionAnalyzer::from_index(index).scan_content(renderer, &fragment.content);
+    if mentions.is_empty() {
+        return Ok(mentions);
+    }
+    let related = related_ids(api, access_token, fragment.id).await?;
+    let dismissed: Option<Vec<Uuid>> = api
+        .request("/mentions/dismissed")
+        .access_token(access_token)
+        .set_param("fragment", fragment.id.to_string())
+        .send()
+        .await?;
+    let dismissed = dismissed.unwrap_or_default();
+    mentions.retain(|mention| {
+        !related.contains(&mention.target.id) && !dismissed.contains(&mention.target.id)
+    });
+    Ok(mentions)
+}
+
+/// sends the writer to the link page when the saved content references unrelated entities.
+async fn offer_links(
+    api: &ApiClient,
+    access_token: &str,
+    id: Uuid,
+    content: &str,
+    redirect: String,
+) -> Redirect {
+    // a failed lookup shouldn't lose the save, the links page can be visited later.
+    let suggestions = match EntityIndex::fetch(api, access_token).await {
+        Ok(index) => link_suggestions(api, access_token, &index, id, content).await,
+        Err(e) => Err(e),
+    };
+    match suggestions {
+        Ok(suggestions) if !suggestions.is_empty() => Redirect::to(format!(
+            "/fragments/{}/links?redirect={}",
+            id,
+            urlencoding::encode(&redirect)
+        )),
+        _ => Redirect::to(redirect),
+    }
+}
+
 #[post("/", data = "<form>")]
 async fn create_fragment<'r>(
     guard: Guard,
@@ -190,7 +295,7 @@ async fn create_fragment<'r>(
     } else {
         format!("/fragments/{}", newfragment.id)
     };
-    Redirect::to(redirect)
+    offer_links(api, guard.access_token(), newfragment.id, builder.content, redirect).await
 }
 
 #[post("/edit", data = "<form>")]
@@ -207,7 +312,7 @@ async fn edit_fragment<'r>(guard: Guard, api: &State<ApiClient>, form: Form<Edit
     } else {
         format!("/fragments/{}", newfragment.id)
     };
-    Redirect::to(redirect)
+    offer_links(api, guard.access_token(), newfragment.id, builder.content, redirect).await
 }
 
 // id and category can be used to generate a redirect, and link automatically
@@ -223,7 +328,7 @@ async fn create_fragment_html(
     // category (the type of entity)
 
     let current = if let Some(fragment) = fragment {
-        Some(fetch_fragment(guard, fragment, api).await.unwrap())
+        Some(fetch_fragment(&guard, fragment, api).await.unwrap())
     }else {
         None
     };
@@ -236,17 +341,166 @@ async fn create_fragment_html(
     ))
 }
 
-#[get("/<id>")]
-async fn get_fragment(guard: Guard, id: Uuid, api: &State<ApiClient>) -> RawHtml<Template> {
-    
-    let fragment = fetch_fragment(guard, id, api).await.unwrap();
+/// the renderer of the story the fragment belongs to, markdown when it isn't in one.
+async fn story_renderer(api: &ApiClient, access_token: &str, id: Uuid) -> SupportedRender {
+    let url = format!("/fragments/{}/story", id);
+    let story: Result<StoryRender, ApiError> = api.get_protected(&url, access_token, None).await;
+    story.map(|story| story.renderer()).unwrap_or_default()
+}
+
+/// fragments don't carry a renderer, so it's the parent story's unless one is passed along.
+#[get("/<id>?<renderer>")]
+async fn get_fragment(
+    guard: Guard,
+    id: Uuid,
+    renderer: Option<SupportedRender>,
+    api: &State<ApiClient>,
+) -> RawHtml<Template> {
+    let fragment = fetch_fragment(&guard, id, api).await.unwrap();
+    let renderer = match renderer {
+        Some(renderer) => renderer,
+        None => story_renderer(api, guard.access_token(), id).await,
+    };
+    let index = EntityIndex::fetch(api, guard.access_token())
+        .await
+        .unwrap_or_default();
+    let content = LinkedRenderer::new(renderer, &index).render(&fragment.content);
+    let unresolved = index.unresolved(&fragment.content);
+    // suggestions are a convenience, the fragment still renders if they can't be fetched.
+    let mentions = mention_suggestions(api, guard.access_token(), &index, renderer, &fragment)
+        .await
+        .unwrap_or_default();
 
     RawHtml(Template::render(
         "fragments/fragment",
-        context! { title: fragment.name.clone(), fragment },
+        context! { title: fragment.name.clone(), fragment, content, renderer, unresolved, mentions },
     ))
 }
 
+#[derive(Debug, Serialize, FromForm)]
+pub struct DismissMention {
+    fragment: Uuid,
+    entity: Uuid,
+}
+
+/// rejects a suggested mention so it isn't offered for the fragment again.
+#[post("/<id>/mentions/dismiss?<renderer>", data = "<form>")]
+async fn dismiss_mention(
+    guard: Guard,
+    id: Uuid,
+    renderer: Option<SupportedRender>,
+    form: Form<DismissMention>,
+    api: &State<ApiClient>,
+) -> Result<Redirect, ApiError> {
+    let mention = form.into_inner();
+    if mention.fragment != id {
+        return Err(ApiError::BadRequest("mention belongs to another fragment".to_string()));
+    }
+    let _: serde_json::Value = api
+        .post("/mentions/dismissed", guard.access_token(), None, &mention)
+        .await?;
+    Ok(Redirect::to(match renderer {
+        Some(renderer) => format!("/fragments/{}?renderer={}", id, renderer),
+        None => format!("/fragments/{}", id),
+    }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct LinkForm {
+    redirect: Option<String>,
+    /// the entities to relate, each as `category:id`.
+    links: Vec<String>,
+}
+
+/// only local paths are followed after linking, anything else goes back to the fragment.
+///
+/// browsers read `//host` and `/\host` as other origins and strip control characters before
+/// parsing, so the slash can't be followed by another slash or backslash.
+pub(crate) fn local_redirect(redirect: Option<String>, id: Uuid) -> String {
+    redirect
+        .filter(|redirect| {
+            let Some(path) = redirect.strip_prefix('/') else {
+                return false;
+            };
+            !path.starts_with(['/', '\\']) && !redirect.chars().any(char::is_control)
+        })
+        .unwrap_or_else(|| format!("/fragments/{}", id))
+}
+
+#[get("/<id>/links?<redirect>")]
+async fn link_fragment_html(
+    guard: Guard,
+    id: Uuid,
+    redirect: Option<String>,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let fragment = fetch_fragment(&guard, id, api).await?;
+    let index = EntityIndex::fetch(api, guard.access_token()).await?;
+    let suggestions =
+        link_suggestions(api, guard.access_token(), &index, id, &fragment.content).await?;
+    let unresolved = index.unresolved(&fragment.content);
+    let redirect = local_redirect(redirect, id);
+    Ok(RawHtml(Template::render(
+        "fragments/links",
+        context! { title: "link references", fragment, suggestions, unresolved, redirect },
+    )))
+}
+
+#[post("/<id>/links", data = "<form>")]
+async fn link_fragment(
+    guard: Guard,
+    id: Uuid,
+    form: Form<LinkForm>,
+    api: &State<ApiClient>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    for link in form.links.iter() {
+        let (category, child) = link
+            .split_once(':')
+            .ok_or(ApiError::BadRequest(format!("malformed link {}", link)))?;
+        if category != "characters" && category != "locations" {
+            return Err(ApiError::BadRequest(format!("can't link to {}", category)));
+        }
+        let child: Uuid = child
+            .parse()
+            .map_err(|_| ApiError::BadRequest(format!("malformed link {}", link)))?;
+        let relation = Relation {
+            parent: id,
+            child,
+            parent_category: "fragments".to_string(),
+            child_category: category.to_string(),
+            description: None,
+            relation_type: None,
+        };
+        let _: Relation = api
+            .post("/relations/", guard.access_token(), None, relation)
+            .await?;
+    }
+    Ok(Redirect::to(local_redirect(form.redirect, id)))
+}
+
+/// exports a single fragment as a pdf, the renderer is passed along as in [`get_fragment`].
+#[get("/<id>/export.pdf?<renderer>&<options..>")]
+async fn export_pdf(
+    guard: Guard,
+    id: Uuid,
+    renderer: Option<SupportedRender>,
+    options: PdfOptions,
+    api: &State<ApiClient>,
+    pdf: &State<PdfRenderer>,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let fragment = fetch_fragment(&guard, id, api).await?;
+    let renderer = match renderer {
+        Some(renderer) => renderer,
+        None => story_renderer(api, guard.access_token(), id).await,
+    };
+    let blocks = content_blocks(renderer, &fragment.content);
+    let export = PdfExport::new(&fragment.name)
+        .title_page(false)
+        .section(PdfSection::new(&fragment.name).blocks(blocks));
+    Ok((ContentType::PDF, pdf.render(export, options).await?))
+}
+
 #[get("/")]
 async fn list_fragments(guard: Guard, api: &State<ApiClient>) -> RawHtml<Template> {
     let fragments: Vec<StoryFragment> = match api
@@ -269,6 +523,10 @@ pub fn get_routes() -> Vec<Route> {
         create_fragment_html,
         create_fragment,
         list_fragments,
-        edit_fragment
+        edit_fragment,
+        export_pdf,
+        link_fragment_html,
+        link_fragment,
+        dismiss_mention
     ]
 }
@@ -0,0 +1,284 @@
+//! finds characters and locations mentioned in a fragment's prose.
+//!
+//! names match case insensitively and ignore possessives, so `Mara Voss's` and `MARA VOSS` both
+//! count as mentions of Mara Voss. characters also get aliases derived from their name: the
+//! nickname in `Mara "Gull" Voss`, and the first and last names of `Captain Mara Voss`. aliases
+//! and single word names must be capitalized in the text, which keeps a character called Hope
+//! from matching every "hope".
+use crate::render::html::html_to_text;
+use crate::render::links::{EntityIndex, LinkTarget};
+use crate::render::{Renderer, SupportedRender};
+use std::collections::HashMap;
+
+/// words dropped from the front of a name before deriving aliases.
+const TITLES: &[&str] = &[
+    "captain", "capt", "dr", "doctor", "mr", "mrs", "ms", "miss", "lady", "lord", "sir", "dame",
+    "king", "queen", "prince", "princess", "professor", "prof", "father", "mother", "sister",
+    "brother", "saint", "st", "the",
+];
+
+/// words that can't be an alias on their own.
+const PARTICLES: &[&str] = &["of", "the", "de", "da", "del", "van", "von", "la", "le", "al", "bin"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mention {
+    pub target: LinkTarget,
+    /// the first matching text as written in the fragment.
+    pub matched: String,
+    pub count: usize,
+    /// text surrounding the first mention.
+    pub excerpt: String,
+}
+
+#[derive(Debug, Clone)]
+struct Phrase {
+    words: Vec<String>,
+    target: usize,
+    /// aliases and single words only match when capitalized in the text.
+    capitalized: bool,
+}
+
+pub struct MentionAnalyzer {
+    targets: Vec<LinkTarget>,
+    phrases: Vec<Phrase>,
+}
+
+struct Word<'t> {
+    start: usize,
+    end: usize,
+    text: &'t str,
+    key: String,
+}
+
+fn normalize(word: &str) -> String {
+    let word = word.replace('\u{2019}', "'");
+    let word = word
+        .strip_suffix("'s")
+        .or_else(|| word.strip_suffix('\''))
+        .unwrap_or(&word);
+    word.to_lowercase()
+}
+
+/// splits text into words, apostrophes and hyphens inside a word are kept.
+fn words(text: &str) -> Vec<Word<'_>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    for (i, &(offset, c)) in chars.iter().enumerate() {
+        let joiner = matches!(c, '\'' | '\u{2019}' | '-');
+        let inner = joiner
+            && start.is_some()
+            && chars.get(i + 1).is_some_and(|(_, next)| next.is_alphanumeric());
+        // a trailing apostrophe is a possessive, as in `Voss'`.
+        let possessive = matches!(c, '\'' | '\u{2019}') && start.is_some();
+        if c.is_alphanumeric() || inner || possessive {
+            start.get_or_insert(offset);
+        } else if let Some(begin) = start.take() {
+            words.push(&text[begin..offset]);
+        }
+    }
+    if let Some(begin) = start {
+        words.push(&text[begin..]);
+    }
+
+    let base = text.as_ptr() as usize;
+    words
+        .into_iter()
+        .map(|word| {
+            let start = word.as_ptr() as usize - base;
+            Word {
+                start,
+                end: start + word.len(),
+                text: word,
+                key: normalize(word),
+            }
+        })
+        .collect()
+}
+
+fn phrase(name: &str) -> Vec<String> {
+    words(name).iter().map(|word| word.key.clone()).collect()
+}
+
+/// the nickname and the plain name of `Mara "Gull" Voss` or `Mara (Gull) Voss`.
+fn split_nickname(name: &str) -> (Option<String>, String) {
+    for (open, close) in [('"', '"'), ('\u{201c}', '\u{201d}'), ('(', ')')] {
+        if let Some(start) = name.find(open) {
+            let inner = &name[start + open.len_utf8()..];
+            if let Some(end) = inner.find(close) {
+                let nickname = inner[..end].trim().to_string();
+                let plain = format!("{} {}", &name[..start], &inner[end + close.len_utf8()..]);
+                let plain = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+                return (Some(nickname).filter(|n| !n.is_empty()), plain);
+            }
+        }
+    }
+    (None, name.to_string())
+}
+
+/// the full names of the target and the aliases derived from them, each as normalized words.
+fn aliases_of(target: &LinkTarget) -> (Vec<Vec<String>>, Vec<Vec<String>>) {
+    let (nickname, plain) = split_nickname(&target.name);
+    let mut names = vec![phrase(&target.name), phrase(&plain)];
+    let mut aliases = Vec::new();
+
+    if target.category == "characters" {
+        aliases.extend(nickname.as_deref().map(phrase));
+        let words = phrase(&plain);
+        let untitled: Vec<String> = words
+            .iter()
+            .skip_while(|word| TITLES.contains(&word.as_str()))
+            .cloned()
+            .collect();
+        if untitled.len() > 1 {
+            aliases.push(untitled.clone());
+            aliases.push(vec![untitled[0].clone()]);
+            aliases.push(vec![untitled[untitled.len() - 1].clone()]);
+        }
+        if untitled.len() < words.len() && !untitled.is_empty() {
+            // `Captain Voss` for `Captain Mara Voss`.
+            let mut titled = words[..words.len() - untitled.len()].to_vec();
+            titled.push(untitled[untitled.len() - 1].clone());
+            aliases.push(titled);
+        }
+    } else if let Some(rest) = plain.strip_prefix("The ").or(plain.strip_prefix("the ")) {
+        names.push(phrase(rest));
+    }
+
+    aliases.retain(|alias| {
+        !(alias.is_empty() || alias.len() == 1 && PARTICLES.contains(&alias[0].as_str()))
+    });
+    names.retain(|name| !name.is_empty());
+    (names, aliases)
+}
+
+impl MentionAnalyzer {
+    pub fn new<'t>(targets: impl IntoIterator<Item = &'t LinkTarget>) -> Self {
+        let targets: Vec<LinkTarget> = targets.into_iter().cloned().collect();
+        let mut names: HashMap<Vec<String>, usize> = HashMap::new();
+        // aliases shared by several entities are ambiguous and dropped.
+        let mut aliases: HashMap<Vec<String>, Option<usize>> = HashMap::new();
+
+        for (i, target) in targets.iter().enumerate() {
+            let (full, derived) = aliases_of(target);
+            for name in full {
+                names.entry(name).or_insert(i);
+            }
+            for alias in derived {
+                aliases
+                    .entry(alias)
+                    .and_modify(|owner| {
+                        if *owner != Some(i) {
+                            *owner = None;
+                        }
+                    })
+                    .or_insert(Some(i));
+            }
+        }
+
+        let mut phrases: Vec<Phrase> = names
+            .iter()
+            .map(|(words, target)| Phrase {
+                words: words.clone(),
+                target: *target,
+                capitalized: words.len() == 1,
+            })
+            .collect();
+        for (words, owner) in aliases {
+            if let Some(target) = owner
+                && !names.contains_key(&words)
+            {
+                phrases.push(Phrase {
+                    words,
+                    target,
+                    capitalized: true,
+                });
+            }
+        }
+        // longest phrases first so `Mara Voss` wins over `Mara`.
+        phrases.sort_by_key(|phrase| std::cmp::Reverse(phrase.words.len()));
+
+        Self { targets, phrases }
+    }
+
+    pub fn from_index(index: &EntityIndex) -> Self {
+        Self::new(index.targets())
+    }
+
+    /// mentions in plain text, ordered by first appearance.
+    pub fn scan(&self, text: &str) -> Vec<Mention> {
+        let words = words(text);
+        let mut mentions: Vec<Mention> = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let matched = self.phrases.iter().find(|phrase| {
+                let n = phrase.words.len();
+                if words.len() - i < n {
+                    return false;
+                }
+                let candidate = &words[i..i + n];
+                let keys = candidate.iter().zip(phrase.words.iter());
+                // a name can't continue past punctuation, `Tomas. Voss` is two sentences.
+                let joined = candidate
+                    .windows(2)
+                    .all(|pair| text[pair[0].end..pair[1].start].trim().is_empty());
+                let capitalized = !phrase.capitalized
+                    || candidate
+                        .iter()
+                        .all(|word| word.text.chars().next().is_some_and(char::is_uppercase));
+                keys.into_iter().all(|(word, key)| &word.key == key) && joined && capitalized
+            });
+            let Some(phrase) = matched else {
+                i += 1;
+                continue;
+            };
+
+            let n = phrase.words.len();
+            let target = &self.targets[phrase.target];
+            match mentions.iter_mut().find(|m| m.target.id == target.id) {
+                Some(mention) => mention.count += 1,
+                None => mentions.push(Mention {
+                    target: target.clone(),
+                    matched: text[words[i].start..words[i + n - 1].end].to_string(),
+                    count: 1,
+                    excerpt: excerpt(text, words[i].start, words[i + n - 1].end),
+                }),
+            }
+            i += n;
+        }
+        mentions
+    }
+
+    /// renders the content first so markup doesn't get mistaken for prose.
+    pub fn scan_content(&self, renderer: SupportedRender, content: &str) -> Vec<Mention> {
+        self.scan(&html_to_text(&renderer.render(content)))
+    }
+}
+
+/// about sixty characters either side of the match, cut at word boundaries.
+fn excerpt(text: &str, start: usize, end: usize) -> String {
+    const CONTEXT: usize = 60;
+    let mut from = start.saturating_sub(CONTEXT);
+    while !text.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (end + CONTEXT).min(text.len());
+    while !text.is_char_boundary(to) {
+        to += 1;
+    }
+    if from > 0 {
+        from = text[from..start].find(' ').map(|i| from + i + 1).unwrap_or(from);
+    }
+    if to < text.len() {
+        to = text[end..to].rfind(' ').map(|i| end + i).unwrap_or(to);
+    }
+
+    let mut excerpt = text[from..to].split_whitespace().collect::<Vec<_>>().join(" ");
+    if from > 0 {
+        excerpt.insert(0, '\u{2026}');
+    }
+    if to < text.len() {
+        excerpt.push('\u{2026}');
+    }
+    excerpt
+}
@@ -1,4 +1,5 @@
 pub mod api;
 pub(crate) mod frontend;
+pub mod mentions;
 
 pub use frontend::get_routes;
@@ -15,10 +15,12 @@ extern crate err_derive;
 use time::UtcOffset;
 use time::{OffsetDateTime, format_description, format_description::well_known::Rfc3339};
 
+pub mod calendars;
 pub mod characters;
 pub mod config;
 pub mod errors;
 pub mod locations;
+pub mod maps;
 mod model;
 pub mod profiles;
 pub mod stories;
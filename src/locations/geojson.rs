@@ -0,0 +1,254 @@
+//! GeoJSON (RFC 7946) import and export of locations.
+//!
+//! every location becomes a `Feature` of a `FeatureCollection`, its shape when it has one and a
+//! `Point` at its coordinates otherwise, with its name, description and tags as properties. an
+//! imported feature whose id is an existing location places that location, any other creates a
+//! new one. positions are `[longitude, latitude]` in decimal degrees, the reverse of
+//! [`Coordinates`], any altitude is ignored.
+use super::{Coordinates, LocationBuilder, LocationRender, Placement};
+use crate::ApiClient;
+use crate::errors::ApiError;
+use crate::model::Location;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+pub type Position = Vec<f64>;
+
+/// the shapes a location can have, GeoJSON geometries without the collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    Point {
+        coordinates: Position,
+    },
+    LineString {
+        coordinates: Vec<Position>,
+    },
+    Polygon {
+        coordinates: Vec<Vec<Position>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<Position>>>,
+    },
+}
+
+fn coordinates(position: &[f64]) -> Option<Coordinates> {
+    match position {
+        [longitude, latitude] | [longitude, latitude, _] => Some(Coordinates {
+            latitude: *latitude,
+            longitude: *longitude,
+        }),
+        _ => None,
+    }
+}
+
+fn invalid(reason: &str) -> ApiError {
+    ApiError::UnprocessableEntity(format!("invalid geometry: {}", reason))
+}
+
+impl Geometry {
+    pub fn point(at: Coordinates) -> Self {
+        Geometry::Point {
+            coordinates: vec![at.longitude, at.latitude],
+        }
+    }
+
+    /// every ring of the shape, a line or a point counting as one.
+    fn rings(&self) -> Vec<&[Position]> {
+        match self {
+            Geometry::Point { coordinates } => vec![std::slice::from_ref(coordinates)],
+            Geometry::LineString { coordinates } => vec![coordinates],
+            Geometry::Polygon { coordinates } => coordinates.iter().map(Vec::as_slice).collect(),
+            Geometry::MultiPolygon { coordinates } => {
+                coordinates.iter().flatten().map(Vec::as_slice).collect()
+            }
+        }
+    }
+
+    /// checks every position is on earth and lines and rings are long enough, rings closed.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let closed = !matches!(self, Geometry::Point { .. } | Geometry::LineString { .. });
+        let rings = self.rings();
+        if rings.is_empty() {
+            return Err(invalid("it has no positions"));
+        }
+        for ring in rings {
+            for position in ring {
+                let at = coordinates(position).ok_or_else(|| invalid("positions are [x, y]"))?;
+                if at.latitude.abs() > 90.0 || at.longitude.abs() > 180.0 {
+                    return Err(invalid("a position is out of range"));
+                }
+            }
+            match self {
+                Geometry::LineString { .. } if ring.len() < 2 => {
+                    return Err(invalid("a line needs two positions"));
+                }
+                _ if closed && (ring.len() < 4 || ring.first() != ring.last()) => {
+                    return Err(invalid(
+                        "a ring needs four positions, the last the same as the first",
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// the point the location is found by, the middle of its vertices.
+    pub fn center(&self) -> Option<Coordinates> {
+        let mut points = Vec::new();
+        for ring in self.rings() {
+            // a closed ring repeats its first vertex, which would weigh it twice.
+            let open = match self {
+                Geometry::Polygon { .. } | Geometry::MultiPolygon { .. } => {
+                    &ring[..ring.len().saturating_sub(1)]
+                }
+                _ => ring,
+            };
+            points.extend(open.iter().filter_map(|position| coordinates(position)));
+        }
+        Coordinates::centroid(&points)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "Feature")]
+pub struct Feature {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub geometry: Option<Geometry>,
+    #[serde(default)]
+    pub properties: Option<Map<String, Value>>,
+}
+
+impl Feature {
+    pub fn from_location(location: &LocationRender) -> Self {
+        let mut properties = Map::new();
+        properties.insert("name".to_string(), Value::from(location.name.clone()));
+        if let Some(description) = &location.description {
+            properties.insert("description".to_string(), Value::from(description.clone()));
+        }
+        let tags: Vec<Value> = location
+            .tags
+            .iter()
+            .flatten()
+            .map(|tag| Value::from(tag.value()))
+            .collect();
+        if !tags.is_empty() {
+            properties.insert("tags".to_string(), Value::Array(tags));
+        }
+        Self {
+            id: Some(Value::from(location.id.to_string())),
+            geometry: location
+                .geometry
+                .clone()
+                .or_else(|| location.coordinates().map(Geometry::point)),
+            properties: Some(properties),
+        }
+    }
+
+    fn property(&self, key: &str) -> Option<&Value> {
+        self.properties.as_ref()?.get(key)
+    }
+
+    fn text(&self, key: &str) -> Option<String> {
+        let text = self.property(key)?.as_str()?.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    /// the `name` property, or `title` as some tools call it.
+    pub fn name(&self) -> Option<String> {
+        self.text("name").or_else(|| self.text("title"))
+    }
+
+    /// the location this feature was exported from, if it was.
+    pub fn location(&self) -> Option<Uuid> {
+        self.id.as_ref()?.as_str()?.parse().ok()
+    }
+
+    /// the coordinates and shape to give a location, a point only sets coordinates.
+    pub fn placement(&self) -> Placement {
+        let coordinates = self.geometry.as_ref().and_then(Geometry::center);
+        let geometry = self
+            .geometry
+            .clone()
+            .filter(|geometry| !matches!(geometry, Geometry::Point { .. }));
+        Placement::new(coordinates, geometry)
+    }
+
+    pub fn to_builder(&self) -> Result<LocationBuilder, ApiError> {
+        let name = self.name().ok_or_else(|| {
+            ApiError::UnprocessableEntity("every feature needs a name property".to_string())
+        })?;
+        let tags = match self.property("tags") {
+            Some(Value::Array(tags)) => tags
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let placement = self.placement();
+        Ok(LocationBuilder {
+            name,
+            description: self.text("description"),
+            tags,
+            thumbnail: None,
+            latitude: placement.latitude,
+            longitude: placement.longitude,
+            geometry: placement.geometry,
+            parent: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct FeatureCollection {
+    pub features: Vec<Feature>,
+}
+
+impl FeatureCollection {
+    pub fn from_locations(locations: &[LocationRender]) -> Self {
+        Self {
+            features: locations.iter().map(Feature::from_location).collect(),
+        }
+    }
+
+    /// reads a collection, checking every geometry before anything is imported.
+    pub fn parse(text: &str) -> Result<Self, ApiError> {
+        let collection: Self = serde_json::from_str(text).map_err(|err| {
+            ApiError::UnprocessableEntity(format!("not a GeoJSON FeatureCollection: {}", err))
+        })?;
+        for feature in collection.features.iter() {
+            if let Some(geometry) = &feature.geometry {
+                geometry.validate()?;
+            }
+        }
+        Ok(collection)
+    }
+
+    /// places the `existing` locations features refer to and creates a location for every
+    /// other feature, returning the locations in the order of the collection.
+    pub async fn import(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+        existing: &[Uuid],
+    ) -> Result<Vec<Uuid>, ApiError> {
+        let mut imported = Vec::new();
+        for feature in self.features.iter() {
+            match feature.location().filter(|id| existing.contains(id)) {
+                Some(id) => {
+                    feature.placement().set(api, access_token, id).await?;
+                    imported.push(id);
+                }
+                None => {
+                    let location: Location = feature.to_builder()?.build(api, access_token).await?;
+                    imported.push(location.id);
+                }
+            }
+        }
+        Ok(imported)
+    }
+}
@@ -0,0 +1,276 @@
+//! locations inside one another.
+//!
+//! every location may have a parent containing it, the tavern in the harbour district of the
+//! capital. a [`Hierarchy`] is built from the list of locations a reader can see, a location
+//! whose parent they can't see is shown at the top level. the api keeps parents from forming a
+//! cycle, the hierarchy still stops walking when it meets a location twice.
+use super::LocationRender;
+use crate::ApiClient;
+use crate::errors::ApiError;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// a location in the tree view, in depth first order.
+#[derive(Debug, Clone, Serialize)]
+pub struct Branch {
+    pub location: LocationRender,
+    pub depth: usize,
+    /// how many locations are directly inside this one.
+    pub children: usize,
+}
+
+/// a fragment set in a location or anywhere inside it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Setting {
+    pub id: Uuid,
+    pub name: String,
+    /// the location the fragment is related to, the location asked about or one inside it.
+    pub location: Uuid,
+    pub location_name: String,
+}
+
+impl Setting {
+    /// every fragment set anywhere inside `location`.
+    pub async fn within(
+        api: &ApiClient,
+        access_token: &str,
+        location: Uuid,
+    ) -> Result<Vec<Self>, ApiError> {
+        let url = format!("/locations/{}/within", location);
+        api.get_protected(&url, access_token, None).await
+    }
+}
+
+/// the body of `PUT /locations/{id}/parent`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Parent {
+    parent: Option<Uuid>,
+}
+
+/// moves `location` inside `parent`, or to the top level.
+pub async fn set_parent(
+    api: &ApiClient,
+    access_token: &str,
+    location: Uuid,
+    parent: Option<Uuid>,
+) -> Result<(), ApiError> {
+    if parent == Some(location) {
+        return Err(ApiError::UnprocessableEntity(
+            "a location can't be inside itself".to_string(),
+        ));
+    }
+    let url = format!("/locations/{}/parent", location);
+    let _: serde_json::Value = api.put(&url, access_token, Parent { parent }).await?;
+    Ok(())
+}
+
+fn quote(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub struct Hierarchy<'a> {
+    locations: HashMap<Uuid, &'a LocationRender>,
+    /// the locations inside each location by name, `None` holding the top level.
+    children: HashMap<Option<Uuid>, Vec<&'a LocationRender>>,
+}
+
+impl<'a> Hierarchy<'a> {
+    pub fn new(locations: &'a [LocationRender]) -> Self {
+        let by_id: HashMap<Uuid, &LocationRender> = locations
+            .iter()
+            .map(|location| (location.id, location))
+            .collect();
+        let mut children: HashMap<Option<Uuid>, Vec<&LocationRender>> = HashMap::new();
+        for location in locations {
+            let parent = location
+                .parent
+                .filter(|parent| *parent != location.id && by_id.contains_key(parent));
+            children.entry(parent).or_default().push(location);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        Self {
+            locations: by_id,
+            children,
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&'a LocationRender> {
+        self.locations.get(&id).copied()
+    }
+
+    /// the locations not inside any other the reader can see.
+    pub fn roots(&self) -> &[&'a LocationRender] {
+        self.children.get(&None).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn children(&self, id: Uuid) -> &[&'a LocationRender] {
+        self.children.get(&Some(id)).map_or(&[], Vec::as_slice)
+    }
+
+    /// the locations containing `id`, outermost first, for the breadcrumb.
+    pub fn ancestors(&self, id: Uuid) -> Vec<&'a LocationRender> {
+        let mut ancestors = Vec::new();
+        let mut seen = HashSet::from([id]);
+        let mut current = self.get(id).and_then(|location| location.parent);
+        while let Some(parent) = current.and_then(|parent| self.get(parent)) {
+            if !seen.insert(parent.id) {
+                break;
+            }
+            ancestors.push(parent);
+            current = parent.parent;
+        }
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// every location inside `id` however deeply, depth first.
+    pub fn descendants(&self, id: Uuid) -> Vec<&'a LocationRender> {
+        let mut descendants = Vec::new();
+        let mut seen = HashSet::from([id]);
+        let mut stack: Vec<&LocationRender> = self.children(id).iter().rev().copied().collect();
+        while let Some(location) = stack.pop() {
+            if !seen.insert(location.id) {
+                continue;
+            }
+            descendants.push(location);
+            stack.extend(self.children(location.id).iter().rev());
+        }
+        descendants
+    }
+
+    /// the locations `id` can be moved inside, every other one but those it contains.
+    pub fn candidates(&self, id: Uuid) -> Vec<&'a LocationRender> {
+        let inside: HashSet<Uuid> = self.descendants(id).iter().map(|l| l.id).collect();
+        let mut candidates: Vec<&LocationRender> = self
+            .locations
+            .values()
+            .filter(|location| location.id != id && !inside.contains(&location.id))
+            .copied()
+            .collect();
+        candidates.sort_by(|a, b| a.name.cmp(&b.name));
+        candidates
+    }
+
+    /// every location, each followed by the ones inside it.
+    pub fn tree(&self) -> Vec<Branch> {
+        let mut tree = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack: Vec<(&LocationRender, usize)> =
+            self.roots().iter().rev().map(|root| (*root, 0)).collect();
+        loop {
+            while let Some((location, depth)) = stack.pop() {
+                if !seen.insert(location.id) {
+                    continue;
+                }
+                let children = self.children(location.id);
+                tree.push(Branch {
+                    location: location.clone(),
+                    depth,
+                    children: children.len(),
+                });
+                stack.extend(children.iter().rev().map(|child| (*child, depth + 1)));
+            }
+            // locations in a cycle never reach the top level, they're listed there instead.
+            let mut stranded: Vec<&LocationRender> = self
+                .locations
+                .values()
+                .filter(|location| !seen.contains(&location.id))
+                .copied()
+                .collect();
+            stranded.sort_by(|a, b| a.name.cmp(&b.name));
+            match stranded.first() {
+                Some(location) => stack.push((location, 0)),
+                None => break,
+            }
+        }
+        tree
+    }
+
+    /// the fragments to draw, each inside the innermost location it's set in.
+    fn placed<'s>(&self, settings: &'s [Setting]) -> HashMap<Uuid, Vec<&'s Setting>> {
+        let mut innermost: HashMap<Uuid, &Setting> = HashMap::new();
+        for setting in settings {
+            let depth = |setting: &Setting| self.ancestors(setting.location).len();
+            match innermost.get(&setting.id) {
+                Some(kept) if depth(kept) >= depth(setting) => {}
+                _ => {
+                    innermost.insert(setting.id, setting);
+                }
+            }
+        }
+        let mut placed: HashMap<Uuid, Vec<&Setting>> = HashMap::new();
+        for setting in innermost.into_values() {
+            placed.entry(setting.location).or_default().push(setting);
+        }
+        for fragments in placed.values_mut() {
+            fragments.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        placed
+    }
+
+    /// graphviz source drawing every location containing others as a cluster around them, with
+    /// the fragments set in each. `root` limits it to one location and what's inside it.
+    pub fn dot(&self, root: Option<Uuid>, settings: &[Setting]) -> String {
+        let placed = self.placed(settings);
+        let roots: Vec<&LocationRender> = match root.and_then(|root| self.get(root)) {
+            Some(root) => vec![root],
+            None => self.roots().to_vec(),
+        };
+        let mut dot = String::from("digraph {\n    compound=true;\n    node [shape=house];\n");
+        let mut seen = HashSet::new();
+        for location in roots {
+            self.write_location(&mut dot, location, &placed, &mut seen, 1);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn write_location(
+        &self,
+        dot: &mut String,
+        location: &LocationRender,
+        placed: &HashMap<Uuid, Vec<&Setting>>,
+        seen: &mut HashSet<Uuid>,
+        depth: usize,
+    ) {
+        if !seen.insert(location.id) {
+            return;
+        }
+        let indent = "    ".repeat(depth);
+        let name = quote(&location.name);
+        let children = self.children(location.id);
+        let fragments = placed.get(&location.id).map_or(&[][..], Vec::as_slice);
+        let node = format!(
+            "\"{}\" [label=\"{}\" URL=\"/locations/{}\" class=\"location\"];",
+            location.id, name, location.id
+        );
+        if children.is_empty() && fragments.is_empty() {
+            let _ = writeln!(dot, "{}{}", indent, node);
+            return;
+        }
+        let _ = writeln!(dot, "{}subgraph \"cluster_{}\" {{", indent, location.id);
+        let _ = writeln!(
+            dot,
+            "{}    label=\"{}\"; URL=\"/locations/{}\"; style=rounded;",
+            indent, name, location.id
+        );
+        let _ = writeln!(dot, "{}    {}", indent, node);
+        for fragment in fragments {
+            let _ = writeln!(
+                dot,
+                "{}    \"{}\" [label=\"{}\" shape=note URL=\"/fragments/{}\" class=\"fragment\"];",
+                indent,
+                fragment.id,
+                quote(&fragment.name),
+                fragment.id
+            );
+        }
+        for child in children {
+            self.write_location(dot, child, placed, seen, depth + 1);
+        }
+        let _ = writeln!(dot, "{}}}", indent);
+    }
+}
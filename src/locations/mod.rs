@@ -1,11 +1,25 @@
+pub mod geojson;
+pub mod hierarchy;
+
 use crate::ApiClient;
-use crate::assets::graphs::{Entity, EntityExt, Renderable, render_children};
-use crate::assets::images::ImageForm;
+use crate::assets::graphs::{Entity, EntityExt, Renderable, render_children, render_clusters};
+use crate::assets::images::{ImageForm, ImageRender, image_id};
 use crate::auth::Guard;
-use crate::errors::LazyError;
+use crate::calendars::api::Calendar;
+use crate::calendars::frontend::preferred;
+use crate::characters::merge::{Link, cascade_links};
+use crate::errors::{ApiError, LazyError};
 use crate::model::{Location, Tag};
+use crate::render::SupportedRender;
+use crate::render::pdf::{PdfExport, PdfOptions, PdfRenderer, PdfSection, content_blocks};
+use crate::timelines::photos::Photo;
+use geojson::{FeatureCollection, Geometry};
+use hierarchy::{Hierarchy, Setting, set_parent};
+use reqwest::Method;
+use rocket::http::{ContentType, CookieJar};
 use rocket::fs::TempFile;
 use std::collections::HashMap;
+use tokio::io::AsyncReadExt;
 use wrappedviz::rgraph::{Edge, Node};
 use wrappedviz::style::{CommonAttr, NodeAttr, shape::NodeShape};
 use wrappedviz::{CompatGraph, CompatNode};
@@ -13,8 +27,8 @@ use wrappedviz::{CompatGraph, CompatNode};
 use crate::assets::images::{ImageBuilder, ImageProcessor};
 use crate::model::Image;
 use rocket::{
-    FromForm, Route, State, delete, form::Form, get, post, put, response::Redirect,
-    response::content::RawHtml, routes,
+    FromForm, Route, State, form::Form, get, post, response::Redirect, response::content::RawHtml,
+    routes,
 };
 use rocket_dyn_templates::{Template, context};
 use uuid::Uuid;
@@ -28,6 +42,165 @@ pub struct LocationRender {
     pub thumbnail: Option<Image>,
     pub tags: Option<Vec<Tag>>,
     pub created: Option<i64>,
+    /// `created` formatted in the reader's calendar, filled in before templating.
+    #[serde(default, skip_deserializing)]
+    pub created_date: Option<String>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    /// the shape of the location, when it's more than a point.
+    #[serde(default)]
+    pub geometry: Option<Geometry>,
+    /// the location this one is inside.
+    #[serde(default)]
+    pub parent: Option<Uuid>,
+}
+
+/// a point on earth in decimal degrees, north and east are positive.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl Coordinates {
+    const EARTH_RADIUS: f64 = 6_371.0;
+
+    /// the great circle distance in kilometres.
+    pub fn distance(&self, other: &Coordinates) -> f64 {
+        let (a, b) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = b - a;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let h = (dlat / 2.0).sin().powi(2) + a.cos() * b.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * Self::EARTH_RADIUS * h.sqrt().min(1.0).asin()
+    }
+
+    /// the middle of some points, averaged on the sphere so points either side of the
+    /// antimeridian meet there. `None` without points or when they cancel out.
+    pub fn centroid(points: &[Coordinates]) -> Option<Coordinates> {
+        if let [point] = points {
+            return Some(*point);
+        }
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        for point in points {
+            let (latitude, longitude) = (point.latitude.to_radians(), point.longitude.to_radians());
+            x += latitude.cos() * longitude.cos();
+            y += latitude.cos() * longitude.sin();
+            z += latitude.sin();
+        }
+        if (x * x + y * y + z * z).sqrt() < 1e-9 {
+            return None;
+        }
+        Some(Coordinates {
+            latitude: z.atan2((x * x + y * y).sqrt()).to_degrees(),
+            longitude: y.atan2(x).to_degrees(),
+        })
+    }
+}
+
+/// the body of `PUT /locations/{id}/coordinates`, `None` everywhere clears them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Placement {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub geometry: Option<Geometry>,
+}
+
+impl Placement {
+    pub fn new(coordinates: Option<Coordinates>, geometry: Option<Geometry>) -> Self {
+        Self {
+            latitude: coordinates.map(|at| at.latitude),
+            longitude: coordinates.map(|at| at.longitude),
+            geometry,
+        }
+    }
+
+    /// from the coordinates form, where a geometry pasted as GeoJSON places the location at its
+    /// centre unless coordinates are given too.
+    pub fn from_form(
+        latitude: Option<f64>,
+        longitude: Option<f64>,
+        geometry: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        let geometry = match geometry.map(str::trim).filter(|text| !text.is_empty()) {
+            Some(text) => {
+                let geometry: Geometry = serde_json::from_str(text).map_err(|err| {
+                    ApiError::UnprocessableEntity(format!("not a GeoJSON geometry: {}", err))
+                })?;
+                geometry.validate()?;
+                Some(geometry)
+            }
+            None => None,
+        };
+        let coordinates = match (latitude, longitude) {
+            (Some(latitude), Some(longitude)) => Some(Coordinates {
+                latitude,
+                longitude,
+            }),
+            (None, None) => geometry.as_ref().and_then(Geometry::center),
+            _ => {
+                return Err(ApiError::UnprocessableEntity(
+                    "a location needs both a latitude and a longitude".to_string(),
+                ));
+            }
+        };
+        if let Some(at) = coordinates
+            && (at.latitude.abs() > 90.0 || at.longitude.abs() > 180.0)
+        {
+            return Err(ApiError::UnprocessableEntity(
+                "coordinates out of range".to_string(),
+            ));
+        }
+        Ok(Self::new(coordinates, geometry))
+    }
+
+    pub async fn set(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+        id: Uuid,
+    ) -> Result<LocationRender, ApiError> {
+        let url = format!("/locations/{}/coordinates", id);
+        api.put(&url, access_token, self).await
+    }
+}
+
+/// another location and how far away it is, in kilometres.
+#[derive(Debug, Clone, Serialize)]
+pub struct Nearby {
+    pub location: LocationRender,
+    pub distance: f64,
+}
+
+/// where the photos attached to a location were taken, read from their GPS tags.
+#[derive(Debug, Clone, Serialize)]
+pub struct Suggestion {
+    /// the middle of the photos, what the location is moved to.
+    pub coordinates: Coordinates,
+    pub photos: Vec<(Image, Coordinates)>,
+    /// how far the location is now from the photos, `None` while it has no coordinates.
+    pub distance: Option<f64>,
+}
+
+impl Suggestion {
+    /// below this many kilometres from the photos a location isn't worth moving.
+    const CLOSE: f64 = 0.05;
+
+    /// `None` when no photo has a position or the location is already where they were taken.
+    pub fn new(current: Option<Coordinates>, photos: Vec<(Image, Coordinates)>) -> Option<Self> {
+        let positions: Vec<Coordinates> = photos.iter().map(|(_, at)| *at).collect();
+        let coordinates = Coordinates::centroid(&positions)?;
+        let distance = current.map(|current| current.distance(&coordinates));
+        if distance.is_some_and(|distance| distance < Self::CLOSE) {
+            return None;
+        }
+        Some(Self {
+            coordinates,
+            photos,
+            distance,
+        })
+    }
 }
 
 #[derive(Debug, FromForm)]
@@ -37,15 +210,24 @@ pub struct LocationForm<'r> {
     tags: Option<Vec<String>>,
     images: Option<Vec<TempFile<'r>>>,
     imagetags: Option<Vec<String>>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    parent: Option<Uuid>,
 }
 
 impl<'r> LocationForm<'r> {
     pub async fn to_builder(&self, processor: &ImageProcessor) -> anyhow::Result<LocationBuilder> {
+        // a location is only placed when both halves of its coordinates are given.
+        let coordinates = self.latitude.zip(self.longitude);
         Ok(LocationBuilder {
             name: self.name.clone(),
             description: self.description.clone(),
             tags: self.tags.as_ref().unwrap_or(&Vec::new()).to_vec(),
             thumbnail: self.into_image_builder(processor).await?,
+            latitude: coordinates.map(|(latitude, _)| latitude),
+            longitude: coordinates.map(|(_, longitude)| longitude),
+            geometry: None,
+            parent: self.parent,
         })
     }
 }
@@ -78,45 +260,411 @@ pub struct LocationBuilder {
     description: Option<String>,
     tags: Vec<String>,
     thumbnail: Option<ImageBuilder>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    geometry: Option<Geometry>,
+    parent: Option<Uuid>,
+}
+
+impl LocationBuilder {
+    /// a location at a point, without tags or images.
+    pub fn at(name: &str, description: Option<String>, coordinates: Coordinates) -> Self {
+        Self {
+            name: name.to_string(),
+            description,
+            tags: Vec::new(),
+            thumbnail: None,
+            latitude: Some(coordinates.latitude),
+            longitude: Some(coordinates.longitude),
+            geometry: None,
+            parent: None,
+        }
+    }
+
+    pub async fn build(&self, api: &ApiClient, access_token: &str) -> Result<Location, ApiError> {
+        api.post("/locations/", access_token, None, self).await
+    }
+}
+
+/// the body of `PUT /locations/{id}`, everything the edit page changes at once. `tags` replace
+/// the location's tags, `remove_images` are unlinked from it and `thumbnail` must be one of the
+/// images it keeps.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LocationUpdate {
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    pub thumbnail: Option<Uuid>,
+    pub remove_images: Vec<Uuid>,
+    pub parent: Option<Uuid>,
+}
+
+impl LocationUpdate {
+    pub async fn edit(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+        id: Uuid,
+    ) -> Result<LocationRender, ApiError> {
+        if self.parent == Some(id) {
+            return Err(ApiError::UnprocessableEntity(
+                "a location can't be inside itself".to_string(),
+            ));
+        }
+        let url = format!("/locations/{}", id);
+        api.put(&url, access_token, self).await
+    }
 }
 
+/// the primary image once an edit is saved: the one picked if it's kept, else the current one if
+/// it's kept, else the first image uploaded, else the first one left.
+pub fn primary_image(
+    picked: Option<Uuid>,
+    current: Option<Uuid>,
+    images: &[Uuid],
+    removed: &[Uuid],
+    uploaded: &[Uuid],
+) -> Option<Uuid> {
+    let kept = |id: &Uuid| !removed.contains(id);
+    picked
+        .filter(|id| kept(id) && (images.contains(id) || uploaded.contains(id)))
+        .or(current.filter(kept))
+        .or_else(|| uploaded.first().copied())
+        .or_else(|| images.iter().find(|id| kept(id)).copied())
+}
+
+#[derive(Debug, FromForm)]
+pub struct EditLocationForm<'r> {
+    id: Uuid,
+    name: String,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+    images: Option<Vec<TempFile<'r>>>,
+    /// the image to show first.
+    thumbnail: Option<Uuid>,
+    /// the images to take off the location.
+    remove: Option<Vec<Uuid>>,
+    /// empty to move the location to the top level.
+    parent: Option<Uuid>,
+}
+
+impl<'r> EditLocationForm<'r> {
+    fn uploads(&self) -> bool {
+        self.images
+            .iter()
+            .flatten()
+            .any(|image| image.name().is_some())
+    }
+
+    /// uploads the new images, they're linked to the location as they're uploaded.
+    pub async fn upload(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+        processor: &ImageProcessor,
+    ) -> Result<Vec<Uuid>, ApiError> {
+        Ok(match self.into_image_builder(processor).await? {
+            Some(builder) if self.uploads() => builder
+                .build(api, access_token)
+                .await?
+                .iter()
+                .map(|image| image.id)
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    /// the update to send once `uploaded` images have been added to the location.
+    pub fn to_update(&self, current: &LocationRender, uploaded: &[Uuid]) -> LocationUpdate {
+        let images: Vec<Uuid> = current
+            .images
+            .iter()
+            .flatten()
+            .map(|image| image.id)
+            .collect();
+        let removed = self.remove.clone().unwrap_or_default();
+        let thumbnail = primary_image(
+            self.thumbnail,
+            current.thumbnail.as_ref().map(|image| image.id),
+            &images,
+            &removed,
+            uploaded,
+        );
+        LocationUpdate {
+            name: self.name.trim().to_string(),
+            description: self
+                .description
+                .clone()
+                .filter(|text| !text.trim().is_empty()),
+            tags: self.tags.clone().unwrap_or_default(),
+            thumbnail,
+            remove_images: removed,
+            parent: self.parent,
+        }
+    }
+}
+
+impl<'r> ImageForm<'r> for EditLocationForm<'r> {
+    fn images(&self) -> Option<&Vec<TempFile<'r>>> {
+        self.images.as_ref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn tags(&self) -> &[String] {
+        self.tags.as_deref().unwrap_or(&[])
+    }
+
+    fn category(&self) -> &str {
+        "locations"
+    }
+
+    fn parent(&self) -> Option<Uuid> {
+        Some(self.id)
+    }
+}
+
+/// every location as a tree, each followed by the locations inside it.
 #[get("/")]
-async fn list_places(guard: Guard, api: &State<ApiClient>) -> RawHtml<Template> {
-    let locations: Vec<LocationRender> = match api
-        .get_protected("/locations/", guard.access_token(), None)
-        .await
-        .unwrap()
-    {
-        Some(locations) => locations,
-        None => Vec::new(),
-    };
-    RawHtml(Template::render(
+async fn list_places(guard: Guard, api: &State<ApiClient>) -> Result<RawHtml<Template>, ApiError> {
+    let locations = all_places(api, guard.access_token()).await?;
+    let tree = Hierarchy::new(&locations).tree();
+    Ok(RawHtml(Template::render(
         "locations/index",
-        context! { title: "settings", locations },
-    ))
+        context! { title: "settings", tree },
+    )))
 }
 
-#[get("/<id>")]
-async fn get_place(guard: Guard, api: &State<ApiClient>, id: Uuid) -> RawHtml<Template> {
+/// the locations drawn as nested clusters with the fragments set in them, `root` limits it to
+/// one location and what's inside it.
+#[get("/graph?<root>")]
+async fn places_graph(
+    guard: Guard,
+    api: &State<ApiClient>,
+    root: Option<Uuid>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let locations = all_places(api, guard.access_token()).await?;
+    let hierarchy = Hierarchy::new(&locations);
+    let root = root.and_then(|root| hierarchy.get(root));
+    let tops: Vec<Uuid> = match root {
+        Some(root) => vec![root.id],
+        None => hierarchy
+            .roots()
+            .iter()
+            .map(|location| location.id)
+            .collect(),
+    };
+    let mut settings = Vec::new();
+    for top in tops {
+        settings.extend(Setting::within(api, guard.access_token(), top).await?);
+    }
+    let (svg, _) = render_clusters(&hierarchy.dot(root.map(|root| root.id), &settings));
+    Ok(RawHtml(Template::render(
+        "locations/graph",
+        context! { title: "settings graph", root, svg },
+    )))
+}
+
+async fn all_places(api: &ApiClient, access_token: &str) -> Result<Vec<LocationRender>, ApiError> {
+    let locations: Option<Vec<LocationRender>> =
+        api.get_protected("/locations/", access_token, None).await?;
+    Ok(locations.unwrap_or_default())
+}
+
+/// the location with the places within `radius` kilometres of it, 25 unless given.
+#[get("/<id>?<radius>")]
+async fn get_place(
+    guard: Guard,
+    api: &State<ApiClient>,
+    id: Uuid,
+    radius: Option<f64>,
+    jar: &CookieJar<'_>,
+) -> Result<RawHtml<Template>, ApiError> {
     let url = format!("/locations/{}", id);
-    let location: LocationRender = api
-        .get_protected(&url, guard.access_token(), None)
-        .await
-        .unwrap();
+    let mut location: LocationRender = api.get_protected(&url, guard.access_token(), None).await?;
+    location.date_created(&preferred(api, guard.access_token(), jar).await);
     println!("location: {:?}", location);
-    RawHtml(Template::render(
+    let radius = radius.filter(|radius| *radius > 0.0).unwrap_or(25.0);
+    let locations = all_places(api, guard.access_token()).await?;
+    let nearby = location.nearby(&locations, radius);
+    let suggestion = location.suggestion(api, guard.access_token()).await;
+    let hierarchy = Hierarchy::new(&locations);
+    let breadcrumb = hierarchy.ancestors(id);
+    let inside = hierarchy.children(id);
+    let candidates = hierarchy.candidates(id);
+    let within = Setting::within(api, guard.access_token(), id).await?;
+    let geometry = match &location.geometry {
+        Some(geometry) => serde_json::to_string(geometry).unwrap_or_default(),
+        None => String::new(),
+    };
+    Ok(RawHtml(Template::render(
         "locations/location",
-        context! {title: location.name.clone(), location },
+        context! {
+            title: location.name.clone(),
+            location,
+            nearby,
+            radius,
+            suggestion,
+            geometry,
+            breadcrumb,
+            inside,
+            candidates,
+            within,
+        },
+    )))
+}
+
+#[derive(Debug, FromForm)]
+struct ParentForm {
+    /// empty to move the location to the top level.
+    parent: Option<Uuid>,
+}
+
+#[post("/<id>/parent", data = "<form>")]
+async fn move_place(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<ParentForm>,
+) -> Result<Redirect, ApiError> {
+    set_parent(api, guard.access_token(), id, form.into_inner().parent).await?;
+    Ok(Redirect::to(format!("/locations/{}", id)))
+}
+
+#[derive(Debug, FromForm)]
+struct CoordinatesForm {
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// a GeoJSON geometry, pasted in.
+    geometry: Option<String>,
+}
+
+/// places the location, or clears where it is when the form is left empty.
+#[post("/<id>/coordinates", data = "<form>")]
+async fn set_coordinates(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<CoordinatesForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let placement = Placement::from_form(form.latitude, form.longitude, form.geometry.as_deref())?;
+    placement.set(api, guard.access_token(), id).await?;
+    Ok(Redirect::to(format!("/locations/{}", id)))
+}
+
+/// every location as a GeoJSON FeatureCollection.
+#[get("/export.geojson")]
+async fn export_places(
+    guard: Guard,
+    api: &State<ApiClient>,
+) -> Result<(ContentType, String), ApiError> {
+    let locations = all_places(api, guard.access_token()).await?;
+    let collection = FeatureCollection::from_locations(&locations);
+    let geojson = serde_json::to_string_pretty(&collection)
+        .map_err(|err| ApiError::InternalServerError(err.to_string()))?;
+    Ok((ContentType::new("application", "geo+json"), geojson))
+}
+
+#[derive(Debug, FromForm)]
+pub struct GeoJsonForm<'r> {
+    /// a `.geojson` file holding a FeatureCollection.
+    file: TempFile<'r>,
+}
+
+#[get("/import")]
+async fn import_places_html(guard: Guard) -> RawHtml<Template> {
+    RawHtml(Template::render(
+        "locations/import",
+        context! { title: "import locations" },
     ))
 }
 
-#[get("/create")]
-async fn create_place_html(api: &State<ApiClient>) -> RawHtml<Template> {
+/// creates or places a location for every feature of an uploaded GeoJSON file.
+#[post("/import", data = "<form>")]
+async fn import_places<'r>(
+    guard: Guard,
+    api: &State<ApiClient>,
+    form: Form<GeoJsonForm<'r>>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let mut bytes = Vec::new();
+    form.file.open().await?.read_to_end(&mut bytes).await?;
+    let collection = FeatureCollection::parse(&String::from_utf8_lossy(&bytes))?;
+    let existing: Vec<Uuid> = all_places(api, guard.access_token())
+        .await?
+        .iter()
+        .map(|location| location.id)
+        .collect();
+    let imported = collection
+        .import(api, guard.access_token(), &existing)
+        .await?;
+    match imported.as_slice() {
+        [id] => Ok(Redirect::to(format!("/locations/{}", id))),
+        _ => Ok(Redirect::to("/locations/")),
+    }
+}
+
+/// a printable handout describing the location, used for tabletop sessions.
+#[get("/<id>/handout.pdf?<options..>")]
+async fn location_handout(
+    guard: Guard,
+    id: Uuid,
+    options: PdfOptions,
+    api: &State<ApiClient>,
+    processor: &State<ImageProcessor>,
+    pdf: &State<PdfRenderer>,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let url = format!("/locations/{}", id);
+    let location: LocationRender = api.get_protected(&url, guard.access_token(), None).await?;
+    let request = location.request(api, guard.access_token());
+    let fragments = location.fragments(request.clone()).await?;
+    let locations = location.locations(request).await?;
+
+    let thumbnail = match &location.thumbnail {
+        Some(image) => processor.load_url(&image.url).await,
+        None => None,
+    };
+    let description = location.description.as_deref().unwrap_or("");
+    let mut section = PdfSection::new(&location.name)
+        .image(thumbnail)
+        .blocks(content_blocks(SupportedRender::Text, description));
+    if !locations.is_empty() {
+        let names: Vec<&str> = locations.iter().map(|location| location.name.as_str()).collect();
+        section = section.field("Nearby", &names.join(", "));
+    }
+    if !fragments.is_empty() {
+        let names: Vec<&str> = fragments.iter().map(|fragment| fragment.name.as_str()).collect();
+        section = section.field("Scenes", &names.join(", "));
+    }
+    if options.images {
+        for image in location.images.iter().flatten() {
+            section = section.image(processor.load_url(&image.url).await);
+        }
+    }
+
+    let export = PdfExport::new(&location.name)
+        .title_page(false)
+        .section(section);
+    Ok((ContentType::PDF, pdf.render(export, options).await?))
+}
+
+/// `parent` picks the location the new one is inside.
+#[get("/create?<parent>")]
+async fn create_place_html(
+    guard: Guard,
+    api: &State<ApiClient>,
+    parent: Option<Uuid>,
+) -> Result<RawHtml<Template>, ApiError> {
     let options = api.get_top_tags(10, 0).await.unwrap();
-    RawHtml(Template::render(
+    let mut locations = all_places(api, guard.access_token()).await?;
+    locations.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(RawHtml(Template::render(
         "locations/create",
-        context! { title: "create a setting", options },
-    ))
+        context! { title: "create a setting", options, locations, parent },
+    )))
 }
 
 #[post("/", data = "<form>")]
@@ -135,14 +683,94 @@ async fn create_place<'r>(
     Redirect::to(format!("/locations/{}", loc.id))
 }
 
-#[put("/<id>")]
-async fn update_place(id: Uuid) {
-    unimplemented!();
+#[get("/edit/<id>")]
+async fn edit_place_html(
+    guard: Guard,
+    api: &State<ApiClient>,
+    id: Uuid,
+) -> Result<RawHtml<Template>, ApiError> {
+    let url = format!("/locations/{}", id);
+    let location: LocationRender = api.get_protected(&url, guard.access_token(), None).await?;
+    let options = api.get_top_tags(10, 0).await?;
+    let selected: Vec<&str> = location.tags.iter().flatten().map(Tag::value).collect();
+    let locations = all_places(api, guard.access_token()).await?;
+    let candidates = Hierarchy::new(&locations).candidates(id);
+    Ok(RawHtml(Template::render(
+        "locations/edit",
+        context! {
+            title: format!("Edit {}", location.name),
+            location: &location,
+            options,
+            selected,
+            candidates,
+        },
+    )))
+}
+
+#[post("/edit", data = "<form>")]
+async fn update_place<'r>(
+    guard: Guard,
+    api: &State<ApiClient>,
+    form: Form<EditLocationForm<'r>>,
+    processor: &State<ImageProcessor>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let url = format!("/locations/{}", form.id);
+    let current: LocationRender = api.get_protected(&url, guard.access_token(), None).await?;
+    // the images are uploaded once the update is accepted, so a rejected edit adds none.
+    let update = form.to_update(&current, &[]);
+    update.edit(api, guard.access_token(), form.id).await?;
+    let uploaded = form.upload(api, guard.access_token(), processor).await?;
+    let with_uploads = form.to_update(&current, &uploaded);
+    if with_uploads.thumbnail != update.thumbnail {
+        let update = LocationUpdate {
+            remove_images: Vec::new(),
+            ..with_uploads
+        };
+        update.edit(api, guard.access_token(), form.id).await?;
+    }
+    Ok(Redirect::to(format!("/locations/{}", form.id)))
+}
+
+/// what deleting the location affects, before it's deleted.
+#[get("/delete/<id>")]
+async fn delete_place_html(
+    guard: Guard,
+    api: &State<ApiClient>,
+    id: Uuid,
+) -> Result<RawHtml<Template>, ApiError> {
+    let url = format!("/locations/{}", id);
+    let location: LocationRender = api.get_protected(&url, guard.access_token(), None).await?;
+    let cascade = cascade_links(&location.links(api, guard.access_token()).await?);
+    let locations = all_places(api, guard.access_token()).await?;
+    let hierarchy = Hierarchy::new(&locations);
+    let inside = hierarchy.children(id);
+    let outside = location.parent.and_then(|parent| hierarchy.get(parent));
+    Ok(RawHtml(Template::render(
+        "locations/delete",
+        context! {
+            title: format!("Delete {}", location.name),
+            location: &location,
+            cascade,
+            inside,
+            outside,
+        },
+    )))
+}
+
+#[derive(Debug, FromForm)]
+struct DeleteRequest {
+    id: Uuid,
 }
 
-#[delete("/<id>")]
-async fn delete_place(id: Uuid) {
-    unimplemented!();
+#[post("/delete", data = "<form>")]
+async fn delete_place(
+    guard: Guard,
+    api: &State<ApiClient>,
+    form: Form<DeleteRequest>,
+) -> Result<Redirect, ApiError> {
+    LocationRender::delete(api, guard.access_token(), form.id).await?;
+    Ok(Redirect::to("/locations/"))
 }
 
 impl Entity for LocationRender {
@@ -160,6 +788,81 @@ impl Entity for LocationRender {
 }
 
 impl LocationRender {
+    /// the relations of the location, seen from its side.
+    pub async fn links(&self, api: &ApiClient, access_token: &str) -> Result<Vec<Link>, ApiError> {
+        let url = format!("/locations/{}/links", self.id);
+        let links: Option<Vec<Link>> = api.get_protected(&url, access_token, None).await?;
+        Ok(links.unwrap_or_default())
+    }
+
+    /// deactivates the location, the locations inside it move up to its parent.
+    pub async fn delete(api: &ApiClient, access_token: &str, id: Uuid) -> Result<(), ApiError> {
+        let url = format!("/locations/{}", id);
+        let _: serde_json::Value = api
+            .empty_request()
+            .method(Method::DELETE)
+            .access_token(access_token)
+            .route(&url)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    pub fn coordinates(&self) -> Option<Coordinates> {
+        Some(Coordinates {
+            latitude: self.latitude?,
+            longitude: self.longitude?,
+        })
+    }
+
+    /// the other placed locations within `radius` kilometres, closest first.
+    pub fn nearby(&self, locations: &[LocationRender], radius: f64) -> Vec<Nearby> {
+        let Some(at) = self.coordinates() else {
+            return Vec::new();
+        };
+        let mut nearby: Vec<Nearby> = locations
+            .iter()
+            .filter(|location| location.id != self.id)
+            .filter_map(|location| {
+                Some(Nearby {
+                    distance: at.distance(&location.coordinates()?),
+                    location: location.clone(),
+                })
+            })
+            .filter(|nearby| nearby.distance <= radius)
+            .collect();
+        nearby.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        nearby
+    }
+
+    /// coordinates suggested by the GPS tags of the location's images, images that can't be
+    /// fetched are left out rather than failing the page.
+    pub async fn suggestion(&self, api: &ApiClient, access_token: &str) -> Option<Suggestion> {
+        let mut photos = Vec::new();
+        for image in self.images.iter().flatten() {
+            let Some(id) = image_id(&image.url) else {
+                continue;
+            };
+            let tags = match ImageRender::fetch(api, access_token, id).await {
+                Ok(image) => image.exif_tags(),
+                Err(e) => {
+                    println!("skipping image {} of location {}: {}", id, self.id, e);
+                    continue;
+                }
+            };
+            if let Some(at) = Photo::from_exif(&image.url, &image.url, &tags).position {
+                photos.push((image.clone(), at));
+            }
+        }
+        Suggestion::new(self.coordinates(), photos)
+    }
+
+    pub fn date_created(&mut self, calendar: &Calendar) {
+        self.created_date = self
+            .created
+            .map(|created| crate::epoch_to_human(created, Some(calendar)));
+    }
+
     pub fn build_node(&self) -> Node {
         let mut node = Node::new(self.id.to_string(), self.name.clone());
         node.set_attr(NodeAttr::Shape(NodeShape::House));
@@ -228,7 +931,16 @@ pub fn get_routes() -> Vec<Route> {
         get_place,
         create_place_html,
         create_place,
+        edit_place_html,
         update_place,
-        delete_place
+        delete_place_html,
+        delete_place,
+        location_handout,
+        set_coordinates,
+        export_places,
+        import_places_html,
+        import_places,
+        places_graph,
+        move_place
     ]
 }
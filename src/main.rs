@@ -14,6 +14,12 @@ use std::path::Path;
 use std::path::PathBuf;
 use storyteller::ApiClient;
 use storyteller::Config;
+use storyteller::errors::ApiError;
+use storyteller::render::SupportedRender;
+use storyteller::stories::import::{self, ImportCache, ImportPlan};
+use storyteller::timelines::photos::{
+    DirectorySource, ImmichSource, PhotoImport, PhotoSource, ZipSource,
+};
 use structopt::StructOpt;
 use tokio::{fs::File, io::AsyncReadExt};
 use ubyte::ByteUnit;
@@ -23,6 +29,28 @@ pub struct Args {
     generate_config: bool,
     #[structopt(short, long)]
     config_file: Option<PathBuf>,
+    /// import a directory or zip of markdown files as a new story and exit.
+    #[structopt(long)]
+    import: Option<PathBuf>,
+    /// import a directory or zip of photos as a new story and timeline and exit.
+    #[structopt(long)]
+    import_photos: Option<PathBuf>,
+    /// import photos from an immich server, given without `/api`, as a new story and timeline
+    /// and exit. the api key is read from IMMICH_API_KEY.
+    #[structopt(long)]
+    import_immich: Option<String>,
+    /// the id of the immich album to import, the whole library when not given.
+    #[structopt(long)]
+    immich_album: Option<String>,
+    /// title of the imported story, defaults to the directory or archive name.
+    #[structopt(long)]
+    title: Option<String>,
+    /// print what would be imported without creating anything.
+    #[structopt(long)]
+    dry_run: bool,
+    /// access token used for the import, read from STORYTELLER_TOKEN when not given.
+    #[structopt(long)]
+    access_token: Option<String>,
 }
 
 use dashmap::DashMap;
@@ -62,6 +90,139 @@ async fn load_config<P: AsRef<Path>>(path: P) -> Result<Config, std::io::Error>
     Ok(serde_json::from_str(&contents).unwrap())
 }
 
+fn access_token(args: &Args) -> Result<String, ApiError> {
+    match args.access_token.clone() {
+        Some(token) => Ok(token),
+        None => std::env::var("STORYTELLER_TOKEN").map_err(|_| {
+            ApiError::AccessDenied("pass --access-token or set STORYTELLER_TOKEN".to_string())
+        }),
+    }
+}
+
+fn import_title(args: &Args, path: &Path, fallback: &str) -> String {
+    match &args.title {
+        Some(title) => title.clone(),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| fallback.to_string()),
+    }
+}
+
+async fn import_story(config: &Config, args: &Args, path: &Path) -> Result<(), ApiError> {
+    let access_token = access_token(args)?;
+    let files = if path.is_dir() {
+        import::read_directory(path)?
+    } else {
+        import::read_zip(&std::fs::read(path)?)?
+    };
+    let title = import_title(args, path, "Imported Story");
+
+    let api = ApiClient::new(config.api_endpoint()).await?;
+    let plan = ImportPlan::prepare(
+        &api,
+        &access_token,
+        &title,
+        None,
+        SupportedRender::Markdown,
+        Vec::new(),
+        files,
+    )
+    .await?;
+
+    println!("{}", plan.title);
+    for fragment in plan.fragments.iter() {
+        let links: Vec<&str> = fragment.links.iter().map(|link| link.name.as_str()).collect();
+        println!("  {:>3}. {} ({})", fragment.idx + 1, fragment.name, fragment.path);
+        if !links.is_empty() {
+            println!("       links: {}", links.join(", "));
+        }
+    }
+    for link in plan.unresolved.iter() {
+        println!("warning: no {} named {}", link.category, link.name);
+    }
+
+    if !args.dry_run {
+        let report = plan.commit(&api, &access_token).await?;
+        for fragment in report.failed.iter() {
+            println!(
+                "warning: {} wasn't imported: {}",
+                fragment.path, fragment.error
+            );
+        }
+        println!(
+            "created story {} with {} of {} fragments",
+            report.story,
+            report.imported,
+            plan.fragments.len()
+        );
+    }
+    Ok(())
+}
+
+fn photo_source(args: &Args) -> Result<Option<(Box<dyn PhotoSource>, String)>, ApiError> {
+    if let Some(path) = &args.import_photos {
+        let source: Box<dyn PhotoSource> = if path.is_dir() {
+            Box::new(DirectorySource::new(path))
+        } else {
+            Box::new(ZipSource::new(std::fs::read(path)?))
+        };
+        return Ok(Some((source, import_title(args, path, "Imported Photos"))));
+    }
+    if let Some(url) = &args.import_immich {
+        let key = std::env::var("IMMICH_API_KEY").map_err(|_| {
+            ApiError::AccessDenied("set IMMICH_API_KEY to import from immich".to_string())
+        })?;
+        let source = ImmichSource::new(url, &key, args.immich_album.clone());
+        let title = args.title.clone().unwrap_or_else(|| "Imported Photos".to_string());
+        return Ok(Some((Box::new(source), title)));
+    }
+    Ok(None)
+}
+
+async fn import_photos(
+    config: &Config,
+    args: &Args,
+    source: &dyn PhotoSource,
+    title: &str,
+) -> Result<(), ApiError> {
+    let access_token = access_token(args)?;
+    let import = PhotoImport::new(title);
+    let events = import.plan(source).await?;
+
+    println!("{}", import.title);
+    for (idx, event) in events.iter().enumerate() {
+        println!(
+            "  {:>3}. {} ({} photos)",
+            idx + 1,
+            event.name(),
+            event.photos.len()
+        );
+    }
+
+    if !args.dry_run {
+        let api = ApiClient::new(config.api_endpoint()).await?;
+        let processor = storyteller::assets::images::ImageProcessor::new(
+            config.url().to_string(),
+            config.images.clone(),
+        )
+        .await;
+        let report = import
+            .commit(source, &events, &api, &access_token, &processor)
+            .await?;
+        for event in report.failed.iter() {
+            println!("warning: {} wasn't imported: {}", event.name, event.error);
+        }
+        println!(
+            "created timeline {} with {} of {} events",
+            report.timeline,
+            report.imported,
+            events.len()
+        );
+    }
+    Ok(())
+}
+
 // Fairing to add custom mime type
 pub struct WasmContentTypeFairing;
 
@@ -88,8 +249,8 @@ impl Fairing for WasmContentTypeFairing {
 async fn rocket() -> _ {
     let args = Args::from_args();
 
-    let config = if let Some(config_file) = args.config_file {
-        load_config(&config_file).await.unwrap()
+    let config = if let Some(config_file) = &args.config_file {
+        load_config(config_file).await.unwrap()
     } else if args.generate_config {
         let config = Config::default();
         println!("{}", serde_json::to_string(&config).unwrap());
@@ -98,11 +259,35 @@ async fn rocket() -> _ {
         panic!("unable to fetch config");
     };
 
+    if let Some(path) = &args.import {
+        if let Err(e) = import_story(&config, &args, path).await {
+            eprintln!("import failed: {:?}", e);
+            std::process::exit(-1);
+        }
+        std::process::exit(0);
+    }
+
+    match photo_source(&args) {
+        Ok(Some((source, title))) => {
+            if let Err(e) = import_photos(&config, &args, source.as_ref(), &title).await {
+                eprintln!("import failed: {:?}", e);
+                std::process::exit(-1);
+            }
+            std::process::exit(0);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("import failed: {:?}", e);
+            std::process::exit(-1);
+        }
+    }
+
     let processor = storyteller::assets::images::ImageProcessor::new(
         config.url().to_string(),
         config.images.clone(),
     )
     .await;
+    let pdf_renderer = storyteller::render::pdf::PdfRenderer::new(config.pdfium.clone());
     let rocketconfig = rocket::Config {
         address: config.listen().parse().unwrap(),
         port: config.port(),
@@ -135,16 +320,20 @@ async fn rocket() -> _ {
     let rocket = rocket::custom(rocketconfig)
         .manage(api)
         .manage(RequestCache::new())
+        .manage(ImportCache::new())
         .attach(WasmContentTypeFairing)
         .manage(validator)
         .mount("/", routes![index])
         .manage(processor)
+        .manage(pdf_renderer)
         .mount("/assets/graphs/", storyteller::assets::graphs::get_routes())
         .mount("/assets", storyteller::assets::get_routes())
         .mount("/stories", storyteller::stories::get_routes())
         .mount("/characters", storyteller::characters::get_routes())
         .mount("/timelines", storyteller::timelines::get_routes())
+        .mount("/calendars", storyteller::calendars::get_routes())
         .mount("/locations", storyteller::locations::get_routes())
+        .mount("/maps", storyteller::maps::get_routes())
         .mount("/relations", storyteller::relations::get_routes())
         .mount("/fragments", storyteller::fragments::get_routes())
         .mount("/organizations", storyteller::organizations::get_routes())
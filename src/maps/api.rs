@@ -0,0 +1,461 @@
+//! maps are uploaded images with a coordinate system that locations are pinned to.
+//!
+//! positions on a map are pixels of its image counted from the top left corner. a map's `scale`
+//! is how far a pixel is in its `unit`, so distances can be measured and a scale bar drawn. a
+//! [`Projection::Geographic`] map also knows the [`Bounds`] of the area it shows, so locations
+//! with coordinates can be placed on it and points on it read back as coordinates. locations are
+//! pinned to a point or drawn as a polygon around their area, see [`MapFeature`].
+use crate::ApiClient;
+use crate::errors::ApiError;
+use crate::locations::Coordinates;
+use crate::model::Image;
+use crate::render::escape_html;
+use reqwest::Method;
+use rocket::FromFormField;
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// how a map's pixels relate to the world it shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    /// a fictional or unsurveyed map, positions are only pixels.
+    #[default]
+    Pixels,
+    /// an equirectangular map of part of the earth.
+    Geographic,
+}
+
+/// the edges of the area a geographic map shows, in decimal degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub north: f64,
+    pub south: f64,
+    pub east: f64,
+    pub west: f64,
+}
+
+impl Bounds {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let latitudes = -90.0..=90.0;
+        let longitudes = -180.0..=180.0;
+        if !latitudes.contains(&self.north) || !latitudes.contains(&self.south) {
+            return Err(ApiError::UnprocessableEntity(
+                "latitudes must be between -90 and 90".to_string(),
+            ));
+        }
+        if !longitudes.contains(&self.east) || !longitudes.contains(&self.west) {
+            return Err(ApiError::UnprocessableEntity(
+                "longitudes must be between -180 and 180".to_string(),
+            ));
+        }
+        if self.north <= self.south || self.east <= self.west {
+            return Err(ApiError::UnprocessableEntity(
+                "the north edge must be above the south and the east right of the west".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// a position on a map in pixels from its top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn distance(&self, other: &Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+/// points written as `x,y` pairs separated by spaces or new lines, as in an svg `points`
+/// attribute.
+pub fn parse_points(text: &str) -> Result<Vec<Point>, ApiError> {
+    text.split_whitespace()
+        .map(|pair| {
+            let invalid =
+                || ApiError::UnprocessableEntity(format!("{} isn't a point written as x,y", pair));
+            let (x, y) = pair.split_once(',').ok_or_else(invalid)?;
+            Ok(Point {
+                x: x.trim().parse().map_err(|_| invalid())?,
+                y: y.trim().parse().map_err(|_| invalid())?,
+            })
+        })
+        .collect()
+}
+
+/// the points as [`parse_points`] reads them.
+pub fn points_text(points: &[Point]) -> String {
+    points
+        .iter()
+        .map(|point| format!("{:.1},{:.1}", point.x, point.y))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureKind {
+    Pin,
+    Polygon,
+}
+
+/// a colour from a form, only `#` hex colours and plain names are allowed into the svg.
+fn valid_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic()),
+    }
+}
+
+/// a location pinned to a map or drawn on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapFeature {
+    pub id: Uuid,
+    pub location: Uuid,
+    pub location_name: String,
+    pub kind: FeatureKind,
+    pub points: Vec<Point>,
+    pub label: Option<String>,
+    pub color: Option<String>,
+}
+
+impl MapFeature {
+    /// what the map shows next to the feature.
+    pub fn name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.location_name)
+    }
+
+    /// where the feature's name goes, the pin itself or the middle of the polygon.
+    pub fn anchor(&self) -> Point {
+        let count = self.points.len().max(1) as f64;
+        let (x, y) = self
+            .points
+            .iter()
+            .fold((0.0, 0.0), |(x, y), point| (x + point.x, y + point.y));
+        Point {
+            x: x / count,
+            y: y / count,
+        }
+    }
+}
+
+/// a scale bar a round distance long.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScaleBar {
+    /// in pixels.
+    pub length: f64,
+    pub distance: f64,
+    pub unit: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapRender {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub image: Option<Image>,
+    pub width: u32,
+    pub height: u32,
+    pub projection: Projection,
+    /// how far a pixel is in `unit`.
+    pub scale: Option<f64>,
+    pub unit: Option<String>,
+    pub bounds: Option<Bounds>,
+    /// the location the map is of.
+    pub location: Option<Uuid>,
+    #[serde(default)]
+    pub features: Vec<MapFeature>,
+}
+
+impl MapRender {
+    pub async fn list(api: &ApiClient, access_token: &str) -> Result<Vec<Self>, ApiError> {
+        let maps: Option<Vec<Self>> = api.get_protected("/maps/", access_token, None).await?;
+        Ok(maps.unwrap_or_default())
+    }
+
+    pub async fn fetch(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Self, ApiError> {
+        let url = format!("/maps/{}", id);
+        api.get_protected(&url, access_token, None).await
+    }
+
+    /// where a point on earth is on the map, `None` unless the map is geographic and shows it.
+    pub fn project(&self, coordinates: &Coordinates) -> Option<Point> {
+        let bounds = self
+            .bounds
+            .filter(|_| self.projection == Projection::Geographic)?;
+        let x = (coordinates.longitude - bounds.west) / (bounds.east - bounds.west);
+        let y = (bounds.north - coordinates.latitude) / (bounds.north - bounds.south);
+        ((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y)).then_some(Point {
+            x: x * self.width as f64,
+            y: y * self.height as f64,
+        })
+    }
+
+    /// the point on earth at a point on a geographic map.
+    pub fn coordinates(&self, point: &Point) -> Option<Coordinates> {
+        let bounds = self
+            .bounds
+            .filter(|_| self.projection == Projection::Geographic)?;
+        let x = point.x / self.width.max(1) as f64;
+        let y = point.y / self.height.max(1) as f64;
+        Some(Coordinates {
+            latitude: bounds.north - y * (bounds.north - bounds.south),
+            longitude: bounds.west + x * (bounds.east - bounds.west),
+        })
+    }
+
+    /// how far a pixel is and in what unit. geographic maps without a scale of their own are
+    /// measured in kilometres across their middle.
+    pub fn scale(&self) -> Option<(f64, String)> {
+        if let Some(scale) = self.scale {
+            return Some((scale, self.unit.clone().unwrap_or_default()));
+        }
+        let middle = self.height as f64 / 2.0;
+        let west = self.coordinates(&Point { x: 0.0, y: middle })?;
+        let east = self.coordinates(&Point {
+            x: self.width as f64,
+            y: middle,
+        })?;
+        Some((
+            west.distance(&east) / self.width.max(1) as f64,
+            "km".to_string(),
+        ))
+    }
+
+    /// how far apart two points on the map are, in the unit of [`MapRender::scale`].
+    pub fn distance(&self, a: &Point, b: &Point) -> Option<f64> {
+        let (scale, _) = self.scale()?;
+        Some(a.distance(b) * scale)
+    }
+
+    /// a bar around a fifth of the map's width, rounded down to 1, 2 or 5 of a power of ten.
+    pub fn scale_bar(&self) -> Option<ScaleBar> {
+        let (scale, unit) = self.scale()?;
+        let target = self.width as f64 / 5.0 * scale;
+        if target <= 0.0 || !target.is_finite() {
+            return None;
+        }
+        let magnitude = 10f64.powf(target.log10().floor());
+        let distance = [5.0, 2.0, 1.0]
+            .into_iter()
+            .map(|step| step * magnitude)
+            .find(|distance| *distance <= target)
+            .unwrap_or(magnitude);
+        Some(ScaleBar {
+            length: distance / scale,
+            distance,
+            unit,
+        })
+    }
+
+    /// the map with its features drawn over the image at `href`. pins and polygons link to their
+    /// location's page.
+    pub fn svg(&self, href: &str) -> String {
+        let (width, height) = (self.width.max(1) as f64, self.height.max(1) as f64);
+        let radius = (width.min(height) / 100.0).max(6.0);
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" id="svg" class="map" viewBox="0 0 {} {}" width="{}" height="{}">"#,
+            width, height, width, height
+        );
+        svg.push_str(concat!(
+            "<style>",
+            ".region{fill:#e8896b;fill-opacity:.3;stroke:#9c4a31;stroke-width:2}",
+            ".pin{fill:#c0392b;stroke:#fff;stroke-width:2}",
+            ".scale{stroke:#222;stroke-width:3}",
+            "text{font-family:sans-serif;font-size:14px;fill:#222;paint-order:stroke;stroke:#fff;stroke-width:3px}",
+            "</style>"
+        ));
+        let _ = write!(
+            svg,
+            r#"<image href="{}" x="0" y="0" width="{}" height="{}"/>"#,
+            escape_html(href),
+            width,
+            height
+        );
+
+        for feature in &self.features {
+            let name = escape_html(feature.name());
+            let style = match feature.color.as_deref().filter(|color| valid_color(color)) {
+                Some(color) => format!(r#" style="fill:{};stroke:{}""#, color, color),
+                None => String::new(),
+            };
+            let anchor = feature.anchor();
+            let _ = write!(svg, r#"<a href="/locations/{}">"#, feature.location);
+            match feature.kind {
+                FeatureKind::Polygon => {
+                    let _ = write!(
+                        svg,
+                        r#"<polygon class="region" points="{}"{}><title>{}</title></polygon><text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+                        points_text(&feature.points),
+                        style,
+                        escape_html(&feature.location_name),
+                        anchor.x,
+                        anchor.y,
+                        name
+                    );
+                }
+                FeatureKind::Pin => {
+                    let _ = write!(
+                        svg,
+                        r#"<circle class="pin" cx="{:.1}" cy="{:.1}" r="{:.1}"{}><title>{}</title></circle><text x="{:.1}" y="{:.1}" text-anchor="middle">{}</text>"#,
+                        anchor.x,
+                        anchor.y,
+                        radius,
+                        style,
+                        escape_html(&feature.location_name),
+                        anchor.x,
+                        anchor.y - radius - 4.0,
+                        name
+                    );
+                }
+            }
+            svg.push_str("</a>");
+        }
+
+        if let Some(bar) = self.scale_bar() {
+            let (x, y) = (20.0, height - 20.0);
+            let _ = write!(
+                svg,
+                r#"<line class="scale" x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}"/><text x="{:.1}" y="{:.1}">{} {}</text>"#,
+                x,
+                y,
+                x + bar.length,
+                y,
+                x,
+                y - 8.0,
+                bar.distance,
+                escape_html(&bar.unit)
+            );
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+/// the body of `POST /maps/`, the image is uploaded first and `width` and `height` are its size.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapBuilder {
+    pub name: String,
+    pub description: Option<String>,
+    pub image: Uuid,
+    pub width: u32,
+    pub height: u32,
+    pub projection: Projection,
+    pub scale: Option<f64>,
+    pub unit: Option<String>,
+    pub bounds: Option<Bounds>,
+    pub location: Option<Uuid>,
+}
+
+impl MapBuilder {
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if self.name.trim().is_empty() {
+            return Err(ApiError::UnprocessableEntity(
+                "a map needs a name".to_string(),
+            ));
+        }
+        if let Some(scale) = self
+            .scale
+            .filter(|scale| !(*scale > 0.0 && scale.is_finite()))
+        {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "a scale of {} isn't a distance",
+                scale
+            )));
+        }
+        match (self.projection, &self.bounds) {
+            (Projection::Geographic, None) => Err(ApiError::UnprocessableEntity(
+                "a geographic map needs the bounds of the area it shows".to_string(),
+            )),
+            (_, Some(bounds)) => bounds.validate(),
+            (Projection::Pixels, None) => Ok(()),
+        }
+    }
+
+    pub async fn build(&self, api: &ApiClient, access_token: &str) -> Result<MapRender, ApiError> {
+        self.validate()?;
+        api.post("/maps/", access_token, None, self).await
+    }
+}
+
+/// the body of `POST /maps/{id}/features`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapFeatureBuilder {
+    pub location: Uuid,
+    pub kind: FeatureKind,
+    pub points: Vec<Point>,
+    pub label: Option<String>,
+    pub color: Option<String>,
+}
+
+impl MapFeatureBuilder {
+    /// pins need a single point and polygons at least three, all of them on the map.
+    pub fn validate(&self, map: &MapRender) -> Result<(), ApiError> {
+        match (self.kind, self.points.len()) {
+            (FeatureKind::Pin, 1) => {}
+            (FeatureKind::Pin, _) => {
+                return Err(ApiError::UnprocessableEntity(
+                    "a pin needs exactly one point".to_string(),
+                ));
+            }
+            (FeatureKind::Polygon, count) if count < 3 => {
+                return Err(ApiError::UnprocessableEntity(
+                    "a polygon needs at least three points".to_string(),
+                ));
+            }
+            (FeatureKind::Polygon, _) => {}
+        }
+        let (width, height) = (map.width as f64, map.height as f64);
+        if let Some(point) = self
+            .points
+            .iter()
+            .find(|point| !(0.0..=width).contains(&point.x) || !(0.0..=height).contains(&point.y))
+        {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "{},{} isn't on the {}x{} map",
+                point.x, point.y, map.width, map.height
+            )));
+        }
+        if let Some(color) = self.color.as_deref().filter(|color| !valid_color(color)) {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "{} isn't a colour, use a name or #hex",
+                color
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn build(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+        map: &MapRender,
+    ) -> Result<MapFeature, ApiError> {
+        self.validate(map)?;
+        let url = format!("/maps/{}/features", map.id);
+        api.post(&url, access_token, None, self).await
+    }
+}
+
+pub async fn remove_feature(
+    api: &ApiClient,
+    access_token: &str,
+    map: Uuid,
+    feature: Uuid,
+) -> Result<(), ApiError> {
+    let url = format!("/maps/{}/features/{}", map, feature);
+    let _: serde_json::Value = api
+        .empty_request()
+        .method(Method::DELETE)
+        .access_token(access_token)
+        .route(&url)
+        .send()
+        .await?;
+    Ok(())
+}
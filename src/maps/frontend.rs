@@ -0,0 +1,251 @@
+use super::api::*;
+use crate::ApiClient;
+use crate::assets::images::{ImageForm, ImageProcessor, image_id};
+use crate::auth::Guard;
+use crate::errors::ApiError;
+use crate::locations::LocationRender;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rocket::{
+    Route, State,
+    form::{Form, FromForm},
+    fs::TempFile,
+    get,
+    http::ContentType,
+    post,
+    response::{Redirect, content::RawHtml},
+    routes,
+};
+use rocket_dyn_templates::{Template, context};
+use std::io::Cursor;
+use uuid::Uuid;
+
+async fn locations(api: &ApiClient, access_token: &str) -> Result<Vec<LocationRender>, ApiError> {
+    let locations: Option<Vec<LocationRender>> =
+        api.get_protected("/locations/", access_token, None).await?;
+    Ok(locations.unwrap_or_default())
+}
+
+#[get("/")]
+async fn list_maps(guard: Guard, api: &State<ApiClient>) -> Result<RawHtml<Template>, ApiError> {
+    let maps = MapRender::list(api, guard.access_token()).await?;
+    Ok(RawHtml(Template::render(
+        "maps/index",
+        context! { title: "Maps", maps },
+    )))
+}
+
+#[get("/create")]
+async fn create_map_html(
+    guard: Guard,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let locations = locations(api, guard.access_token()).await?;
+    Ok(RawHtml(Template::render(
+        "maps/create",
+        context! { title: "Create A Map", locations },
+    )))
+}
+
+#[derive(Debug, FromForm)]
+struct MapForm<'r> {
+    name: String,
+    description: Option<String>,
+    images: Option<Vec<TempFile<'r>>>,
+    projection: Projection,
+    scale: Option<f64>,
+    unit: Option<String>,
+    north: Option<f64>,
+    south: Option<f64>,
+    east: Option<f64>,
+    west: Option<f64>,
+    location: Option<Uuid>,
+}
+
+impl<'r> ImageForm<'r> for MapForm<'r> {
+    fn images(&self) -> Option<&Vec<TempFile<'r>>> {
+        self.images.as_ref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn tags(&self) -> &[String] {
+        &[]
+    }
+
+    fn category(&self) -> &str {
+        "maps"
+    }
+
+    fn parent(&self) -> Option<Uuid> {
+        None
+    }
+}
+
+impl MapForm<'_> {
+    fn bounds(&self) -> Option<Bounds> {
+        Some(Bounds {
+            north: self.north?,
+            south: self.south?,
+            east: self.east?,
+            west: self.west?,
+        })
+    }
+}
+
+#[post("/", data = "<form>")]
+async fn create_map(
+    guard: Guard,
+    api: &State<ApiClient>,
+    processor: &State<ImageProcessor>,
+    form: Form<MapForm<'_>>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    // checked before the upload so a rejected map doesn't leave its image behind.
+    let mut builder = MapBuilder {
+        name: form.name.clone(),
+        description: form
+            .description
+            .clone()
+            .filter(|text| !text.trim().is_empty()),
+        image: Uuid::nil(),
+        width: 0,
+        height: 0,
+        projection: form.projection,
+        scale: form.scale,
+        unit: form.unit.clone().filter(|unit| !unit.trim().is_empty()),
+        bounds: form.bounds(),
+        location: form.location,
+    };
+    builder.validate()?;
+    let missing = || ApiError::UnprocessableEntity("a map needs an image".to_string());
+    let images = match form.into_image_builder(processor).await? {
+        Some(builder) => builder.build(api, guard.access_token()).await?,
+        None => return Err(missing()),
+    };
+    let image = images.into_iter().next().ok_or_else(missing)?;
+    let loaded = processor.load_url(&image.url).await.ok_or_else(missing)?;
+    builder.image = image.id;
+    builder.width = loaded.width();
+    builder.height = loaded.height();
+    let map = builder.build(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!("/maps/{}", map.id)))
+}
+
+/// the map with its pins and polygons, clicking the map fills in the points of a new feature.
+#[get("/<id>")]
+async fn get_map(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let map = MapRender::fetch(api, guard.access_token(), id).await?;
+    let href = map
+        .image
+        .as_ref()
+        .map(|image| image.url.clone())
+        .unwrap_or_default();
+    let svg = map.svg(&href);
+    let scale = map.scale_bar();
+    let locations = locations(api, guard.access_token()).await?;
+    Ok(RawHtml(Template::render(
+        "maps/map",
+        context! { title: map.name.clone(), map, svg, scale, locations },
+    )))
+}
+
+/// the annotated map as a standalone svg with the image embedded.
+#[get("/<id>/map.svg")]
+async fn export_map(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    processor: &State<ImageProcessor>,
+) -> Result<(ContentType, String), ApiError> {
+    let map = MapRender::fetch(api, guard.access_token(), id).await?;
+    let image = map
+        .image
+        .as_ref()
+        .and_then(|image| image_id(&image.url))
+        .ok_or_else(|| ApiError::NotFound(format!("image of map {}", id)))?;
+    let (image, format) = processor.get_image(image).await?;
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), format)?;
+    let href = format!(
+        "data:{};base64,{}",
+        format.to_mime_type(),
+        STANDARD.encode(&bytes)
+    );
+    Ok((ContentType::SVG, map.svg(&href)))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct FeatureForm {
+    location: Uuid,
+    kind: FeatureKind,
+    /// `x,y` pairs, a pin left empty on a geographic map is placed at the location's
+    /// coordinates.
+    points: String,
+    label: Option<String>,
+    color: Option<String>,
+}
+
+#[post("/<id>/features", data = "<form>")]
+async fn add_feature(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<FeatureForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let map = MapRender::fetch(api, guard.access_token(), id).await?;
+    let mut points = parse_points(&form.points)?;
+    if points.is_empty() && form.kind == FeatureKind::Pin {
+        let url = format!("/locations/{}", form.location);
+        let location: LocationRender = api.get_protected(&url, guard.access_token(), None).await?;
+        let point = location
+            .coordinates()
+            .and_then(|coordinates| map.project(&coordinates))
+            .ok_or_else(|| {
+                ApiError::UnprocessableEntity(format!(
+                    "{} has no coordinates on this map, click the map to place it",
+                    location.name
+                ))
+            })?;
+        points.push(point);
+    }
+    let builder = MapFeatureBuilder {
+        location: form.location,
+        kind: form.kind,
+        points,
+        label: form.label.filter(|label| !label.trim().is_empty()),
+        color: form.color.filter(|color| !color.trim().is_empty()),
+    };
+    builder.build(api, guard.access_token(), &map).await?;
+    Ok(Redirect::to(format!("/maps/{}", id)))
+}
+
+#[post("/<id>/features/<feature>/remove")]
+async fn remove_map_feature(
+    guard: Guard,
+    id: Uuid,
+    feature: Uuid,
+    api: &State<ApiClient>,
+) -> Result<Redirect, ApiError> {
+    remove_feature(api, guard.access_token(), id, feature).await?;
+    Ok(Redirect::to(format!("/maps/{}", id)))
+}
+
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        list_maps,
+        create_map_html,
+        create_map,
+        get_map,
+        export_map,
+        add_feature,
+        remove_map_feature
+    ]
+}
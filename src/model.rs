@@ -89,6 +89,12 @@ pub struct Tag {
     value: String,
 }
 
+impl Tag {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
 #[derive(Model, Debug, Clone, Serialize, Deserialize)]
 pub struct Note {
     id: Uuid,
@@ -0,0 +1,376 @@
+pub mod paths;
+
+use crate::ApiClient;
+use crate::assets::graphs::render_ranked;
+use crate::auth::Guard;
+use crate::errors::ApiError;
+use paths::{MAX_HOPS, RelationGraph};
+use rocket::response::content::{RawHtml, RawJson};
+use rocket::{FromForm, Route, State, form::Form, get, post, response::Redirect, routes};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::get_access_token;
+use rocket::http::CookieJar;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelatedEntity {
+    id: Uuid,
+    name: String,
+    description: Option<String>,
+    group_name: Option<String>,
+    group_id: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupedEntity {
+    id: Uuid,
+    name: String,
+    entities: Vec<RelatedEntity>,
+}
+
+#[non_exhaustive]
+pub enum Category {
+    Story,
+    Fragment,
+    Timeline,
+    Character,
+    Location,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromForm)]
+pub struct Relation {
+    pub parent: Uuid,
+    pub child: Uuid,
+    pub parent_category: String,
+    pub child_category: String,
+    pub description: Option<String>,
+    /// what kind of relation this is, see [`RelationType`].
+    #[serde(default)]
+    pub relation_type: Option<Uuid>,
+}
+
+/// how an edge of a relation type is drawn, as a class of the edge in the rendered svg.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeStyle {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+    Bold,
+}
+
+impl EdgeStyle {
+    pub const ALL: [EdgeStyle; 4] = [
+        EdgeStyle::Solid,
+        EdgeStyle::Dashed,
+        EdgeStyle::Dotted,
+        EdgeStyle::Bold,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeStyle::Solid => "solid",
+            EdgeStyle::Dashed => "dashed",
+            EdgeStyle::Dotted => "dotted",
+            EdgeStyle::Bold => "bold",
+        }
+    }
+}
+
+/// a kind of relation, like "appears in" or "rival of". the built in types have no creator. a
+/// directed type reads from the parent of a relation to its child and `inverse` the other way
+/// round, an undirected one reads the same both ways.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelationType {
+    pub id: Uuid,
+    pub name: String,
+    pub inverse: Option<String>,
+    pub directed: bool,
+    #[serde(default)]
+    pub style: EdgeStyle,
+    pub creator: Option<Uuid>,
+}
+
+impl RelationType {
+    /// the built in types and the ones the reader made.
+    pub async fn list(api: &ApiClient, access_token: &str) -> Result<Vec<Self>, ApiError> {
+        let types: Option<Vec<Self>> = api
+            .get_protected("/relations/types/", access_token, None)
+            .await?;
+        Ok(types.unwrap_or_default())
+    }
+
+    pub fn builtin(&self) -> bool {
+        self.creator.is_none()
+    }
+
+    /// the type read from the parent of the relation when `forward`, from the child otherwise.
+    pub fn reading(&self, forward: bool) -> String {
+        match (forward || !self.directed, &self.inverse) {
+            (true, _) => self.name.clone(),
+            (false, Some(inverse)) => inverse.clone(),
+            (false, None) => format!("\u{2190} {}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelationTypeBuilder {
+    pub name: String,
+    pub inverse: Option<String>,
+    pub directed: bool,
+    pub style: EdgeStyle,
+}
+
+impl RelationTypeBuilder {
+    pub async fn build(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+    ) -> Result<RelationType, ApiError> {
+        api.post("/relations/types/", access_token, None, self)
+            .await
+    }
+}
+
+/// a relation of an entity with its type, when it has one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypedRelation {
+    pub parent: Uuid,
+    pub child: Uuid,
+    pub parent_category: String,
+    pub child_category: String,
+    pub description: Option<String>,
+    pub relation_type: Option<RelationType>,
+}
+
+impl TypedRelation {
+    /// every relation `entity` is either end of.
+    pub async fn of(
+        api: &ApiClient,
+        access_token: &str,
+        entity: Uuid,
+    ) -> Result<Vec<Self>, ApiError> {
+        let url = format!("/relations/typed/{}", entity);
+        let relations: Option<Vec<Self>> = api.get_protected(&url, access_token, None).await?;
+        Ok(relations.unwrap_or_default())
+    }
+
+    /// the relation between `a` and `b`, whichever is the parent.
+    pub fn between(relations: &[Self], a: Uuid, b: Uuid) -> Option<&Self> {
+        relations.iter().find(|relation| {
+            (relation.parent == a && relation.child == b)
+                || (relation.parent == b && relation.child == a)
+        })
+    }
+}
+
+/// how the edge of a relation is drawn in a graph.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EdgeLabel {
+    pub text: Option<String>,
+    /// the edge's classes, `relation`, the category it leads to and the type's style.
+    pub class: String,
+    pub tooltip: Option<String>,
+}
+
+impl EdgeLabel {
+    /// the edge drawn from `tail` along `relation` to an entity of `category`. an untyped
+    /// relation has no text, its description is shown on hover.
+    pub fn new(relation: Option<&TypedRelation>, tail: Uuid, category: &str) -> Self {
+        let tooltip = relation
+            .and_then(|relation| relation.description.clone())
+            .filter(|description| !description.trim().is_empty());
+        let typed = relation.and_then(|relation| {
+            let relation_type = relation.relation_type.as_ref()?;
+            Some((relation_type, relation.parent == tail))
+        });
+        match typed {
+            Some((relation_type, forward)) => {
+                let mut class = format!("relation {} {}", category, relation_type.style.as_str());
+                if !relation_type.directed {
+                    class.push_str(" undirected");
+                }
+                Self {
+                    text: Some(relation_type.reading(forward)),
+                    class,
+                    tooltip,
+                }
+            }
+            None => Self {
+                text: None,
+                class: format!("relation {}", category),
+                tooltip,
+            },
+        }
+    }
+}
+
+pub struct Entity {
+    id: Uuid,
+    category: Category,
+}
+
+/*pub struct RelationBuilder<'l> {
+    parent: String,
+    category: String,
+}
+
+impl<'l> RelationBuilder<'l> {
+    pub fn new(parent: Uuid, category: String) -> Self {
+        let parent = parent.to_string();
+        Self {
+            parent,
+            category
+        }
+    }
+    pub fn get_params() -> HashMap<&'static str, &'l str> {
+        let mut params = HashMap::new();
+        params.insert("parent", self.parent.as_str());
+        params.insert("category", self.category.as_str())
+        params
+    }
+}*/
+
+use rocket_dyn_templates::{Template, context};
+#[get("/create?<id>&<parent>&<child>")]
+async fn create_link_html(
+    guard: Guard,
+    api: &State<ApiClient>,
+    id: Uuid,
+    parent: String,
+    child: String,
+) -> RawHtml<Template> {
+    //let mut params = HashMap::new();
+    //params.insert("category", child.as_str());
+    let url = format!("/relations/{}", child);
+    let items: Option<Vec<RelatedEntity>> = api
+        .get_protected(&url, guard.access_token(), None)
+        .await
+        .unwrap();
+    let types = RelationType::list(api, guard.access_token()).await.unwrap();
+    RawHtml(Template::render(
+        "links/create",
+        context! { title: "create link", category: child, parent_category: parent, parent: id, items, types },
+    ))
+}
+
+/// the relation types to choose from, with a form to add one.
+#[get("/types")]
+async fn relation_types_html(
+    guard: Guard,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let types = RelationType::list(api, guard.access_token()).await?;
+    let styles: Vec<&str> = EdgeStyle::ALL.iter().map(EdgeStyle::as_str).collect();
+    Ok(RawHtml(Template::render(
+        "links/types",
+        context! { title: "relation types", types, styles },
+    )))
+}
+
+#[derive(Debug, FromForm)]
+struct RelationTypeForm {
+    name: String,
+    inverse: Option<String>,
+    directed: bool,
+    style: String,
+}
+
+#[post("/types", data = "<form>")]
+async fn create_relation_type(
+    guard: Guard,
+    api: &State<ApiClient>,
+    form: Form<RelationTypeForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let style = EdgeStyle::ALL
+        .into_iter()
+        .find(|style| style.as_str() == form.style)
+        .ok_or_else(|| ApiError::BadRequest(format!("unknown style {}", form.style)))?;
+    let builder = RelationTypeBuilder {
+        name: form.name.trim().to_string(),
+        inverse: form
+            .inverse
+            .map(|inverse| inverse.trim().to_string())
+            .filter(|inverse| form.directed && !inverse.is_empty()),
+        directed: form.directed,
+        style,
+    };
+    builder.build(api, guard.access_token()).await?;
+    Ok(Redirect::to("/relations/types"))
+}
+
+#[post("/", data = "<rel>")]
+async fn create_link(guard: Guard, api: &State<ApiClient>, rel: Form<Relation>) -> Redirect {
+    let relation = rel.into_inner();
+    let redirect = format!("/{}/{}", relation.parent_category, relation.parent);
+    let _: Relation = api
+        .post("/relations/", guard.access_token(), None, relation)
+        .await
+        .unwrap();
+    Redirect::to(redirect)
+}
+
+#[get("/<category>")]
+async fn list_by_type(guard: Guard, category: &str, api: &State<ApiClient>) -> RawJson<String> {
+    let url = format!("/relations/{}", category);
+    let entities: Option<Vec<RelatedEntity>> = api
+        .get_protected(url, guard.access_token(), None)
+        .await
+        .unwrap();
+    RawJson(serde_json::to_string(&entities).unwrap())
+}
+
+/// how `from` and `to` are connected: the shortest paths between them and every path of up to
+/// `hops` hops, 4 unless given.
+#[get("/paths?<from>&<to>&<hops>")]
+async fn relation_paths(
+    guard: Guard,
+    api: &State<ApiClient>,
+    from: Option<Uuid>,
+    to: Option<Uuid>,
+    hops: Option<usize>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let graph = RelationGraph::fetch(api, guard.access_token()).await?;
+    let hops = hops.unwrap_or(4).clamp(1, MAX_HOPS);
+    // searching a well connected graph is cpu bound, so it runs on the blocking thread pool.
+    let (graph, shortest, paths) = tokio::task::spawn_blocking(move || {
+        let (shortest, paths) = match from.zip(to) {
+            Some((from, to)) => (graph.shortest(from, to), graph.within(from, to, hops)),
+            None => (Vec::new(), Vec::new()),
+        };
+        (graph, shortest, paths)
+    })
+    .await
+    .map_err(|e| ApiError::InternalServerError(e.to_string()))?;
+    // the shortest paths are among the others unless they're longer than `hops`.
+    let shown = if paths.is_empty() { &shortest } else { &paths };
+    let svg = (!shown.is_empty()).then(|| render_ranked(&graph.dot(shown, &shortest)).0);
+    Ok(RawHtml(Template::render(
+        "links/paths",
+        context! {
+            title: "connections",
+            entities: graph.entities(),
+            from: from.and_then(|from| graph.get(from)),
+            to: to.and_then(|to| graph.get(to)),
+            hops,
+            max_hops: MAX_HOPS,
+            shortest,
+            paths,
+            svg,
+        },
+    )))
+}
+
+pub fn get_routes() -> Vec<Route> {
+    routes![
+        create_link_html,
+        create_link,
+        list_by_type,
+        relation_types_html,
+        create_relation_type,
+        relation_paths
+    ]
+}
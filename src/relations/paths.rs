@@ -0,0 +1,324 @@
+//! how two entities are connected through their relations.
+//!
+//! a [`RelationGraph`] has every story, fragment, character and location the reader can see as
+//! a node and every relation between two of them as an edge, whichever way round the relation
+//! was made. [`RelationGraph::shortest`] finds the paths with the fewest hops between two
+//! entities and [`RelationGraph::within`] every path up to a number of hops, each a [`Path`]
+//! read as a chain of links and drawn by [`RelationGraph::dot`].
+use super::{EdgeLabel, TypedRelation};
+use crate::ApiClient;
+use crate::errors::ApiError;
+use petgraph::algo::dijkstra;
+use petgraph::graph::{EdgeIndex, NodeIndex, UnGraph};
+use petgraph::visit::EdgeRef;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use uuid::Uuid;
+
+/// at most this many paths are listed, there can be very many between well connected entities.
+pub const MAX_PATHS: usize = 50;
+pub const MAX_HOPS: usize = 8;
+/// the search gives up after stepping this many times, so a dense graph can't keep it going.
+const MAX_VISITS: usize = 100_000;
+
+/// an entity in the relation graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub id: Uuid,
+    pub name: String,
+    /// the category of the entity, such as `characters` or `locations`.
+    pub category: String,
+}
+
+/// the body of `GET /relations/graph`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Visible {
+    entities: Vec<Endpoint>,
+    relations: Vec<TypedRelation>,
+}
+
+/// one hop of a path, every relation between the two entities read from `from`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Step {
+    pub from: Endpoint,
+    pub to: Endpoint,
+    pub links: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Path {
+    pub steps: Vec<Step>,
+    /// the path as one line, `Anne Bonny —appears in→ The Heist —features→ Calico Jack`.
+    pub chain: String,
+    #[serde(skip)]
+    edges: Vec<EdgeIndex>,
+}
+
+impl Path {
+    pub fn hops(&self) -> usize {
+        self.steps.len()
+    }
+}
+
+/// how a relation reads from `from`, its type when it has one.
+fn reading(relation: &TypedRelation, from: Uuid) -> String {
+    match &relation.relation_type {
+        Some(relation_type) => relation_type.reading(relation.parent == from),
+        None => "linked to".to_string(),
+    }
+}
+
+fn quote(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub struct RelationGraph {
+    graph: UnGraph<Endpoint, TypedRelation>,
+    nodes: HashMap<Uuid, NodeIndex>,
+}
+
+impl RelationGraph {
+    /// relations with an end that isn't one of `entities` are left out.
+    pub fn new(entities: Vec<Endpoint>, relations: Vec<TypedRelation>) -> Self {
+        let mut graph = UnGraph::new_undirected();
+        let mut nodes = HashMap::new();
+        for entity in entities {
+            nodes
+                .entry(entity.id)
+                .or_insert_with(|| graph.add_node(entity));
+        }
+        for relation in relations {
+            if let (Some(parent), Some(child)) =
+                (nodes.get(&relation.parent), nodes.get(&relation.child))
+                && parent != child
+            {
+                graph.add_edge(*parent, *child, relation);
+            }
+        }
+        Self { graph, nodes }
+    }
+
+    /// every entity the reader can see and the relations between them.
+    pub async fn fetch(api: &ApiClient, access_token: &str) -> Result<Self, ApiError> {
+        let visible: Visible = api
+            .get_protected("/relations/graph", access_token, None)
+            .await?;
+        Ok(Self::new(visible.entities, visible.relations))
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Endpoint> {
+        self.nodes.get(&id).map(|node| &self.graph[*node])
+    }
+
+    /// every entity by name, to pick the two to connect.
+    pub fn entities(&self) -> Vec<&Endpoint> {
+        let mut entities: Vec<&Endpoint> = self.graph.node_weights().collect();
+        entities.sort_by(|a, b| a.name.cmp(&b.name).then(a.category.cmp(&b.category)));
+        entities
+    }
+
+    fn ends(&self, from: Uuid, to: Uuid) -> Option<(NodeIndex, NodeIndex)> {
+        let ends = (*self.nodes.get(&from)?, *self.nodes.get(&to)?);
+        (ends.0 != ends.1).then_some(ends)
+    }
+
+    /// every path between the two with the fewest hops.
+    pub fn shortest(&self, from: Uuid, to: Uuid) -> Vec<Path> {
+        let Some((start, end)) = self.ends(from, to) else {
+            return Vec::new();
+        };
+        match dijkstra(&self.graph, start, Some(end), |_| 1usize).get(&end) {
+            Some(hops) => self.paths(start, end, *hops),
+            None => Vec::new(),
+        }
+    }
+
+    /// every path between the two of at most `hops` hops, shortest first.
+    pub fn within(&self, from: Uuid, to: Uuid, hops: usize) -> Vec<Path> {
+        match self.ends(from, to) {
+            Some((start, end)) => self.paths(start, end, hops.clamp(1, MAX_HOPS)),
+            None => Vec::new(),
+        }
+    }
+
+    /// the paths of up to `hops` hops, a length at a time so the shortest are kept when there
+    /// are too many.
+    fn paths(&self, start: NodeIndex, end: NodeIndex, hops: usize) -> Vec<Path> {
+        let name = |nodes: &Vec<NodeIndex>| -> Vec<&str> {
+            nodes
+                .iter()
+                .map(|node| self.graph[*node].name.as_str())
+                .collect()
+        };
+        // hops from every node to `end`, branches that can't reach it in time aren't followed.
+        let distances: HashMap<NodeIndex, usize> = dijkstra(&self.graph, end, None, |_| 1usize)
+            .into_iter()
+            .collect();
+        let mut budget = MAX_VISITS;
+        let mut found: Vec<Vec<NodeIndex>> = Vec::new();
+        for length in 1..=hops {
+            let remaining = MAX_PATHS - found.len();
+            if remaining == 0 || budget == 0 {
+                break;
+            }
+            let mut paths = self.walk(start, end, length, &distances, remaining, &mut budget);
+            paths.sort_by_key(|path| name(path));
+            found.extend(paths);
+        }
+        found.iter().map(|nodes| self.path(nodes)).collect()
+    }
+
+    /// the simple paths of exactly `length` hops, each sequence of entities once however many
+    /// relations join them.
+    fn walk(
+        &self,
+        start: NodeIndex,
+        end: NodeIndex,
+        length: usize,
+        distances: &HashMap<NodeIndex, usize>,
+        limit: usize,
+        budget: &mut usize,
+    ) -> Vec<Vec<NodeIndex>> {
+        let mut found = Vec::new();
+        let mut stack = vec![(start, self.neighbors(start))];
+        while let Some((_, next)) = stack.last_mut() {
+            let Some(next) = next.pop() else {
+                stack.pop();
+                continue;
+            };
+            if found.len() == limit || *budget == 0 {
+                break;
+            }
+            *budget -= 1;
+            let hops = stack.len();
+            if next == end {
+                if hops == length {
+                    let mut nodes: Vec<NodeIndex> = stack.iter().map(|(node, _)| *node).collect();
+                    nodes.push(end);
+                    found.push(nodes);
+                }
+                continue;
+            }
+            let reachable = distances
+                .get(&next)
+                .is_some_and(|distance| hops + distance <= length);
+            if reachable && !stack.iter().any(|(node, _)| *node == next) {
+                stack.push((next, self.neighbors(next)));
+            }
+        }
+        found
+    }
+
+    /// the distinct entities related to `node`, two relations between a pair are one step.
+    fn neighbors(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut neighbors: Vec<NodeIndex> = self.graph.neighbors(node).collect();
+        neighbors.sort();
+        neighbors.dedup();
+        neighbors
+    }
+
+    fn path(&self, nodes: &[NodeIndex]) -> Path {
+        let mut steps = Vec::new();
+        let mut edges = Vec::new();
+        let mut chain = match nodes.first() {
+            Some(first) => self.graph[*first].name.clone(),
+            None => String::new(),
+        };
+        for pair in nodes.windows(2) {
+            let (from, to) = (&self.graph[pair[0]], &self.graph[pair[1]]);
+            let mut links = Vec::new();
+            for edge in self.graph.edges_connecting(pair[0], pair[1]) {
+                edges.push(edge.id());
+                let link = reading(edge.weight(), from.id);
+                if !links.contains(&link) {
+                    links.push(link);
+                }
+            }
+            let _ = write!(
+                chain,
+                " \u{2014}{}\u{2192} {}",
+                links.join(" and "),
+                to.name
+            );
+            steps.push(Step {
+                from: from.clone(),
+                to: to.clone(),
+                links,
+            });
+        }
+        Path {
+            steps,
+            chain,
+            edges,
+        }
+    }
+
+    /// graphviz source drawing only the entities and relations on `paths`, with those on
+    /// `highlighted` ones emphasised and the two ends filled.
+    pub fn dot(&self, paths: &[Path], highlighted: &[Path]) -> String {
+        let emphasised: HashSet<EdgeIndex> = highlighted
+            .iter()
+            .flat_map(|path| path.edges.iter().copied())
+            .collect();
+        let mut edges: Vec<EdgeIndex> = paths
+            .iter()
+            .flat_map(|path| path.edges.iter().copied())
+            .collect();
+        edges.sort();
+        edges.dedup();
+        let ends: HashSet<Uuid> = paths
+            .iter()
+            .filter_map(|path| Some([path.steps.first()?.from.id, path.steps.last()?.to.id]))
+            .flatten()
+            .collect();
+
+        let mut dot =
+            String::from("digraph {\n    rankdir=LR;\n    node [shape=box style=rounded];\n");
+        let mut drawn = HashSet::new();
+        for edge in &edges {
+            let Some((a, b)) = self.graph.edge_endpoints(*edge) else {
+                continue;
+            };
+            for node in [a, b] {
+                let entity = &self.graph[node];
+                if !drawn.insert(node) {
+                    continue;
+                }
+                let fill = if ends.contains(&entity.id) {
+                    " style=\"rounded,filled\" fillcolor=\"#f6d365\""
+                } else {
+                    ""
+                };
+                let _ = writeln!(
+                    dot,
+                    "    \"{}\" [label=\"{}\" URL=\"/{}/{}\" class=\"{}\"{}];",
+                    entity.id,
+                    quote(&entity.name),
+                    entity.category,
+                    entity.id,
+                    entity.category,
+                    fill
+                );
+            }
+            let relation = &self.graph[*edge];
+            let (tail, head) = (relation.parent, relation.child);
+            let head_category = &self.graph[if self.graph[a].id == head { a } else { b }].category;
+            let label = EdgeLabel::new(Some(relation), tail, head_category);
+            let mut attributes = format!("class=\"{}", label.class);
+            if emphasised.contains(edge) {
+                attributes.push_str(" highlighted\" penwidth=3 color=\"#c0392b\"");
+            } else {
+                attributes.push_str("\" color=\"#999999\"");
+            }
+            if let Some(text) = &label.text {
+                let _ = write!(attributes, " label=\"{}\"", quote(text));
+            }
+            if let Some(tooltip) = &label.tooltip {
+                let _ = write!(attributes, " tooltip=\"{}\"", quote(tooltip));
+            }
+            let _ = writeln!(dot, "    \"{}\" -> \"{}\" [{}];", tail, head, attributes);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
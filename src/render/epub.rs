@@ -0,0 +1,368 @@
+//! EPUB 3 packaging for stories.
+//!
+//! The builder only deals with already rendered xhtml bodies, fetching a story and running its
+//! fragments through the story's [`SupportedRender`](super::SupportedRender) happens in the
+//! stories module.
+//!
+//! ```rust,ignore
+//! let book = EpubBuilder::new(story.id, &story.name)
+//!     .description(story.description.as_deref())
+//!     .chapter("Chapter One", "<p>It was a dark and stormy night.</p>")
+//!     .build()
+//!     .unwrap();
+//! ```
+use super::escape_html;
+use crate::errors::ApiError;
+use image::ImageFormat;
+use std::io::{Cursor, Write};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+pub const EPUB_MIMETYPE: &str = "application/epub+zip";
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+const STYLESHEET: &str = r#"body { font-family: serif; line-height: 1.5; margin: 0 5%; }
+h1 { text-align: center; margin: 2em 0 1em 0; }
+p { text-indent: 1.5em; margin: 0 0 0.5em 0; }
+img { max-width: 100%; }
+.cover { text-align: center; }
+.appendix h1 { text-align: left; }
+"#;
+
+/// A single xhtml document within the book.
+#[derive(Debug, Clone)]
+pub struct EpubChapter {
+    pub title: String,
+    /// xhtml body content, this must be well formed xml.
+    pub body: String,
+}
+
+/// Cover image for the book.
+#[derive(Debug, Clone)]
+pub struct EpubCover {
+    pub data: Vec<u8>,
+    pub media_type: &'static str,
+    pub extension: &'static str,
+}
+
+impl EpubCover {
+    /// Builds a cover from raw image bytes, images that aren't an EPUB core media type are
+    /// re-encoded as png.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, ApiError> {
+        let format = image::guess_format(&data)?;
+        let (media_type, extension) = match format {
+            ImageFormat::Jpeg => ("image/jpeg", "jpg"),
+            ImageFormat::Png => ("image/png", "png"),
+            ImageFormat::Gif => ("image/gif", "gif"),
+            ImageFormat::WebP => ("image/webp", "webp"),
+            _ => {
+                let img = image::load_from_memory(&data)?;
+                let mut output = Vec::new();
+                img.write_to(&mut Cursor::new(&mut output), ImageFormat::Png)?;
+                return Ok(Self {
+                    data: output,
+                    media_type: "image/png",
+                    extension: "png",
+                });
+            }
+        };
+        Ok(Self {
+            data,
+            media_type,
+            extension,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EpubBuilder {
+    id: Uuid,
+    title: String,
+    description: Option<String>,
+    language: String,
+    modified: i64,
+    cover: Option<EpubCover>,
+    chapters: Vec<EpubChapter>,
+    appendices: Vec<EpubChapter>,
+}
+
+impl EpubBuilder {
+    pub fn new(id: Uuid, title: &str) -> Self {
+        Self {
+            id,
+            title: title.to_string(),
+            description: None,
+            language: "en".to_string(),
+            modified: OffsetDateTime::now_utc().unix_timestamp(),
+            cover: None,
+            chapters: Vec::new(),
+            appendices: Vec::new(),
+        }
+    }
+
+    pub fn description(mut self, description: Option<&str>) -> Self {
+        self.description = description.map(|s| s.to_string());
+        self
+    }
+
+    pub fn language(mut self, language: &str) -> Self {
+        self.language = language.to_string();
+        self
+    }
+
+    /// sets the `dcterms:modified` date from a unix epoch.
+    pub fn modified(mut self, epoch: i64) -> Self {
+        self.modified = epoch;
+        self
+    }
+
+    pub fn cover(mut self, cover: EpubCover) -> Self {
+        self.cover = Some(cover);
+        self
+    }
+
+    pub fn chapter(mut self, title: &str, body: &str) -> Self {
+        self.chapters.push(EpubChapter {
+            title: title.to_string(),
+            body: body.to_string(),
+        });
+        self
+    }
+
+    /// appendices come after every chapter, and are used for character and location pages.
+    pub fn appendix(mut self, title: &str, body: &str) -> Self {
+        self.appendices.push(EpubChapter {
+            title: title.to_string(),
+            body: body.to_string(),
+        });
+        self
+    }
+
+    /// Packages the book into an EPUB container.
+    pub fn build(self) -> Result<Vec<u8>, ApiError> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        // the mimetype must be the first entry in the archive and must not be compressed.
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(EPUB_MIMETYPE.as_bytes())?;
+
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER_XML.as_bytes())?;
+
+        zip.start_file("OEBPS/style.css", deflated)?;
+        zip.write_all(STYLESHEET.as_bytes())?;
+
+        if let Some(cover) = &self.cover {
+            zip.start_file(format!("OEBPS/images/cover.{}", cover.extension), stored)?;
+            zip.write_all(&cover.data)?;
+            zip.start_file("OEBPS/text/cover.xhtml", deflated)?;
+            zip.write_all(self.cover_page(cover).as_bytes())?;
+        }
+
+        for (stem, chapter) in self.documents() {
+            let mut body = format!("<h1>{}</h1>\n{}", escape_html(&chapter.title), chapter.body);
+            if stem.starts_with("appendix") {
+                body = format!("<div class=\"appendix\">\n{}\n</div>", body);
+            }
+            zip.start_file(format!("OEBPS/text/{}.xhtml", stem), deflated)?;
+            zip.write_all(xhtml_document(&self.language, &chapter.title, &body).as_bytes())?;
+        }
+
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(self.nav().as_bytes())?;
+
+        zip.start_file("OEBPS/toc.ncx", deflated)?;
+        zip.write_all(self.ncx().as_bytes())?;
+
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.package()?.as_bytes())?;
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    /// every chapter followed by every appendix, paired with the file stem it is written to.
+    fn documents(&self) -> impl Iterator<Item = (String, &EpubChapter)> {
+        let chapters = self
+            .chapters
+            .iter()
+            .enumerate()
+            .map(|(idx, chapter)| (format!("chapter-{}", idx + 1), chapter));
+        let appendices = self
+            .appendices
+            .iter()
+            .enumerate()
+            .map(|(idx, appendix)| (format!("appendix-{}", idx + 1), appendix));
+        chapters.chain(appendices)
+    }
+
+    fn cover_page(&self, cover: &EpubCover) -> String {
+        let body = format!(
+            "<div class=\"cover\"><img src=\"../images/cover.{}\" alt=\"{}\" /></div>",
+            cover.extension,
+            escape_html(&self.title)
+        );
+        xhtml_document(&self.language, &self.title, &body)
+    }
+
+    fn package(&self) -> Result<String, ApiError> {
+        let mut manifest = String::new();
+        let mut spine = String::new();
+
+        manifest.push_str(
+            "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+        );
+        manifest.push_str(
+            "    <item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n",
+        );
+        manifest.push_str("    <item id=\"css\" href=\"style.css\" media-type=\"text/css\"/>\n");
+
+        let mut cover_meta = String::new();
+        if let Some(cover) = &self.cover {
+            manifest.push_str(&format!(
+                "    <item id=\"cover-image\" href=\"images/cover.{}\" media-type=\"{}\" properties=\"cover-image\"/>\n",
+                cover.extension, cover.media_type
+            ));
+            manifest.push_str(
+                "    <item id=\"cover\" href=\"text/cover.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+            );
+            spine.push_str("    <itemref idref=\"cover\" linear=\"no\"/>\n");
+            cover_meta.push_str("    <meta name=\"cover\" content=\"cover-image\"/>\n");
+        }
+
+        for (stem, _) in self.documents() {
+            manifest.push_str(&format!(
+                "    <item id=\"{stem}\" href=\"text/{stem}.xhtml\" media-type=\"application/xhtml+xml\"/>\n"
+            ));
+            spine.push_str(&format!("    <itemref idref=\"{}\"/>\n", stem));
+        }
+
+        let description = match &self.description {
+            Some(description) => format!(
+                "    <dc:description>{}</dc:description>\n",
+                escape_html(description)
+            ),
+            None => String::new(),
+        };
+
+        Ok(format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{lang}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">urn:uuid:{id}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{lang}</dc:language>
+{description}    <meta property="dcterms:modified">{modified}</meta>
+{cover_meta}  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine toc="ncx">
+{spine}  </spine>
+</package>
+"#,
+            lang = escape_html(&self.language),
+            id = self.id,
+            title = escape_html(&self.title),
+            modified = format_modified(self.modified)?,
+        ))
+    }
+
+    fn nav(&self) -> String {
+        let mut items = String::new();
+        for (stem, chapter) in self.documents() {
+            items.push_str(&format!(
+                "      <li><a href=\"text/{}.xhtml\">{}</a></li>\n",
+                stem,
+                escape_html(&chapter.title)
+            ));
+        }
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{lang}" lang="{lang}">
+<head>
+  <title>{title}</title>
+</head>
+<body>
+  <nav epub:type="toc" id="toc">
+    <h1>Contents</h1>
+    <ol>
+{items}    </ol>
+  </nav>
+</body>
+</html>
+"#,
+            lang = escape_html(&self.language),
+            title = escape_html(&self.title),
+        )
+    }
+
+    /// the ncx is not required by EPUB 3 but older readers rely on it for navigation.
+    fn ncx(&self) -> String {
+        let mut points = String::new();
+        for (order, (stem, chapter)) in self.documents().enumerate() {
+            points.push_str(&format!(
+                r#"    <navPoint id="nav-{stem}" playOrder="{order}">
+      <navLabel><text>{title}</text></navLabel>
+      <content src="text/{stem}.xhtml"/>
+    </navPoint>
+"#,
+                order = order + 1,
+                title = escape_html(&chapter.title),
+            ));
+        }
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head>
+    <meta name="dtb:uid" content="urn:uuid:{id}"/>
+  </head>
+  <docTitle><text>{title}</text></docTitle>
+  <navMap>
+{points}  </navMap>
+</ncx>
+"#,
+            id = self.id,
+            title = escape_html(&self.title),
+        )
+    }
+}
+
+fn xhtml_document(language: &str, title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{lang}" lang="{lang}">
+<head>
+  <title>{title}</title>
+  <link rel="stylesheet" type="text/css" href="../style.css"/>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        lang = escape_html(language),
+        title = escape_html(title),
+    )
+}
+
+fn format_modified(epoch: i64) -> Result<String, ApiError> {
+    let datetime = OffsetDateTime::from_unix_timestamp(epoch)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    // whole second utc timestamps format as `CCYY-MM-DDThh:mm:ssZ` which is what EPUB requires.
+    datetime
+        .format(&Rfc3339)
+        .map_err(|e| ApiError::InternalServerError(e.to_string()))
+}
@@ -0,0 +1,501 @@
+//! the [Fountain](https://fountain.io/syntax) screenplay format.
+//!
+//! content is parsed into a list of [`Element`]s which are then rendered to html or turned into
+//! pdf blocks. sections, synopses and boneyard comments are parsed but never printed, as in
+//! other fountain tools. `[[notes]]` are left in the text because they share their syntax with
+//! wiki-links, the link pass turns them into links or marks them as unresolved.
+use super::html::html_to_text;
+use super::links::{EntityIndex, WikiLink};
+use super::pdf::{FontStyle, PdfBlock};
+use super::{Renderer, escape_html};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    /// `Key: value` pairs from the top of the script.
+    TitlePage(Vec<(String, String)>),
+    SceneHeading {
+        text: String,
+        number: Option<String>,
+    },
+    Action(String),
+    /// a character cue and the dialogue that follows it.
+    Dialogue {
+        character: String,
+        extension: Option<String>,
+        /// set by a trailing `^`, the dialogue sits beside the previous one.
+        dual: bool,
+        lines: Vec<DialogueLine>,
+    },
+    Transition(String),
+    Centered(String),
+    Lyrics(String),
+    Section {
+        depth: usize,
+        text: String,
+    },
+    Synopsis(String),
+    PageBreak,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogueLine {
+    Parenthetical(String),
+    Speech(String),
+}
+
+const SCENE_PREFIXES: &[&str] = &["INT./EXT", "INT/EXT", "I/E", "INT", "EXT", "EST"];
+
+/// removes `/* boneyard */` comments, which may span several lines.
+fn strip_boneyard(content: &str) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("/*") {
+        output.push_str(&rest[..start]);
+        match rest[start..].find("*/") {
+            Some(end) => rest = &rest[start + end + 2..],
+            None => {
+                rest = "";
+                break;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn is_scene_heading(line: &str) -> bool {
+    let upper = line.to_uppercase();
+    SCENE_PREFIXES.iter().any(|prefix| {
+        upper
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with(['.', ' ']))
+    })
+}
+
+/// a line of capitals with at least one letter, ignoring the extension.
+fn is_upper(line: &str) -> bool {
+    let name = line.split('(').next().unwrap_or(line);
+    name.chars().any(char::is_alphabetic) && !name.chars().any(char::is_lowercase)
+}
+
+/// splits `#12#` off the end of a scene heading.
+fn scene_number(text: &str) -> (String, Option<String>) {
+    let trimmed = text.trim_end();
+    if let Some(body) = trimmed.strip_suffix('#')
+        && let Some(start) = body.rfind('#')
+    {
+        let number = &body[start + 1..];
+        if !number.is_empty() && !number.contains(char::is_whitespace) {
+            return (body[..start].trim_end().to_string(), Some(number.to_string()));
+        }
+    }
+    (trimmed.to_string(), None)
+}
+
+/// `MARA (V.O.) ^` becomes the name, the extension and whether it's dual dialogue.
+fn character_cue(cue: &str) -> (String, Option<String>, bool) {
+    let cue = cue.trim();
+    let (cue, dual) = match cue.strip_suffix('^') {
+        Some(cue) => (cue.trim_end(), true),
+        None => (cue, false),
+    };
+    match cue.find('(') {
+        Some(start) => (
+            cue[..start].trim().to_string(),
+            Some(cue[start..].trim().to_string()),
+            dual,
+        ),
+        None => (cue.to_string(), None, dual),
+    }
+}
+
+fn title_page(lines: &[&str]) -> Option<(Vec<(String, String)>, usize)> {
+    let first = lines.first()?;
+    let (key, _) = first.split_once(':')?;
+    if key.is_empty() || key.starts_with(char::is_whitespace) || key.contains(['.', '!', '@']) {
+        return None;
+    }
+
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut consumed = 0;
+    for line in lines.iter() {
+        if line.trim().is_empty() {
+            break;
+        }
+        consumed += 1;
+        if line.starts_with([' ', '\t'])
+            && let Some((_, value)) = fields.last_mut()
+        {
+            // indented lines continue the previous value.
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(line.trim());
+        } else if let Some((key, value)) = line.split_once(':') {
+            fields.push((key.trim().to_string(), value.trim().to_string()));
+        } else {
+            return None;
+        }
+    }
+    Some((fields, consumed))
+}
+
+/// parses fountain content into elements in the order they appear.
+pub fn parse(content: &str) -> Vec<Element> {
+    let content = strip_boneyard(&crate::normalize_newlines(content));
+    let lines: Vec<&str> = content.lines().collect();
+    let mut elements = Vec::new();
+    let mut i = 0;
+    if let Some((fields, consumed)) = title_page(&lines) {
+        elements.push(Element::TitlePage(fields));
+        i = consumed;
+    }
+
+    let blank = |idx: usize| lines.get(idx).is_none_or(|line| line.trim().is_empty());
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            i += 1;
+            continue;
+        }
+        let before = i == 0 || blank(i - 1);
+        let after = blank(i + 1);
+
+        if trimmed.chars().all(|c| c == '=') && trimmed.len() >= 3 {
+            elements.push(Element::PageBreak);
+        } else if let Some(text) = trimmed.strip_prefix('#') {
+            let depth = 1 + text.chars().take_while(|&c| c == '#').count();
+            elements.push(Element::Section {
+                depth,
+                text: text.trim_start_matches('#').trim().to_string(),
+            });
+        } else if let Some(text) = trimmed.strip_prefix('=') {
+            elements.push(Element::Synopsis(text.trim().to_string()));
+        } else if let Some(text) = trimmed.strip_prefix('~') {
+            elements.push(Element::Lyrics(text.trim().to_string()));
+        } else if trimmed.starts_with('>') && trimmed.ends_with('<') && trimmed.len() > 1 {
+            let text = &trimmed[1..trimmed.len() - 1];
+            elements.push(Element::Centered(text.trim().to_string()));
+        } else if let Some(text) = trimmed.strip_prefix('>') {
+            elements.push(Element::Transition(text.trim().to_string()));
+        } else if trimmed.starts_with('.') && !trimmed.starts_with("..") {
+            let (text, number) = scene_number(&trimmed[1..]);
+            elements.push(Element::SceneHeading { text, number });
+        } else if before && after && is_scene_heading(trimmed) {
+            let (text, number) = scene_number(trimmed);
+            elements.push(Element::SceneHeading { text, number });
+        } else if before && after && is_upper(trimmed) && trimmed.ends_with("TO:") {
+            elements.push(Element::Transition(trimmed.to_string()));
+        } else if let Some(text) = trimmed.strip_prefix('!') {
+            i = action(&lines, i, text, &mut elements);
+            continue;
+        } else if before
+            && !after
+            && (trimmed.starts_with('@') || is_upper(trimmed))
+        {
+            let (character, extension, dual) =
+                character_cue(trimmed.strip_prefix('@').unwrap_or(trimmed));
+            let mut dialogue = Vec::new();
+            i += 1;
+            while i < lines.len() {
+                let line = lines[i];
+                // a line of two spaces keeps the dialogue going across a gap.
+                if line.trim().is_empty() && line != "  " {
+                    break;
+                }
+                let text = line.trim();
+                if text.starts_with('(') && text.ends_with(')') {
+                    dialogue.push(DialogueLine::Parenthetical(text.to_string()));
+                } else {
+                    match dialogue.last_mut() {
+                        Some(DialogueLine::Speech(speech)) => {
+                            speech.push('\n');
+                            speech.push_str(text);
+                        }
+                        _ => dialogue.push(DialogueLine::Speech(text.to_string())),
+                    }
+                }
+                i += 1;
+            }
+            elements.push(Element::Dialogue {
+                character,
+                extension,
+                dual,
+                lines: dialogue,
+            });
+            continue;
+        } else {
+            i = action(&lines, i, line, &mut elements);
+            continue;
+        }
+        i += 1;
+    }
+    elements
+}
+
+/// collects an action paragraph starting at `start`, returning the line after it.
+fn action(lines: &[&str], start: usize, first: &str, elements: &mut Vec<Element>) -> usize {
+    let mut text = vec![first.trim_end().to_string()];
+    let mut i = start + 1;
+    while i < lines.len() && !lines[i].trim().is_empty() {
+        text.push(lines[i].trim_end().to_string());
+        i += 1;
+    }
+    elements.push(Element::Action(text.join("\n")));
+    i
+}
+
+/// applies `***bold italic***`, `**bold**`, `*italic*` and `_underline_` to escaped text.
+/// a backslash keeps the next character literal.
+fn emphasis(text: &str) -> String {
+    const ESCAPES: [(&str, &str); 3] = [("\\*", "\u{e000}"), ("\\_", "\u{e001}"), ("\\\\", "\u{e002}")];
+    let mut text = escape_html(text);
+    for (escape, placeholder) in ESCAPES {
+        text = text.replace(escape, placeholder);
+    }
+    for (marker, open, close) in [
+        ("***", "<strong><em>", "</em></strong>"),
+        ("**", "<strong>", "</strong>"),
+        ("*", "<em>", "</em>"),
+        ("_", "<u>", "</u>"),
+    ] {
+        text = wrap_pairs(&text, marker, open, close);
+    }
+    text.replace('\u{e000}', "*")
+        .replace('\u{e001}', "_")
+        .replace('\u{e002}', "\\")
+}
+
+/// replaces matched pairs of `marker` on the same line, an unpaired marker is left as is.
+fn wrap_pairs(text: &str, marker: &str, open: &str, close: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            let parts: Vec<&str> = line.split(marker).collect();
+            let pairs = (parts.len() - 1) / 2;
+            let mut output = String::from(parts[0]);
+            for (idx, part) in parts.iter().enumerate().skip(1) {
+                if idx <= pairs * 2 {
+                    output.push_str(if idx % 2 == 1 { open } else { close });
+                } else {
+                    output.push_str(marker);
+                }
+                output.push_str(part);
+            }
+            output
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn lines_html(text: &str) -> String {
+    emphasis(text).replace('\n', "<br />\n")
+}
+
+/// renders fountain into html, character cues link to characters of the same name when an
+/// index is given.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FountainRenderer<'i> {
+    index: Option<&'i EntityIndex>,
+}
+
+impl<'i> FountainRenderer<'i> {
+    pub fn new() -> Self {
+        Self { index: None }
+    }
+
+    pub fn index(mut self, index: &'i EntityIndex) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    fn cue_html(&self, character: &str) -> String {
+        let link = WikiLink {
+            category: Some("characters".to_string()),
+            name: character.to_string(),
+            label: None,
+        };
+        match self.index.and_then(|index| index.resolve(&link)) {
+            Some(target) => format!(
+                "<a href=\"{}\">{}</a>",
+                target.url(),
+                escape_html(character)
+            ),
+            None => escape_html(character),
+        }
+    }
+
+    fn dialogue_html(
+        &self,
+        character: &str,
+        extension: Option<&str>,
+        lines: &[DialogueLine],
+    ) -> String {
+        let mut html = String::from("<div class=\"dialogue\">\n<p class=\"character\">");
+        html.push_str(&self.cue_html(character));
+        if let Some(extension) = extension {
+            html.push_str(&format!(
+                " <span class=\"extension\">{}</span>",
+                escape_html(extension)
+            ));
+        }
+        html.push_str("</p>\n");
+        for line in lines {
+            match line {
+                DialogueLine::Parenthetical(text) => html.push_str(&format!(
+                    "<p class=\"parenthetical\">{}</p>\n",
+                    emphasis(text)
+                )),
+                DialogueLine::Speech(text) => {
+                    html.push_str(&format!("<p class=\"speech\">{}</p>\n", lines_html(text)))
+                }
+            }
+        }
+        html.push_str("</div>\n");
+        html
+    }
+}
+
+impl Renderer for FountainRenderer<'_> {
+    fn render(&self, content: &str) -> String {
+        let elements = parse(content);
+        let mut html = String::from("<div class=\"screenplay\">\n");
+        let mut iter = elements.iter().peekable();
+        while let Some(element) = iter.next() {
+            match element {
+                Element::TitlePage(fields) => {
+                    html.push_str("<div class=\"title-page\">\n");
+                    for (key, value) in fields {
+                        html.push_str(&format!(
+                            "<p class=\"{}\">{}</p>\n",
+                            escape_html(&key.to_lowercase().replace(' ', "-")),
+                            lines_html(value)
+                        ));
+                    }
+                    html.push_str("</div>\n");
+                }
+                Element::SceneHeading { text, number } => {
+                    html.push_str("<h3 class=\"scene-heading\">");
+                    html.push_str(&emphasis(&text.to_uppercase()));
+                    if let Some(number) = number {
+                        html.push_str(&format!(
+                            " <span class=\"scene-number\">{}</span>",
+                            escape_html(number)
+                        ));
+                    }
+                    html.push_str("</h3>\n");
+                }
+                Element::Action(text) => {
+                    html.push_str(&format!("<p class=\"action\">{}</p>\n", lines_html(text)))
+                }
+                Element::Dialogue {
+                    character,
+                    extension,
+                    lines,
+                    ..
+                } => {
+                    let first = self.dialogue_html(character, extension.as_deref(), lines);
+                    match iter.peek() {
+                        Some(Element::Dialogue {
+                            character,
+                            extension,
+                            dual: true,
+                            lines,
+                        }) => {
+                            html.push_str("<div class=\"dual-dialogue\">\n");
+                            html.push_str(&first);
+                            html.push_str(&self.dialogue_html(
+                                character,
+                                extension.as_deref(),
+                                lines,
+                            ));
+                            html.push_str("</div>\n");
+                            iter.next();
+                        }
+                        _ => html.push_str(&first),
+                    }
+                }
+                Element::Transition(text) => html.push_str(&format!(
+                    "<p class=\"transition\">{}</p>\n",
+                    emphasis(&text.to_uppercase())
+                )),
+                Element::Centered(text) => {
+                    html.push_str(&format!("<p class=\"centered\">{}</p>\n", emphasis(text)))
+                }
+                Element::Lyrics(text) => {
+                    html.push_str(&format!("<p class=\"lyrics\">{}</p>\n", emphasis(text)))
+                }
+                Element::PageBreak => html.push_str("<hr class=\"page-break\" />\n"),
+                Element::Section { .. } | Element::Synopsis(_) => {}
+            }
+        }
+        html.push_str("</div>\n");
+        html
+    }
+}
+
+/// strips emphasis markers, pdf text is set without inline styles.
+fn plain(text: &str) -> String {
+    html_to_text(&emphasis(text)).trim().to_string()
+}
+
+/// screenplay layout for pdf export, dialogue and cues are set in narrower indented columns.
+pub fn pdf_blocks(content: &str) -> Vec<PdfBlock> {
+    let column = |text: String, style: FontStyle, left: f32, right: f32| PdfBlock::Column {
+        text,
+        style,
+        left,
+        right,
+    };
+    let mut blocks = Vec::new();
+    for element in parse(content) {
+        match element {
+            Element::TitlePage(fields) => {
+                for (key, value) in fields {
+                    blocks.push(PdfBlock::Field(key, plain(&value)));
+                }
+                blocks.push(PdfBlock::Rule);
+            }
+            Element::SceneHeading { text, number } => {
+                let text = plain(&text).to_uppercase();
+                blocks.push(PdfBlock::Heading(match number {
+                    Some(number) => format!("{} {}", number, text),
+                    None => text,
+                }));
+            }
+            Element::Action(text) => {
+                blocks.extend(text.lines().map(|line| PdfBlock::Paragraph(plain(line))))
+            }
+            Element::Dialogue {
+                character,
+                extension,
+                lines,
+                ..
+            } => {
+                let cue = match extension {
+                    Some(extension) => format!("{} {}", character, extension),
+                    None => character,
+                };
+                blocks.push(column(plain(&cue), FontStyle::Regular, 0.37, 0.0));
+                for line in lines {
+                    blocks.push(match line {
+                        DialogueLine::Parenthetical(text) => {
+                            column(plain(&text), FontStyle::Regular, 0.27, 0.3)
+                        }
+                        DialogueLine::Speech(text) => {
+                            column(plain(&text), FontStyle::Regular, 0.17, 0.25)
+                        }
+                    });
+                }
+            }
+            Element::Transition(text) => {
+                blocks.push(PdfBlock::RightAligned(plain(&text).to_uppercase()))
+            }
+            Element::Centered(text) => blocks.push(column(plain(&text), FontStyle::Regular, 0.3, 0.3)),
+            Element::Lyrics(text) => blocks.push(column(plain(&text), FontStyle::Italic, 0.1, 0.0)),
+            Element::PageBreak => blocks.push(PdfBlock::PageBreak),
+            Element::Section { .. } | Element::Synopsis(_) => {}
+        }
+    }
+    blocks
+}
@@ -0,0 +1,396 @@
+//! allowlist based html sanitizer.
+//!
+//! anything that isn't explicitly allowed is dropped: unknown elements are unwrapped so their
+//! text survives, while elements such as `script` and `style` are removed along with their
+//! content. attribute values are entity decoded before urls are checked, and the output is
+//! re-escaped and balanced so it is also well formed xhtml.
+use super::{Renderer, escape_html};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+/// elements whose content is dropped along with the element itself.
+const DROP_CONTENT: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "noscript", "template", "textarea", "select",
+    "svg", "math", "head", "title", "frameset", "frame", "applet",
+];
+
+/// elements that never have content, written as `<br />`.
+const VOID: &[&str] = &["br", "hr", "img", "wbr"];
+
+const URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+const IMAGE_SCHEMES: &[&str] = &["http", "https"];
+
+pub struct Sanitizer {
+    /// allowed elements and the attributes allowed on each of them.
+    elements: HashMap<&'static str, HashSet<&'static str>>,
+    /// attributes allowed on every allowed element.
+    global: HashSet<&'static str>,
+}
+
+impl Default for Sanitizer {
+    /// allows the formatting, list, table, link and image elements writers reach for.
+    fn default() -> Self {
+        let formatting = [
+            "p", "br", "hr", "h1", "h2", "h3", "h4", "h5", "h6", "strong", "b", "em", "i", "u",
+            "s", "del", "ins", "sub", "sup", "small", "mark", "abbr", "cite", "q", "blockquote",
+            "pre", "code", "kbd", "span", "div", "figure", "figcaption", "ul", "ol", "li", "dl",
+            "dt", "dd", "table", "thead", "tbody", "tfoot", "tr", "caption", "wbr",
+        ];
+        let sanitizer = Self {
+            elements: HashMap::new(),
+            global: HashSet::from(["title", "lang"]),
+        };
+        formatting
+            .into_iter()
+            .fold(sanitizer, |sanitizer, element| sanitizer.allow(element, &[]))
+            .allow("a", &["href"])
+            .allow("img", &["src", "alt", "width", "height"])
+            .allow("th", &["colspan", "rowspan", "align"])
+            .allow("td", &["colspan", "rowspan", "align"])
+            .allow("ol", &["start"])
+            .allow("code", &["class"])
+    }
+}
+
+impl Sanitizer {
+    /// an empty allowlist, only text survives.
+    pub fn new() -> Self {
+        Self {
+            elements: HashMap::new(),
+            global: HashSet::new(),
+        }
+    }
+
+    /// allows an element along with the given attributes.
+    pub fn allow(mut self, element: &'static str, attributes: &[&'static str]) -> Self {
+        self.elements
+            .entry(element)
+            .or_default()
+            .extend(attributes.iter().copied());
+        self
+    }
+
+    /// allows an attribute on every allowed element.
+    pub fn allow_global(mut self, attribute: &'static str) -> Self {
+        self.global.insert(attribute);
+        self
+    }
+
+    pub fn clean(&self, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+        let mut open: Vec<String> = Vec::new();
+        let mut rest = input;
+
+        while !rest.is_empty() {
+            let Some(start) = rest.find('<') else {
+                push_text(&mut output, rest);
+                break;
+            };
+            push_text(&mut output, &rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = comment.find("-->").map(|end| &comment[end + 3..]).unwrap_or("");
+                continue;
+            }
+            if rest.starts_with("<!") || rest.starts_with("<?") {
+                rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+                continue;
+            }
+
+            let Some(tag) = Tag::parse(rest) else {
+                // a `<` that doesn't start a tag is just text.
+                output.push_str("&lt;");
+                rest = &rest[1..];
+                continue;
+            };
+            rest = &rest[tag.len..];
+
+            if !tag.closing && DROP_CONTENT.contains(&tag.name.as_str()) {
+                rest = skip_element(rest, &tag.name);
+                continue;
+            }
+            let Some(allowed) = self.elements.get(tag.name.as_str()) else {
+                continue;
+            };
+
+            if tag.closing {
+                if let Some(position) = open.iter().rposition(|name| *name == tag.name) {
+                    for name in open.drain(position..).rev() {
+                        output.push_str(&format!("</{}>", name));
+                    }
+                }
+                continue;
+            }
+
+            output.push('<');
+            output.push_str(&tag.name);
+            for (name, value) in tag.attributes.iter() {
+                if !allowed.contains(name.as_str()) && !self.global.contains(name.as_str()) {
+                    continue;
+                }
+                if let Some(value) = clean_attribute(&tag.name, name, value) {
+                    output.push_str(&format!(" {}=\"{}\"", name, escape_html(&value)));
+                }
+            }
+            if VOID.contains(&tag.name.as_str()) {
+                output.push_str(" />");
+            } else if tag.self_closing {
+                output.push_str(&format!("></{}>", tag.name));
+            } else {
+                output.push('>');
+                open.push(tag.name);
+            }
+        }
+
+        for name in open.into_iter().rev() {
+            output.push_str(&format!("</{}>", name));
+        }
+        output
+    }
+}
+
+/// renders user supplied html through the default [`Sanitizer`].
+pub struct HtmlRenderer {
+    sanitizer: Sanitizer,
+}
+
+impl HtmlRenderer {
+    pub fn new(sanitizer: Sanitizer) -> Self {
+        Self { sanitizer }
+    }
+}
+
+impl Default for HtmlRenderer {
+    fn default() -> Self {
+        Self::new(Sanitizer::default())
+    }
+}
+
+impl Renderer for HtmlRenderer {
+    fn render(&self, content: &str) -> String {
+        self.sanitizer.clean(content)
+    }
+}
+
+struct Tag {
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    attributes: Vec<(String, String)>,
+    /// length of the tag in the source, including the angle brackets.
+    len: usize,
+}
+
+impl Tag {
+    /// parses the tag at the start of `source`, `None` if it isn't a tag.
+    fn parse(source: &str) -> Option<Self> {
+        let bytes = source.as_bytes();
+        let mut i = 1;
+        let closing = bytes.get(i) == Some(&b'/');
+        if closing {
+            i += 1;
+        }
+        let name_start = i;
+        if !bytes.get(i)?.is_ascii_alphabetic() {
+            return None;
+        }
+        while i < bytes.len() && bytes[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        let name = source[name_start..i].to_ascii_lowercase();
+
+        let mut attributes = Vec::new();
+        let mut self_closing = false;
+        loop {
+            while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+                self_closing = bytes[i] == b'/';
+                i += 1;
+            }
+            match bytes.get(i) {
+                // an unterminated tag swallows the rest of the input.
+                None => break,
+                Some(b'>') => {
+                    i += 1;
+                    break;
+                }
+                Some(_) => self_closing = false,
+            }
+
+            let attr_start = i;
+            while i < bytes.len()
+                && !bytes[i].is_ascii_whitespace()
+                && !matches!(bytes[i], b'=' | b'>' | b'/')
+            {
+                i += 1;
+            }
+            if i == attr_start {
+                // a stray quote or similar, skip it rather than looping forever.
+                i += 1;
+                continue;
+            }
+            let attr = source[attr_start..i].to_ascii_lowercase();
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+
+            let mut value = String::new();
+            if bytes.get(i) == Some(&b'=') {
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                match bytes.get(i) {
+                    Some(&quote @ (b'"' | b'\'')) => {
+                        let end = source[i + 1..]
+                            .find(quote as char)
+                            .map(|end| i + 1 + end)
+                            .unwrap_or(bytes.len());
+                        value = source[i + 1..end].to_string();
+                        i = (end + 1).min(bytes.len());
+                    }
+                    _ => {
+                        let value_start = i;
+                        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>'
+                        {
+                            i += 1;
+                        }
+                        value = source[value_start..i].to_string();
+                    }
+                }
+            }
+            attributes.push((attr, value));
+        }
+
+        Some(Self {
+            name,
+            closing,
+            self_closing,
+            attributes,
+            len: i,
+        })
+    }
+}
+
+/// skips past the closing tag of an element whose content is dropped.
+fn skip_element<'s>(source: &'s str, name: &str) -> &'s str {
+    let closing = format!("</{}", name);
+    let lower = source.to_ascii_lowercase();
+    match lower.find(&closing) {
+        Some(start) => {
+            let rest = &source[start..];
+            rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("")
+        }
+        None => "",
+    }
+}
+
+fn push_text(output: &mut String, text: &str) {
+    output.push_str(&escape_html(&decode_entities(text)));
+}
+
+/// returns the decoded attribute value, or `None` if it should be dropped.
+fn clean_attribute(element: &str, name: &str, value: &str) -> Option<String> {
+    let value = decode_entities(value);
+    match name {
+        "href" => safe_url(&value, URL_SCHEMES).then_some(value),
+        "src" if element == "img" => safe_url(&value, IMAGE_SCHEMES).then_some(value),
+        "colspan" | "rowspan" | "width" | "height" | "start" => {
+            value.trim().parse::<u32>().ok().map(|n| n.to_string())
+        }
+        "class" => {
+            // only the `language-*` classes markdown code blocks carry.
+            let classes: Vec<&str> = value
+                .split_ascii_whitespace()
+                .filter(|class| class.starts_with("language-"))
+                .collect();
+            (!classes.is_empty()).then(|| classes.join(" "))
+        }
+        _ => Some(value),
+    }
+}
+
+/// relative urls are always allowed, absolute ones need one of the given schemes.
+fn safe_url(url: &str, schemes: &[&str]) -> bool {
+    // browsers ignore whitespace and control characters inside the scheme.
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect();
+    let Some(colon) = url.find(':') else {
+        return true;
+    };
+    if url[..colon].contains(['/', '?', '#']) {
+        return true;
+    }
+    let scheme = url[..colon].to_ascii_lowercase();
+    schemes.contains(&scheme.as_str())
+}
+
+/// flattens sanitized html into plain text, block elements become blank lines so the
+/// paragraphs survive.
+pub fn html_to_text(html: &str) -> String {
+    let blocks = Regex::new(r"</(p|div|h[1-6]|li|blockquote|pre|tr|table|ul|ol)>").unwrap();
+    let breaks = Regex::new(r"<br />").unwrap();
+    let tags = Regex::new(r"(?s)<[^>]*>").unwrap();
+    let text = blocks.replace_all(html, "\n\n");
+    let text = breaks.replace_all(&text, "\n");
+    decode_entities(&tags.replace_all(&text, "")).replace('\u{a0}', " ")
+}
+
+/// decodes numeric entities and the named entities common in hand written html,
+/// anything else is left as is and will be escaped.
+pub fn decode_entities(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .char_indices()
+            .take(12)
+            .find(|(_, c)| *c == ';')
+            .map(|(i, _)| i);
+        let decoded = end.and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)));
+        match decoded {
+            Some((c, end)) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(number) = entity.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code).filter(|c| *c != '\0');
+    }
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "mdash" => '\u{2014}',
+        "ndash" => '\u{2013}',
+        "hellip" => '\u{2026}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "copy" => '\u{a9}',
+        _ => return None,
+    };
+    Some(c)
+}
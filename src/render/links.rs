@@ -0,0 +1,295 @@
+//! wiki-link syntax for fragment content.
+//!
+//! `[[Mara]]` links to the character or location named Mara, `[[location:Old Harbor]]`
+//! restricts the lookup to one category and `[[Mara|the captain]]` changes the link text.
+//! links are resolved after the content is rendered, so the pass works the same for every
+//! [`SupportedRender`](super::SupportedRender) and only ever sees sanitized html.
+use super::html::decode_entities;
+use super::{Renderer, escape_html};
+use crate::ApiClient;
+use crate::characters::api::CharacterRender;
+use crate::errors::ApiError;
+use crate::locations::LocationRender;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// a `[[...]]` link as written in the content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WikiLink {
+    /// the category prefix normalized to a route name such as `characters`.
+    pub category: Option<String>,
+    pub name: String,
+    pub label: Option<String>,
+}
+
+impl WikiLink {
+    /// parses the text between the brackets, `None` if there is no name.
+    pub fn parse(inner: &str) -> Option<Self> {
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label.trim().to_string())),
+            None => (inner, None),
+        };
+        let (category, name) = match target.split_once(':') {
+            Some((prefix, name)) => match category_route(prefix) {
+                Some(category) => (Some(category.to_string()), name),
+                None => (None, target),
+            },
+            None => (None, target),
+        };
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return None;
+        }
+        Some(Self {
+            category,
+            name,
+            label: label.filter(|label| !label.is_empty()),
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// maps the prefixes writers use to the route of the category.
+fn category_route(prefix: &str) -> Option<&'static str> {
+    match prefix.trim().to_ascii_lowercase().as_str() {
+        "character" | "characters" | "char" => Some("characters"),
+        "location" | "locations" | "place" | "places" => Some("locations"),
+        _ => None,
+    }
+}
+
+/// every wiki-link in raw content, in the order they appear.
+pub fn find_links(content: &str) -> Vec<WikiLink> {
+    let mut links = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        let inner = &rest[start + 2..];
+        let Some(end) = inner.find("]]") else {
+            break;
+        };
+        if let Some(link) = WikiLink::parse(&inner[..end]) {
+            links.push(link);
+        }
+        rest = &inner[end + 2..];
+    }
+    links
+}
+
+/// an entity a wiki-link can point at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkTarget {
+    pub id: Uuid,
+    pub name: String,
+    pub category: String,
+    pub description: Option<String>,
+    pub thumbnail: Option<String>,
+}
+
+impl LinkTarget {
+    pub fn url(&self) -> String {
+        format!("/{}/{}", self.category, self.id)
+    }
+}
+
+/// the entities visible to the current user, indexed by lowercased name.
+#[derive(Debug, Clone, Default)]
+pub struct EntityIndex {
+    targets: HashMap<String, Vec<LinkTarget>>,
+}
+
+impl EntityIndex {
+    pub fn new(characters: &[CharacterRender], locations: &[LocationRender]) -> Self {
+        let mut index = Self::default();
+        for character in characters.iter() {
+            index.insert(LinkTarget {
+                id: character.id,
+                name: character.name.clone(),
+                category: "characters".to_string(),
+                description: character.description.clone(),
+                thumbnail: character.thumbnail.as_ref().map(|image| image.url.clone()),
+            });
+        }
+        for location in locations.iter() {
+            index.insert(LinkTarget {
+                id: location.id,
+                name: location.name.clone(),
+                category: "locations".to_string(),
+                description: location.description.clone(),
+                thumbnail: location.thumbnail.as_ref().map(|image| image.url.clone()),
+            });
+        }
+        index
+    }
+
+    pub async fn fetch(api: &ApiClient, access_token: &str) -> Result<Self, ApiError> {
+        let characters: Option<Vec<CharacterRender>> =
+            api.get_protected("/characters", access_token, None).await?;
+        let locations: Option<Vec<LocationRender>> =
+            api.get_protected("/locations/", access_token, None).await?;
+        Ok(Self::new(
+            &characters.unwrap_or_default(),
+            &locations.unwrap_or_default(),
+        ))
+    }
+
+    pub fn insert(&mut self, target: LinkTarget) {
+        self.targets
+            .entry(target.name.trim().to_lowercase())
+            .or_default()
+            .push(target);
+    }
+
+    pub fn targets(&self) -> impl Iterator<Item = &LinkTarget> {
+        self.targets.values().flatten()
+    }
+
+    /// characters win over locations when an unprefixed name matches both.
+    pub fn resolve(&self, link: &WikiLink) -> Option<&LinkTarget> {
+        let candidates = self.targets.get(&link.name.to_lowercase())?;
+        match &link.category {
+            Some(category) => candidates.iter().find(|target| &target.category == category),
+            None => candidates
+                .iter()
+                .find(|target| target.category == "characters")
+                .or_else(|| candidates.first()),
+        }
+    }
+
+    /// the distinct entities referenced by `content`, in order of first appearance.
+    pub fn referenced(&self, content: &str) -> Vec<LinkTarget> {
+        let mut targets: Vec<LinkTarget> = Vec::new();
+        for link in find_links(content) {
+            if let Some(target) = self.resolve(&link)
+                && !targets.iter().any(|other| other.id == target.id)
+            {
+                targets.push(target.clone());
+            }
+        }
+        targets
+    }
+
+    /// the distinct links in `content` that don't match any entity.
+    pub fn unresolved(&self, content: &str) -> Vec<WikiLink> {
+        let mut links: Vec<WikiLink> = Vec::new();
+        for link in find_links(content) {
+            let seen = links
+                .iter()
+                .any(|other| other.name == link.name && other.category == link.category);
+            if self.resolve(&link).is_none() && !seen {
+                links.push(link);
+            }
+        }
+        links
+    }
+
+    /// replaces the wiki-links in rendered html with links and hover cards, links inside
+    /// `code` and `pre` elements are left alone.
+    pub fn link_html(&self, html: &str) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut code_depth = 0usize;
+        let mut rest = html;
+        while !rest.is_empty() {
+            let (text, tag) = match rest.find('<') {
+                Some(start) => {
+                    let end = rest[start..].find('>').map(|end| start + end + 1);
+                    let end = end.unwrap_or(rest.len());
+                    (&rest[..start], &rest[start..end])
+                }
+                None => (rest, ""),
+            };
+            if code_depth == 0 {
+                self.link_text(text, &mut output);
+            } else {
+                output.push_str(text);
+            }
+            output.push_str(tag);
+
+            let name = tag.trim_start_matches(['<', '/']);
+            if name.starts_with("code") || name.starts_with("pre") {
+                if tag.starts_with("</") {
+                    code_depth = code_depth.saturating_sub(1);
+                } else {
+                    code_depth += 1;
+                }
+            }
+            rest = &rest[text.len() + tag.len()..];
+        }
+        output
+    }
+
+    fn link_text(&self, text: &str, output: &mut String) {
+        let mut rest = text;
+        while let Some(start) = rest.find("[[") {
+            let inner = &rest[start + 2..];
+            let Some(end) = inner.find("]]") else {
+                break;
+            };
+            // the text is already escaped, names are matched against the decoded form.
+            let Some(link) = WikiLink::parse(&decode_entities(&inner[..end])) else {
+                output.push_str(&rest[..start + 2 + end + 2]);
+                rest = &inner[end + 2..];
+                continue;
+            };
+            output.push_str(&rest[..start]);
+            match self.resolve(&link) {
+                Some(target) => output.push_str(&hover_card(&link, target)),
+                None => output.push_str(&format!(
+                    "<span class=\"wikilink unresolved\" title=\"{}\">{}</span>",
+                    escape_html(&format!(
+                        "nothing named {} in {}",
+                        link.name,
+                        link.category.as_deref().unwrap_or("characters or locations")
+                    )),
+                    escape_html(link.text())
+                )),
+            }
+            rest = &inner[end + 2..];
+        }
+        output.push_str(rest);
+    }
+}
+
+fn hover_card(link: &WikiLink, target: &LinkTarget) -> String {
+    let mut card = String::new();
+    if let Some(thumbnail) = &target.thumbnail {
+        card.push_str(&format!(
+            "<img src=\"{}\" alt=\"\" />",
+            escape_html(thumbnail)
+        ));
+    }
+    card.push_str(&format!("<strong>{}</strong>", escape_html(&target.name)));
+    if let Some(description) = target.description.as_deref().filter(|d| !d.trim().is_empty()) {
+        let mut summary: String = description.chars().take(200).collect();
+        if summary.len() < description.len() {
+            summary.push('\u{2026}');
+        }
+        card.push_str(&format!("<span>{}</span>", escape_html(&summary)));
+    }
+    format!(
+        "<span class=\"wikilink\"><a href=\"{}\">{}</a><span class=\"hovercard\">{}</span></span>",
+        target.url(),
+        escape_html(link.text()),
+        card
+    )
+}
+
+/// runs the wiki-link pass over the output of another renderer.
+pub struct LinkedRenderer<'i, R> {
+    inner: R,
+    index: &'i EntityIndex,
+}
+
+impl<'i, R: Renderer> LinkedRenderer<'i, R> {
+    pub fn new(inner: R, index: &'i EntityIndex) -> Self {
+        Self { inner, index }
+    }
+}
+
+impl<R: Renderer> Renderer for LinkedRenderer<'_, R> {
+    fn render(&self, content: &str) -> String {
+        self.inner.render_linked(content, self.index)
+    }
+}
@@ -1,11 +1,31 @@
-use crate::render::{Renderer};
-use std::string::FromUtf8Error;
-pub struct MDRenderer;
+use crate::render::Renderer;
+use crate::render::html::Sanitizer;
+use comrak::Options;
+
+/// renders commonmark with the github extensions, inline html is allowed but passed
+/// through the sanitizer like any other user supplied html.
+pub struct MDRenderer {
+    options: Options<'static>,
+    sanitizer: Sanitizer,
+}
+
+impl Default for MDRenderer {
+    fn default() -> Self {
+        let mut options = Options::default();
+        options.extension.strikethrough = true;
+        options.extension.table = true;
+        options.extension.autolink = true;
+        options.render.unsafe_ = true;
+        Self {
+            options,
+            sanitizer: Sanitizer::default(),
+        }
+    }
+}
 
 impl Renderer for MDRenderer {
-    type Err = FromUtf8Error;
-    fn process(&self, data: &[u8]) -> Result<RawHtml, Self::Err> {
-        let content = String::from_utf8(data)?;
-        Ok(RawHtml(markdown_to_html(&content)))
+    fn render(&self, content: &str) -> String {
+        let html = comrak::markdown_to_html(content, &self.options);
+        self.sanitizer.clean(&html)
     }
-}
\ No newline at end of file
+}
@@ -1,14 +1,79 @@
+pub mod epub;
+pub mod fountain;
+pub mod html;
+pub mod links;
+pub mod markdown;
+pub mod pdf;
+pub mod text;
+
+use crate::errors::ApiError;
 use crate::model::StoryFragment;
 use rocket::FromFormField;
 use rocket::response::content::RawHtml;
 use std::fmt;
+use std::str::FromStr;
 use std::string::FromUtf8Error;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Copy, FromFormField)]
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, FromFormField, Default)]
 pub enum SupportedRender {
+    #[default]
     Markdown,
     Text,
     HTML,
+    /// screenplays written in the fountain format.
+    Fountain,
+}
+
+/// turns user content into an html body fragment that is safe to embed in a page.
+/// the output is also well formed xhtml so it can be used in exported documents.
+pub trait Renderer {
+    fn render(&self, content: &str) -> String;
+
+    fn process(&self, data: &[u8]) -> Result<RawHtml<String>, FromUtf8Error> {
+        let content = String::from_utf8(data.to_vec())?;
+        Ok(RawHtml(self.render(&content)))
+    }
+
+    /// renders with wiki-links resolved against `index`, formats that link more than
+    /// wiki-links override this.
+    fn render_linked(&self, content: &str, index: &links::EntityIndex) -> String {
+        index.link_html(&self.render(content))
+    }
+}
+
+impl Renderer for SupportedRender {
+    fn render(&self, content: &str) -> String {
+        match self {
+            Self::Markdown => markdown::MDRenderer::default().render(content),
+            Self::Text => text::TextRenderer.render(content),
+            Self::HTML => html::HtmlRenderer::default().render(content),
+            Self::Fountain => fountain::FountainRenderer::new().render(content),
+        }
+    }
+
+    fn render_linked(&self, content: &str, index: &links::EntityIndex) -> String {
+        match self {
+            // character cues link to the character of the same name.
+            Self::Fountain => {
+                index.link_html(&fountain::FountainRenderer::new().index(index).render(content))
+            }
+            _ => index.link_html(&self.render(content)),
+        }
+    }
+}
+
+impl FromStr for SupportedRender {
+    type Err = ApiError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "markdown" => Ok(Self::Markdown),
+            "text" => Ok(Self::Text),
+            "html" => Ok(Self::HTML),
+            "fountain" => Ok(Self::Fountain),
+            other => Err(ApiError::BadRequest(format!("unsupported renderer {}", other))),
+        }
+    }
 }
 
 impl fmt::Display for SupportedRender {
@@ -17,7 +82,42 @@ impl fmt::Display for SupportedRender {
             Self::Markdown => "Markdown",
             Self::Text => "Text",
             Self::HTML => "HTML",
+            Self::Fountain => "Fountain",
         };
         write!(f, "{}", strval)
     }
 }
+
+/// escapes the characters that are significant in html and xml text and attribute values.
+pub fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// converts plain text into paragraphs, blank lines separate paragraphs
+/// and single newlines become line breaks.
+pub fn text_to_html(input: &str) -> String {
+    let input = crate::normalize_newlines(input);
+    let mut html = String::new();
+    for paragraph in input.split("\n\n") {
+        let paragraph = paragraph.trim_matches('\n');
+        if paragraph.trim().is_empty() {
+            continue;
+        }
+        let lines: Vec<String> = paragraph.lines().map(escape_html).collect();
+        html.push_str("<p>");
+        html.push_str(&lines.join("<br />\n"));
+        html.push_str("</p>\n");
+    }
+    html
+}
@@ -0,0 +1,881 @@
+//! PDF export for stories, fragments, character sheets and location handouts.
+//!
+//! A document is described as a list of [`PdfSection`]s made up of [`PdfBlock`]s. Sections are
+//! laid out into pages by a small line breaking layout engine, and the positioned pages are then
+//! drawn with pdfium-render. Keeping layout separate from pdfium means the same pipeline serves
+//! every exported entity, only the sections differ.
+//!
+//! ```rust,ignore
+//! let export = PdfExport::new(&story.name)
+//!     .subtitle(story.description.as_deref())
+//!     .section(PdfSection::new("Chapter One").paragraph("It was a dark and stormy night."));
+//! let bytes = renderer.render(export, PdfOptions::default()).await?;
+//! ```
+use super::SupportedRender;
+use super::fountain;
+use super::html::{Sanitizer, html_to_text};
+use crate::errors::ApiError;
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{Arena, Options, parse_document};
+use image::DynamicImage;
+use pdfium_render::prelude::*;
+use regex::Regex;
+use rocket::{FromForm, FromFormField};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// paper sizes offered on export forms, dimensions are in points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField, Default)]
+pub enum PageSize {
+    #[default]
+    A4,
+    A5,
+    Letter,
+    Legal,
+}
+
+impl PageSize {
+    pub fn dimensions(&self) -> (f32, f32) {
+        match self {
+            Self::A4 => (595.28, 841.89),
+            Self::A5 => (419.53, 595.28),
+            Self::Letter => (612.0, 792.0),
+            Self::Legal => (612.0, 1008.0),
+        }
+    }
+}
+
+/// the standard pdf font families, these don't need to be embedded in the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField, Default)]
+pub enum FontFamily {
+    #[default]
+    Times,
+    Helvetica,
+    Courier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+    Mono,
+}
+
+/// Export options, these are read from the query string of the export routes.
+#[derive(Debug, Clone, Serialize, Deserialize, FromForm)]
+pub struct PdfOptions {
+    #[field(default = PageSize::A4)]
+    pub page_size: PageSize,
+    /// margin on every side of the page in points.
+    #[field(default = 56.0)]
+    pub margin: f32,
+    #[field(default = FontFamily::Times)]
+    pub font: FontFamily,
+    #[field(default = 11.0)]
+    pub font_size: f32,
+    /// text printed at the top of every page, defaults to the document title.
+    pub header: Option<String>,
+    /// text printed at the bottom of every page, `{page}` and `{pages}` are replaced with
+    /// the page number and page count.
+    pub footer: Option<String>,
+    #[field(default = true)]
+    pub images: bool,
+    #[field(default = true)]
+    pub toc: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            page_size: PageSize::A4,
+            margin: 56.0,
+            font: FontFamily::Times,
+            font_size: 11.0,
+            header: None,
+            footer: None,
+            images: true,
+            toc: true,
+        }
+    }
+}
+
+impl PdfOptions {
+    /// the smallest content area, in points either way, left inside the margins.
+    const MIN_CONTENT: f32 = 72.0;
+
+    /// rejects font sizes and margins that leave no room to lay text out.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if !self.font_size.is_finite() || !(4.0..=72.0).contains(&self.font_size) {
+            return Err(ApiError::UnprocessableEntity(
+                "the font size must be between 4 and 72 points".to_string(),
+            ));
+        }
+        let (width, height) = self.page_size.dimensions();
+        let content = width.min(height) - self.margin * 2.0;
+        if !self.margin.is_finite() || self.margin < 0.0 || content < Self::MIN_CONTENT {
+            return Err(ApiError::UnprocessableEntity(format!(
+                "the margin must leave at least {} points of the page for content",
+                Self::MIN_CONTENT
+            )));
+        }
+        Ok(())
+    }
+
+    fn footer_text(&self, page: usize, pages: usize) -> String {
+        self.footer
+            .as_deref()
+            .unwrap_or("{page} / {pages}")
+            .replace("{page}", &page.to_string())
+            .replace("{pages}", &pages.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum PdfBlock {
+    Heading(String),
+    Paragraph(String),
+    /// monospaced text where line breaks are preserved.
+    Preformatted(String),
+    /// a labeled value, used for character sheets and handouts.
+    Field(String, String),
+    Image(DynamicImage),
+    Rule,
+    /// continues on a new page, nothing happens when the current page is still empty.
+    PageBreak,
+    /// text set in a narrower column, `left` and `right` are the fractions of the content
+    /// width left empty on either side. used for screenplay cues and dialogue.
+    Column {
+        text: String,
+        style: FontStyle,
+        left: f32,
+        right: f32,
+    },
+    /// a single line set against the right margin, such as a screenplay transition.
+    RightAligned(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PdfSection {
+    pub title: String,
+    pub blocks: Vec<PdfBlock>,
+}
+
+impl PdfSection {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn block(mut self, block: PdfBlock) -> Self {
+        self.blocks.push(block);
+        self
+    }
+
+    pub fn blocks(mut self, blocks: Vec<PdfBlock>) -> Self {
+        self.blocks.extend(blocks);
+        self
+    }
+
+    pub fn paragraph(self, text: &str) -> Self {
+        self.block(PdfBlock::Paragraph(text.to_string()))
+    }
+
+    pub fn field(self, label: &str, value: &str) -> Self {
+        self.block(PdfBlock::Field(label.to_string(), value.to_string()))
+    }
+
+    pub fn image(self, image: Option<DynamicImage>) -> Self {
+        match image {
+            Some(image) => self.block(PdfBlock::Image(image)),
+            None => self,
+        }
+    }
+}
+
+/// A document to export, an optional title page followed by sections that each start a new page.
+#[derive(Debug, Clone)]
+pub struct PdfExport {
+    title: String,
+    subtitle: Option<String>,
+    cover: Option<DynamicImage>,
+    title_page: bool,
+    sections: Vec<PdfSection>,
+}
+
+impl PdfExport {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            subtitle: None,
+            cover: None,
+            title_page: true,
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn subtitle(mut self, subtitle: Option<&str>) -> Self {
+        self.subtitle = subtitle.map(|s| s.to_string());
+        self
+    }
+
+    pub fn cover(mut self, cover: Option<DynamicImage>) -> Self {
+        self.cover = cover;
+        self
+    }
+
+    /// single page documents such as character sheets don't need a title page.
+    pub fn title_page(mut self, title_page: bool) -> Self {
+        self.title_page = title_page;
+        self
+    }
+
+    pub fn section(mut self, section: PdfSection) -> Self {
+        self.sections.push(section);
+        self
+    }
+}
+
+/// converts fragment content into blocks, following the structure of the source format.
+pub fn content_blocks(renderer: SupportedRender, content: &str) -> Vec<PdfBlock> {
+    match renderer {
+        SupportedRender::Markdown => markdown_blocks(content),
+        SupportedRender::Text => paragraphs(content),
+        SupportedRender::HTML => {
+            let text = html_to_text(&Sanitizer::default().clean(content));
+            paragraphs(&text)
+        }
+        SupportedRender::Fountain => fountain::pdf_blocks(content),
+    }
+}
+
+fn paragraphs(text: &str) -> Vec<PdfBlock> {
+    crate::normalize_newlines(text)
+        .split("\n\n")
+        .map(|paragraph| paragraph.trim())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| PdfBlock::Paragraph(paragraph.lines().collect::<Vec<_>>().join(" ")))
+        .collect()
+}
+
+fn markdown_blocks(content: &str) -> Vec<PdfBlock> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, content, &Options::default());
+    let mut blocks = Vec::new();
+    for node in root.children() {
+        push_markdown_block(node, &mut blocks, "");
+    }
+    blocks
+}
+
+fn push_markdown_block<'a>(node: &'a AstNode<'a>, blocks: &mut Vec<PdfBlock>, prefix: &str) {
+    match &node.data.borrow().value {
+        NodeValue::Heading(_) => blocks.push(PdfBlock::Heading(inline_text(node))),
+        NodeValue::Paragraph => {
+            blocks.push(PdfBlock::Paragraph(format!("{}{}", prefix, inline_text(node))))
+        }
+        NodeValue::CodeBlock(code) => {
+            blocks.push(PdfBlock::Preformatted(code.literal.trim_end().to_string()))
+        }
+        NodeValue::ThematicBreak => blocks.push(PdfBlock::Rule),
+        NodeValue::List(_) | NodeValue::BlockQuote => {
+            for child in node.children() {
+                push_markdown_block(child, blocks, prefix);
+            }
+        }
+        NodeValue::Item(_) => {
+            // only the first paragraph of an item carries the bullet
+            let mut bullet = "\u{2022} ";
+            for child in node.children() {
+                push_markdown_block(child, blocks, bullet);
+                bullet = "";
+            }
+        }
+        _ => {
+            let text = inline_text(node);
+            if !text.trim().is_empty() {
+                blocks.push(PdfBlock::Paragraph(format!("{}{}", prefix, text)));
+            }
+        }
+    }
+}
+
+fn inline_text<'a>(node: &'a AstNode<'a>) -> String {
+    let mut text = String::new();
+    for descendant in node.descendants() {
+        match &descendant.data.borrow().value {
+            NodeValue::Text(value) => text.push_str(value),
+            NodeValue::Code(code) => text.push_str(&code.literal),
+            NodeValue::SoftBreak | NodeValue::LineBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Measures the advance width of a run of text in points.
+pub trait TextMeasure {
+    fn width(&self, text: &str, style: FontStyle, size: f32) -> f32;
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Placed {
+    Text {
+        x: f32,
+        /// distance of the text baseline from the top of the page
+        baseline: f32,
+        text: String,
+        style: FontStyle,
+        size: f32,
+    },
+    Image {
+        x: f32,
+        top: f32,
+        width: f32,
+        height: f32,
+        image: DynamicImage,
+    },
+    Rule {
+        x1: f32,
+        x2: f32,
+        y: f32,
+    },
+}
+
+/// lays out blocks top to bottom, starting new pages as the content area fills.
+struct Layout<'m, M: TextMeasure> {
+    measure: &'m M,
+    options: &'m PdfOptions,
+    width: f32,
+    height: f32,
+    pages: Vec<Vec<Placed>>,
+    cursor: f32,
+}
+
+impl<'m, M: TextMeasure> Layout<'m, M> {
+    fn new(measure: &'m M, options: &'m PdfOptions) -> Self {
+        let (width, height) = options.page_size.dimensions();
+        Self {
+            measure,
+            options,
+            width,
+            height,
+            pages: Vec::new(),
+            cursor: 0.0,
+        }
+    }
+
+    fn content_width(&self) -> f32 {
+        self.width - self.options.margin * 2.0
+    }
+
+    fn bottom(&self) -> f32 {
+        self.height - self.options.margin
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+        self.cursor = self.options.margin;
+    }
+
+    fn ensure(&mut self, height: f32) {
+        if self.pages.is_empty() || self.cursor + height > self.bottom() {
+            self.new_page();
+        }
+    }
+
+    fn push(&mut self, item: Placed) {
+        if let Some(page) = self.pages.last_mut() {
+            page.push(item);
+        }
+    }
+
+    fn wrap(&self, text: &str, style: FontStyle, size: f32, width: f32) -> Vec<String> {
+        let space = self.measure.width(" ", style, size);
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut line_width = 0.0;
+        for word in text.split_whitespace() {
+            let mut word = word.to_string();
+            let mut word_width = self.measure.width(&word, style, size);
+            // words wider than the column are broken across lines
+            while word_width > width && word.chars().count() > 1 {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                    line_width = 0.0;
+                }
+                let mut split = word.chars().count() - 1;
+                while split > 1 {
+                    let head: String = word.chars().take(split).collect();
+                    if self.measure.width(&head, style, size) <= width {
+                        break;
+                    }
+                    split -= 1;
+                }
+                lines.push(word.chars().take(split).collect());
+                word = word.chars().skip(split).collect();
+                word_width = self.measure.width(&word, style, size);
+            }
+            if line.is_empty() {
+                line = word;
+                line_width = word_width;
+            } else if line_width + space + word_width <= width {
+                line.push(' ');
+                line.push_str(&word);
+                line_width += space + word_width;
+            } else {
+                lines.push(std::mem::replace(&mut line, word));
+                line_width = word_width;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn text(&mut self, text: &str, style: FontStyle, size: f32, indent: f32, after: f32) {
+        let width = self.content_width() - indent;
+        self.text_in(text, style, size, indent, width, after);
+    }
+
+    /// sets text in a column `width` wide starting `indent` from the left margin.
+    fn text_in(
+        &mut self,
+        text: &str,
+        style: FontStyle,
+        size: f32,
+        indent: f32,
+        width: f32,
+        after: f32,
+    ) {
+        let line_height = size * 1.4;
+        let lines = match style {
+            // preformatted lines keep their indentation unless they have to be wrapped
+            FontStyle::Mono => text
+                .lines()
+                .flat_map(|line| {
+                    if self.measure.width(line, style, size) <= width {
+                        vec![line.trim_end().to_string()]
+                    } else {
+                        self.wrap(line, style, size, width)
+                    }
+                })
+                .collect(),
+            _ => self.wrap(text, style, size, width),
+        };
+        for line in lines {
+            self.ensure(line_height);
+            if !line.is_empty() {
+                let item = Placed::Text {
+                    x: self.options.margin + indent,
+                    baseline: self.cursor + size,
+                    text: line,
+                    style,
+                    size,
+                };
+                self.push(item);
+            }
+            self.cursor += line_height;
+        }
+        self.cursor += after;
+    }
+
+    fn image(&mut self, image: &DynamicImage, max_height: f32) {
+        if image.width() == 0 || image.height() == 0 {
+            return;
+        }
+        let ratio = image.height() as f32 / image.width() as f32;
+        let mut width = self.content_width().min(image.width() as f32);
+        let mut height = width * ratio;
+        if height > max_height {
+            height = max_height;
+            width = height / ratio;
+        }
+        self.ensure(height);
+        let item = Placed::Image {
+            x: self.options.margin + (self.content_width() - width) / 2.0,
+            top: self.cursor,
+            width,
+            height,
+            image: image.clone(),
+        };
+        self.push(item);
+        self.cursor += height + self.options.font_size;
+    }
+
+    fn rule(&mut self) {
+        self.ensure(self.options.font_size);
+        let y = self.cursor + self.options.font_size / 2.0;
+        let item = Placed::Rule {
+            x1: self.options.margin,
+            x2: self.width - self.options.margin,
+            y,
+        };
+        self.push(item);
+        self.cursor += self.options.font_size;
+    }
+
+    fn block(&mut self, block: &PdfBlock) {
+        let size = self.options.font_size;
+        match block {
+            PdfBlock::Heading(text) => self.text(text, FontStyle::Bold, size * 1.3, 0.0, size * 0.5),
+            PdfBlock::Paragraph(text) => self.text(text, FontStyle::Regular, size, 0.0, size * 0.6),
+            PdfBlock::Preformatted(text) => {
+                self.text(text, FontStyle::Mono, size * 0.9, size, size * 0.6)
+            }
+            PdfBlock::Field(label, value) => {
+                let label = format!("{}:", label);
+                let label_width = self.measure.width(&label, FontStyle::Bold, size) + size * 0.5;
+                self.ensure(size * 1.4);
+                let item = Placed::Text {
+                    x: self.options.margin,
+                    baseline: self.cursor + size,
+                    text: label,
+                    style: FontStyle::Bold,
+                    size,
+                };
+                self.push(item);
+                self.text(value, FontStyle::Regular, size, label_width, size * 0.3);
+            }
+            PdfBlock::Image(image) => {
+                if self.options.images {
+                    let max_height = (self.bottom() - self.options.margin) / 2.0;
+                    self.image(image, max_height);
+                }
+            }
+            PdfBlock::Rule => self.rule(),
+            PdfBlock::PageBreak => {
+                if self.pages.last().is_some_and(|page| !page.is_empty()) {
+                    self.new_page();
+                }
+            }
+            PdfBlock::Column {
+                text,
+                style,
+                left,
+                right,
+            } => {
+                let indent = self.content_width() * left;
+                let width = self.content_width() * (1.0 - left - right);
+                // consecutive lines of dialogue stay together, only the last one gets spacing.
+                for line in text.lines() {
+                    self.text_in(line, *style, size, indent, width, 0.0);
+                }
+                self.cursor += size * 0.6;
+            }
+            PdfBlock::RightAligned(text) => {
+                let width = self.measure.width(text, FontStyle::Regular, size);
+                let indent = (self.content_width() - width).max(0.0);
+                self.text(text, FontStyle::Regular, size, indent, size * 0.6);
+            }
+        }
+    }
+
+    fn section(&mut self, section: &PdfSection) -> usize {
+        self.new_page();
+        let start = self.pages.len() - 1;
+        let size = self.options.font_size;
+        self.text(&section.title, FontStyle::Bold, size * 1.8, 0.0, size);
+        for block in section.blocks.iter() {
+            self.block(block);
+        }
+        start
+    }
+
+    fn title_page(&mut self, export: &PdfExport) {
+        self.new_page();
+        let size = self.options.font_size;
+        self.cursor = self.height / 4.0;
+        self.text(&export.title, FontStyle::Bold, size * 2.6, 0.0, size * 2.0);
+        if let Some(subtitle) = &export.subtitle {
+            self.text(subtitle, FontStyle::Italic, size * 1.2, 0.0, size * 2.0);
+        }
+        if let Some(cover) = &export.cover {
+            let max_height = self.bottom() - self.cursor;
+            if self.options.images && max_height > 0.0 {
+                self.image(cover, max_height);
+            }
+        }
+    }
+
+    /// table of contents entries with page numbers right aligned.
+    fn contents(&mut self, entries: &[(String, usize)]) {
+        self.new_page();
+        let size = self.options.font_size;
+        self.text("Contents", FontStyle::Bold, size * 1.8, 0.0, size);
+        for (title, page) in entries {
+            let number = page.to_string();
+            let number_width = self.measure.width(&number, FontStyle::Regular, size);
+            let lines = self.wrap(
+                title,
+                FontStyle::Regular,
+                size,
+                self.content_width() - number_width - size * 2.0,
+            );
+            let last = lines.len().saturating_sub(1);
+            for (idx, line) in lines.into_iter().enumerate() {
+                self.ensure(size * 1.4);
+                let item = Placed::Text {
+                    x: self.options.margin,
+                    baseline: self.cursor + size,
+                    text: line,
+                    style: FontStyle::Regular,
+                    size,
+                };
+                self.push(item);
+                if idx == last {
+                    let item = Placed::Text {
+                        x: self.width - self.options.margin - number_width,
+                        baseline: self.cursor + size,
+                        text: number.clone(),
+                        style: FontStyle::Regular,
+                        size,
+                    };
+                    self.push(item);
+                }
+                self.cursor += size * 1.4;
+            }
+        }
+    }
+
+    /// adds the header and footer to every page except the title page.
+    fn decorate(&mut self, title: &str, skip: usize) {
+        let total = self.pages.len();
+        let size = self.options.font_size * 0.8;
+        let (measure, options, width, height) = (self.measure, self.options, self.width, self.height);
+        let header = options.header.clone().unwrap_or(title.to_string());
+        let center = |text: &str| (width - measure.width(text, FontStyle::Italic, size)) / 2.0;
+        for (idx, page) in self.pages.iter_mut().enumerate().skip(skip) {
+            let footer = options.footer_text(idx + 1, total);
+            if !header.trim().is_empty() {
+                page.push(Placed::Text {
+                    x: center(&header),
+                    baseline: options.margin / 2.0 + size / 2.0,
+                    text: header.clone(),
+                    style: FontStyle::Italic,
+                    size,
+                });
+            }
+            if !footer.trim().is_empty() {
+                page.push(Placed::Text {
+                    x: center(&footer),
+                    baseline: height - options.margin / 2.0,
+                    text: footer,
+                    style: FontStyle::Italic,
+                    size,
+                });
+            }
+        }
+    }
+}
+
+/// lays out the whole document, returning the positioned content of every page.
+pub(crate) fn layout<M: TextMeasure>(
+    measure: &M,
+    export: &PdfExport,
+    options: &PdfOptions,
+) -> Vec<Vec<Placed>> {
+    let mut body = Layout::new(measure, options);
+    let starts: Vec<usize> = export
+        .sections
+        .iter()
+        .map(|section| body.section(section))
+        .collect();
+
+    let mut front = Layout::new(measure, options);
+    if export.title_page {
+        front.title_page(export);
+    }
+    if options.toc && export.sections.len() > 1 {
+        // the contents can't be numbered until we know how many pages it takes itself
+        let placeholder: Vec<(String, usize)> = export
+            .sections
+            .iter()
+            .map(|section| (section.title.clone(), body.pages.len() * 10))
+            .collect();
+        let mut sizing = Layout::new(measure, options);
+        sizing.contents(&placeholder);
+        let offset = front.pages.len() + sizing.pages.len() + 1;
+        let entries: Vec<(String, usize)> = export
+            .sections
+            .iter()
+            .zip(starts.iter())
+            .map(|(section, start)| (section.title.clone(), start + offset))
+            .collect();
+        front.contents(&entries);
+    }
+
+    let skip = if export.title_page { 1 } else { 0 };
+    front.pages.extend(body.pages);
+    front.decorate(&export.title, skip);
+    front.pages
+}
+
+/// measures text by creating detached pdfium text objects, widths are cached per word.
+struct PdfiumMeasure<'d, 'a> {
+    document: &'d PdfDocument<'a>,
+    fonts: &'d HashMap<FontStyle, PdfFontToken>,
+    cache: RefCell<HashMap<(String, FontStyle, u32), f32>>,
+}
+
+impl TextMeasure for PdfiumMeasure<'_, '_> {
+    fn width(&self, text: &str, style: FontStyle, size: f32) -> f32 {
+        if text.is_empty() {
+            return 0.0;
+        }
+        let key = (text.to_string(), style, size.to_bits());
+        if let Some(width) = self.cache.borrow().get(&key) {
+            return *width;
+        }
+        let token = self.fonts[&style];
+        // pdfium doesn't include trailing whitespace in bounds, so spaces are measured between glyphs
+        let width = if text.trim().is_empty() {
+            let spaced = self.raw_width(&format!("n{}n", text), token, size);
+            spaced - self.raw_width("nn", token, size)
+        } else {
+            self.raw_width(text, token, size)
+        };
+        self.cache.borrow_mut().insert(key, width);
+        width
+    }
+}
+
+impl PdfiumMeasure<'_, '_> {
+    fn raw_width(&self, text: &str, token: PdfFontToken, size: f32) -> f32 {
+        PdfPageTextObject::new(self.document, text, token, PdfPoints::new(size))
+            .and_then(|object| object.width())
+            .map(|width| width.value)
+            // fall back to an average glyph width rather than failing the export
+            .unwrap_or(text.chars().count() as f32 * size * 0.5)
+    }
+}
+
+/// Renders [`PdfExport`]s with a pdfium library, this is managed as rocket state.
+pub struct PdfRenderer {
+    library: Option<PathBuf>,
+}
+
+impl PdfRenderer {
+    /// `library` is a directory containing the pdfium shared library, when it isn't set the
+    /// system library is used.
+    pub fn new(library: Option<PathBuf>) -> Self {
+        Self { library }
+    }
+
+    /// pdfium calls are blocking, so rendering happens on the blocking thread pool.
+    pub async fn render(&self, export: PdfExport, options: PdfOptions) -> Result<Vec<u8>, ApiError> {
+        options.validate()?;
+        let library = self.library.clone();
+        tokio::task::spawn_blocking(move || render_blocking(library, &export, &options))
+            .await
+            .map_err(|e| ApiError::InternalServerError(e.to_string()))?
+    }
+}
+
+fn render_blocking(
+    library: Option<PathBuf>,
+    export: &PdfExport,
+    options: &PdfOptions,
+) -> Result<Vec<u8>, ApiError> {
+    let bindings = match &library {
+        Some(path) => {
+            Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(path))?
+        }
+        None => Pdfium::bind_to_system_library()?,
+    };
+    let pdfium = Pdfium::new(bindings);
+    let mut document = pdfium.create_new_pdf()?;
+
+    let fonts = {
+        let registry = document.fonts_mut();
+        let mut fonts = HashMap::new();
+        let (regular, bold, italic) = match options.font {
+            FontFamily::Times => (
+                registry.times_roman(),
+                registry.times_bold(),
+                registry.times_italic(),
+            ),
+            FontFamily::Helvetica => (
+                registry.helvetica(),
+                registry.helvetica_bold(),
+                registry.helvetica_oblique(),
+            ),
+            FontFamily::Courier => (
+                registry.courier(),
+                registry.courier_bold(),
+                registry.courier_oblique(),
+            ),
+        };
+        fonts.insert(FontStyle::Regular, regular);
+        fonts.insert(FontStyle::Bold, bold);
+        fonts.insert(FontStyle::Italic, italic);
+        fonts.insert(FontStyle::Mono, registry.courier());
+        fonts
+    };
+
+    let pages = {
+        let measure = PdfiumMeasure {
+            document: &document,
+            fonts: &fonts,
+            cache: RefCell::new(HashMap::new()),
+        };
+        layout(&measure, export, options)
+    };
+
+    let (width, height) = options.page_size.dimensions();
+    let size = PdfPagePaperSize::from_points(PdfPoints::new(width), PdfPoints::new(height));
+    for items in pages {
+        let mut page = document.pages_mut().create_page_at_end(size)?;
+        let objects = page.objects_mut();
+        for item in items {
+            match item {
+                Placed::Text {
+                    x,
+                    baseline,
+                    text,
+                    style,
+                    size,
+                } => {
+                    objects.create_text_object(
+                        PdfPoints::new(x),
+                        PdfPoints::new(height - baseline),
+                        text,
+                        fonts[&style],
+                        PdfPoints::new(size),
+                    )?;
+                }
+                Placed::Image {
+                    x,
+                    top,
+                    width,
+                    height: image_height,
+                    image,
+                } => {
+                    objects.create_image_object(
+                        PdfPoints::new(x),
+                        PdfPoints::new(height - top - image_height),
+                        &image,
+                        Some(PdfPoints::new(width)),
+                        Some(PdfPoints::new(image_height)),
+                    )?;
+                }
+                Placed::Rule { x1, x2, y } => {
+                    objects.create_path_object_line(
+                        PdfPoints::new(x1),
+                        PdfPoints::new(height - y),
+                        PdfPoints::new(x2),
+                        PdfPoints::new(height - y),
+                        PdfColor::new(128, 128, 128, 255),
+                        PdfPoints::new(0.5),
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(document.save_to_bytes()?)
+}
@@ -0,0 +1,11 @@
+use crate::render::{Renderer, text_to_html};
+
+/// renders plain text, blank lines separate paragraphs and everything is escaped.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn render(&self, content: &str) -> String {
+        text_to_html(content)
+    }
+}
@@ -0,0 +1,542 @@
+//! imports a folder (or zip archive) of markdown files as a new story.
+//!
+//! every `.md` file becomes a fragment of the story. files may start with a yaml (`---`)
+//! or toml (`+++`) frontmatter block:
+//!
+//! ```text
+//! ---
+//! title: The Crossing
+//! order: 3
+//! tags: [draft, sea]
+//! characters: [Mara, Tomas]
+//! location: Old Harbor
+//! ---
+//! ```
+//!
+//! fragments are ordered by `order` when given and by file path otherwise, names in
+//! `characters` and `location`/`locations` are matched against existing entities and linked
+//! to both the fragment and the story. [`ImportPlan`] is built first so the result can be
+//! previewed before anything is written.
+use super::StoryBuilder;
+use crate::ApiClient;
+use crate::characters::api::CharacterRender;
+use crate::errors::ApiError;
+use crate::fragments::api::FragmentBuilder;
+use crate::locations::LocationRender;
+use crate::model::{Story, StoryFragment};
+use crate::relations::Relation;
+use crate::render::SupportedRender;
+use dashmap::DashMap;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+/// how long a previewed plan waits for confirmation before it's dropped.
+pub const PLAN_TTL: Duration = Duration::from_secs(30 * 60);
+/// the largest markdown file read from an archive, in bytes.
+pub const MAX_ENTRY_SIZE: u64 = 4 * 1024 * 1024;
+/// the most markdown read from a single archive, in bytes.
+pub const MAX_ARCHIVE_SIZE: u64 = 32 * 1024 * 1024;
+
+struct PendingImport {
+    /// subject of the user who previewed the plan, only they may confirm it.
+    owner: String,
+    created: Instant,
+    plan: ImportPlan,
+}
+
+/// plans waiting on confirmation after a dry run, keyed by [`ImportPlan::id`].
+///
+/// plans expire after [`PLAN_TTL`], expired plans are evicted whenever the cache is used.
+#[derive(Default)]
+pub struct ImportCache {
+    plans: DashMap<Uuid, PendingImport>,
+}
+
+impl ImportCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, owner: &str, plan: ImportPlan) {
+        self.insert_at(owner, plan, Instant::now());
+    }
+
+    pub(crate) fn insert_at(&self, owner: &str, plan: ImportPlan, now: Instant) {
+        self.evict(now);
+        let pending = PendingImport {
+            owner: owner.to_string(),
+            created: now,
+            plan,
+        };
+        self.plans.insert(pending.plan.id, pending);
+    }
+
+    /// removes the plan so it can be committed, plans previewed by another user aren't found.
+    pub fn take(&self, id: &Uuid, owner: &str) -> Result<ImportPlan, ApiError> {
+        self.take_at(id, owner, Instant::now())
+    }
+
+    pub(crate) fn take_at(
+        &self,
+        id: &Uuid,
+        owner: &str,
+        now: Instant,
+    ) -> Result<ImportPlan, ApiError> {
+        self.evict(now);
+        self.plans
+            .remove_if(id, |_, pending| pending.owner == owner)
+            .map(|(_, pending)| pending.plan)
+            .ok_or(ApiError::NotFound(
+                "import preview has expired, please upload the archive again".to_string(),
+            ))
+    }
+
+    fn evict(&self, now: Instant) {
+        self.plans
+            .retain(|_, pending| now.saturating_duration_since(pending.created) < PLAN_TTL);
+    }
+}
+
+/// metadata read from the frontmatter block of a markdown file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    pub order: Option<i32>,
+    pub tags: Vec<String>,
+    pub characters: Vec<String>,
+    pub location: Option<String>,
+    pub locations: Vec<String>,
+}
+
+/// a markdown file read from a directory or archive, `path` is relative to the import root.
+#[derive(Debug, Clone)]
+pub struct MarkdownFile {
+    pub path: String,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedLink {
+    pub name: String,
+    pub category: String,
+    /// the matching entity, `None` when nothing with this name exists yet.
+    pub id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedFragment {
+    pub path: String,
+    pub idx: i32,
+    pub name: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub links: Vec<ImportedLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportPlan {
+    pub id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub renderer: SupportedRender,
+    pub tags: Vec<String>,
+    pub fragments: Vec<ImportedFragment>,
+    /// names from frontmatter that didn't match an existing character or location.
+    pub unresolved: Vec<ImportedLink>,
+}
+
+/// splits the frontmatter from the body of a file, files without frontmatter get the default.
+pub fn split_frontmatter<'s>(
+    path: &str,
+    source: &'s str,
+) -> Result<(Frontmatter, &'s str), ApiError> {
+    let source = source.trim_start_matches('\u{feff}');
+    let delimiter = match source.lines().next().map(str::trim_end) {
+        Some("---") => "---",
+        Some("+++") => "+++",
+        _ => return Ok((Frontmatter::default(), source)),
+    };
+
+    let rest = &source[source.find('\n').map(|i| i + 1).unwrap_or(source.len())..];
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            let header = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let frontmatter = if delimiter == "---" {
+                serde_yaml::from_str::<Option<Frontmatter>>(header)
+                    .map(Option::unwrap_or_default)
+                    .map_err(|e| invalid_frontmatter(path, e))?
+            } else {
+                toml::from_str(header).map_err(|e| invalid_frontmatter(path, e))?
+            };
+            return Ok((frontmatter, body.trim_start_matches(['\r', '\n'])));
+        }
+        offset += line.len();
+    }
+    Err(ApiError::UnprocessableEntity(format!(
+        "{}: frontmatter is missing its closing {}",
+        path, delimiter
+    )))
+}
+
+fn invalid_frontmatter(path: &str, err: impl std::fmt::Display) -> ApiError {
+    ApiError::UnprocessableEntity(format!("{}: invalid frontmatter: {}", path, err))
+}
+
+fn is_markdown(path: &str) -> bool {
+    let hidden = path
+        .split('/')
+        .any(|part| part.starts_with('.') || part == "__MACOSX");
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+    !hidden && matches!(extension.as_deref(), Some("md" | "markdown"))
+}
+
+/// reads every markdown file below `root`.
+pub fn read_directory<P: AsRef<Path>>(root: P) -> Result<Vec<MarkdownFile>, ApiError> {
+    let root = root.as_ref();
+    let mut files = Vec::new();
+    for entry in WalkDir::new(root) {
+        let entry = entry.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let path = relative.to_string_lossy().replace('\\', "/");
+        if is_markdown(&path) {
+            let source = std::fs::read_to_string(entry.path())?;
+            files.push(MarkdownFile { path, source });
+        }
+    }
+    Ok(files)
+}
+
+fn too_large(what: &str, limit: u64) -> ApiError {
+    ApiError::UnprocessableEntity(format!(
+        "{} is larger than {} MiB",
+        what,
+        limit / (1024 * 1024)
+    ))
+}
+
+/// reads every markdown file in a zip archive.
+///
+/// files over [`MAX_ENTRY_SIZE`] or archives holding more than [`MAX_ARCHIVE_SIZE`] of
+/// markdown are rejected, the sizes are checked while reading since the headers can lie.
+pub fn read_zip(bytes: &[u8]) -> Result<Vec<MarkdownFile>, ApiError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+    let mut files = Vec::new();
+    let mut total = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        // enclosed_name rejects absolute paths and `..` components.
+        let Some(path) = entry.enclosed_name() else {
+            continue;
+        };
+        let path = path.to_string_lossy().replace('\\', "/");
+        if entry.is_file() && is_markdown(&path) {
+            if entry.size() > MAX_ENTRY_SIZE {
+                return Err(too_large(&path, MAX_ENTRY_SIZE));
+            }
+            let mut source = String::new();
+            entry
+                .by_ref()
+                .take(MAX_ENTRY_SIZE + 1)
+                .read_to_string(&mut source)
+                .map_err(|e| ApiError::UnprocessableEntity(format!("{}: {}", path, e)))?;
+            let size = source.len() as u64;
+            if size > MAX_ENTRY_SIZE {
+                return Err(too_large(&path, MAX_ENTRY_SIZE));
+            }
+            total += size;
+            if total > MAX_ARCHIVE_SIZE {
+                return Err(too_large("the archive", MAX_ARCHIVE_SIZE));
+            }
+            files.push(MarkdownFile { path, source });
+        }
+    }
+    Ok(files)
+}
+
+/// orders paths so that `chapter-2.md` sorts before `chapter-10.md`.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut take = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (x, y) = (take(&mut a), take(&mut b));
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// the first heading of the body, used when the frontmatter has no title.
+fn heading_title(body: &str) -> Option<String> {
+    body.lines()
+        .map(str::trim)
+        .find(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim().to_string())
+        .filter(|title| !title.is_empty())
+}
+
+fn file_title(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().replace(['-', '_'], " "))
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn resolve<'e>(
+    name: &str,
+    category: &str,
+    entities: impl IntoIterator<Item = (&'e str, Uuid)>,
+) -> ImportedLink {
+    let wanted = name.trim().to_lowercase();
+    let id = entities
+        .into_iter()
+        .find(|(candidate, _)| candidate.trim().to_lowercase() == wanted)
+        .map(|(_, id)| id);
+    ImportedLink {
+        name: name.trim().to_string(),
+        category: category.to_string(),
+        id,
+    }
+}
+
+impl ImportPlan {
+    /// parses and orders the files, matching frontmatter names against the given entities.
+    pub fn new(
+        title: &str,
+        description: Option<String>,
+        renderer: SupportedRender,
+        tags: Vec<String>,
+        files: Vec<MarkdownFile>,
+        characters: &[CharacterRender],
+        locations: &[LocationRender],
+    ) -> Result<Self, ApiError> {
+        if files.is_empty() {
+            return Err(ApiError::BadRequest(
+                "no markdown files found to import".to_string(),
+            ));
+        }
+
+        let mut parsed = Vec::with_capacity(files.len());
+        for file in files.iter() {
+            let (frontmatter, body) = split_frontmatter(&file.path, &file.source)?;
+            parsed.push((file, frontmatter, body));
+        }
+        parsed.sort_by(|(a, fa, _), (b, fb, _)| {
+            let (oa, ob) = (fa.order.unwrap_or(i32::MAX), fb.order.unwrap_or(i32::MAX));
+            oa.cmp(&ob).then_with(|| natural_cmp(&a.path, &b.path))
+        });
+
+        let mut fragments = Vec::with_capacity(parsed.len());
+        let mut unresolved: Vec<ImportedLink> = Vec::new();
+        for (idx, (file, frontmatter, body)) in parsed.into_iter().enumerate() {
+            let name = frontmatter
+                .title
+                .clone()
+                .or_else(|| heading_title(body))
+                .unwrap_or_else(|| file_title(&file.path));
+
+            let mut links: Vec<ImportedLink> = frontmatter
+                .characters
+                .iter()
+                .map(|name| {
+                    let entities = characters.iter().map(|c| (c.name.as_str(), c.id));
+                    resolve(name, "characters", entities)
+                })
+                .collect();
+            let places = frontmatter.location.iter().chain(frontmatter.locations.iter());
+            links.extend(places.map(|name| {
+                let entities = locations.iter().map(|l| (l.name.as_str(), l.id));
+                resolve(name, "locations", entities)
+            }));
+            links.retain(|link| !link.name.is_empty());
+
+            for link in links.iter().filter(|link| link.id.is_none()) {
+                let seen = unresolved
+                    .iter()
+                    .any(|other| other.category == link.category && other.name == link.name);
+                if !seen {
+                    unresolved.push(link.clone());
+                }
+            }
+
+            fragments.push(ImportedFragment {
+                path: file.path.clone(),
+                idx: idx as i32,
+                name,
+                content: crate::normalize_newlines(body),
+                tags: frontmatter.tags,
+                links,
+            });
+        }
+
+        Ok(Self {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            description,
+            renderer,
+            tags,
+            fragments,
+            unresolved,
+        })
+    }
+
+    /// like [`ImportPlan::new`], fetching the characters and locations to match from the api.
+    pub async fn prepare(
+        api: &ApiClient,
+        access_token: &str,
+        title: &str,
+        description: Option<String>,
+        renderer: SupportedRender,
+        tags: Vec<String>,
+        files: Vec<MarkdownFile>,
+    ) -> Result<Self, ApiError> {
+        let characters: Option<Vec<CharacterRender>> =
+            api.get_protected("/characters", access_token, None).await?;
+        let locations: Option<Vec<LocationRender>> =
+            api.get_protected("/locations/", access_token, None).await?;
+        Self::new(
+            title,
+            description,
+            renderer,
+            tags,
+            files,
+            &characters.unwrap_or_default(),
+            &locations.unwrap_or_default(),
+        )
+    }
+
+    /// creates the story, its fragments and the relations to every resolved entity. a fragment
+    /// that fails is reported and the import carries on with the next one.
+    pub async fn commit(
+        &self,
+        api: &ApiClient,
+        access_token: &str,
+    ) -> Result<ImportReport, ApiError> {
+        let builder = StoryBuilder {
+            title: self.title.clone(),
+            description: self.description.clone(),
+            renderer: self.renderer,
+            tags: self.tags.clone(),
+            group: None,
+        };
+        let story: Story = api.post("/stories", access_token, None, &builder).await?;
+        let mut report = ImportReport {
+            story: story.id,
+            imported: 0,
+            failed: Vec::new(),
+        };
+
+        let mut linked = HashSet::new();
+        for imported in self.fragments.iter() {
+            let result: Result<(), ApiError> = async {
+                let fragment = FragmentBuilder {
+                    id: None,
+                    parent: Some(story.id),
+                    category: Some("stories"),
+                    name: &imported.name,
+                    content: &imported.content,
+                    tags: &imported.tags,
+                    idx: Some(imported.idx),
+                };
+                let fragment: StoryFragment = fragment.build(api, access_token).await?;
+
+                for link in imported.links.iter() {
+                    let Some(child) = link.id else {
+                        continue;
+                    };
+                    let relation = Relation {
+                        parent: fragment.id,
+                        child,
+                        parent_category: "fragments".to_string(),
+                        child_category: link.category.clone(),
+                        description: None,
+                        relation_type: None,
+                    };
+                    let _: Relation = api
+                        .post("/relations/", access_token, None, relation)
+                        .await?;
+
+                    if !linked.contains(&child) {
+                        let relation = Relation {
+                            parent: story.id,
+                            child,
+                            parent_category: "stories".to_string(),
+                            child_category: link.category.clone(),
+                            description: None,
+                            relation_type: None,
+                        };
+                        let _: Relation = api
+                            .post("/relations/", access_token, None, relation)
+                            .await?;
+                        linked.insert(child);
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            match result {
+                Ok(()) => report.imported += 1,
+                Err(err) => report.failed.push(FailedFragment {
+                    name: imported.name.clone(),
+                    path: imported.path.clone(),
+                    error: err.to_string(),
+                }),
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// a file that couldn't be imported, the import carries on with the files after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedFragment {
+    pub name: String,
+    pub path: String,
+    pub error: String,
+}
+
+/// what [`ImportPlan::commit`] created. nothing is rolled back, so a file that failed part way
+/// through may have left its fragment behind without every link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub story: Uuid,
+    /// the number of files imported.
+    pub imported: usize,
+    pub failed: Vec<FailedFragment>,
+}
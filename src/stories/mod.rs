@@ -1,14 +1,19 @@
+pub mod import;
+
 use crate::api::{ApiClient, ApiRequest, get_access_token};
 use crate::assets::graphs::{Entity, EntityExt, Renderable, render_children};
+use crate::assets::images::{ImageProcessor, image_id};
 use crate::auth::Guard;
 use crate::characters::api::CharacterRender;
-use crate::errors::LazyError;
+use crate::errors::{ApiError, LazyError};
 use crate::fragments::frontend::FragmentRender;
 use crate::model::Note;
 use crate::model::{Character, Story, StoryFragment, Tag};
 use rocket::form::Form;
-use rocket::http::CookieJar;
-use rocket::{FromForm, FromFormField};
+use rocket::fs::TempFile;
+use tokio::io::AsyncReadExt;
+use rocket::http::{ContentType, CookieJar};
+use rocket::{Either, FromForm, FromFormField};
 use std::collections::HashMap;
 use wrappedviz::rgraph::{Edge, Node};
 use wrappedviz::style::*;
@@ -21,8 +26,16 @@ use rocket::{
 };
 
 use crate::locations::LocationRender;
-
-use crate::render::SupportedRender;
+use import::{ImportCache, ImportPlan, ImportReport};
+
+use crate::render::links::{EntityIndex, LinkedRenderer};
+use crate::render::{Renderer, SupportedRender};
+use crate::render::epub::{EPUB_MIMETYPE, EpubBuilder, EpubCover};
+use crate::render::pdf::{PdfExport, PdfOptions, PdfRenderer, PdfSection, content_blocks};
+use crate::timelines::continuity::{self, Beat};
+use crate::calendars::frontend::preferred;
+use crate::characters::network::{self, Metric, Network};
+use crate::assets::graphs::render_str;
 use rocket_dyn_templates::{Template, context};
 use uuid::Uuid;
 
@@ -54,8 +67,25 @@ pub struct StoryBuilder {
     pub group: Option<Uuid>,
 }
 
+impl StoryBuilder {
+    pub fn new(title: &str, description: Option<String>, renderer: SupportedRender) -> Self {
+        Self {
+            title: title.to_string(),
+            description,
+            renderer,
+            tags: Vec::new(),
+            group: None,
+        }
+    }
+}
+
 #[get("/<id>")]
-async fn get_story(guard: Guard, id: Uuid, api: &State<ApiClient>) -> RawHtml<Template> {
+async fn get_story(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    jar: &CookieJar<'_>,
+) -> RawHtml<Template> {
     let access_token = guard.access_token();
     let url = format!("/stories/{}", id);
     let id_string = id.to_string();
@@ -70,17 +100,184 @@ async fn get_story(guard: Guard, id: Uuid, api: &State<ApiClient>) -> RawHtml<Te
         .access_token(&guard.access_token())
         .params(params);
 
-    let fragments = story.fragments(request.clone()).await.unwrap();
+    let renderer = story.renderer();
+    let index = EntityIndex::fetch(api, access_token)
+        .await
+        .unwrap_or_default();
+    let linked = LinkedRenderer::new(renderer, &index);
+    let mut fragments = story.fragments(request.clone()).await.unwrap();
+    for fragment in fragments.iter_mut() {
+        fragment.render_content(&linked);
+    }
+    let description = story
+        .description
+        .as_deref()
+        .map(|description| renderer.render(description));
     let characters = story.characters(request.clone()).await.unwrap();
-    let locations = story.locations(request.clone()).await.unwrap();
+    let mut locations = story.locations(request.clone()).await.unwrap();
+    let calendar = preferred(api, access_token, jar).await;
+    for location in locations.iter_mut() {
+        location.date_created(&calendar);
+    }
     let tags = story.tags(request.clone()).await.unwrap();
     let notes = story.notes(request).await.unwrap();
     RawHtml(Template::render(
         "stories/story",
-        context! { title: story.name.clone(), notes, story, fragments, characters, tags, locations },
+        context! { title: story.name.clone(), notes, story, description, renderer, fragments, characters, tags, locations },
     ))
 }
 
+/// exports the story as an EPUB 3 book, with fragments as chapters in `idx` order.
+/// character and location pages are appended when `appendices` is set.
+#[get("/<id>/export.epub?<appendices>")]
+async fn export_epub(
+    guard: Guard,
+    id: Uuid,
+    appendices: Option<bool>,
+    api: &State<ApiClient>,
+    processor: &State<ImageProcessor>,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let url = format!("/stories/{}", id);
+    let story: StoryRender = api.get_protected(&url, guard.access_token(), None).await?;
+    let request = story.request(api, guard.access_token());
+
+    let mut fragments = story.fragments(request.clone()).await?;
+    fragments.sort_by_key(|fragment| fragment.idx);
+
+    let renderer = story.renderer();
+    let mut book = EpubBuilder::new(story.id, &story.name)
+        .description(story.description.as_deref())
+        .modified(story.last_edited.unwrap_or(story.created));
+
+    if let Some(cover) = story.cover(processor).await? {
+        book = book.cover(cover);
+    }
+
+    for fragment in fragments.iter() {
+        book = book.chapter(&fragment.name, &renderer.render(&fragment.content));
+    }
+
+    if appendices.unwrap_or(false) {
+        for character in story.characters(request.clone()).await? {
+            let body = crate::render::text_to_html(character.description.as_deref().unwrap_or(""));
+            book = book.appendix(&character.name, &body);
+        }
+        for location in story.locations(request).await? {
+            let body = crate::render::text_to_html(location.description.as_deref().unwrap_or(""));
+            book = book.appendix(&location.name, &body);
+        }
+    }
+
+    let (top, sub) = EPUB_MIMETYPE.split_once('/').unwrap();
+    Ok((ContentType::new(top, sub), book.build()?))
+}
+
+/// exports the story as a pdf, each fragment starts a new page and is listed in the table of contents.
+#[get("/<id>/export.pdf?<options..>")]
+async fn export_pdf(
+    guard: Guard,
+    id: Uuid,
+    options: PdfOptions,
+    api: &State<ApiClient>,
+    processor: &State<ImageProcessor>,
+    pdf: &State<PdfRenderer>,
+) -> Result<(ContentType, Vec<u8>), ApiError> {
+    let url = format!("/stories/{}", id);
+    let story: StoryRender = api.get_protected(&url, guard.access_token(), None).await?;
+    let request = story.request(api, guard.access_token());
+
+    let mut fragments = story.fragments(request).await?;
+    fragments.sort_by_key(|fragment| fragment.idx);
+
+    let renderer = story.renderer();
+    let cover = match &story.image {
+        Some(url) => processor.load_url(url).await,
+        None => None,
+    };
+    let mut export = PdfExport::new(&story.name)
+        .subtitle(story.description.as_deref())
+        .cover(cover);
+
+    for fragment in fragments.iter() {
+        let mut section =
+            PdfSection::new(&fragment.name).blocks(content_blocks(renderer, &fragment.content));
+        if options.images {
+            for image in fragment.images.iter().flatten() {
+                section = section.image(processor.load_url(&image.url).await);
+            }
+        }
+        export = export.section(section);
+    }
+
+    Ok((ContentType::PDF, pdf.render(export, options).await?))
+}
+
+/// checks the story's fragments in `idx` order, each fragment is its own point in time.
+#[get("/<id>/continuity")]
+async fn story_continuity(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let url = format!("/stories/{}", id);
+    let story: StoryRender = api.get_protected(&url, guard.access_token(), None).await?;
+    let request = story.request(api, guard.access_token());
+    let mut fragments = story.fragments(request).await?;
+    fragments.sort_by_key(|fragment| fragment.idx);
+    let beats = fragments
+        .iter()
+        .enumerate()
+        .map(|(position, fragment)| Beat::new(position as i64, fragment.id, &fragment.name))
+        .collect();
+    let report = continuity::analyze(api, guard.access_token(), beats).await?;
+    Ok(RawHtml(Template::render(
+        "timelines/continuity",
+        context! {
+            title: format!("continuity of {}", story.name),
+            subject: story.name.clone(),
+            back: format!("/stories/{}", story.id),
+            report,
+        },
+    )))
+}
+
+/// the relationship network between the story's characters, with its sub-stories when
+/// `universe` is set.
+#[get("/<id>/network?<sort>&<universe>")]
+async fn story_network(
+    guard: Guard,
+    id: Uuid,
+    sort: Option<Metric>,
+    universe: Option<bool>,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let url = format!("/stories/{}", id);
+    let story: StoryRender = api.get_protected(&url, guard.access_token(), None).await?;
+    let universe = universe.unwrap_or(false);
+    let sort = sort.unwrap_or_default();
+    let network = Network::fetch(api, guard.access_token(), &story, universe).await?;
+    let communities = network.communities();
+    let modularity = network.modularity(&communities);
+    let (svg, _) = render_str(&network.dot(&communities));
+    let metrics: Vec<&str> = Metric::ALL.iter().map(Metric::as_str).collect();
+    Ok(RawHtml(Template::render(
+        "stories/network",
+        context! {
+            title: format!("network of {}", story.name),
+            table: network.table(sort),
+            isolated: network.isolated(5),
+            communities: network.community_members(&communities),
+            modularity,
+            svg,
+            sort: sort.as_str(),
+            metrics,
+            universe,
+            palette: network::PALETTE,
+            story,
+        },
+    )))
+}
+
 #[get("/")]
 async fn list_stories(
     guard: Guard,
@@ -124,6 +321,89 @@ async fn create_story(
     Redirect::to(format!("/stories/{}", result.id))
 }
 
+#[derive(Debug, FromForm)]
+pub struct ImportForm<'r> {
+    /// a zip archive of markdown files.
+    archive: TempFile<'r>,
+    title: String,
+    description: Option<String>,
+    renderer: SupportedRender,
+    tags: Vec<String>,
+    /// when set nothing is created, instead the planned story is shown for confirmation.
+    dry_run: bool,
+}
+
+#[get("/import")]
+async fn import_story_html(api: &State<ApiClient>) -> Result<RawHtml<Template>, ApiError> {
+    let selected: Vec<String> = Vec::new();
+    let options = api.get_top_tags(10, 0).await?;
+    Ok(RawHtml(Template::render(
+        "stories/import",
+        context! { title: "import story", selected, options },
+    )))
+}
+
+#[post("/import", data = "<form>")]
+async fn import_story<'r>(
+    guard: Guard,
+    form: Form<ImportForm<'r>>,
+    api: &State<ApiClient>,
+    cache: &State<ImportCache>,
+) -> Result<Either<Redirect, RawHtml<Template>>, ApiError> {
+    let form = form.into_inner();
+    let mut bytes = Vec::new();
+    form.archive.open().await?.read_to_end(&mut bytes).await?;
+    let files = import::read_zip(&bytes)?;
+
+    let description = form.description.filter(|d| !d.trim().is_empty());
+    let plan = ImportPlan::prepare(
+        api,
+        guard.access_token(),
+        &form.title,
+        description,
+        form.renderer,
+        form.tags,
+        files,
+    )
+    .await?;
+
+    if form.dry_run {
+        let preview = RawHtml(Template::render(
+            "stories/import_preview",
+            context! { title: "import preview", plan: &plan },
+        ));
+        cache.insert(&guard.claims.sub, plan);
+        return Ok(Either::Right(preview));
+    }
+
+    let report = plan.commit(api, guard.access_token()).await?;
+    Ok(import_result(report))
+}
+
+/// commits a plan previously shown by a dry run.
+#[post("/import/<plan>")]
+async fn confirm_import(
+    guard: Guard,
+    plan: Uuid,
+    api: &State<ApiClient>,
+    cache: &State<ImportCache>,
+) -> Result<Either<Redirect, RawHtml<Template>>, ApiError> {
+    let plan = cache.take(&plan, &guard.claims.sub)?;
+    let report = plan.commit(api, guard.access_token()).await?;
+    Ok(import_result(report))
+}
+
+/// the new story, or what was imported when some of the files failed.
+fn import_result(report: ImportReport) -> Either<Redirect, RawHtml<Template>> {
+    if report.failed.is_empty() {
+        return Either::Left(Redirect::to(format!("/stories/{}", report.story)));
+    }
+    Either::Right(RawHtml(Template::render(
+        "stories/import_report",
+        context! { title: "story import", report },
+    )))
+}
+
 pub struct Edit {
     pub id: Uuid,
     pub date: i64,
@@ -157,6 +437,29 @@ impl StoryRender {
     pub fn name(&self) -> &str {
         &self.name
     }
+
+    /// the renderer selected when the story was created, falling back to markdown.
+    pub fn renderer(&self) -> SupportedRender {
+        self.renderer
+            .as_deref()
+            .and_then(|renderer| renderer.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// loads the story image from the image directory for use as a book cover.
+    pub async fn cover(&self, processor: &ImageProcessor) -> Result<Option<EpubCover>, ApiError> {
+        let id = match self.image.as_deref().and_then(image_id) {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let data = match tokio::fs::read(processor.get_image_url(id)).await {
+            Ok(data) => data,
+            // a missing image shouldn't prevent exporting the rest of the story
+            Err(_) => return Ok(None),
+        };
+        Ok(Some(EpubCover::from_bytes(data)?))
+    }
+
     pub async fn notes<'a>(&self, mut request: ApiRequest<'a>) -> Result<Vec<Note>, LazyError> {
         let notes: Vec<Note> = match request.route("/notes").send().await? {
             Some(notes) => notes,
@@ -283,6 +586,13 @@ pub fn get_routes() -> Vec<Route> {
         create_story_html,
         get_story,
         edit_story,
-        delete_story
+        delete_story,
+        export_epub,
+        export_pdf,
+        story_continuity,
+        story_network,
+        import_story_html,
+        import_story,
+        confirm_import
     ]
 }
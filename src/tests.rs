@@ -39,3 +39,2769 @@ macro_rules! generate_post_integration_test {
 }
 
 generate_post_integration_test!(create_fragment_test, "/fragments/", FragmentForm);
+
+#[cfg(test)]
+mod epub {
+    use crate::render::epub::{EPUB_MIMETYPE, EpubBuilder, EpubCover};
+    use crate::render::{Renderer, SupportedRender};
+    use quick_xml::Reader;
+    use quick_xml::events::Event;
+    use std::collections::HashSet;
+    use std::io::{Cursor, Read};
+    use uuid::Uuid;
+    use zip::{CompressionMethod, ZipArchive};
+
+    fn sample_book() -> Vec<u8> {
+        let mut png = Vec::new();
+        image::DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let renderer = SupportedRender::Markdown;
+        EpubBuilder::new(Uuid::new_v4(), "The <Harbor> & the Sea")
+            .description(Some("a story about \"boats\""))
+            .cover(EpubCover::from_bytes(png).unwrap())
+            .chapter("Arrival", &renderer.render("# Arrival\n\nMara *walks* in.<br>\n\n---"))
+            .chapter("Departure", &SupportedRender::Text.render("line one\nline two\n\n<b>not bold</b>"))
+            .appendix("Mara", "<p>a sailor</p>")
+            .build()
+            .unwrap()
+    }
+
+    fn read_entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut contents = String::new();
+        archive
+            .by_name(name)
+            .unwrap_or_else(|_| panic!("missing {}", name))
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    }
+
+    /// parses the document, returning the value of `attr` for every element named `tag`.
+    fn well_formed(name: &str, xml: &str, tag: &[u8], attr: &[u8]) -> Vec<String> {
+        let mut reader = Reader::from_str(xml);
+        let mut values = Vec::new();
+        loop {
+            match reader.read_event() {
+                Ok(Event::Eof) => break,
+                Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.name().as_ref() == tag => {
+                    if let Some(value) = e.try_get_attribute(attr).unwrap() {
+                        values.push(value.unescape_value().unwrap().to_string());
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => panic!("{} is not well formed: {}", name, e),
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn mimetype_is_first_and_stored() {
+        let mut archive = ZipArchive::new(Cursor::new(sample_book())).unwrap();
+        let mut first = archive.by_index(0).unwrap();
+        assert_eq!(first.name(), "mimetype");
+        assert_eq!(first.compression(), CompressionMethod::Stored);
+        assert!(first.extra_data().map(|extra| extra.is_empty()).unwrap_or(true));
+        let mut mimetype = String::new();
+        first.read_to_string(&mut mimetype).unwrap();
+        assert_eq!(mimetype, EPUB_MIMETYPE);
+    }
+
+    #[test]
+    fn package_is_consistent() {
+        let mut archive = ZipArchive::new(Cursor::new(sample_book())).unwrap();
+        let names: HashSet<String> = archive.file_names().map(|name| name.to_string()).collect();
+
+        let container = read_entry(&mut archive, "META-INF/container.xml");
+        let rootfiles = well_formed("container.xml", &container, b"rootfile", b"full-path");
+        assert_eq!(rootfiles, vec!["OEBPS/content.opf".to_string()]);
+
+        let opf = read_entry(&mut archive, "OEBPS/content.opf");
+        let hrefs = well_formed("content.opf", &opf, b"item", b"href");
+        let ids: HashSet<String> = well_formed("content.opf", &opf, b"item", b"id")
+            .into_iter()
+            .collect();
+        let properties = well_formed("content.opf", &opf, b"item", b"properties");
+        assert!(properties.contains(&"nav".to_string()));
+        assert!(properties.contains(&"cover-image".to_string()));
+        assert!(opf.contains("dcterms:modified"));
+        assert!(opf.contains("<dc:identifier id=\"book-id\">urn:uuid:"));
+
+        for href in hrefs.iter() {
+            let path = format!("OEBPS/{}", href);
+            assert!(names.contains(&path), "manifest references missing {}", path);
+        }
+        for name in names.iter().filter(|name| name.starts_with("OEBPS/")) {
+            if name == "OEBPS/content.opf" {
+                continue;
+            }
+            let href = name.trim_start_matches("OEBPS/");
+            assert!(hrefs.iter().any(|h| h == href), "{} missing from manifest", name);
+        }
+
+        let spine = well_formed("content.opf", &opf, b"itemref", b"idref");
+        assert_eq!(spine.len(), 4);
+        for idref in spine.iter() {
+            assert!(ids.contains(idref), "spine references unknown item {}", idref);
+        }
+
+        for name in names.iter().filter(|name| name.ends_with(".xhtml") || name.ends_with(".ncx")) {
+            let document = read_entry(&mut archive, name);
+            well_formed(name, &document, b"html", b"xmlns");
+        }
+
+        let nav = read_entry(&mut archive, "OEBPS/nav.xhtml");
+        let links = well_formed("nav.xhtml", &nav, b"a", b"href");
+        assert_eq!(
+            links,
+            vec!["text/chapter-1.xhtml", "text/chapter-2.xhtml", "text/appendix-1.xhtml"]
+        );
+    }
+
+    #[test]
+    fn html_content_is_escaped() {
+        let mut archive = ZipArchive::new(Cursor::new(sample_book())).unwrap();
+        let chapter = read_entry(&mut archive, "OEBPS/text/chapter-2.xhtml");
+        assert!(chapter.contains("&lt;b&gt;not bold&lt;/b&gt;"));
+        assert!(chapter.contains("line one<br />\nline two"));
+    }
+}
+
+#[cfg(test)]
+mod sanitizer {
+    use crate::render::html::Sanitizer;
+    use crate::render::{Renderer, SupportedRender};
+
+    #[test]
+    fn scripts_and_handlers_are_removed() {
+        let clean = Sanitizer::default().clean(
+            r#"<p onclick="steal()">hi<script>alert(1)</script></p><img src=x onerror=alert(1)><STYLE>p{}</STYLE>"#,
+        );
+        assert_eq!(clean, r#"<p>hi</p><img src="x" />"#);
+    }
+
+    #[test]
+    fn unsafe_urls_are_dropped() {
+        let clean = Sanitizer::default().clean(
+            r#"<a href="java&#x09;script:alert(1)">a</a><a href=" JavaScript:x">b</a><a href="/stories/1">c</a><a href="https://example.com?a=1&amp;b=2">d</a>"#,
+        );
+        assert_eq!(
+            clean,
+            r#"<a>a</a><a>b</a><a href="/stories/1">c</a><a href="https://example.com?a=1&amp;b=2">d</a>"#
+        );
+    }
+
+    #[test]
+    fn output_is_balanced_and_escaped() {
+        let clean = Sanitizer::default().clean("<em>a < b & c<blink>d</blink><br><p>e &nbsp; f");
+        assert_eq!(clean, "<em>a &lt; b &amp; cd<br /><p>e \u{a0} f</p></em>");
+    }
+
+    #[test]
+    fn markdown_inline_html_is_sanitized() {
+        let html = SupportedRender::Markdown.render("*hi* <iframe src=x></iframe>[x](javascript:alert(1))");
+        assert!(html.contains("<em>hi</em>"));
+        assert!(!html.contains("iframe"));
+        assert!(!html.contains("javascript"));
+    }
+}
+
+#[cfg(test)]
+mod continuity {
+    use crate::render::links::LinkTarget;
+    use crate::timelines::continuity::{Beat, ConflictKind, Lifespan, check};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn target(name: &str, category: &str) -> LinkTarget {
+        LinkTarget {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category: category.to_string(),
+            description: None,
+            thumbnail: None,
+        }
+    }
+
+    fn beat(position: i64, characters: &[&LinkTarget], locations: &[&LinkTarget]) -> Beat {
+        let mut beat = Beat::new(position, Uuid::new_v4(), &format!("fragment {}", position));
+        beat.characters = characters.iter().map(|&target| target.clone()).collect();
+        beat.locations = locations.iter().map(|&target| target.clone()).collect();
+        beat
+    }
+
+    #[test]
+    fn appearances_outside_lifespan() {
+        let mara = target("Mara", "characters");
+        let harbor = target("Old Harbor", "locations");
+        let beats = vec![
+            beat(0, &[&mara], &[&harbor]),
+            beat(1, &[], &[&harbor]),
+            beat(2, &[&mara], &[]),
+            beat(3, &[&mara], &[]),
+            beat(4, &[&mara], &[]),
+        ];
+        let lifespans = HashMap::from([
+            (
+                mara.id,
+                Lifespan {
+                    entity: mara.id,
+                    begins: Some(beats[2].fragment.id),
+                    ends: Some(beats[3].fragment.id),
+                },
+            ),
+            (
+                harbor.id,
+                Lifespan {
+                    entity: harbor.id,
+                    begins: Some(beats[1].fragment.id),
+                    ends: None,
+                },
+            ),
+        ]);
+
+        let report = check(&beats, &lifespans);
+        let kinds: Vec<ConflictKind> = report.conflicts.iter().map(|c| c.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ConflictKind::BeforeBirth,
+                ConflictKind::BeforeEstablished,
+                ConflictKind::AfterDeath
+            ]
+        );
+        assert_eq!(report.conflicts[0].against, vec!["fragment 2"]);
+        assert_eq!(report.conflicts[2].fragments[0].id, beats[4].fragment.id);
+    }
+
+    #[test]
+    fn anchors_missing_from_the_run_are_ignored() {
+        let mara = target("Mara", "characters");
+        let beats = vec![beat(0, &[&mara], &[])];
+        let lifespans = HashMap::from([(
+            mara.id,
+            Lifespan {
+                entity: mara.id,
+                begins: Some(Uuid::new_v4()),
+                ends: None,
+            },
+        )]);
+        assert!(check(&beats, &lifespans).conflicts.is_empty());
+    }
+
+    #[test]
+    fn two_places_at_once() {
+        let mara = target("Mara", "characters");
+        let tomas = target("Tomas", "characters");
+        let harbor = target("Old Harbor", "locations");
+        let keep = target("The Keep", "locations");
+        let beats = vec![
+            beat(8, &[&mara, &tomas], &[&harbor]),
+            beat(8, &[&mara], &[&keep]),
+            beat(8, &[&tomas], &[&harbor, &keep]),
+            beat(16, &[&mara], &[&harbor]),
+        ];
+
+        let report = check(&beats, &HashMap::new());
+        assert_eq!(report.conflicts.len(), 1);
+        let conflict = &report.conflicts[0];
+        assert_eq!(conflict.kind, ConflictKind::TwoPlaces);
+        assert_eq!(conflict.entity.id, mara.id);
+        assert_eq!(conflict.against, vec!["Old Harbor", "The Keep"]);
+        assert_eq!(conflict.fragments.len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod fountain {
+    use crate::characters::api::CharacterRender;
+    use crate::render::Renderer;
+    use crate::render::fountain::{DialogueLine, Element, FountainRenderer, parse};
+    use crate::render::links::EntityIndex;
+    use uuid::Uuid;
+
+    const SCRIPT: &str = "Title: The Gull\nAuthor: M. Voss\n\nINT. LIGHTHOUSE - NIGHT #4#\n\nRain hammers the glass.\n\nMARA (V.O.)\n(quietly)\nThe lamp is out.\n\nTOMAS ^\nThen light it.\n\nCUT TO:\n\n> THE END <\n";
+
+    #[test]
+    fn parses_elements() {
+        let elements = parse(SCRIPT);
+        assert_eq!(
+            elements[0],
+            Element::TitlePage(vec![
+                ("Title".to_string(), "The Gull".to_string()),
+                ("Author".to_string(), "M. Voss".to_string())
+            ])
+        );
+        assert_eq!(
+            elements[1],
+            Element::SceneHeading {
+                text: "INT. LIGHTHOUSE - NIGHT".to_string(),
+                number: Some("4".to_string())
+            }
+        );
+        assert_eq!(elements[2], Element::Action("Rain hammers the glass.".to_string()));
+        assert_eq!(
+            elements[3],
+            Element::Dialogue {
+                character: "MARA".to_string(),
+                extension: Some("(V.O.)".to_string()),
+                dual: false,
+                lines: vec![
+                    DialogueLine::Parenthetical("(quietly)".to_string()),
+                    DialogueLine::Speech("The lamp is out.".to_string())
+                ]
+            }
+        );
+        assert!(matches!(&elements[4], Element::Dialogue { dual: true, .. }));
+        assert_eq!(elements[5], Element::Transition("CUT TO:".to_string()));
+        assert_eq!(elements[6], Element::Centered("THE END".to_string()));
+    }
+
+    #[test]
+    fn uppercase_action_without_dialogue_is_not_a_cue() {
+        let elements = parse("She runs.\n\nBOOM.\n\nSilence.");
+        assert!(elements.iter().all(|element| matches!(element, Element::Action(_))));
+    }
+
+    #[test]
+    fn renders_and_links_cues() {
+        let mara = CharacterRender {
+            id: Uuid::new_v4(),
+            name: "Mara".to_string(),
+            description: None,
+            thumbnail: None,
+            tags: None,
+            images: None,
+        };
+        let index = EntityIndex::new(std::slice::from_ref(&mara), &[]);
+        let html = FountainRenderer::new().index(&index).render(SCRIPT);
+        assert!(html.contains(&format!(
+            "<p class=\"character\"><a href=\"/characters/{}\">MARA</a> <span class=\"extension\">(V.O.)</span></p>",
+            mara.id
+        )));
+        assert!(html.contains("<p class=\"character\">TOMAS</p>"));
+        assert!(html.contains("<div class=\"dual-dialogue\">"));
+        assert!(html.contains("<p class=\"transition\">CUT TO:</p>"));
+    }
+
+    #[test]
+    fn emphasis_is_escaped() {
+        let html = FountainRenderer::new().render("!He *whispers* **<script>** \\*not\\*");
+        assert!(html.contains("<em>whispers</em> <strong>&lt;script&gt;</strong> *not*"));
+    }
+}
+
+#[cfg(test)]
+mod calendars {
+    use crate::calendars::api::{
+        Calendar, CalendarDate, CalendarDefinition, Era, LeapRule, Month, Moon,
+    };
+    use uuid::Uuid;
+
+    fn date(year: i64, month: u32, day: u32) -> CalendarDate {
+        CalendarDate { year, month, day }
+    }
+
+    fn reckoning() -> Calendar {
+        Calendar {
+            id: Uuid::new_v4(),
+            name: "Harbor Reckoning".to_string(),
+            description: None,
+            definition: CalendarDefinition {
+                months: vec![
+                    Month { name: "Frostmoon".to_string(), days: 10, leap_days: 0 },
+                    Month { name: "Thaw".to_string(), days: 10, leap_days: 1 },
+                ],
+                weekdays: vec!["Oneday".to_string(), "Twoday".to_string(), "Threeday".to_string()],
+                eras: vec![Era {
+                    name: "After Landing".to_string(),
+                    abbreviation: "AL".to_string(),
+                    start: 1,
+                    reverse: false,
+                }],
+                leap: LeapRule { every: Some(4), except: Some(10), unless: Some(20) },
+                moons: vec![Moon { name: "Gull".to_string(), cycle: 7.5, offset: 0.0 }],
+                epoch: 100,
+                weekday_offset: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn gregorian_days() {
+        let gregorian = CalendarDefinition::gregorian();
+        assert_eq!(gregorian.to_day(date(1970, 1, 1)).unwrap(), 0);
+        assert_eq!(gregorian.to_day(date(2000, 2, 29)).unwrap(), 11_016);
+        assert_eq!(gregorian.to_day(date(1, 1, 1)).unwrap(), -719_162);
+        assert_eq!(gregorian.from_day(-719_163), date(0, 12, 31));
+        assert!(gregorian.to_day(date(1900, 2, 29)).is_err());
+        assert_eq!(gregorian.format(0), "Thursday, 1 January 1970 CE");
+        assert_eq!(gregorian.format(19_723), "Monday, 1 January 2024 CE");
+        assert_eq!(gregorian.format(-719_163), "Sunday, 31 December 1 BCE");
+        assert_eq!(gregorian.format_epoch(90_061), "Friday, 2 January 1970 CE 01:01");
+        assert_eq!(gregorian.moon_phases(6)[0].phase, "new");
+    }
+
+    #[test]
+    fn leap_years_follow_the_rule() {
+        let rule = reckoning().definition.leap;
+        let leaps = (-100..100).filter(|&year| rule.is_leap(year)).count();
+        let definition = reckoning().definition;
+        let span = definition.to_day(date(100, 1, 1)).unwrap()
+            - definition.to_day(date(-100, 1, 1)).unwrap();
+        assert_eq!(span, 200 * 20 + leaps as i64);
+        assert!(rule.is_leap(20) && !rule.is_leap(10) && rule.is_leap(4));
+    }
+
+    #[test]
+    fn custom_round_trip() {
+        let definition = reckoning().definition;
+        for day in -2_000..2_000 {
+            let converted = definition.from_day(day);
+            assert_eq!(definition.to_day(converted).unwrap(), day, "{:?}", converted);
+        }
+        assert_eq!(definition.from_day(100), date(1, 1, 1));
+        assert_eq!(definition.format(100), "Twoday, 1 Frostmoon 1 AL");
+    }
+
+    #[test]
+    fn converts_between_calendars() {
+        let gregorian = Calendar::gregorian();
+        let converted = gregorian.convert(date(1970, 4, 11), &reckoning()).unwrap();
+        assert_eq!(converted, date(1, 1, 1));
+        assert_eq!(reckoning().convert(converted, &gregorian).unwrap(), date(1970, 4, 11));
+    }
+
+    #[test]
+    fn huge_divisors_and_years_dont_overflow() {
+        let mut definition = reckoning().definition;
+        definition.leap = LeapRule { every: Some(u32::MAX), except: Some(u32::MAX - 1), unless: Some(u32::MAX - 2) };
+        assert!(definition.validate().is_err());
+        // calendars saved before the limits still convert dates.
+        let day = definition.to_day(date(2, 1, 1)).unwrap();
+        assert_eq!(definition.from_day(day), date(2, 1, 1));
+        assert_eq!(definition.from_day(i64::MAX).year, definition.from_day(i64::MAX - 1).year);
+        assert!(definition.from_day(i64::MIN).year < 0);
+
+        let gregorian = CalendarDefinition::gregorian();
+        assert!(gregorian.to_day(date(i64::MAX, 1, 1)).is_err());
+        assert!(gregorian.to_day(date(i64::MIN, 1, 1)).is_err());
+        let far = gregorian.to_day(date(1_000_000_000, 12, 31)).unwrap();
+        assert_eq!(gregorian.from_day(far), date(1_000_000_000, 12, 31));
+
+        definition = reckoning().definition;
+        definition.epoch = i64::MAX;
+        assert!(definition.validate().is_err());
+    }
+}
+
+#[cfg(test)]
+mod lanes {
+    use crate::calendars::api::{CalendarDefinition, MAX_DAY};
+    use crate::timelines::continuity::FragmentRef;
+    use crate::timelines::lanes::{self, Axis, Chart, Lane, Span, Zoom};
+    use uuid::Uuid;
+
+    fn span(name: &str, idx: i64, day: Option<i64>, length: f64, subplot: Option<&str>) -> Span {
+        Span {
+            moment: Uuid::new_v4(),
+            fragment: FragmentRef {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+            },
+            idx,
+            start: day.unwrap_or_default() as f64,
+            length,
+            dated: day.is_some(),
+            subplot: subplot.map(str::to_string),
+            date: None,
+            overlaps: false,
+        }
+    }
+
+    #[test]
+    fn undated_moments_fall_between_dated_ones() {
+        let mut spans = vec![
+            span("arrival", 0, Some(100), 1.0, None),
+            span("first night", 8, None, 1.0, None),
+            span("second night", 16, None, 1.0, None),
+            span("storm", 16, None, 1.0, None),
+            span("departure", 24, Some(110), 2.0, None),
+        ];
+        assert_eq!(lanes::place(&mut spans), Axis::Days);
+        assert_eq!(spans[1].start, 101.0 + 3.0);
+        assert_eq!(spans[2].start, 101.0 + 6.0);
+        assert_eq!(spans[3].start, spans[2].start);
+
+        let mut undated = vec![span("a", 0, None, 1.0, None), span("b", 8, None, 1.0, None)];
+        assert_eq!(lanes::place(&mut undated), Axis::Order);
+        assert_eq!(undated[1].start, 1.0);
+    }
+
+    #[test]
+    fn overlapping_spans_stack() {
+        let spans = vec![
+            span("siege", 0, Some(0), 10.0, Some("war")),
+            span("parley", 8, Some(3), 1.0, Some("war")),
+            span("retreat", 16, Some(10), 2.0, Some("war")),
+            span("wedding", 24, Some(4), 1.0, None),
+        ];
+        let lanes = lanes::by_subplot(&spans);
+        assert_eq!(lanes.len(), 2);
+        assert_eq!(lanes[0].name, "war");
+        assert_eq!(lanes[0].rows.len(), 2);
+        assert_eq!(lanes[0].rows[0].len(), 2);
+        assert!(lanes[0].rows[1][0].overlaps);
+        assert!(!lanes[0].rows[0][1].overlaps);
+        assert_eq!(lanes[1].name, "unassigned");
+    }
+
+    #[test]
+    fn ticks_follow_the_zoom_level() {
+        let gregorian = CalendarDefinition::gregorian();
+        // 1970 through 1979.
+        let years = lanes::ticks(&gregorian, Zoom::Years, 0, 3_652);
+        assert_eq!(years.len(), 10);
+        assert_eq!(years[1].label, "1971 CE");
+        assert_eq!(years[1].zoom, Some((Zoom::Months, 365, 730)));
+        let months = lanes::ticks(&gregorian, Zoom::Months, 0, 365);
+        assert_eq!(months.len(), 12);
+        assert_eq!(months[1].label, "February 1970 CE");
+        assert_eq!(Zoom::fit(&gregorian, 40.0), Zoom::Days);
+    }
+
+    #[test]
+    fn chart_escapes_names() {
+        let spans = vec![span("<duel>", 0, Some(0), 1.0, Some("Ada & Grace"))];
+        let chart = Chart::new(
+            Axis::Days,
+            lanes::by_subplot(&spans),
+            &CalendarDefinition::gregorian(),
+            None,
+            None,
+        );
+        let svg = chart.svg("/timelines/x?lanes=subplots");
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Ada &amp; Grace"));
+        assert!(svg.contains("&lt;duel&gt;"));
+    }
+
+    #[test]
+    fn windows_out_of_range_dont_panic() {
+        let spans = vec![span("duel", 0, Some(0), 1.0, None)];
+        let gregorian = CalendarDefinition::gregorian();
+        let everything = Chart::new(
+            Axis::Days,
+            lanes::by_subplot(&spans),
+            &gregorian,
+            None,
+            Some((i64::MIN, i64::MAX)),
+        );
+        assert_eq!((everything.from, everything.to), (0.0, 1.0));
+        let edges = [
+            (i64::MAX - 10, i64::MAX),
+            (i64::MIN, i64::MIN + 10),
+            (MAX_DAY - 10, MAX_DAY),
+            (-MAX_DAY, 10 - MAX_DAY),
+        ];
+        for window in edges {
+            for zoom in [None, Some(Zoom::Days), Some(Zoom::Years), Some(Zoom::Eras)] {
+                Chart::new(
+                    Axis::Days,
+                    lanes::by_subplot(&spans),
+                    &gregorian,
+                    zoom,
+                    Some(window),
+                )
+                .svg("/timelines/x");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod branches {
+    use crate::timelines::api::Timeline;
+    use crate::timelines::branches;
+    use crate::timelines::continuity::Beat;
+    use crate::render::links::LinkTarget;
+    use serde_json::{Value, json};
+    use uuid::Uuid;
+
+    fn moment(id: Uuid, name: &str, idx: i64, branch: Option<Uuid>) -> Value {
+        json!({
+            "id": id,
+            "timeline": Uuid::nil(),
+            "fragment": {
+                "id": Uuid::new_v4(),
+                "name": name,
+                "metadata": Uuid::nil(),
+                "idx": 0,
+                "content": "",
+                "image": null,
+                "last_edited": 0,
+                "created": 0,
+            },
+            "idx": idx,
+            "branch": branch,
+        })
+    }
+
+    /// a trunk of three moments, a branch forking after the second and one forking off that
+    /// branch after its first own moment.
+    fn timeline() -> (Timeline, Uuid, Uuid) {
+        let (fork, nested_fork) = (Uuid::new_v4(), Uuid::new_v4());
+        let (heist, double_cross) = (Uuid::new_v4(), Uuid::new_v4());
+        let value = json!({
+            "id": Uuid::new_v4(),
+            "name": "The Job",
+            "description": null,
+            "created": 0,
+            "moments": [
+                moment(Uuid::new_v4(), "planning", 0, None),
+                moment(fork, "the vault", 8, None),
+                moment(Uuid::new_v4(), "getaway", 16, None),
+                moment(nested_fork, "alarm", 16, Some(heist)),
+                moment(Uuid::new_v4(), "shootout", 24, Some(heist)),
+                moment(Uuid::new_v4(), "betrayal", 24, Some(double_cross)),
+            ],
+            "branches": [
+                { "id": heist, "timeline": Uuid::nil(), "name": "caught", "parent": null, "fork": fork },
+                { "id": double_cross, "timeline": Uuid::nil(), "name": "double cross", "parent": heist, "fork": nested_fork },
+            ],
+        });
+        (serde_json::from_value(value).unwrap(), heist, double_cross)
+    }
+
+    fn names(timeline: &Timeline) -> Vec<String> {
+        timeline
+            .beats()
+            .into_iter()
+            .map(|beat| beat.fragment.name)
+            .collect()
+    }
+
+    #[test]
+    fn branches_share_history_up_to_the_fork() {
+        let (timeline, heist, double_cross) = timeline();
+        let trunk = timeline.on_branch(None).unwrap();
+        assert_eq!(names(&trunk), ["planning", "the vault", "getaway"]);
+        let caught = timeline.on_branch(Some(heist)).unwrap();
+        assert_eq!(names(&caught), ["planning", "the vault", "alarm", "shootout"]);
+        let nested = timeline.on_branch(Some(double_cross)).unwrap();
+        assert_eq!(names(&nested), ["planning", "the vault", "alarm", "betrayal"]);
+        assert!(timeline.on_branch(Some(Uuid::new_v4())).is_none());
+
+        let tree = timeline.tree();
+        assert_eq!(tree.moments, 3);
+        assert_eq!(tree.children[0].fork.as_deref(), Some("the vault"));
+        assert_eq!(tree.children[0].children[0].name, "double cross");
+    }
+
+    #[test]
+    fn comparison_marks_where_things_exist() {
+        let (timeline, ..) = timeline();
+        let columns = branches::columns(&timeline.tree());
+        assert_eq!(columns.len(), 3);
+        let sequences: Vec<_> = columns
+            .into_iter()
+            .map(|column| {
+                let fragments = timeline
+                    .on_branch(column.id)
+                    .unwrap()
+                    .beats()
+                    .into_iter()
+                    .map(|beat| beat.fragment)
+                    .collect();
+                (column, fragments)
+            })
+            .collect();
+        let mut beats: Vec<Beat> = timeline.beats();
+        let informant = LinkTarget {
+            id: Uuid::new_v4(),
+            name: "Informant".to_string(),
+            category: "characters".to_string(),
+            description: None,
+            thumbnail: None,
+        };
+        beats[5].characters.push(informant);
+
+        let comparison = branches::compare(&sequences, &beats);
+        let vault = comparison
+            .fragments
+            .iter()
+            .find(|row| row.name == "the vault")
+            .unwrap();
+        assert!(vault.shared);
+        let getaway = comparison
+            .fragments
+            .iter()
+            .find(|row| row.name == "getaway")
+            .unwrap();
+        assert_eq!(getaway.present, [true, false, false]);
+        assert_eq!(comparison.characters.len(), 1);
+        assert_eq!(comparison.characters[0].present, [false, false, true]);
+    }
+}
+
+#[cfg(test)]
+mod editing {
+    use crate::timelines::editing::{self, MomentOrder, Move};
+    use uuid::Uuid;
+
+    fn ids(order: &[MomentOrder]) -> Vec<(usize, i64)> {
+        order.iter().map(|entry| (entry.id.as_u128() as usize, entry.position)).collect()
+    }
+
+    /// moments 1 to 4, where 2 and 3 happen at the same time.
+    fn order() -> Vec<MomentOrder> {
+        editing::positions([
+            (Uuid::from_u128(1), 0),
+            (Uuid::from_u128(2), 8),
+            (Uuid::from_u128(3), 8),
+            (Uuid::from_u128(4), 16),
+        ])
+    }
+
+    #[test]
+    fn simultaneous_moments_share_a_position() {
+        assert_eq!(ids(&order()), [(1, 0), (2, 1), (3, 1), (4, 2)]);
+    }
+
+    #[test]
+    fn moves_renumber_positions() {
+        let up = editing::apply(&order(), Uuid::from_u128(4), Move::Up);
+        assert_eq!(ids(&up), [(1, 0), (4, 1), (2, 2), (3, 2)]);
+        // leaving a group puts the moment in a position of its own.
+        let down = editing::apply(&order(), Uuid::from_u128(2), Move::Down);
+        assert_eq!(ids(&down), [(1, 0), (3, 1), (2, 2), (4, 3)]);
+        let first = editing::apply(&order(), Uuid::from_u128(1), Move::Up);
+        assert_eq!(ids(&first), ids(&order()));
+        let to = editing::apply(&order(), Uuid::from_u128(1), Move::To(10));
+        assert_eq!(ids(&to), [(2, 0), (3, 0), (4, 1), (1, 2)]);
+        let unknown = editing::apply(&order(), Uuid::from_u128(9), Move::Down);
+        assert_eq!(ids(&unknown), ids(&order()));
+    }
+}
+
+#[cfg(test)]
+mod ical {
+    use crate::timelines::ical::{self, Event};
+
+    fn event() -> Event {
+        Event {
+            uid: "1@storyteller".to_string(),
+            summary: "Arrival, at last; the harbour".to_string(),
+            description: Some(
+                "first line\nsecond line with a long tail that runs well past the \
+                seventy five octet limit of a content line, ünïcode included"
+                    .to_string(),
+            ),
+            url: Some("http://localhost:8000/fragments/1".to_string()),
+            categories: vec!["journey".to_string()],
+            // 2024-02-28 to 2024-03-01, across a leap day.
+            start: 19_781,
+            until: 19_783,
+            stamp: Some(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn written_events_parse_back() {
+        let written = ical::write("travels", &[event()]);
+        assert!(written.contains("DTSTART;VALUE=DATE:20240228\r\n"));
+        assert!(written.contains("DTEND;VALUE=DATE:20240302\r\n"));
+        assert!(written.contains("DTSTAMP:20231114T221320Z\r\n"));
+        assert!(written.lines().all(|line| line.len() <= 75));
+        assert_eq!(ical::parse(&written).unwrap(), [event()]);
+    }
+
+    #[test]
+    fn times_are_dropped_and_alarms_ignored() {
+        let source = "BEGIN:VCALENDAR\n\
+            BEGIN:VEVENT\n\
+            SUMMARY:Dinner\n\
+            DTSTART;TZID=\"Europe/Paris\":20240105T190000\n\
+            DTEND;TZID=\"Europe/Paris\":20240105T220000\n\
+            BEGIN:VALARM\n\
+            DESCRIPTION:reminder\n\
+            END:VALARM\n\
+            END:VEVENT\n\
+            BEGIN:VEVENT\n\
+            SUMMARY:Hik\n \
+             ing\n\
+            DTSTART;VALUE=DATE:20240101\n\
+            DURATION:P1W\n\
+            END:VEVENT\n\
+            BEGIN:VEVENT\n\
+            SUMMARY:Overnight\n\
+            DTSTART:20240103T220000Z\n\
+            DTEND:20240104T000000Z\n\
+            END:VEVENT\n\
+            BEGIN:VEVENT\n\
+            SUMMARY:never happened\n\
+            END:VEVENT\n\
+            END:VCALENDAR\n";
+        let events = ical::parse(source).unwrap();
+        let days: Vec<(&str, i64, i64)> = events
+            .iter()
+            .map(|event| (event.summary.as_str(), event.start, event.until))
+            .collect();
+        assert_eq!(
+            days,
+            [
+                ("Hiking", 19_723, 19_729),
+                ("Overnight", 19_725, 19_725),
+                ("Dinner", 19_727, 19_727),
+            ]
+        );
+        assert_eq!(events[2].description, None);
+    }
+
+    #[test]
+    fn calendars_without_events_are_rejected() {
+        assert!(ical::parse("BEGIN:VCALENDAR\nEND:VCALENDAR\n").is_err());
+        assert!(ical::parse("BEGIN:VEVENT\nDTSTART:2024-01-01\nEND:VEVENT\n").is_err());
+    }
+}
+
+#[cfg(test)]
+mod photos {
+    use crate::assets::images::ExifTag;
+    use crate::locations::Coordinates;
+    use crate::timelines::photos::{self, Grouping, MAX_PHOTO_SIZE, Photo, PhotoSource, ZipSource};
+    use std::io::{Cursor, Write};
+    use uuid::Uuid;
+    use zip::write::SimpleFileOptions;
+
+    const LISBON: Coordinates = Coordinates {
+        latitude: 38.7223,
+        longitude: -9.1393,
+    };
+    const PORTO: Coordinates = Coordinates {
+        latitude: 41.1579,
+        longitude: -8.6291,
+    };
+
+    fn photo(name: &str, taken: Option<&str>, position: Option<Coordinates>) -> Photo {
+        Photo {
+            id: name.to_string(),
+            name: name.to_string(),
+            taken: taken.and_then(photos::wall_clock),
+            position,
+            place: None,
+        }
+    }
+
+    #[test]
+    fn times_and_positions_are_read_from_exif() {
+        assert_eq!(photos::wall_clock("2024:01:01 00:00:10"), Some(19_723 * 86_400 + 10));
+        assert_eq!(
+            photos::wall_clock("2024-01-01T00:00:10+08:00"),
+            photos::wall_clock("2024-01-01 00:00:10")
+        );
+        assert_eq!(photos::wall_clock("0000:00:00 00:00:00"), None);
+
+        let tags = [
+            ExifTag::new(0x0001, "R98".to_string()),
+            ExifTag::new(0x0001, "S".to_string()),
+            ExifTag::new(
+                0x0002,
+                "URationalArray[33/1 (33.0000), 51/1 (51.0000), 3600/100 (36.0000)]".to_string(),
+            ),
+            ExifTag::new(0x0003, "E".to_string()),
+            ExifTag::new(0x0004, "151.2093".to_string()),
+            ExifTag::new(0x9003, "2024-01-01 09:30:00".to_string()),
+        ];
+        let photo = Photo::from_exif("beach.jpg", "beach.jpg", &tags);
+        assert_eq!(photo.taken, Some(19_723 * 86_400 + 9 * 3_600 + 30 * 60));
+        let position = photo.position.unwrap();
+        assert!((position.latitude + 33.86).abs() < 1e-9);
+        assert!((position.longitude - 151.2093).abs() < 1e-9);
+
+        // a reference without its coordinate leaves the photo unplaced.
+        let photo = Photo::from_exif("a.jpg", "a.jpg", &tags[..2]);
+        assert_eq!(photo.position, None);
+    }
+
+    #[test]
+    fn photos_split_into_events_by_time_and_distance() {
+        let events = photos::group(
+            vec![
+                photo("undated.jpg", None, Some(LISBON)),
+                photo("tram.jpg", Some("2024:07:09 14:00:00"), None),
+                photo("castle.jpg", Some("2024:07:09 10:00:00"), Some(LISBON)),
+                photo("train.jpg", Some("2024:07:09 18:00:00"), Some(PORTO)),
+                photo("bridge.jpg", Some("2024:07:10 09:00:00"), Some(PORTO)),
+            ],
+            Grouping::default(),
+        );
+        let names: Vec<Vec<&str>> = events
+            .iter()
+            .map(|event| event.photos.iter().map(|photo| photo.name.as_str()).collect())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                vec!["castle.jpg", "tram.jpg"],
+                vec!["train.jpg"],
+                vec!["bridge.jpg"],
+                vec!["undated.jpg"],
+            ]
+        );
+        assert_eq!(events[0].name(), "Photos, 2024-07-09");
+        assert_eq!(events[0].position(), Some(LISBON));
+        assert_eq!(events[3].name(), "Undated photos");
+    }
+
+    #[test]
+    fn events_use_the_nearest_location_in_range() {
+        assert!((LISBON.distance(&PORTO) - 274.0).abs() < 2.0);
+        let (lisbon, porto) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let locations = [(lisbon, LISBON), (porto, PORTO)];
+        let nearby = Coordinates {
+            latitude: 38.725,
+            longitude: -9.15,
+        };
+        assert_eq!(photos::nearest(&locations, nearby, 2.0), Some(lisbon));
+        assert_eq!(photos::nearest(&locations, nearby, 0.5), None);
+    }
+
+    #[test]
+    fn oversized_photos_in_archives_are_rejected() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored =
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("small.jpg", stored).unwrap();
+        writer.write_all(b"small").unwrap();
+        writer.start_file("large.jpg", stored).unwrap();
+        writer
+            .write_all(&vec![0; MAX_PHOTO_SIZE as usize + 1])
+            .unwrap();
+        let source = ZipSource::new(writer.finish().unwrap().into_inner());
+
+        let read = |path: &str| {
+            let photo = photo(path, None, None);
+            rocket::async_test(source.read(&photo))
+        };
+        assert_eq!(read("small.jpg").unwrap(), b"small");
+        assert!(read("large.jpg").is_err());
+    }
+}
+
+#[cfg(test)]
+mod kinship {
+    use crate::calendars::api::Calendar;
+    use crate::characters::gedcom::{self, Gedcom};
+    use crate::characters::kinship::{Family, FamilyMember, Kinship, KinshipKind, Role};
+    use uuid::Uuid;
+
+    fn member(id: u128, name: &str, gender: &str) -> FamilyMember {
+        FamilyMember {
+            id: Uuid::from_u128(id),
+            name: name.to_string(),
+            description: None,
+            gender: gender.to_string(),
+        }
+    }
+
+    fn kin(character: u128, relative: u128, kind: KinshipKind) -> Kinship {
+        Kinship {
+            id: Uuid::new_v4(),
+            character: Uuid::from_u128(character),
+            relative: Uuid::from_u128(relative),
+            kind,
+            begins: None,
+            ends: None,
+        }
+    }
+
+    /// two grandparents, their son, his wife who married into the family, and their two
+    /// children, one of them adopted.
+    fn family() -> Family {
+        let mut wedding = kin(3, 4, KinshipKind::Spouse);
+        wedding.begins = Some(-3_653);
+        wedding.ends = Some(0);
+        Family {
+            members: vec![
+                member(5, "Ada", "female"),
+                member(1, "Grandfather", "male"),
+                member(2, "Grandmother", "female"),
+                member(3, "Father", "male"),
+                member(4, "Mother", "female"),
+                member(6, "Ben", "male"),
+            ],
+            kinship: vec![
+                kin(1, 2, KinshipKind::Spouse),
+                kin(1, 3, KinshipKind::Parent),
+                kin(2, 3, KinshipKind::Parent),
+                wedding,
+                kin(3, 5, KinshipKind::Parent),
+                kin(4, 5, KinshipKind::Parent),
+                kin(3, 6, KinshipKind::AdoptiveParent),
+                kin(4, 6, KinshipKind::AdoptiveParent),
+                kin(5, 6, KinshipKind::Sibling),
+            ],
+        }
+    }
+
+    #[test]
+    fn generations_put_children_below_parents() {
+        let family = family();
+        let rows = family.rows(Uuid::from_u128(5));
+        let ids = |row: &[u128]| {
+            row.iter()
+                .map(|id| Uuid::from_u128(*id))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rows, [ids(&[1, 2]), ids(&[3, 4]), ids(&[5, 6])]);
+
+        let ada = Uuid::from_u128(5);
+        let roles: Vec<(Role, &str)> = family
+            .relatives(ada, &Calendar::gregorian())
+            .iter()
+            .map(|relative| (relative.role, relative.member.name.as_str()))
+            .collect();
+        assert_eq!(
+            roles,
+            [
+                (Role::Parent, "Father"),
+                (Role::Parent, "Mother"),
+                (Role::Sibling, "Ben"),
+            ]
+        );
+        let father = family.relatives(Uuid::from_u128(3), &Calendar::gregorian());
+        assert!(
+            father
+                .iter()
+                .any(|relative| relative.role == Role::AdoptedChild)
+        );
+        assert!(
+            family
+                .svg(ada, &Calendar::gregorian())
+                .contains("Grandmother")
+        );
+    }
+
+    #[test]
+    fn gedcom_reads_families_pedigrees_and_dates() {
+        let source = concat!(
+            "0 HEAD\r\n1 GEDC\r\n2 VERS 5.5.1\r\n",
+            "0 @I1@ INDI\r\n1 NAME John /Smith/\r\n1 SEX M\r\n1 BIRT\r\n2 DATE ABT 1820\r\n2 PLAC York\r\n",
+            "0 @I2@ INDI\r\n1 NAME Mary /Jones/\r\n1 SEX F\r\n1 NOTE kept a diary,\r\n2 CONC  every day\r\n",
+            "0 @I3@ INDI\r\n1 NAME Tom /Smith/\r\n1 FAMC @F1@\r\n2 PEDI adopted\r\n",
+            "0 @I4@ INDI\r\n1 NAME Ann\r\n",
+            "0 @F1@ FAM\r\n1 HUSB @I1@\r\n1 WIFE @I2@\r\n1 MARR\r\n2 DATE 12 JUN 1850\r\n1 CHIL @I3@\r\n",
+            "0 @F2@ FAM\r\n1 WIFE @I2@\r\n1 CHIL @I4@\r\n2 _MREL Step\r\n",
+            "0 TRLR\r\n",
+        );
+        let gedcom = Gedcom::parse(source).unwrap();
+        let names: Vec<&str> = gedcom
+            .individuals
+            .iter()
+            .map(|person| person.name.as_str())
+            .collect();
+        assert_eq!(names, ["John Smith", "Mary Jones", "Tom Smith", "Ann"]);
+        assert_eq!(gedcom.individuals[0].gender.as_deref(), Some("male"));
+        assert_eq!(
+            gedcom.individuals[0].description.as_deref(),
+            Some("Born ABT 1820 in York.")
+        );
+        assert_eq!(
+            gedcom.individuals[1].description.as_deref(),
+            Some("kept a diary, every day")
+        );
+
+        let links: Vec<(&str, &str, KinshipKind)> = gedcom
+            .links
+            .iter()
+            .map(|link| (link.character.as_str(), link.relative.as_str(), link.kind))
+            .collect();
+        assert_eq!(
+            links,
+            [
+                ("@I1@", "@I2@", KinshipKind::Spouse),
+                ("@I1@", "@I3@", KinshipKind::AdoptiveParent),
+                ("@I2@", "@I3@", KinshipKind::AdoptiveParent),
+                ("@I2@", "@I4@", KinshipKind::StepParent),
+            ]
+        );
+        assert_eq!(gedcom.links[0].begins, gedcom::parse_date("12 JUN 1850"));
+        assert_eq!(gedcom::parse_date("BET JAN 1970 AND 1980"), Some(0));
+        assert_eq!(gedcom::parse_date("31 DEC 1 B.C."), Some(-719_163));
+        assert_eq!(gedcom::format_date(-719_163), "31 DEC 1 B.C.");
+        assert!(Gedcom::parse("0 HEAD\n2 VERS 5.5.1\n").is_err());
+    }
+
+    #[test]
+    fn gedcom_export_reads_back_the_same_kinship() {
+        let family = family();
+        let exported = gedcom::write(&family);
+        assert!(exported.starts_with("0 HEAD\n"));
+        assert!(exported.ends_with("0 TRLR\n"));
+        let gedcom = Gedcom::parse(&exported).unwrap();
+
+        let name = |xref: &str| {
+            let person = gedcom.individuals.iter().find(|person| person.xref == xref);
+            person.unwrap().name.clone()
+        };
+        let mut links: Vec<(String, String, &str)> = gedcom
+            .links
+            .iter()
+            .map(|link| {
+                (
+                    name(&link.character),
+                    name(&link.relative),
+                    link.kind.as_str(),
+                )
+            })
+            .collect();
+        links.sort();
+        let mut expected: Vec<(String, String, &str)> = family
+            .kinship
+            .iter()
+            // siblings sharing parents are only siblings through them in a GEDCOM file.
+            .filter(|kinship| kinship.kind != KinshipKind::Sibling)
+            .map(|kinship| {
+                let name = |id: Uuid| family.member(id).unwrap().name.clone();
+                (
+                    name(kinship.character),
+                    name(kinship.relative),
+                    kinship.kind.as_str(),
+                )
+            })
+            .collect();
+        expected.sort();
+        assert_eq!(links, expected);
+
+        let wedding = gedcom
+            .links
+            .iter()
+            .find(|link| name(&link.character) == "Father" && link.kind == KinshipKind::Spouse)
+            .unwrap();
+        assert_eq!((wedding.begins, wedding.ends), (Some(-3_653), Some(0)));
+        assert_eq!(gedcom.individuals[0].gender.as_deref(), Some("female"));
+    }
+
+    #[test]
+    fn gedcom_symmetric_links_are_read_once() {
+        let source = concat!(
+            "0 HEAD\n1 GEDC\n2 VERS 5.5.1\n",
+            "0 @I1@ INDI\n1 NAME Mara\n1 SEX U\n",
+            "0 @I2@ INDI\n1 NAME Tomas\n",
+            "0 @I3@ INDI\n1 NAME Ada\n",
+            "0 @F1@ FAM\n1 HUSB @I1@\n1 WIFE @I2@\n1 MARR\n",
+            "0 @F2@ FAM\n1 HUSB @I2@\n1 WIFE @I1@\n1 MARR\n",
+            "0 @F3@ FAM\n1 CHIL @I2@\n1 CHIL @I3@\n",
+            "0 @F4@ FAM\n1 CHIL @I3@\n1 CHIL @I2@\n",
+            "0 TRLR\n",
+        );
+        let gedcom = Gedcom::parse(source).unwrap();
+        let kinds: Vec<KinshipKind> = gedcom.links.iter().map(|link| link.kind).collect();
+        assert_eq!(kinds, [KinshipKind::Spouse, KinshipKind::Sibling]);
+        // unknown sexes are left for the import to send as unknown.
+        assert_eq!(gedcom.individuals[0].gender, None);
+    }
+}
+
+#[cfg(test)]
+mod sheets {
+    use crate::characters::sheets::{Dice, Formula, SchemaDefinition, SheetValue, TEMPLATES};
+    use std::collections::HashMap;
+
+    fn form(values: &[(&str, &str)]) -> HashMap<String, String> {
+        values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn templates_are_valid() {
+        for template in TEMPLATES.iter() {
+            let definition = SchemaDefinition::parse(template.attributes).unwrap();
+            definition.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn computes_modifiers_and_validates_values() {
+        let definition = SchemaDefinition::parse(TEMPLATES[0].attributes).unwrap();
+        let values = definition
+            .values(&form(&[
+                ("class", "Wizard"),
+                ("level", "5"),
+                ("strength", "8"),
+                ("dexterity", "15"),
+                ("wisdom", "12"),
+                ("hit_dice", "5d6 + con_mod"),
+                ("equipment", "spellbook\n\n quarterstaff "),
+                ("str_mod", "99"),
+            ]))
+            .unwrap();
+        assert_eq!(values.get("str_mod"), None);
+        assert_eq!(
+            values.get("equipment"),
+            Some(&SheetValue::List(vec![
+                "spellbook".to_string(),
+                "quarterstaff".to_string()
+            ]))
+        );
+        let numbers = definition.numbers(&values);
+        assert_eq!(numbers.get("str_mod"), Some(&-1.0));
+        assert_eq!(numbers.get("initiative"), Some(&2.0));
+        assert_eq!(numbers.get("proficiency"), Some(&3.0));
+        assert_eq!(numbers.get("passive_perception"), Some(&11.0));
+        // constitution was left empty so nothing depending on it can be worked out
+        assert_eq!(numbers.get("con_mod"), None);
+
+        let fields: Vec<_> = definition
+            .sections(&values)
+            .into_iter()
+            .flat_map(|section| section.fields)
+            .collect();
+        let display = |key: &str| {
+            fields
+                .iter()
+                .find(|field| field.key == key)
+                .unwrap()
+                .display
+                .clone()
+        };
+        assert_eq!(display("str_mod").as_deref(), Some("-1"));
+        assert_eq!(display("dex_mod").as_deref(), Some("+2"));
+        assert_eq!(display("hit_dice").as_deref(), Some("5d6 (5–30)"));
+
+        assert!(definition.values(&form(&[("strength", "31")])).is_err());
+        assert!(definition.values(&form(&[("class", "Pirate")])).is_err());
+        assert!(
+            definition
+                .values(&form(&[("hit_dice", "2d6 + race")]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_broken_schemas() {
+        let cycle =
+            SchemaDefinition::parse("a, A, computed, b + 1\nb, B, computed, a * 2").unwrap();
+        assert!(cycle.validate().is_err());
+        let text =
+            SchemaDefinition::parse("name, Name, text\nbonus, Bonus, computed, name + 1").unwrap();
+        assert!(text.validate().is_err());
+        let duplicate = SchemaDefinition::parse("a, A, number\na, Again, number").unwrap();
+        assert!(duplicate.validate().is_err());
+        assert!(SchemaDefinition::parse("a, A, spell").is_err());
+        assert!(Formula::parse("floor(1, 2)").is_err());
+        assert!(Formula::parse("sqrt(4)").is_err());
+        assert!(Formula::parse("(1 + 2").is_err());
+    }
+
+    #[test]
+    fn parses_dice() {
+        let numbers: HashMap<String, f64> = [("dex_mod".to_string(), 3.0)].into_iter().collect();
+        let dice = Dice::parse("2D6+1d4 - 1 + dex_mod").unwrap();
+        assert_eq!(dice.to_string(), "2d6 + 1d4 - 1 + dex_mod");
+        assert_eq!(dice.resolve(&numbers), "2d6 + 1d4 + 2");
+        assert_eq!(dice.range(&numbers), (5, 18));
+        assert_eq!(Dice::parse("d%").unwrap().range(&numbers), (1, 100));
+        assert!(Dice::parse("2d").is_err());
+        assert!(Dice::parse("2d6 +").is_err());
+        assert!(Dice::parse("").is_err());
+    }
+
+    #[test]
+    fn rejects_formulas_nested_too_deeply_or_too_long() {
+        let lookup = |_: &str| None;
+        let nested = format!("{}1{}", "(".repeat(20), ")".repeat(20));
+        assert_eq!(Formula::parse(&nested).unwrap().eval(&lookup), Some(1.0));
+        assert!(
+            Formula::parse(&format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000))).is_err()
+        );
+        assert!(Formula::parse(&format!("{}1", "-".repeat(100_000))).is_err());
+        assert!(Formula::parse(&format!("{}1)", "abs(".repeat(100))).is_err());
+        assert!(Formula::parse(&vec!["1"; 100_000].join(" + ")).is_err());
+    }
+
+    #[test]
+    fn dice_totals_saturate_instead_of_overflowing() {
+        let numbers: HashMap<String, f64> = [("huge".to_string(), 1e300)].into_iter().collect();
+        assert!(Dice::parse("1d100000").is_err());
+        assert!(Dice::parse("1001d6").is_err());
+        assert_eq!(
+            Dice::parse("1000d1000").unwrap().range(&numbers),
+            (1_000, 1_000_000)
+        );
+        let flat = Dice::parse("1d6 - 9223372036854775807 - 9223372036854775807").unwrap();
+        assert_eq!(flat.range(&numbers).0, i64::MIN);
+        assert_eq!(
+            flat.resolve(&numbers),
+            format!("1d6 - {}", i64::MIN.unsigned_abs())
+        );
+        let attribute = Dice::parse("-huge - huge").unwrap();
+        assert_eq!(attribute.resolve(&numbers), i64::MIN.to_string());
+        assert_eq!(attribute.range(&numbers), (i64::MIN, i64::MIN));
+    }
+}
+#[cfg(test)]
+mod arcs {
+    use crate::characters::arcs::{ArcState, CharacterArc, parse_custom};
+    use crate::timelines::continuity::FragmentRef;
+    use std::collections::HashSet;
+    use uuid::Uuid;
+
+    fn fragment(id: u128) -> FragmentRef {
+        FragmentRef {
+            id: Uuid::from_u128(id),
+            name: format!("chapter {}", id),
+        }
+    }
+
+    fn state(fragment: u128, emotion: &str, valence: i16) -> ArcState {
+        ArcState {
+            fragment: Uuid::from_u128(fragment),
+            goals: Some("find the map".to_string()),
+            emotion: Some(emotion.to_string()),
+            valence: Some(valence),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn walks_fragments_and_flags_missing_annotations() {
+        let fragments = (1..=4).map(fragment).collect();
+        let appearances: HashSet<Uuid> = [1, 2, 4].into_iter().map(Uuid::from_u128).collect();
+        let mut later = state(3, "hopeful", 2);
+        later
+            .custom
+            .insert("secret".to_string(), "knows the way".to_string());
+        // an annotation of a fragment from another story is left out
+        let states = vec![later, state(1, "afraid", -3), state(9, "calm", 0)];
+        let arc = CharacterArc::new(fragments, &appearances, states);
+
+        assert_eq!(arc.beats.len(), 4);
+        let positions: Vec<usize> = arc.beats.iter().map(|beat| beat.position).collect();
+        assert_eq!(positions, vec![1, 2, 3, 4]);
+        assert!(arc.beats[0].changes.is_empty());
+        assert_eq!(arc.beats[2].changes, vec!["emotion", "valence", "secret"]);
+        assert_eq!(arc.beats[2].custom, "secret: knows the way");
+        // fragment 3 is annotated without the character being related to it
+        assert!(!arc.beats[2].appears);
+        let missing: Vec<Uuid> = arc.missing.iter().map(|fragment| fragment.id).collect();
+        assert_eq!(missing, vec![Uuid::from_u128(2), Uuid::from_u128(4)]);
+
+        let svg = arc.svg();
+        assert!(svg.contains("<polyline class=\"arc\""));
+        assert_eq!(svg.matches("class=\"missing\"").count(), 2);
+    }
+
+    #[test]
+    fn parses_custom_fields() {
+        let custom = parse_custom("loyalty: the crown\n\n wound : lost a brother").unwrap();
+        assert_eq!(custom.get("loyalty").map(String::as_str), Some("the crown"));
+        assert_eq!(
+            custom.get("wound").map(String::as_str),
+            Some("lost a brother")
+        );
+        assert!(parse_custom("no separator").is_err());
+    }
+}
+#[cfg(test)]
+mod network {
+    use crate::characters::network::{Member, Metric, Network};
+    use uuid::Uuid;
+
+    fn member(id: u128, name: &str) -> Member {
+        Member {
+            id: Uuid::from_u128(id),
+            name: name.to_string(),
+            appearances: 0,
+        }
+    }
+
+    /// two groups of three sharing three fragments each, bridged by a relation between the
+    /// third and fourth, and a seventh character who's in no fragment at all.
+    fn network() -> Network {
+        let names = ["Ada", "Bram", "Cleo", "Dov", "Esme", "Finn", "Gus"];
+        let members = names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| member(idx as u128 + 1, name))
+            .collect();
+        let ids = |range: std::ops::RangeInclusive<u128>| {
+            range.map(Uuid::from_u128).collect::<Vec<Uuid>>()
+        };
+        let scenes = vec![
+            ids(1..=3),
+            ids(1..=3),
+            ids(1..=3),
+            ids(4..=6),
+            ids(4..=6),
+            ids(4..=6),
+        ];
+        let relations = vec![
+            (Uuid::from_u128(3), Uuid::from_u128(4)),
+            (Uuid::from_u128(4), Uuid::from_u128(3)),
+        ];
+        Network::new(members, &scenes, &relations)
+    }
+
+    #[test]
+    fn weighs_ties_and_ranks_bridges() {
+        let network = network();
+        let tie = network.tie(Uuid::from_u128(1), Uuid::from_u128(2)).unwrap();
+        assert_eq!(tie.coappearances, 3);
+        assert!(!tie.related);
+        let bridge = network.tie(Uuid::from_u128(4), Uuid::from_u128(3)).unwrap();
+        assert!(bridge.related);
+        assert_eq!(bridge.weight(), 2.0);
+        assert!(
+            network
+                .tie(Uuid::from_u128(1), Uuid::from_u128(4))
+                .is_none()
+        );
+
+        let table = network.table(Metric::Betweenness);
+        assert_eq!(table[0].name, "Cleo");
+        assert_eq!(table[1].name, "Dov");
+        assert!((table[0].betweenness - 0.4).abs() < 1e-9);
+        assert_eq!(table[0].degree, 3);
+        assert_eq!(table[0].appearances, 3);
+        assert_eq!(network.table(Metric::Name)[0].name, "Ada");
+
+        let isolated = network.isolated(2);
+        assert_eq!(isolated[0].name, "Gus");
+        assert_eq!(isolated[0].degree, 0);
+    }
+
+    #[test]
+    fn finds_communities() {
+        let network = network();
+        let communities = network.communities();
+        assert_eq!(communities, vec![0, 0, 0, 1, 1, 1, 2]);
+        assert!(network.modularity(&communities) > 0.3);
+        let groups = network.community_members(&communities);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[2].members[0].name, "Gus");
+
+        let dot = network.dot(&communities);
+        assert!(dot.starts_with("graph {"));
+        assert!(dot.contains("class=\"character community-1\""));
+        assert!(dot.contains(&format!("URL=\"/characters/{}\"", Uuid::from_u128(7))));
+        assert!(dot.contains("style=bold"));
+    }
+}
+#[cfg(test)]
+mod merge {
+    use crate::calendars::api::Calendar;
+    use crate::characters::kinship::{Kinship, KinshipKind};
+    use crate::characters::merge::{
+        Footprint, Link, MergePreview, combine_descriptions, likely_duplicate,
+    };
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn footprint(id: u128, name: &str, description: &str) -> Footprint {
+        Footprint {
+            id: Uuid::from_u128(id),
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            gender: "female".to_string(),
+            thumbnail: None,
+            links: Vec::new(),
+            tags: Vec::new(),
+            kinship: Vec::new(),
+            sheets: Vec::new(),
+            arcs: Vec::new(),
+            lifespan: None,
+        }
+    }
+
+    fn link(entity: u128, category: &str, description: &str) -> Link {
+        Link {
+            entity: Uuid::from_u128(entity),
+            name: Some(format!("{} {}", category, entity)),
+            category: category.to_string(),
+            description: Some(description.to_string()),
+            parent: false,
+        }
+    }
+
+    #[test]
+    fn combines_descriptions_without_repeating_them() {
+        assert_eq!(
+            combine_descriptions(Some("a sailor"), Some("a sailor from the north")),
+            Some("a sailor from the north".to_string())
+        );
+        assert_eq!(
+            combine_descriptions(Some("a sailor "), Some("  ")),
+            Some("a sailor".to_string())
+        );
+        assert_eq!(
+            combine_descriptions(Some("a sailor"), Some("afraid of the dark")),
+            Some("a sailor\n\nafraid of the dark".to_string())
+        );
+        assert_eq!(combine_descriptions(None, None), None);
+        assert!(likely_duplicate("Anne-Marie  Bonny", "anne marie bonny"));
+        assert!(!likely_duplicate("Anne Bonny", "Mary Read"));
+    }
+
+    #[test]
+    fn previews_conflicts_and_what_moves() {
+        let mut survivor = footprint(1, "Anne Bonny", "a pirate");
+        let mut merged = footprint(2, "Anne Bonny", "sails with Calico Jack");
+        merged.gender = "unknown".to_string();
+        let schema = Uuid::from_u128(50);
+        survivor.sheets.push(schema);
+        merged.sheets.push(schema);
+        survivor.links.push(link(10, "stories", "captain"));
+        merged.links.push(link(10, "stories", "first mate"));
+        merged.links.push(link(11, "fragments", "appears"));
+        merged.links.push(link(1, "characters", "the same person"));
+        merged.tags.push("pirate".to_string());
+        merged.kinship.push(Kinship {
+            id: Uuid::from_u128(20),
+            character: merged.id,
+            relative: survivor.id,
+            kind: KinshipKind::Sibling,
+            begins: None,
+            ends: None,
+        });
+        let names = HashMap::from([(schema, "Adventurer".to_string())]);
+
+        let preview = MergePreview::new(survivor, merged, &names, &Calendar::gregorian());
+        let fields: Vec<&str> = preview
+            .conflicts
+            .iter()
+            .map(|conflict| conflict.field.as_str())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "description",
+                "gender",
+                "relation to stories 10",
+                "relation between Anne Bonny and Anne Bonny",
+                "sibling kinship between the two",
+                "Adventurer sheet",
+            ]
+        );
+        assert_eq!(
+            preview.description.as_deref(),
+            Some("a pirate\n\nsails with Calico Jack")
+        );
+        let moved: Vec<(&str, usize)> = preview
+            .moved
+            .iter()
+            .map(|cascade| (cascade.label.as_str(), cascade.count))
+            .collect();
+        assert_eq!(
+            moved,
+            [
+                ("links to characters", 1),
+                ("links to fragments", 1),
+                ("links to stories", 1),
+                ("tags", 1),
+                ("kinship", 1),
+                ("sheets", 1),
+            ]
+        );
+    }
+}
+#[cfg(test)]
+mod maps {
+    use crate::locations::Coordinates;
+    use crate::maps::api::{
+        Bounds, FeatureKind, MapFeature, MapFeatureBuilder, MapRender, Point, Projection,
+        parse_points,
+    };
+    use uuid::Uuid;
+
+    fn map(projection: Projection, bounds: Option<Bounds>) -> MapRender {
+        MapRender {
+            id: Uuid::from_u128(1),
+            name: "Europe".to_string(),
+            description: None,
+            image: None,
+            width: 1000,
+            height: 500,
+            projection,
+            scale: None,
+            unit: None,
+            bounds,
+            location: None,
+            features: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parses_points_like_svg() {
+        assert_eq!(
+            parse_points("10,20 30.5,40\n 0,0").unwrap(),
+            [
+                Point { x: 10.0, y: 20.0 },
+                Point { x: 30.5, y: 40.0 },
+                Point { x: 0.0, y: 0.0 },
+            ]
+        );
+        assert!(parse_points("").unwrap().is_empty());
+        assert!(parse_points("10 20").is_err());
+        assert!(parse_points("10,north").is_err());
+    }
+
+    #[test]
+    fn projects_coordinates_onto_geographic_maps() {
+        let bounds = Bounds {
+            north: 60.0,
+            south: 40.0,
+            east: 20.0,
+            west: -20.0,
+        };
+        let europe = map(Projection::Geographic, Some(bounds));
+        let paris = Coordinates {
+            latitude: 48.86,
+            longitude: 2.35,
+        };
+        let point = europe.project(&paris).unwrap();
+        assert!((point.x - 558.75).abs() < 0.01);
+        assert!((point.y - 278.5).abs() < 0.01);
+        let back = europe.coordinates(&point).unwrap();
+        assert!((back.latitude - paris.latitude).abs() < 1e-9);
+        assert!((back.longitude - paris.longitude).abs() < 1e-9);
+
+        let tokyo = Coordinates {
+            latitude: 35.68,
+            longitude: 139.69,
+        };
+        assert_eq!(europe.project(&tokyo), None);
+        assert_eq!(map(Projection::Pixels, Some(bounds)).project(&paris), None);
+
+        let (scale, unit) = europe.scale().unwrap();
+        assert_eq!(unit, "km");
+        assert!(scale > 2.7 && scale < 3.0);
+        let bar = europe.scale_bar().unwrap();
+        assert_eq!(bar.distance, 500.0);
+        assert!(bar.length < 200.0);
+    }
+
+    #[test]
+    fn checks_features_and_links_them_in_the_svg() {
+        let mut island = map(Projection::Pixels, None);
+        island.scale = Some(0.5);
+        island.unit = Some("miles".to_string());
+        let location = Uuid::from_u128(7);
+        let pin = |points: Vec<Point>, color: Option<&str>| MapFeatureBuilder {
+            location,
+            kind: FeatureKind::Pin,
+            points,
+            label: None,
+            color: color.map(str::to_string),
+        };
+        assert!(
+            pin(vec![Point { x: 10.0, y: 10.0 }], Some("#c0392b"))
+                .validate(&island)
+                .is_ok()
+        );
+        assert!(pin(vec![], None).validate(&island).is_err());
+        assert!(
+            pin(vec![Point { x: 1200.0, y: 10.0 }], None)
+                .validate(&island)
+                .is_err()
+        );
+        assert!(
+            pin(vec![Point { x: 10.0, y: 10.0 }], Some("red\" onclick=\"x"))
+                .validate(&island)
+                .is_err()
+        );
+
+        island.features.push(MapFeature {
+            id: Uuid::from_u128(8),
+            location,
+            location_name: "Skull & Rock".to_string(),
+            kind: FeatureKind::Polygon,
+            points: vec![
+                Point { x: 0.0, y: 0.0 },
+                Point { x: 30.0, y: 0.0 },
+                Point { x: 0.0, y: 30.0 },
+            ],
+            label: None,
+            color: None,
+        });
+        let svg = island.svg("/assets/images/island");
+        assert!(svg.contains(&format!(r#"<a href="/locations/{}">"#, location)));
+        assert!(svg.contains(r#"points="0.0,0.0 30.0,0.0 0.0,30.0""#));
+        assert!(svg.contains("Skull &amp; Rock"));
+        assert!(svg.contains("100 miles"));
+    }
+}
+
+#[cfg(test)]
+mod geojson {
+    use crate::locations::geojson::{FeatureCollection, Geometry};
+    use crate::locations::{Coordinates, LocationRender, Placement, Suggestion};
+    use crate::model::Image;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn location(id: u128, name: &str, at: Option<(f64, f64)>) -> LocationRender {
+        serde_json::from_value(json!({
+            "id": Uuid::from_u128(id),
+            "name": name,
+            "description": "a harbour town",
+            "images": null,
+            "thumbnail": null,
+            "tags": [{ "id": Uuid::from_u128(99), "entity": null, "value": "coast" }],
+            "created": 0,
+            "latitude": at.map(|(latitude, _)| latitude),
+            "longitude": at.map(|(_, longitude)| longitude),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn locations_round_trip_through_a_feature_collection() {
+        let locations = [
+            location(1, "Bergen", Some((60.39, 5.32))),
+            location(2, "Atlantis", None),
+        ];
+        let collection = FeatureCollection::from_locations(&locations);
+        let exported = serde_json::to_value(&collection).unwrap();
+        assert_eq!(exported["type"], "FeatureCollection");
+        assert_eq!(exported["features"][0]["type"], "Feature");
+        assert_eq!(
+            exported["features"][0]["geometry"],
+            json!({ "type": "Point", "coordinates": [5.32, 60.39] })
+        );
+        assert_eq!(
+            exported["features"][0]["properties"]["tags"],
+            json!(["coast"])
+        );
+        assert!(exported["features"][1]["geometry"].is_null());
+
+        let parsed = FeatureCollection::parse(&exported.to_string()).unwrap();
+        assert_eq!(parsed, collection);
+        let feature = &parsed.features[0];
+        assert_eq!(feature.location(), Some(Uuid::from_u128(1)));
+        let placement = feature.placement();
+        assert_eq!(
+            (placement.latitude, placement.longitude),
+            (Some(60.39), Some(5.32))
+        );
+        // a point is only coordinates, there's no shape to keep.
+        assert_eq!(placement.geometry, None);
+        assert!(feature.to_builder().is_ok());
+
+        let unnamed = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "geometry": null, "properties": {"title": " "}}
+        ]}"#;
+        assert!(
+            FeatureCollection::parse(unnamed).unwrap().features[0]
+                .to_builder()
+                .is_err()
+        );
+        assert!(FeatureCollection::parse(r#"{"type": "Feature"}"#).is_err());
+    }
+
+    #[test]
+    fn shapes_are_checked_and_placed_at_their_centre() {
+        let square = r#"{"type": "Polygon", "coordinates": [[[10, 50], [12, 50], [12, 52], [10, 52], [10, 50]]]}"#;
+        let placement = Placement::from_form(None, None, Some(square)).unwrap();
+        let centre = Coordinates {
+            latitude: placement.latitude.unwrap(),
+            longitude: placement.longitude.unwrap(),
+        };
+        assert!(
+            centre.distance(&Coordinates {
+                latitude: 51.0,
+                longitude: 11.0
+            }) < 1.0
+        );
+        assert!(matches!(placement.geometry, Some(Geometry::Polygon { .. })));
+
+        // given coordinates win over the centre of the shape.
+        let placement = Placement::from_form(Some(50.5), Some(10.5), Some(square)).unwrap();
+        assert_eq!(placement.latitude, Some(50.5));
+
+        let open =
+            r#"{"type": "Polygon", "coordinates": [[[10, 50], [12, 50], [12, 52], [10, 52]]]}"#;
+        assert!(Placement::from_form(None, None, Some(open)).is_err());
+        let off_earth = r#"{"type": "Point", "coordinates": [200, 10]}"#;
+        assert!(Placement::from_form(None, None, Some(off_earth)).is_err());
+        assert!(Placement::from_form(Some(10.0), None, None).is_err());
+        assert_eq!(
+            Placement::from_form(None, None, Some("  ")).unwrap(),
+            Placement::default()
+        );
+    }
+
+    #[test]
+    fn nearby_locations_and_photo_suggestions_use_distance() {
+        let bergen = location(1, "Bergen", Some((60.39, 5.32)));
+        let others = [
+            bergen.clone(),
+            location(2, "Oslo", Some((59.91, 10.75))),
+            location(3, "Fjell", Some((60.33, 5.08))),
+            location(4, "Atlantis", None),
+        ];
+        let nearby = bergen.nearby(&others, 50.0);
+        let names: Vec<&str> = nearby
+            .iter()
+            .map(|near| near.location.name.as_str())
+            .collect();
+        assert_eq!(names, ["Fjell"]);
+        assert_eq!(bergen.nearby(&others, 500.0).len(), 2);
+
+        // photos either side of the antimeridian are taken in the middle, not across the globe.
+        let centre = Coordinates::centroid(&[
+            Coordinates {
+                latitude: -17.0,
+                longitude: 179.9,
+            },
+            Coordinates {
+                latitude: -17.0,
+                longitude: -179.9,
+            },
+        ])
+        .unwrap();
+        assert!(centre.longitude.abs() > 179.0);
+        assert_eq!(Coordinates::centroid(&[]), None);
+
+        let image: Image = serde_json::from_value(json!({
+            "id": Uuid::from_u128(7),
+            "url": format!("/assets/images/{}", Uuid::from_u128(7)),
+            "description": null,
+        }))
+        .unwrap();
+        let taken = Coordinates {
+            latitude: 60.3901,
+            longitude: 5.3202,
+        };
+        let photos = vec![(image, taken)];
+        assert!(Suggestion::new(bergen.coordinates(), photos.clone()).is_none());
+        let suggestion = Suggestion::new(None, photos.clone()).unwrap();
+        assert!(suggestion.coordinates.distance(&taken) < 0.001);
+        assert_eq!(suggestion.distance, None);
+        let far = Coordinates {
+            latitude: 59.91,
+            longitude: 10.75,
+        };
+        let moved = Suggestion::new(Some(far), photos).unwrap();
+        assert!(moved.distance.unwrap() > 300.0);
+    }
+}
+
+#[cfg(test)]
+mod hierarchy {
+    use crate::locations::LocationRender;
+    use crate::locations::hierarchy::{Hierarchy, Setting};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn location(id: u128, name: &str, parent: Option<u128>) -> LocationRender {
+        serde_json::from_value(json!({
+            "id": Uuid::from_u128(id),
+            "name": name,
+            "description": null,
+            "images": null,
+            "thumbnail": null,
+            "tags": null,
+            "created": 0,
+            "parent": parent.map(Uuid::from_u128),
+        }))
+        .unwrap()
+    }
+
+    fn names(locations: &[&LocationRender]) -> Vec<String> {
+        locations
+            .iter()
+            .map(|location| location.name.clone())
+            .collect()
+    }
+
+    fn city() -> Vec<LocationRender> {
+        vec![
+            location(4, "The Drowned Rat", Some(2)),
+            location(1, "Capital", None),
+            location(2, "Harbour District", Some(1)),
+            location(3, "Castle", Some(1)),
+            // its parent isn't visible, so it's at the top level.
+            location(5, "Outpost", Some(99)),
+        ]
+    }
+
+    #[test]
+    fn locations_nest_inside_one_another() {
+        let locations = city();
+        let hierarchy = Hierarchy::new(&locations);
+        let tavern = Uuid::from_u128(4);
+        assert_eq!(
+            names(&hierarchy.ancestors(tavern)),
+            ["Capital", "Harbour District"]
+        );
+        assert_eq!(
+            names(&hierarchy.descendants(Uuid::from_u128(1))),
+            ["Castle", "Harbour District", "The Drowned Rat"]
+        );
+        // the capital can't move inside its own districts.
+        assert_eq!(
+            names(&hierarchy.candidates(Uuid::from_u128(1))),
+            ["Outpost"]
+        );
+
+        let tree: Vec<(String, usize, usize)> = hierarchy
+            .tree()
+            .into_iter()
+            .map(|branch| (branch.location.name, branch.depth, branch.children))
+            .collect();
+        assert_eq!(
+            tree,
+            [
+                ("Capital".to_string(), 0, 2),
+                ("Castle".to_string(), 1, 0),
+                ("Harbour District".to_string(), 1, 1),
+                ("The Drowned Rat".to_string(), 2, 0),
+                ("Outpost".to_string(), 0, 0),
+            ]
+        );
+
+        // a cycle doesn't hang the walk or lose its locations.
+        let looped = vec![location(1, "Here", Some(2)), location(2, "There", Some(1))];
+        let hierarchy = Hierarchy::new(&looped);
+        assert_eq!(names(&hierarchy.ancestors(Uuid::from_u128(1))), ["There"]);
+        assert_eq!(hierarchy.tree().len(), 2);
+    }
+
+    #[test]
+    fn graphs_draw_containment_as_nested_clusters() {
+        let locations = city();
+        let hierarchy = Hierarchy::new(&locations);
+        let setting = |location: u128, name: &str| Setting {
+            id: Uuid::from_u128(100),
+            name: "The \"Brawl\"".to_string(),
+            location: Uuid::from_u128(location),
+            location_name: name.to_string(),
+        };
+        // the same fragment related to the city and the tavern is drawn in the tavern.
+        let settings = [setting(1, "Capital"), setting(4, "The Drowned Rat")];
+        let dot = hierarchy.dot(None, &settings);
+        let capital = dot
+            .find(&format!("subgraph \"cluster_{}\"", Uuid::from_u128(1)))
+            .unwrap();
+        let district = dot
+            .find(&format!("subgraph \"cluster_{}\"", Uuid::from_u128(2)))
+            .unwrap();
+        let tavern = dot
+            .find(&format!("subgraph \"cluster_{}\"", Uuid::from_u128(4)))
+            .unwrap();
+        assert!(capital < district && district < tavern);
+        assert_eq!(dot.matches("The \\\"Brawl\\\"").count(), 1);
+        // locations with nothing inside them are plain nodes.
+        assert!(!dot.contains(&format!("cluster_{}", Uuid::from_u128(3))));
+        assert!(dot.contains(&format!("\"{}\" [label=\"Castle\"", Uuid::from_u128(3))));
+        assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+
+        let district = hierarchy.dot(Some(Uuid::from_u128(2)), &settings[1..]);
+        assert!(!district.contains("Capital"));
+        assert!(district.contains("The Drowned Rat"));
+    }
+}
+
+#[cfg(test)]
+mod location_edit {
+    use crate::characters::merge::{Link, cascade_links};
+    use crate::locations::primary_image;
+    use uuid::Uuid;
+
+    fn ids(ids: &[u128]) -> Vec<Uuid> {
+        ids.iter().copied().map(Uuid::from_u128).collect()
+    }
+
+    #[test]
+    fn primary_image_keeps_the_picked_or_current_image() {
+        let images = ids(&[1, 2, 3]);
+        let picked = Some(Uuid::from_u128(2));
+        let current = Some(Uuid::from_u128(1));
+        assert_eq!(primary_image(picked, current, &images, &[], &[]), picked);
+        // an image picked and removed at once is removed.
+        assert_eq!(
+            primary_image(picked, current, &images, &ids(&[2]), &[]),
+            current
+        );
+        // an id that was never the location's isn't used.
+        assert_eq!(
+            primary_image(Some(Uuid::from_u128(9)), current, &images, &[], &[]),
+            current
+        );
+    }
+
+    #[test]
+    fn primary_image_falls_back_to_uploads_then_what_is_left() {
+        let images = ids(&[1, 2, 3]);
+        let current = Some(Uuid::from_u128(1));
+        let removed = ids(&[1]);
+        assert_eq!(
+            primary_image(None, current, &images, &removed, &ids(&[7, 8])),
+            Some(Uuid::from_u128(7))
+        );
+        assert_eq!(
+            primary_image(None, current, &images, &removed, &[]),
+            Some(Uuid::from_u128(2))
+        );
+        assert_eq!(primary_image(None, current, &images, &images, &[]), None);
+    }
+
+    #[test]
+    fn cascade_groups_links_by_category() {
+        let link = |id: u128, name: Option<&str>, category: &str| Link {
+            entity: Uuid::from_u128(id),
+            name: name.map(str::to_string),
+            category: category.to_string(),
+            description: None,
+            parent: false,
+        };
+        let links = [
+            link(1, Some("The Heist"), "fragments"),
+            link(2, Some("Arrival"), "fragments"),
+            link(3, None, "images"),
+            link(2, Some("Arrival"), "fragments"),
+        ];
+        let cascade: Vec<(String, usize, Vec<String>)> = cascade_links(&links)
+            .into_iter()
+            .map(|cascade| (cascade.label, cascade.count, cascade.names))
+            .collect();
+        assert_eq!(
+            cascade,
+            [
+                (
+                    "links to fragments".to_string(),
+                    3,
+                    vec!["Arrival".to_string(), "The Heist".to_string()]
+                ),
+                ("links to images".to_string(), 1, Vec::new()),
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod relation_types {
+    use crate::relations::{EdgeLabel, EdgeStyle, RelationType, TypedRelation};
+    use uuid::Uuid;
+
+    fn relation_type(name: &str, inverse: Option<&str>, directed: bool) -> RelationType {
+        RelationType {
+            id: Uuid::from_u128(100),
+            name: name.to_string(),
+            inverse: inverse.map(str::to_string),
+            directed,
+            style: EdgeStyle::Dashed,
+            creator: None,
+        }
+    }
+
+    fn relation(relation_type: Option<RelationType>) -> TypedRelation {
+        TypedRelation {
+            parent: Uuid::from_u128(1),
+            child: Uuid::from_u128(2),
+            parent_category: "characters".to_string(),
+            child_category: "stories".to_string(),
+            description: Some("since the first chapter".to_string()),
+            relation_type,
+        }
+    }
+
+    #[test]
+    fn directed_types_read_their_inverse_backwards() {
+        let appears = relation(Some(relation_type("appears in", Some("features"), true)));
+        let relations = [appears.clone()];
+        let found = TypedRelation::between(&relations, Uuid::from_u128(2), Uuid::from_u128(1));
+        assert_eq!(found, Some(&appears));
+
+        let forward = EdgeLabel::new(found, Uuid::from_u128(1), "stories");
+        assert_eq!(forward.text.as_deref(), Some("appears in"));
+        assert_eq!(forward.class, "relation stories dashed");
+        let backward = EdgeLabel::new(found, Uuid::from_u128(2), "characters");
+        assert_eq!(backward.text.as_deref(), Some("features"));
+
+        let owns = relation_type("owns", None, true);
+        assert_eq!(owns.reading(false), "\u{2190} owns");
+    }
+
+    #[test]
+    fn undirected_types_read_the_same_both_ways() {
+        let rivals = relation(Some(relation_type("rival of", None, false)));
+        let backward = EdgeLabel::new(Some(&rivals), Uuid::from_u128(2), "characters");
+        assert_eq!(backward.text.as_deref(), Some("rival of"));
+        assert_eq!(backward.class, "relation characters dashed undirected");
+    }
+
+    #[test]
+    fn untyped_relations_keep_their_description_as_a_tooltip() {
+        let untyped = relation(None);
+        let label = EdgeLabel::new(Some(&untyped), Uuid::from_u128(1), "stories");
+        assert_eq!(
+            label,
+            EdgeLabel {
+                text: None,
+                class: "relation stories".to_string(),
+                tooltip: Some("since the first chapter".to_string()),
+            }
+        );
+        assert_eq!(
+            EdgeLabel::new(None, Uuid::from_u128(1), "stories").tooltip,
+            None
+        );
+    }
+}
+
+#[cfg(test)]
+mod relation_paths {
+    use crate::relations::paths::{Endpoint, RelationGraph};
+    use crate::relations::{EdgeStyle, RelationType, TypedRelation};
+    use uuid::Uuid;
+
+    fn entity(id: u128, name: &str, category: &str) -> Endpoint {
+        Endpoint {
+            id: Uuid::from_u128(id),
+            name: name.to_string(),
+            category: category.to_string(),
+        }
+    }
+
+    fn relation(parent: u128, child: u128, relation_type: Option<(&str, &str)>) -> TypedRelation {
+        TypedRelation {
+            parent: Uuid::from_u128(parent),
+            child: Uuid::from_u128(child),
+            parent_category: String::new(),
+            child_category: String::new(),
+            description: None,
+            relation_type: relation_type.map(|(name, inverse)| RelationType {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+                inverse: Some(inverse.to_string()),
+                directed: true,
+                style: EdgeStyle::Solid,
+                creator: None,
+            }),
+        }
+    }
+
+    /// Anne appears in the heist, which is set in the harbour where Jack lives. Anne and Jack
+    /// are also both in the crew, and Mary is related to no one visible.
+    fn graph() -> RelationGraph {
+        RelationGraph::new(
+            vec![
+                entity(1, "Anne Bonny", "characters"),
+                entity(2, "The Heist", "fragments"),
+                entity(3, "Port Royal", "locations"),
+                entity(4, "Calico Jack", "characters"),
+                entity(5, "The Crew", "stories"),
+                entity(6, "Mary Read", "characters"),
+            ],
+            vec![
+                relation(1, 2, Some(("appears in", "features"))),
+                relation(2, 3, Some(("located in", "location of"))),
+                relation(4, 3, Some(("located in", "location of"))),
+                relation(5, 1, None),
+                relation(5, 4, None),
+                // a relation to an entity the reader can't see.
+                relation(6, 99, None),
+            ],
+        )
+    }
+
+    #[test]
+    fn shortest_paths_read_as_chains() {
+        let graph = graph();
+        let shortest = graph.shortest(Uuid::from_u128(1), Uuid::from_u128(4));
+        let chains: Vec<&str> = shortest.iter().map(|path| path.chain.as_str()).collect();
+        assert_eq!(
+            chains,
+            ["Anne Bonny \u{2014}linked to\u{2192} The Crew \u{2014}linked to\u{2192} Calico Jack"]
+        );
+
+        let reversed = graph.shortest(Uuid::from_u128(3), Uuid::from_u128(1));
+        assert_eq!(
+            reversed[0].chain,
+            "Port Royal \u{2014}location of\u{2192} The Heist \u{2014}features\u{2192} Anne Bonny"
+        );
+        assert!(
+            graph
+                .shortest(Uuid::from_u128(1), Uuid::from_u128(6))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn paths_within_hops_come_shortest_first() {
+        let graph = graph();
+        let (anne, jack) = (Uuid::from_u128(1), Uuid::from_u128(4));
+        let hops: Vec<usize> = graph
+            .within(anne, jack, 3)
+            .iter()
+            .map(|path| path.hops())
+            .collect();
+        assert_eq!(hops, [2, 3]);
+        assert!(graph.within(anne, jack, 1).is_empty());
+
+        let paths = graph.within(anne, jack, 3);
+        let dot = graph.dot(&paths, &graph.shortest(anne, jack));
+        assert_eq!(dot.matches(" -> ").count(), 5);
+        assert_eq!(dot.matches("highlighted").count(), 2);
+        assert!(dot.contains("label=\"appears in\""));
+        assert!(!dot.contains("Mary Read"));
+    }
+
+    #[test]
+    fn relations_both_ways_are_one_step() {
+        // Anne and Jack are each other's captain, and Jack lives in Port Royal.
+        let graph = RelationGraph::new(
+            vec![
+                entity(1, "Anne Bonny", "characters"),
+                entity(3, "Port Royal", "locations"),
+                entity(4, "Calico Jack", "characters"),
+            ],
+            vec![
+                relation(1, 4, Some(("captain of", "crew of"))),
+                relation(4, 1, Some(("captain of", "crew of"))),
+                relation(4, 3, Some(("located in", "location of"))),
+            ],
+        );
+        let (anne, port) = (Uuid::from_u128(1), Uuid::from_u128(3));
+        let chains: Vec<String> = graph
+            .within(anne, port, 4)
+            .into_iter()
+            .map(|path| path.chain)
+            .collect();
+        assert_eq!(
+            chains,
+            [
+                "Anne Bonny \u{2014}captain of and crew of\u{2192} Calico Jack \u{2014}located in\u{2192} Port Royal"
+            ]
+        );
+        assert_eq!(graph.shortest(anne, port).len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod pdf_layout {
+    use crate::render::fountain;
+    use crate::render::pdf::{
+        FontStyle, PageSize, PdfBlock, PdfExport, PdfOptions, PdfSection, Placed, TextMeasure,
+        layout,
+    };
+
+    /// every glyph half the font size wide.
+    struct Fixed;
+
+    impl TextMeasure for Fixed {
+        fn width(&self, text: &str, _style: FontStyle, size: f32) -> f32 {
+            text.chars().count() as f32 * size * 0.5
+        }
+    }
+
+    fn texts(page: &[Placed]) -> Vec<(f32, f32, &str, f32)> {
+        page.iter()
+            .filter_map(|item| match item {
+                Placed::Text {
+                    x,
+                    baseline,
+                    text,
+                    size,
+                    ..
+                } => Some((*x, *baseline, text.as_str(), *size)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn text_wraps_inside_the_margins_and_flows_onto_new_pages() {
+        let options = PdfOptions {
+            toc: false,
+            ..PdfOptions::default()
+        };
+        let (width, height) = PageSize::A4.dimensions();
+        let paragraph = "the lamp is out and the rain hammers the glass ".repeat(400);
+        let export = PdfExport::new("The Gull")
+            .title_page(false)
+            .section(PdfSection::new("One").paragraph(&paragraph));
+        let pages = layout(&Fixed, &export, &options);
+        assert!(pages.len() > 1);
+        for page in &pages {
+            for (x, baseline, text, size) in texts(page) {
+                assert!(x >= options.margin / 2.0);
+                assert!(
+                    x + Fixed.width(text, FontStyle::Regular, size)
+                        <= width - options.margin + 0.01
+                );
+                assert!(baseline <= height);
+            }
+        }
+        // the body lines stay above the bottom margin, only the footer goes below it.
+        let body: Vec<f32> = texts(&pages[0])
+            .iter()
+            .filter(|(_, _, text, _)| text.starts_with("the lamp"))
+            .map(|(_, baseline, _, _)| *baseline)
+            .collect();
+        assert!(
+            body.iter()
+                .all(|baseline| *baseline <= height - options.margin)
+        );
+    }
+
+    #[test]
+    fn contents_number_the_pages_sections_start_on() {
+        let options = PdfOptions::default();
+        let export = PdfExport::new("The Gull")
+            .section(PdfSection::new("One").paragraph("the lamp is out"))
+            .section(PdfSection::new("Two").paragraph("then light it"));
+        let pages = layout(&Fixed, &export, &options);
+        // a title page, the contents and a page for each section.
+        assert_eq!(pages.len(), 4);
+        let contents: Vec<&str> = texts(&pages[1])
+            .iter()
+            .map(|(_, _, text, _)| *text)
+            .collect();
+        assert!(contents.contains(&"Contents"));
+        assert!(contents.windows(2).any(|pair| pair == ["One", "3"]));
+        assert!(contents.windows(2).any(|pair| pair == ["Two", "4"]));
+        // the title page has no header or footer.
+        assert!(
+            !texts(&pages[0])
+                .iter()
+                .any(|(_, _, text, _)| text.contains('/'))
+        );
+        assert!(
+            texts(&pages[2])
+                .iter()
+                .any(|(_, _, text, _)| *text == "3 / 4")
+        );
+    }
+
+    #[test]
+    fn options_leaving_no_room_for_content_are_rejected() {
+        assert!(PdfOptions::default().validate().is_ok());
+        let wide_margin = PdfOptions {
+            margin: 300.0,
+            ..PdfOptions::default()
+        };
+        assert!(wide_margin.validate().is_err());
+        let negative = PdfOptions {
+            margin: -10.0,
+            ..PdfOptions::default()
+        };
+        assert!(negative.validate().is_err());
+        for font_size in [0.0, 200.0, f32::NAN] {
+            let options = PdfOptions {
+                font_size,
+                ..PdfOptions::default()
+            };
+            assert!(options.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn screenplay_page_breaks_start_new_pages() {
+        let options = PdfOptions {
+            toc: false,
+            ..PdfOptions::default()
+        };
+        let blocks =
+            fountain::pdf_blocks("EXT. HARBOR - NIGHT\n\nRain.\n\n===\n\n===\n\nINT. KEEP - DAY");
+        assert!(matches!(
+            blocks[..],
+            [
+                PdfBlock::Heading(_),
+                PdfBlock::Paragraph(_),
+                PdfBlock::PageBreak,
+                PdfBlock::PageBreak,
+                PdfBlock::Heading(_)
+            ]
+        ));
+        let section = blocks
+            .into_iter()
+            .fold(PdfSection::new("Act One"), PdfSection::block);
+        let export = PdfExport::new("The Gull")
+            .title_page(false)
+            .section(section);
+        // back to back breaks don't leave an empty page.
+        let pages = layout(&Fixed, &export, &options);
+        assert_eq!(pages.len(), 2);
+        assert!(
+            texts(&pages[1])
+                .iter()
+                .any(|(_, _, text, _)| *text == "INT. KEEP - DAY")
+        );
+    }
+}
+
+#[cfg(test)]
+mod story_import {
+    use crate::characters::api::CharacterRender;
+    use crate::render::SupportedRender;
+    use crate::stories::import::{
+        ImportCache, ImportPlan, MAX_ENTRY_SIZE, MarkdownFile, PLAN_TTL, read_zip,
+    };
+    use std::io::{Cursor, Write};
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+    use zip::write::SimpleFileOptions;
+
+    fn file(path: &str, source: &str) -> MarkdownFile {
+        MarkdownFile {
+            path: path.to_string(),
+            source: source.to_string(),
+        }
+    }
+
+    fn plan(files: Vec<MarkdownFile>, characters: &[CharacterRender]) -> ImportPlan {
+        ImportPlan::new(
+            "The Gull",
+            None,
+            SupportedRender::Markdown,
+            Vec::new(),
+            files,
+            characters,
+            &[],
+        )
+        .unwrap()
+    }
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn fragments_are_ordered_and_named() {
+        let plan = plan(
+            vec![
+                file("chapter-10.md", "# The Storm\nrain"),
+                file("chapter-2.md", "wind"),
+                file("prologue.md", "---\ntitle: Before\norder: 1\n---\nquiet"),
+            ],
+            &[],
+        );
+        let names: Vec<&str> = plan.fragments.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["Before", "chapter 2", "The Storm"]);
+        let indices: Vec<i32> = plan.fragments.iter().map(|f| f.idx).collect();
+        assert_eq!(indices, [0, 1, 2]);
+        assert_eq!(plan.fragments[0].content, "quiet");
+    }
+
+    #[test]
+    fn frontmatter_names_are_resolved_and_unmatched_ones_collected() {
+        let mara = CharacterRender {
+            id: Uuid::new_v4(),
+            name: "Mara".to_string(),
+            description: None,
+            thumbnail: None,
+            tags: None,
+            images: None,
+        };
+        let plan = plan(
+            vec![
+                file("one.md", "---\ncharacters: [mara, Tomas]\n---\none"),
+                file(
+                    "two.md",
+                    "+++\ncharacters = [\"Tomas\"]\nlocation = \"Old Harbor\"\n+++\ntwo",
+                ),
+            ],
+            std::slice::from_ref(&mara),
+        );
+        assert_eq!(plan.fragments[0].links[0].id, Some(mara.id));
+        assert_eq!(plan.fragments[0].links[1].id, None);
+        let unresolved: Vec<(&str, &str)> = plan
+            .unresolved
+            .iter()
+            .map(|link| (link.category.as_str(), link.name.as_str()))
+            .collect();
+        assert_eq!(
+            unresolved,
+            [("characters", "Tomas"), ("locations", "Old Harbor")]
+        );
+    }
+
+    #[test]
+    fn empty_imports_and_unclosed_frontmatter_are_rejected() {
+        let build = |files| {
+            ImportPlan::new(
+                "The Gull",
+                None,
+                SupportedRender::Markdown,
+                Vec::new(),
+                files,
+                &[],
+                &[],
+            )
+        };
+        assert!(build(Vec::new()).is_err());
+        assert!(build(vec![file("one.md", "---\ntitle: One\nbody")]).is_err());
+    }
+
+    #[test]
+    fn zips_only_yield_visible_markdown() {
+        let bytes = zip(&[
+            ("book/one.md", b"one"),
+            ("book/notes.txt", b"notes"),
+            ("book/.draft.md", b"draft"),
+            ("__MACOSX/book/one.md", b"resource fork"),
+            ("book/Two.MARKDOWN", b"two"),
+        ]);
+        let files = read_zip(&bytes).unwrap();
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["book/one.md", "book/Two.MARKDOWN"]);
+        assert_eq!(files[0].source, "one");
+    }
+
+    #[test]
+    fn oversized_zip_entries_are_rejected() {
+        let large = vec![b'a'; MAX_ENTRY_SIZE as usize + 1];
+        let bytes = zip(&[("one.md", b"one"), ("two.md", &large)]);
+        assert!(read_zip(&bytes).is_err());
+        // entries that aren't markdown are never read.
+        let bytes = zip(&[("one.md", b"one"), ("cover.png", &large)]);
+        assert_eq!(read_zip(&bytes).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cached_plans_belong_to_their_owner_and_expire() {
+        let cache = ImportCache::new();
+        let now = Instant::now();
+        let first = plan(vec![file("one.md", "one")], &[]);
+        let id = first.id;
+        cache.insert_at("mara", first, now);
+        assert!(cache.take_at(&id, "tomas", now).is_err());
+        assert_eq!(cache.take_at(&id, "mara", now).unwrap().id, id);
+        // a plan can only be confirmed once.
+        assert!(cache.take_at(&id, "mara", now).is_err());
+
+        let second = plan(vec![file("one.md", "one")], &[]);
+        let id = second.id;
+        cache.insert_at("mara", second, now);
+        let later = now + PLAN_TTL + Duration::from_secs(1);
+        assert!(cache.take_at(&id, "mara", later).is_err());
+    }
+}
+
+#[cfg(test)]
+mod wiki_links {
+    use crate::fragments::frontend::local_redirect;
+    use crate::render::links::{EntityIndex, LinkTarget, WikiLink, find_links};
+    use uuid::Uuid;
+
+    fn target(name: &str, category: &str) -> LinkTarget {
+        LinkTarget {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category: category.to_string(),
+            description: None,
+            thumbnail: None,
+        }
+    }
+
+    fn index(targets: &[&LinkTarget]) -> EntityIndex {
+        let mut index = EntityIndex::default();
+        for target in targets {
+            index.insert((*target).clone());
+        }
+        index
+    }
+
+    #[test]
+    fn links_are_parsed_with_categories_and_labels() {
+        let link = WikiLink::parse(" place: Old   Harbor |the docks ").unwrap();
+        assert_eq!(link.category.as_deref(), Some("locations"));
+        assert_eq!(link.name, "Old Harbor");
+        assert_eq!(link.text(), "the docks");
+
+        // unknown prefixes are part of the name.
+        let link = WikiLink::parse("Book: The Gull|").unwrap();
+        assert_eq!(link.category, None);
+        assert_eq!(link.name, "Book: The Gull");
+        assert_eq!(link.text(), "Book: The Gull");
+
+        assert_eq!(WikiLink::parse("  |label"), None);
+        assert_eq!(WikiLink::parse("character:"), None);
+    }
+
+    #[test]
+    fn links_are_found_in_order_until_one_is_unclosed() {
+        let names: Vec<String> = find_links("[[Mara]] met [[char:Tomas]], [[]] [[Old Harbor")
+            .into_iter()
+            .map(|link| link.name)
+            .collect();
+        assert_eq!(names, ["Mara", "Tomas"]);
+    }
+
+    #[test]
+    fn characters_win_unless_a_category_is_given() {
+        let mara = target("Mara", "characters");
+        let harbor = target("Mara", "locations");
+        let index = index(&[&harbor, &mara]);
+        let resolve = |inner: &str| {
+            index
+                .resolve(&WikiLink::parse(inner).unwrap())
+                .map(|t| t.id)
+        };
+        assert_eq!(resolve("mara"), Some(mara.id));
+        assert_eq!(resolve("location:MARA"), Some(harbor.id));
+        assert_eq!(resolve("Tomas"), None);
+    }
+
+    #[test]
+    fn references_and_unresolved_links_are_distinct() {
+        let mara = target("Mara", "characters");
+        let index = index(&[&mara]);
+        let content = "[[Mara]] and [[mara|her]] looked for [[Tomas]], [[Tomas]] was gone";
+        let referenced = index.referenced(content);
+        assert_eq!(referenced.len(), 1);
+        assert_eq!(referenced[0].id, mara.id);
+        let unresolved = index.unresolved(content);
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!(unresolved[0].name, "Tomas");
+    }
+
+    #[test]
+    fn html_is_linked_outside_code() {
+        let mara = target("Mara & Co", "characters");
+        let index = index(&[&mara]);
+        let content =
+            "<p>[[Mara &amp; Co|&lt;b&gt;]] <code>[[Mara &amp; Co]]</code> [[Nobody]]</p>";
+        let html = index.link_html(content);
+        assert!(html.contains(&format!(
+            "<a href=\"/characters/{}\">&lt;b&gt;</a>",
+            mara.id
+        )));
+        assert!(html.contains("<strong>Mara &amp; Co</strong>"));
+        assert!(html.contains("<code>[[Mara &amp; Co]]</code>"));
+        assert!(html.contains("<span class=\"wikilink unresolved\""));
+    }
+
+    #[test]
+    fn only_local_redirects_are_followed() {
+        let id = Uuid::new_v4();
+        let fallback = format!("/fragments/{}", id);
+        let redirect = |path: &str| local_redirect(Some(path.to_string()), id);
+        assert_eq!(redirect("/stories/1?tab=links"), "/stories/1?tab=links");
+        assert_eq!(redirect("/"), "/");
+        for path in [
+            "https://evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "/\t/evil.com",
+            "/\n/evil.com",
+            "evil.com",
+            "",
+        ] {
+            assert_eq!(redirect(path), fallback, "{:?}", path);
+        }
+        assert_eq!(local_redirect(None, id), fallback);
+    }
+}
+
+#[cfg(test)]
+mod mentions {
+    use crate::fragments::mentions::{Mention, MentionAnalyzer};
+    use crate::render::links::LinkTarget;
+    use uuid::Uuid;
+
+    fn target(name: &str, category: &str) -> LinkTarget {
+        LinkTarget {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            category: category.to_string(),
+            description: None,
+            thumbnail: None,
+        }
+    }
+
+    fn found(mentions: &[Mention]) -> Vec<(&str, &str, usize)> {
+        mentions
+            .iter()
+            .map(|m| (m.target.name.as_str(), m.matched.as_str(), m.count))
+            .collect()
+    }
+
+    #[test]
+    fn names_match_any_case_and_possessives() {
+        let mara = target("Mara Voss", "characters");
+        let harbor = target("The Old Harbor", "locations");
+        let analyzer = MentionAnalyzer::new([&mara, &harbor]);
+        let mentions = analyzer.scan(
+            "MARA VOSS's boat left the old harbor. Mara Voss\u{2019} crew stayed at Old Harbor's inn.",
+        );
+        assert_eq!(
+            found(&mentions),
+            [
+                ("Mara Voss", "MARA VOSS's", 2),
+                ("The Old Harbor", "the old harbor", 2)
+            ]
+        );
+        assert!(mentions[0].excerpt.starts_with("MARA VOSS's boat"));
+    }
+
+    #[test]
+    fn aliases_come_from_nicknames_titles_and_name_parts() {
+        let voss = target("Captain Mara \"Gull\" Voss", "characters");
+        let analyzer = MentionAnalyzer::new([&voss]);
+        for text in [
+            "Gull laughed",
+            "Captain Voss laughed",
+            "Mara laughed",
+            "Voss' laugh",
+        ] {
+            let mentions = analyzer.scan(text);
+            assert_eq!(mentions.len(), 1, "{}", text);
+            assert_eq!(mentions[0].target.id, voss.id);
+        }
+        // aliases have to be capitalized, and particles and titles aren't aliases alone.
+        assert!(analyzer.scan("the gull laughed at the captain").is_empty());
+    }
+
+    #[test]
+    fn shared_aliases_are_ambiguous() {
+        let mara = target("Mara Voss", "characters");
+        let tomas = target("Tomas Voss", "characters");
+        let analyzer = MentionAnalyzer::new([&mara, &tomas]);
+        let mentions = analyzer.scan("Voss waited for Tomas, then Mara Voss arrived");
+        assert_eq!(
+            found(&mentions),
+            [("Tomas Voss", "Tomas", 1), ("Mara Voss", "Mara Voss", 1)]
+        );
+    }
+
+    #[test]
+    fn single_word_names_respect_word_boundaries() {
+        let hope = target("Hope", "characters");
+        let mara = target("Mara", "characters");
+        let analyzer = MentionAnalyzer::new([&hope, &mara]);
+        assert!(analyzer.scan("there is hope after the Marathon").is_empty());
+        assert!(
+            analyzer
+                .scan("a Mara-like calm, Hopeful and Hope's-end")
+                .is_empty()
+        );
+        assert_eq!(
+            found(&analyzer.scan("Hope, then Mara.")),
+            [("Hope", "Hope", 1), ("Mara", "Mara", 1)]
+        );
+    }
+
+    #[test]
+    fn names_stop_at_punctuation() {
+        let voss = target("Tomas Voss", "characters");
+        let analyzer = MentionAnalyzer::new([&voss]);
+        let mentions = analyzer.scan("I spoke to Tomas. Voss was gone");
+        // each word on its own is still an alias.
+        assert_eq!(found(&mentions), [("Tomas Voss", "Tomas", 2)]);
+    }
+}
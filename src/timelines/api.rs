@@ -1,7 +1,12 @@
+use crate::ApiClient;
+use crate::calendars::api::{Calendar, CalendarDate};
+use crate::errors::ApiError;
 use crate::fragments::api::FragmentBuilder;
+use crate::timelines::continuity::{Beat, FragmentRef};
+use crate::timelines::editing::{self, MomentOrder};
+use crate::timelines::ical::{self, Event};
+use crate::timelines::lanes::{self, Axis, Span};
 use crate::model::StoryFragment;
-use petgraph::graph::DiGraph;
-use std::collections::HashMap;
 use uuid::Uuid;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TimelineRender {
@@ -10,6 +15,27 @@ pub struct TimelineRender {
     pub description: Option<String>,
     pub image: Option<String>,
     pub svg: Option<String>,
+    pub moments: Vec<MomentRender>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MomentRender {
+    pub id: Uuid,
+    pub fragment: Uuid,
+    pub name: String,
+    pub idx: i64,
+    pub day: Option<i64>,
+    /// `day` formatted in the calendar the timeline is shown in.
+    pub date: Option<String>,
+    /// the first and last day in that calendar, used to fill in the date form.
+    pub start: Option<CalendarDate>,
+    pub end: Option<CalendarDate>,
+    pub duration: Option<i64>,
+    pub lane: Option<String>,
+    /// the branch the moment belongs to, moments of other branches are shared history.
+    pub branch: Option<Uuid>,
+    /// the position among the moments of its branch, see [`editing::positions`].
+    pub position: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,37 +45,262 @@ pub struct Timeline {
     description: Option<String>,
     created: i64,
     moments: Vec<Moment>,
+    #[serde(default)]
+    branches: Vec<Branch>,
+}
+
+/// an alternate continuation of a timeline, sharing its parent's moments up to the fork.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Branch {
+    pub id: Uuid,
+    pub timeline: Uuid,
+    pub name: String,
+    /// the branch this one forks off, `None` for the trunk.
+    pub parent: Option<Uuid>,
+    /// the last shared moment, `None` when the branch shares no history.
+    pub fork: Option<Uuid>,
+}
+
+/// a branch with the branches forking off it, for drawing the branch tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchNode {
+    /// `None` for the trunk.
+    pub id: Option<Uuid>,
+    pub name: String,
+    /// the name of the fragment at the fork.
+    pub fork: Option<String>,
+    /// moments in the branch itself, not counting shared history.
+    pub moments: usize,
+    pub children: Vec<BranchNode>,
+}
+
+/// the body of `POST /timelines/branches`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchBuilder {
+    pub timeline: Uuid,
+    pub name: String,
+    pub parent: Option<Uuid>,
+    pub fork: Option<Uuid>,
+    /// fragments the branch continues with, in order.
+    pub fragments: Vec<Uuid>,
+}
+
+impl BranchBuilder {
+    pub async fn build(&self, api: &ApiClient, access_token: &str) -> Result<Branch, ApiError> {
+        api.post("/timelines/branches", access_token, None, self)
+            .await
+    }
 }
 
 impl Timeline {
-    /// this is a particularly complex render implementation because of the reliance on graphviz
-    pub fn render(self) -> TimelineRender {
-        let mut graph = DiGraph::new();
-        let mut moments = self.moments.into_iter().enumerate();
-        let mut idmap = HashMap::new();
-        if let Some((fidx, first_moment)) = moments.next() {
-            let mut previous_node = graph.add_node(first_moment.fragment.name);
-            idmap.insert(fidx, first_moment.fragment.id);
-            for (idx, moment) in moments {
-                let current_node = graph.add_node(moment.fragment.name);
-                idmap.insert(idx, moment.fragment.id);
-                graph.add_edge(previous_node, current_node, "weight");
-                previous_node = current_node;
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn branches(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    pub fn branch(&self, id: Uuid) -> Option<&Branch> {
+        self.branches.iter().find(|branch| branch.id == id)
+    }
+
+    /// the timeline as seen from `branch`, the trunk when `None`: the parent's moments up to the
+    /// fork followed by the branch's own. returns `None` for a branch that isn't part of it.
+    pub fn on_branch(&self, branch: Option<Uuid>) -> Option<Timeline> {
+        Some(Timeline {
+            moments: self.sequence(branch, self.branches.len())?,
+            ..self.clone()
+        })
+    }
+
+    /// `depth` bounds the walk up the parents in case the branches form a cycle.
+    fn sequence(&self, branch: Option<Uuid>, depth: usize) -> Option<Vec<Moment>> {
+        let own = |branch: Option<Uuid>| {
+            self.moments
+                .iter()
+                .filter(move |moment| moment.branch == branch)
+                .cloned()
+        };
+        let Some(id) = branch else {
+            return Some(own(None).collect());
+        };
+        let branch = self.branch(id)?;
+        let mut moments = match branch.fork {
+            Some(fork) => {
+                let inherited = self.sequence(branch.parent, depth.checked_sub(1)?)?;
+                let cut = self
+                    .moments
+                    .iter()
+                    .find(|moment| moment.id == fork)
+                    .map(|moment| moment.idx)
+                    .unwrap_or(i64::MIN);
+                inherited
+                    .into_iter()
+                    .filter(|moment| moment.idx <= cut)
+                    .collect()
             }
+            None => Vec::new(),
+        };
+        moments.extend(own(Some(id)));
+        moments.sort_by_key(|moment| moment.idx);
+        Some(moments)
+    }
+
+    /// the trunk with every branch nested under the branch it forks off.
+    pub fn tree(&self) -> BranchNode {
+        self.node(None, &self.name, None)
+    }
+
+    /// the positions of a branch's own moments, the trunk when `None`.
+    pub fn order(&self, branch: Option<Uuid>) -> Vec<MomentOrder> {
+        let mut own: Vec<&Moment> = self
+            .moments
+            .iter()
+            .filter(|moment| moment.branch == branch)
+            .collect();
+        own.sort_by_key(|moment| moment.idx);
+        editing::positions(own.into_iter().map(|moment| (moment.id, moment.idx)))
+    }
+
+    /// the name of the fragment at a moment.
+    pub fn moment_name(&self, moment: Uuid) -> Option<&str> {
+        self.moments
+            .iter()
+            .find(|candidate| candidate.id == moment)
+            .map(|moment| moment.fragment.name.as_str())
+    }
+
+    fn node(&self, id: Option<Uuid>, name: &str, fork: Option<Uuid>) -> BranchNode {
+        BranchNode {
+            id,
+            name: name.to_string(),
+            fork: fork
+                .and_then(|fork| self.moment_name(fork))
+                .map(str::to_string),
+            moments: self
+                .moments
+                .iter()
+                .filter(|moment| moment.branch == id)
+                .count(),
+            children: self
+                .branches
+                .iter()
+                .filter(|branch| branch.parent == id)
+                .map(|branch| self.node(Some(branch.id), &branch.name, branch.fork))
+                .collect(),
         }
+    }
+
+    /// one beat per moment, moments sharing an `idx` happen at the same time.
+    pub fn beats(&self) -> Vec<Beat> {
+        self.moments
+            .iter()
+            .map(|moment| Beat::new(moment.idx, moment.fragment.id, &moment.fragment.name))
+            .collect()
+    }
+
+    /// every moment placed on a shared axis, dates are formatted in `calendar`.
+    pub fn spans(&self, calendar: &Calendar) -> (Vec<Span>, Axis) {
+        let mut spans: Vec<Span> = self
+            .moments
+            .iter()
+            .map(|moment| Span {
+                moment: moment.id,
+                fragment: FragmentRef {
+                    id: moment.fragment.id,
+                    name: moment.fragment.name.clone(),
+                },
+                idx: moment.idx,
+                start: moment.day.unwrap_or_default() as f64,
+                length: moment.length() as f64,
+                dated: moment.day.is_some(),
+                subplot: moment.lane.clone(),
+                date: moment.day.map(|day| match moment.length() {
+                    1 => calendar.format(day),
+                    length => format!(
+                        "{} to {}",
+                        calendar.format(day),
+                        calendar.format(day + length - 1)
+                    ),
+                }),
+                overlaps: false,
+            })
+            .collect();
+        let axis = lanes::place(&mut spans);
+        (spans, axis)
+    }
 
-        let svg = if graph.node_count() > 0 {
-            Some(crate::assets::graphs::render_graph(graph, idmap))
-        } else {
-            None
+    /// the dated moments as calendar events, each linking back to its fragment under `origin`.
+    /// the sub-plot of a moment becomes the category of its event.
+    pub fn events(&self, origin: &str) -> Vec<Event> {
+        self.moments
+            .iter()
+            .filter_map(|moment| {
+                let start = moment.day?;
+                Some(Event {
+                    uid: format!("{}@storyteller", moment.id),
+                    summary: moment.fragment.name.clone(),
+                    description: ical::summary(&moment.fragment.content),
+                    url: Some(format!("{}/fragments/{}", origin, moment.fragment.id)),
+                    categories: moment.lane.iter().cloned().collect(),
+                    start,
+                    until: start + moment.length() - 1,
+                    stamp: Some(moment.fragment.last_edited),
+                })
+            })
+            .collect()
+    }
+
+    /// the moments with their dates in `calendar`, the chart is drawn separately, see
+    /// [`lanes::Chart`].
+    pub fn render(self, calendar: &Calendar) -> TimelineRender {
+        let mut positions = Vec::new();
+        for branch in self.moments.iter().map(|moment| moment.branch) {
+            if !positions.iter().any(|(other, _)| *other == branch) {
+                positions.push((branch, self.order(branch)));
+            }
+        }
+        let position = |moment: &Moment| {
+            positions
+                .iter()
+                .flat_map(|(_, order)| order.iter())
+                .find(|order| order.id == moment.id)
+                .map(|order| order.position)
+                .unwrap_or_default()
         };
+        let moments = self
+            .moments
+            .iter()
+            .map(|moment| MomentRender {
+                id: moment.id,
+                fragment: moment.fragment.id,
+                name: moment.fragment.name.clone(),
+                idx: moment.idx,
+                day: moment.day,
+                date: moment.day.map(|day| calendar.format(day)),
+                start: moment.day.map(|day| calendar.definition.from_day(day)),
+                end: moment
+                    .until
+                    .map(|until| calendar.definition.from_day(until)),
+                duration: moment.duration,
+                lane: moment.lane.clone(),
+                branch: moment.branch,
+                position: position(moment),
+            })
+            .collect();
 
         TimelineRender {
             id: self.id,
             name: self.name,
             description: self.description,
             image: None,
-            svg,
+            svg: None,
+            moments,
         }
     }
 }
@@ -62,6 +313,22 @@ pub struct TimelineBuilder {
     category: String,
 }
 
+impl TimelineBuilder {
+    /// a timeline of the fragments `source` already has, in the order they were last edited.
+    pub fn new(name: &str, description: Option<String>, source: Uuid, category: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description,
+            source,
+            category: category.to_string(),
+        }
+    }
+
+    pub async fn build(&self, api: &ApiClient, access_token: &str) -> Result<Timeline, ApiError> {
+        api.post("/timelines", access_token, None, self).await
+    }
+}
+
 /// this type represents an individual link within a timeline
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Moment {
@@ -69,4 +336,40 @@ pub struct Moment {
     timeline: Uuid,
     fragment: StoryFragment,
     idx: i64,
+    /// days since 1970-01-01, see [`Calendar`].
+    #[serde(default)]
+    day: Option<i64>,
+    /// the calendar the date was entered in, `None` for gregorian.
+    #[serde(default)]
+    calendar: Option<Uuid>,
+    /// the last day the moment covers, inclusive.
+    #[serde(default)]
+    until: Option<i64>,
+    /// how many days the moment lasts when there is no `until`.
+    #[serde(default)]
+    duration: Option<i64>,
+    /// the sub-plot the moment belongs to.
+    #[serde(default)]
+    lane: Option<String>,
+    /// the branch the moment belongs to, `None` for the trunk.
+    #[serde(default)]
+    branch: Option<Uuid>,
+}
+
+impl Moment {
+    /// days covered, a moment without an end or duration takes up a single day.
+    fn length(&self) -> i64 {
+        let until = self.day.zip(self.until).map(|(day, until)| until - day + 1);
+        until.or(self.duration).unwrap_or(1).max(1)
+    }
+}
+
+/// the body of `PUT /timelines/moments/<id>`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MomentDate {
+    pub day: Option<i64>,
+    pub calendar: Option<Uuid>,
+    pub until: Option<i64>,
+    pub duration: Option<i64>,
+    pub lane: Option<String>,
 }
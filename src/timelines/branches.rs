@@ -0,0 +1,110 @@
+//! comparing what exists in each branch of a timeline.
+//!
+//! every branch, the trunk included, becomes a column and every fragment, character and
+//! location seen in any branch becomes a row marking the columns it shows up in. characters and
+//! locations come from the relations of the fragments, so a character only present in
+//! fragments after a fork exists in just the branches that include those fragments.
+use crate::render::links::LinkTarget;
+use crate::timelines::api::BranchNode;
+use crate::timelines::continuity::{Beat, FragmentRef};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    /// `None` for the trunk.
+    pub id: Option<Uuid>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Row {
+    pub name: String,
+    pub href: String,
+    /// one flag per column.
+    pub present: Vec<bool>,
+    /// present in every branch.
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Comparison {
+    pub columns: Vec<Column>,
+    pub fragments: Vec<Row>,
+    pub characters: Vec<Row>,
+    pub locations: Vec<Row>,
+}
+
+/// the branches of a tree depth first, each after the branch it forks off.
+pub fn columns(tree: &BranchNode) -> Vec<Column> {
+    let mut columns = vec![Column {
+        id: tree.id,
+        name: tree.name.clone(),
+    }];
+    for child in tree.children.iter() {
+        columns.extend(self::columns(child));
+    }
+    columns
+}
+
+/// `branches` holds the fragments of each column in order, `beats` the fetched beats of every
+/// fragment in the timeline.
+pub fn compare(branches: &[(Column, Vec<FragmentRef>)], beats: &[Beat]) -> Comparison {
+    let mut fragments: Vec<Row> = Vec::new();
+    let mut characters: Vec<Row> = Vec::new();
+    let mut locations: Vec<Row> = Vec::new();
+    let width = branches.len();
+    for (column, (_, sequence)) in branches.iter().enumerate() {
+        for fragment in sequence {
+            mark(
+                &mut fragments,
+                &fragment.name,
+                format!("/fragments/{}", fragment.id),
+                column,
+                width,
+            );
+            let beat = beats.iter().find(|beat| beat.fragment.id == fragment.id);
+            for target in beat.iter().flat_map(|beat| beat.characters.iter()) {
+                mark_target(&mut characters, target, column, width);
+            }
+            for target in beat.iter().flat_map(|beat| beat.locations.iter()) {
+                mark_target(&mut locations, target, column, width);
+            }
+        }
+    }
+    characters.sort_by(|a, b| a.name.cmp(&b.name));
+    locations.sort_by(|a, b| a.name.cmp(&b.name));
+    for row in fragments
+        .iter_mut()
+        .chain(characters.iter_mut())
+        .chain(locations.iter_mut())
+    {
+        row.shared = row.present.iter().all(|present| *present);
+    }
+    Comparison {
+        columns: branches.iter().map(|(column, _)| column.clone()).collect(),
+        fragments,
+        characters,
+        locations,
+    }
+}
+
+fn mark_target(rows: &mut Vec<Row>, target: &LinkTarget, column: usize, width: usize) {
+    let href = format!("/{}/{}", target.category, target.id);
+    mark(rows, &target.name, href, column, width);
+}
+
+fn mark(rows: &mut Vec<Row>, name: &str, href: String, column: usize, width: usize) {
+    let row = match rows.iter().position(|row| row.href == href) {
+        Some(position) => &mut rows[position],
+        None => {
+            rows.push(Row {
+                name: name.to_string(),
+                href,
+                present: vec![false; width],
+                shared: false,
+            });
+            rows.last_mut().unwrap()
+        }
+    };
+    row.present[column] = true;
+}
@@ -0,0 +1,294 @@
+//! continuity checks over an ordered run of fragments.
+//!
+//! a timeline's moments, or a story's fragments in `idx` order, become a list of [`Beat`]s, each
+//! carrying the characters and locations related to its fragment. lifespans are anchored to
+//! fragments rather than dates, so a character born in one fragment is missing from every beat
+//! before it on any timeline that includes that fragment. anchors that aren't part of the run
+//! being checked are ignored.
+use crate::ApiClient;
+use crate::characters::api::CharacterRender;
+use crate::errors::ApiError;
+use crate::locations::LocationRender;
+use crate::render::links::{EntityIndex, LinkTarget};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+/// the fragments an entity's existence is bounded by, either end may be open.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lifespan {
+    pub entity: Uuid,
+    /// the birth of a character or the founding of a location.
+    pub begins: Option<Uuid>,
+    /// the death of a character or the destruction of a location.
+    pub ends: Option<Uuid>,
+}
+
+impl Lifespan {
+    pub async fn fetch(api: &ApiClient, access_token: &str, id: Uuid) -> Result<Self, ApiError> {
+        let url = format!("/lifespans/{}", id);
+        let lifespan: Option<Lifespan> = api.get_protected(&url, access_token, None).await?;
+        Ok(lifespan.unwrap_or(Lifespan {
+            entity: id,
+            ..Default::default()
+        }))
+    }
+
+    pub async fn fetch_all(
+        api: &ApiClient,
+        access_token: &str,
+    ) -> Result<HashMap<Uuid, Self>, ApiError> {
+        let lifespans: Option<Vec<Lifespan>> =
+            api.get_protected("/lifespans", access_token, None).await?;
+        Ok(lifespans
+            .unwrap_or_default()
+            .into_iter()
+            .map(|lifespan| (lifespan.entity, lifespan))
+            .collect())
+    }
+
+    pub async fn save(&self, api: &ApiClient, access_token: &str) -> Result<Self, ApiError> {
+        api.put("/lifespans", access_token, self).await
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FragmentRef {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// a fragment at a position in the run, beats sharing a position happen at the same time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Beat {
+    pub position: i64,
+    pub fragment: FragmentRef,
+    pub characters: Vec<LinkTarget>,
+    pub locations: Vec<LinkTarget>,
+}
+
+impl Beat {
+    pub fn new(position: i64, id: Uuid, name: &str) -> Self {
+        Self {
+            position,
+            fragment: FragmentRef {
+                id,
+                name: name.to_string(),
+            },
+            characters: Vec::new(),
+            locations: Vec::new(),
+        }
+    }
+
+    /// fills in the characters and locations related to the fragment.
+    pub async fn fetch(mut self, api: &ApiClient, access_token: &str) -> Result<Self, ApiError> {
+        let request = api
+            .empty_request()
+            .access_token(access_token)
+            .set_param("parent", self.fragment.id.to_string())
+            .set_param("category", "fragments".to_string());
+        let characters: Option<Vec<CharacterRender>> =
+            request.clone().route("/characters/filter").send().await?;
+        let locations: Option<Vec<LocationRender>> =
+            request.route("/locations/filter").send().await?;
+        let index = EntityIndex::new(
+            &characters.unwrap_or_default(),
+            &locations.unwrap_or_default(),
+        );
+        for target in index.targets() {
+            match target.category.as_str() {
+                "characters" => self.characters.push(target.clone()),
+                _ => self.locations.push(target.clone()),
+            }
+        }
+        self.characters.sort_by(|a, b| a.name.cmp(&b.name));
+        self.locations.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// a character related to different locations in beats at the same position.
+    TwoPlaces,
+    BeforeBirth,
+    AfterDeath,
+    /// a location used before the fragment it was founded in.
+    BeforeEstablished,
+    AfterDestroyed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub entity: LinkTarget,
+    /// the beats the conflict shows up in.
+    pub fragments: Vec<FragmentRef>,
+    /// the lifespan anchor that was crossed, or the clashing locations.
+    pub against: Vec<String>,
+}
+
+/// the result of a check, ready for the report template.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContinuityReport {
+    pub beats: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// fetches the characters and locations of every beat in turn, see [`Beat::fetch`].
+pub async fn fetch_beats(
+    api: &ApiClient,
+    access_token: &str,
+    beats: Vec<Beat>,
+) -> Result<Vec<Beat>, ApiError> {
+    let mut fetched = Vec::with_capacity(beats.len());
+    for beat in beats {
+        fetched.push(beat.fetch(api, access_token).await?);
+    }
+    Ok(fetched)
+}
+
+/// fetches the relations of every beat and checks them against the stored lifespans.
+pub async fn analyze(
+    api: &ApiClient,
+    access_token: &str,
+    beats: Vec<Beat>,
+) -> Result<ContinuityReport, ApiError> {
+    let fetched = fetch_beats(api, access_token, beats).await?;
+    let lifespans = Lifespan::fetch_all(api, access_token).await?;
+    Ok(check(&fetched, &lifespans))
+}
+
+pub fn check(beats: &[Beat], lifespans: &HashMap<Uuid, Lifespan>) -> ContinuityReport {
+    // an anchor fragment's position is where it first appears in the run.
+    let mut positions: HashMap<Uuid, (i64, &FragmentRef)> = HashMap::new();
+    for beat in beats {
+        positions
+            .entry(beat.fragment.id)
+            .or_insert((beat.position, &beat.fragment));
+    }
+
+    let mut conflicts = Vec::new();
+    for beat in beats {
+        let entities = beat
+            .characters
+            .iter()
+            .map(|target| (target, ConflictKind::BeforeBirth, ConflictKind::AfterDeath))
+            .chain(beat.locations.iter().map(|target| {
+                (
+                    target,
+                    ConflictKind::BeforeEstablished,
+                    ConflictKind::AfterDestroyed,
+                )
+            }));
+        for (target, before, after) in entities {
+            let Some(lifespan) = lifespans.get(&target.id) else {
+                continue;
+            };
+            let anchors = [(lifespan.begins, before), (lifespan.ends, after)];
+            for (anchor, kind) in anchors {
+                let Some((position, fragment)) = anchor.and_then(|id| positions.get(&id)) else {
+                    continue;
+                };
+                let crossed = match kind {
+                    ConflictKind::BeforeBirth | ConflictKind::BeforeEstablished => {
+                        beat.position < *position
+                    }
+                    _ => beat.position > *position,
+                };
+                if crossed {
+                    push_conflict(&mut conflicts, kind, target, &beat.fragment, &fragment.name);
+                }
+            }
+        }
+    }
+
+    let mut simultaneous: BTreeMap<i64, Vec<&Beat>> = BTreeMap::new();
+    for beat in beats {
+        simultaneous.entry(beat.position).or_default().push(beat);
+    }
+    for group in simultaneous.values().filter(|group| group.len() > 1) {
+        conflicts.extend(two_places(group));
+    }
+
+    ContinuityReport {
+        beats: beats.len(),
+        conflicts,
+    }
+}
+
+/// merges repeats of the same crossing into one conflict listing every fragment.
+fn push_conflict(
+    conflicts: &mut Vec<Conflict>,
+    kind: ConflictKind,
+    target: &LinkTarget,
+    fragment: &FragmentRef,
+    against: &str,
+) {
+    let existing = conflicts
+        .iter_mut()
+        .find(|conflict| conflict.kind == kind && conflict.entity.id == target.id);
+    match existing {
+        Some(conflict) => {
+            if !conflict.fragments.contains(fragment) {
+                conflict.fragments.push(fragment.clone());
+            }
+        }
+        None => conflicts.push(Conflict {
+            kind,
+            entity: target.clone(),
+            fragments: vec![fragment.clone()],
+            against: vec![against.to_string()],
+        }),
+    }
+}
+
+/// characters placed in two beats at the same time whose locations don't overlap. a beat with
+/// several locations is fine on its own, the character may travel within it.
+fn two_places(group: &[&Beat]) -> Vec<Conflict> {
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    let placed: Vec<&&Beat> = group.iter().filter(|beat| !beat.locations.is_empty()).collect();
+    for (i, first) in placed.iter().enumerate() {
+        for second in placed[i + 1..].iter() {
+            let overlap = first
+                .locations
+                .iter()
+                .any(|location| second.locations.iter().any(|other| other.id == location.id));
+            if overlap {
+                continue;
+            }
+            let shared = first
+                .characters
+                .iter()
+                .filter(|character| second.characters.iter().any(|c| c.id == character.id));
+            for character in shared {
+                let conflict = match conflicts
+                    .iter_mut()
+                    .find(|conflict| conflict.entity.id == character.id)
+                {
+                    Some(conflict) => conflict,
+                    None => {
+                        conflicts.push(Conflict {
+                            kind: ConflictKind::TwoPlaces,
+                            entity: character.clone(),
+                            fragments: Vec::new(),
+                            against: Vec::new(),
+                        });
+                        conflicts.last_mut().unwrap()
+                    }
+                };
+                for beat in [first, second] {
+                    if !conflict.fragments.contains(&beat.fragment) {
+                        conflict.fragments.push(beat.fragment.clone());
+                    }
+                    for location in beat.locations.iter() {
+                        if !conflict.against.contains(&location.name) {
+                            conflict.against.push(location.name.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    conflicts
+}
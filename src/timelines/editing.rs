@@ -0,0 +1,135 @@
+//! reordering the moments of a timeline.
+//!
+//! the moments of a branch, the trunk included, are edited as a list of positions, moments
+//! sharing a position happen at the same time. edits work on positions and the api turns them
+//! back into `idx` values spaced out from the branch's fork, see `PUT /timelines/order`.
+use crate::ApiClient;
+use crate::errors::ApiError;
+use reqwest::Method;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MomentOrder {
+    pub id: Uuid,
+    pub position: i64,
+}
+
+/// the body of `PUT /timelines/order`, `moments` must hold every moment of the branch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineOrder {
+    pub timeline: Uuid,
+    pub branch: Option<Uuid>,
+    pub moments: Vec<MomentOrder>,
+}
+
+impl TimelineOrder {
+    pub async fn save(&self, api: &ApiClient, access_token: &str) -> Result<(), ApiError> {
+        let _: serde_json::Value = api.put("/timelines/order", access_token, self).await?;
+        Ok(())
+    }
+}
+
+/// the body of `POST /timelines/moments`, the moment goes before the moments at `position`, or
+/// last when there is none.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MomentBuilder {
+    pub timeline: Uuid,
+    pub fragment: Uuid,
+    pub branch: Option<Uuid>,
+    pub position: Option<i64>,
+}
+
+/// the part of the created moment that's needed to refer back to it.
+#[derive(Debug, Deserialize)]
+struct CreatedMoment {
+    id: Uuid,
+}
+
+impl MomentBuilder {
+    /// adds the moment, returning its id.
+    pub async fn build(&self, api: &ApiClient, access_token: &str) -> Result<Uuid, ApiError> {
+        let moment: CreatedMoment = api
+            .post("/timelines/moments", access_token, None, self)
+            .await?;
+        Ok(moment.id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Up,
+    Down,
+    /// to a position of its own before the moments now at that position.
+    To(i64),
+}
+
+/// positions for moments in timeline order given their `idx`, counting from zero.
+pub fn positions(moments: impl IntoIterator<Item = (Uuid, i64)>) -> Vec<MomentOrder> {
+    let mut order: Vec<MomentOrder> = Vec::new();
+    let mut previous = None;
+    for (id, idx) in moments {
+        let position = match (order.last(), previous) {
+            (Some(last), Some(previous)) if previous == idx => last.position,
+            (Some(last), _) => last.position + 1,
+            (None, _) => 0,
+        };
+        order.push(MomentOrder { id, position });
+        previous = Some(idx);
+    }
+    order
+}
+
+/// moves a moment and renumbers every position, a moment leaving a group of simultaneous
+/// moments gets a position of its own. unknown moments leave the order as is.
+pub fn apply(order: &[MomentOrder], moment: Uuid, movement: Move) -> Vec<MomentOrder> {
+    let mut sorted = order.to_vec();
+    sorted.sort_by_key(|entry| entry.position);
+    let mut groups: Vec<Vec<Uuid>> = Vec::new();
+    let mut previous = None;
+    for entry in sorted {
+        match groups.last_mut() {
+            Some(group) if previous == Some(entry.position) => group.push(entry.id),
+            _ => groups.push(vec![entry.id]),
+        }
+        previous = Some(entry.position);
+    }
+    let Some(from) = groups.iter().position(|group| group.contains(&moment)) else {
+        return order.to_vec();
+    };
+    let alone = groups[from].len() == 1;
+    groups[from].retain(|id| *id != moment);
+    if alone {
+        groups.remove(from);
+    }
+    // where the moment's own group goes, counted in the groups left after removing it.
+    let target = match movement {
+        Move::Up if alone => from.saturating_sub(1),
+        Move::Up => from,
+        Move::Down => from + 1,
+        Move::To(position) => position.max(0) as usize,
+    };
+    groups.insert(target.min(groups.len()), vec![moment]);
+    groups
+        .into_iter()
+        .enumerate()
+        .flat_map(|(position, group)| {
+            group.into_iter().map(move |id| MomentOrder {
+                id,
+                position: position as i64,
+            })
+        })
+        .collect()
+}
+
+/// deletes a moment, branches forking at it fork at the moment before it instead.
+pub async fn remove(api: &ApiClient, access_token: &str, moment: Uuid) -> Result<(), ApiError> {
+    let url = format!("/timelines/moments/{}", moment);
+    let _: serde_json::Value = api
+        .empty_request()
+        .method(Method::DELETE)
+        .access_token(access_token)
+        .route(&url)
+        .send()
+        .await?;
+    Ok(())
+}
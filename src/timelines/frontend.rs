@@ -1,14 +1,30 @@
 use crate::ApiClient;
+use crate::assets::images::ImageProcessor;
 use crate::auth::Guard;
+use crate::calendars::api::{Calendar, CalendarDate};
+use crate::calendars::frontend::preferred;
+use crate::errors::ApiError;
 use crate::fragments::api::FragmentBuilder;
 use crate::relations::RelatedEntity;
-use crate::timelines::api::Timeline;
+use crate::model::StoryFragment;
+use crate::timelines::api::{BranchBuilder, MomentDate, Timeline};
+use crate::timelines::branches::{self, Column};
+use crate::timelines::continuity::{self, FragmentRef, Lifespan};
+use crate::timelines::editing::{self, MomentBuilder, MomentOrder, Move, TimelineOrder};
+use crate::timelines::ical;
+use crate::timelines::lanes::{self, Chart, LaneKind, Zoom};
+use crate::timelines::photos::{PhotoImport, ZipSource};
 use rocket::fs::TempFile;
-use rocket::http::CookieJar;
+use rocket::http::uri::Host;
+use rocket::http::{ContentType, CookieJar};
 use rocket::response::Redirect;
 use rocket::response::content::RawHtml;
-use rocket::{FromForm, Route, State, form::Form, get, post, routes};
+use rocket::{
+    Config, Either, FromForm, FromFormField, Route, State, form::Form, get, post, routes,
+};
 use rocket_dyn_templates::{Template, context};
+use std::collections::HashMap;
+use tokio::io::AsyncReadExt;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromForm)]
@@ -19,19 +35,376 @@ pub struct TimelineForm {
     pub category: String,
 }
 
-#[get("/<id>")]
-async fn get_timeline(guard: Guard, id: Uuid, api: &State<ApiClient>) -> RawHtml<Template> {
-    let url = format!("/timelines/{}", id);
-    let timeline: Timeline = api
-        .get_protected(&url, guard.access_token(), None)
-        .await
-        .unwrap();
+/// the timeline as a lane chart with its moments listed below. `lanes` picks what the chart is
+/// split by, `zoom`, `from` and `to` narrow it down to a level and a window of day numbers.
+#[get("/<id>?<branch>&<lanes>&<zoom>&<from>&<to>")]
+async fn get_timeline(
+    guard: Guard,
+    id: Uuid,
+    branch: Option<Uuid>,
+    lanes: Option<LaneKind>,
+    zoom: Option<Zoom>,
+    from: Option<i64>,
+    to: Option<i64>,
+    api: &State<ApiClient>,
+    jar: &CookieJar<'_>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let (full, timeline) = fetch_branch(api, guard.access_token(), id, branch).await?;
+    let tree = full.tree();
+    let branch_name = branch
+        .and_then(|branch| full.branch(branch))
+        .map(|branch| branch.name.clone());
 
-    let render = timeline.render();
-    RawHtml(Template::render(
+    let calendar = preferred(api, guard.access_token(), jar).await;
+    let calendars = Calendar::list(api, guard.access_token())
+        .await
+        .unwrap_or_else(|_| vec![Calendar::gregorian()]);
+    let kind = lanes.unwrap_or_default();
+    let (spans, axis) = timeline.spans(&calendar);
+    let lanes = match kind {
+        LaneKind::Subplots => lanes::by_subplot(&spans),
+        kind => {
+            let beats =
+                continuity::fetch_beats(api, guard.access_token(), timeline.beats()).await?;
+            lanes::by_entity(&spans, &beats, kind)
+        }
+    };
+    let chart = Chart::new(axis, lanes, &calendar.definition, zoom, from.zip(to));
+    let fragments: Option<Vec<StoryFragment>> = api
+        .get_protected("/fragments/", guard.access_token(), None)
+        .await?;
+    let mut fragments = fragments.unwrap_or_default();
+    fragments.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut render = timeline.render(&calendar);
+    let mut base = format!("/timelines/{}?lanes={}", id, kind.as_str());
+    if let Some(branch) = branch {
+        base.push_str(&format!("&branch={}", branch));
+    }
+    render.svg = Some(chart.svg(&base));
+    Ok(RawHtml(Template::render(
         "timelines/timeline",
-        context! { title: render.name.clone(), timeline: render },
-    ))
+        context! {
+            title: render.name.clone(),
+            timeline: render,
+            calendar,
+            calendars,
+            lanes: kind,
+            zoom: chart.zoom,
+            axis: chart.axis,
+            tree,
+            branch,
+            branch_name,
+            fragments,
+        },
+    )))
+}
+
+/// the whole timeline and the part of it seen from `branch`.
+async fn fetch_branch(
+    api: &ApiClient,
+    access_token: &str,
+    id: Uuid,
+    branch: Option<Uuid>,
+) -> Result<(Timeline, Timeline), ApiError> {
+    let url = format!("/timelines/{}", id);
+    let full: Timeline = api.get_protected(&url, access_token, None).await?;
+    let timeline = full
+        .on_branch(branch)
+        .ok_or_else(|| ApiError::NotFound(format!("{} has no such branch", full.name())))?;
+    Ok((full, timeline))
+}
+
+#[get("/<id>/branches/create?<parent>&<fork>")]
+async fn create_branch_html(
+    guard: Guard,
+    id: Uuid,
+    parent: Option<Uuid>,
+    fork: Option<Uuid>,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let (full, _) = fetch_branch(api, guard.access_token(), id, parent).await?;
+    let fork_name = fork.and_then(|fork| full.moment_name(fork));
+    let fragments: Option<Vec<StoryFragment>> = api
+        .get_protected("/fragments/", guard.access_token(), None)
+        .await?;
+    let mut fragments = fragments.unwrap_or_default();
+    fragments.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(RawHtml(Template::render(
+        "timelines/branch",
+        context! {
+            title: format!("branch {}", full.name()),
+            timeline: id,
+            name: full.name(),
+            parent,
+            fork,
+            fork_name,
+            fragments,
+        },
+    )))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct BranchForm {
+    name: String,
+    parent: Option<Uuid>,
+    fork: Option<Uuid>,
+    /// the fragments the branch continues with, in the order they were picked.
+    fragments: Vec<Uuid>,
+}
+
+#[post("/<id>/branches", data = "<form>")]
+async fn create_branch(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<BranchForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    if form.name.trim().is_empty() {
+        return Err(ApiError::UnprocessableEntity(
+            "a branch needs a name".to_string(),
+        ));
+    }
+    let builder = BranchBuilder {
+        timeline: id,
+        name: form.name.trim().to_string(),
+        parent: form.parent,
+        fork: form.fork,
+        fragments: form.fragments,
+    };
+    let branch = builder.build(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!(
+        "/timelines/{}?branch={}",
+        id, branch.id
+    )))
+}
+
+/// which fragments, characters and locations exist in each branch.
+#[get("/<id>/branches/compare")]
+async fn compare_branches(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let (full, _) = fetch_branch(api, guard.access_token(), id, None).await?;
+    let sequences: Vec<(Column, Vec<FragmentRef>)> = branches::columns(&full.tree())
+        .into_iter()
+        .filter_map(|column| {
+            let fragments = full
+                .on_branch(column.id)?
+                .beats()
+                .into_iter()
+                .map(|beat| beat.fragment)
+                .collect();
+            Some((column, fragments))
+        })
+        .collect();
+    let beats = continuity::fetch_beats(api, guard.access_token(), full.beats()).await?;
+    let comparison = branches::compare(&sequences, &beats);
+    Ok(RawHtml(Template::render(
+        "timelines/compare",
+        context! {
+            title: format!("branches of {}", full.name()),
+            timeline: id,
+            name: full.name(),
+            comparison,
+        },
+    )))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct MomentDateForm {
+    calendar: Uuid,
+    year: Option<i64>,
+    month: Option<u32>,
+    day: Option<u32>,
+    /// the last day of the moment, inclusive.
+    end_year: Option<i64>,
+    end_month: Option<u32>,
+    end_day: Option<u32>,
+    /// days the moment lasts, for moments without an end date.
+    duration: Option<i64>,
+    /// the sub-plot the moment belongs to.
+    lane: Option<String>,
+    /// the branch the form was shown on, to return to it.
+    branch: Option<Uuid>,
+}
+
+/// dates a moment and sets its span and sub-plot, leaving the year empty clears the date.
+#[post("/<id>/moments/<moment>/date", data = "<form>")]
+async fn set_moment_date(
+    guard: Guard,
+    id: Uuid,
+    moment: Uuid,
+    api: &State<ApiClient>,
+    form: Form<MomentDateForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let calendar = Calendar::fetch(api, guard.access_token(), form.calendar).await?;
+    let to_day = |year, month, day| match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => calendar
+            .definition
+            .to_day(CalendarDate { year, month, day })
+            .map(Some),
+        _ => Ok(None),
+    };
+    let day = to_day(form.year, form.month, form.day)?;
+    let until = to_day(form.end_year, form.end_month, form.end_day)?;
+    if let (Some(day), Some(until)) = (day, until)
+        && until < day
+    {
+        return Err(ApiError::UnprocessableEntity(
+            "a moment can't end before it begins".to_string(),
+        ));
+    }
+    if form.duration.is_some_and(|duration| duration < 1) {
+        return Err(ApiError::UnprocessableEntity(
+            "a moment lasts at least one day".to_string(),
+        ));
+    }
+    let date = MomentDate {
+        day,
+        calendar: Some(form.calendar).filter(|calendar| !calendar.is_nil() && day.is_some()),
+        until: until.filter(|_| day.is_some()),
+        duration: form.duration,
+        lane: form.lane.filter(|lane| !lane.trim().is_empty()),
+    };
+    let url = format!("/timelines/moments/{}", moment);
+    let _: serde_json::Value = api.put(&url, guard.access_token(), &date).await?;
+    Ok(back_to(id, form.branch))
+}
+
+fn back_to(id: Uuid, branch: Option<Uuid>) -> Redirect {
+    Redirect::to(match branch {
+        Some(branch) => format!("/timelines/{}?branch={}", id, branch),
+        None => format!("/timelines/{}", id),
+    })
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct MomentForm {
+    fragment: Uuid,
+    branch: Option<Uuid>,
+    /// one based, the moment goes last when empty.
+    position: Option<i64>,
+}
+
+/// adds an existing fragment to the timeline as a moment of the shown branch.
+#[post("/<id>/moments", data = "<form>")]
+async fn add_moment(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<MomentForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let builder = MomentBuilder {
+        timeline: id,
+        fragment: form.fragment,
+        branch: form.branch,
+        position: form.position.map(|position| position - 1),
+    };
+    builder.build(api, guard.access_token()).await?;
+    Ok(back_to(id, form.branch))
+}
+
+#[derive(Debug, Clone, Copy, FromFormField)]
+enum Direction {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct MoveForm {
+    branch: Option<Uuid>,
+    direction: Option<Direction>,
+    /// one based, takes precedence over `direction`.
+    position: Option<i64>,
+}
+
+#[post("/<id>/moments/<moment>/move", data = "<form>")]
+async fn move_moment(
+    guard: Guard,
+    id: Uuid,
+    moment: Uuid,
+    api: &State<ApiClient>,
+    form: Form<MoveForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let movement = match (form.position, form.direction) {
+        (Some(position), _) => Move::To(position - 1),
+        (None, Some(Direction::Up)) => Move::Up,
+        (None, Some(Direction::Down)) => Move::Down,
+        (None, None) => {
+            return Err(ApiError::BadRequest(
+                "a move needs a direction or a position".to_string(),
+            ));
+        }
+    };
+    let (full, _) = fetch_branch(api, guard.access_token(), id, form.branch).await?;
+    let order = TimelineOrder {
+        timeline: id,
+        branch: form.branch,
+        moments: editing::apply(&full.order(form.branch), moment, movement),
+    };
+    order.save(api, guard.access_token()).await?;
+    Ok(back_to(id, form.branch))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct RemoveForm {
+    branch: Option<Uuid>,
+}
+
+#[post("/<id>/moments/<moment>/remove", data = "<form>")]
+async fn remove_moment(
+    guard: Guard,
+    id: Uuid,
+    moment: Uuid,
+    api: &State<ApiClient>,
+    form: Form<RemoveForm>,
+) -> Result<Redirect, ApiError> {
+    editing::remove(api, guard.access_token(), moment).await?;
+    Ok(back_to(id, form.branch))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct ReorderForm {
+    branch: Option<Uuid>,
+    /// one based positions by moment, moments given the same position happen together.
+    positions: HashMap<Uuid, i64>,
+}
+
+/// sets the position of every moment of a branch at once.
+#[post("/<id>/order", data = "<form>")]
+async fn reorder_moments(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<ReorderForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let (full, _) = fetch_branch(api, guard.access_token(), id, form.branch).await?;
+    let current = full.order(form.branch);
+    let mut moments = Vec::with_capacity(current.len());
+    for entry in current.iter() {
+        let position = form.positions.get(&entry.id).ok_or_else(|| {
+            ApiError::UnprocessableEntity("every moment needs a position".to_string())
+        })?;
+        moments.push(MomentOrder {
+            id: entry.id,
+            position: *position,
+        });
+    }
+    // ties keep their current order, the api spaces the positions out again.
+    moments.sort_by_key(|entry| entry.position);
+    let order = TimelineOrder {
+        timeline: id,
+        branch: form.branch,
+        moments,
+    };
+    order.save(api, guard.access_token()).await?;
+    Ok(back_to(id, form.branch))
 }
 
 #[get("/")]
@@ -71,6 +444,264 @@ async fn create_timeline(
         .unwrap();
     Redirect::to(format!("/{}/{}", form.category, form.source))
 }
+#[get("/<id>/continuity?<branch>")]
+async fn timeline_continuity(
+    guard: Guard,
+    id: Uuid,
+    branch: Option<Uuid>,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let (full, timeline) = fetch_branch(api, guard.access_token(), id, branch).await?;
+    let report = continuity::analyze(api, guard.access_token(), timeline.beats()).await?;
+    let (subject, back) = match branch.and_then(|branch| full.branch(branch)) {
+        Some(branch) => (
+            format!("{}, {}", timeline.name(), branch.name),
+            format!("/timelines/{}?branch={}", timeline.id(), branch.id),
+        ),
+        None => (
+            timeline.name().to_string(),
+            format!("/timelines/{}", timeline.id()),
+        ),
+    };
+    Ok(RawHtml(Template::render(
+        "timelines/continuity",
+        context! {
+            title: format!("continuity of {}", subject),
+            subject,
+            back,
+            report,
+        },
+    )))
+}
+
+/// the name of a branch as shown to the user, prefixed with the timeline's.
+fn branch_title(full: &Timeline, branch: Option<Uuid>) -> String {
+    match branch.and_then(|branch| full.branch(branch)) {
+        Some(branch) => format!("{}, {}", full.name(), branch.name),
+        None => full.name().to_string(),
+    }
+}
+
+/// the dated moments of a branch as an iCalendar file, undated moments are left out.
+#[get("/<id>/export.ics?<branch>")]
+async fn export_ics(
+    guard: Guard,
+    id: Uuid,
+    branch: Option<Uuid>,
+    host: &Host<'_>,
+    config: &Config,
+    api: &State<ApiClient>,
+) -> Result<(ContentType, String), ApiError> {
+    let (full, timeline) = fetch_branch(api, guard.access_token(), id, branch).await?;
+    let scheme = if config.tls_enabled() {
+        "https"
+    } else {
+        "http"
+    };
+    let events = timeline.events(&format!("{}://{}", scheme, host));
+    let calendar = ical::write(&branch_title(&full, branch), &events);
+    Ok((ContentType::Calendar, calendar))
+}
+
+#[derive(Debug, FromForm)]
+pub struct IcsForm<'r> {
+    /// an `.ics` file exported from a calendar application.
+    calendar: TempFile<'r>,
+    branch: Option<Uuid>,
+    /// the sub-plot every imported moment is put in.
+    lane: Option<String>,
+}
+
+#[get("/<id>/import?<branch>")]
+async fn import_ics_html(
+    guard: Guard,
+    id: Uuid,
+    branch: Option<Uuid>,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let (full, _) = fetch_branch(api, guard.access_token(), id, branch).await?;
+    let name = branch_title(&full, branch);
+    Ok(RawHtml(Template::render(
+        "timelines/import",
+        context! { title: format!("import into {}", name), timeline: id, name, branch },
+    )))
+}
+
+/// adds every event of an uploaded calendar to the end of a branch as a new fragment.
+#[post("/<id>/import", data = "<form>")]
+async fn import_ics<'r>(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<IcsForm<'r>>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let mut bytes = Vec::new();
+    form.calendar.open().await?.read_to_end(&mut bytes).await?;
+    let events = ical::parse(&String::from_utf8_lossy(&bytes))?;
+    let lane = form.lane.filter(|lane| !lane.trim().is_empty());
+    ical::import(api, guard.access_token(), id, form.branch, lane, &events).await?;
+    Ok(back_to(id, form.branch))
+}
+
+/// photos can only be uploaded here, immich libraries are imported from the command line so
+/// the server never fetches addresses given in a form.
+#[derive(Debug, FromForm)]
+pub struct PhotoForm<'r> {
+    title: String,
+    /// a zip of photos.
+    archive: Option<TempFile<'r>>,
+    /// hours between two photos that start a new event.
+    gap: f64,
+    /// kilometres between two photos that start a new event.
+    distance: f64,
+    /// kilometres within which an existing location is used.
+    radius: f64,
+}
+
+#[get("/photos")]
+async fn import_photos_html(guard: Guard) -> RawHtml<Template> {
+    let import = PhotoImport::new("");
+    RawHtml(Template::render(
+        "timelines/photos",
+        context! {
+            title: "import photos",
+            gap: import.grouping.gap / 3_600,
+            distance: import.grouping.distance,
+            radius: import.radius,
+        },
+    ))
+}
+
+/// creates a story and a timeline with a fragment and a moment for every event in the photos.
+#[post("/photos", data = "<form>")]
+async fn import_photos<'r>(
+    guard: Guard,
+    api: &State<ApiClient>,
+    processor: &State<ImageProcessor>,
+    form: Form<PhotoForm<'r>>,
+) -> Result<Either<Redirect, RawHtml<Template>>, ApiError> {
+    let form = form.into_inner();
+    if form.gap <= 0.0 || form.distance <= 0.0 || form.radius < 0.0 {
+        return Err(ApiError::UnprocessableEntity(
+            "the gap and distance between events have to be positive".to_string(),
+        ));
+    }
+    let mut import = PhotoImport::new(&form.title);
+    import.grouping.gap = (form.gap * 3_600.0) as i64;
+    import.grouping.distance = form.distance;
+    import.radius = form.radius;
+
+    let Some(archive) = form.archive.filter(|archive| archive.len() > 0) else {
+        return Err(ApiError::BadRequest(
+            "upload a zip of photos to import".to_string(),
+        ));
+    };
+    let mut bytes = Vec::new();
+    archive.open().await?.read_to_end(&mut bytes).await?;
+    let source = ZipSource::new(bytes);
+    let events = import.plan(&source).await?;
+    let report = import
+        .commit(&source, &events, api, guard.access_token(), processor)
+        .await?;
+    if report.failed.is_empty() {
+        return Ok(Either::Left(Redirect::to(format!(
+            "/timelines/{}",
+            report.timeline
+        ))));
+    }
+    Ok(Either::Right(RawHtml(Template::render(
+        "timelines/photos_report",
+        context! { title: "photo import", report },
+    ))))
+}
+
+/// the name of the character or location a lifespan belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LifespanSubject {
+    id: Uuid,
+    name: String,
+}
+
+fn lifespan_category(category: &str) -> Result<&str, ApiError> {
+    match category {
+        "characters" | "locations" => Ok(category),
+        _ => Err(ApiError::BadRequest(format!(
+            "{} don't have lifespans",
+            category
+        ))),
+    }
+}
+
+#[get("/lifespans/<id>?<category>")]
+async fn lifespan_html(
+    guard: Guard,
+    id: Uuid,
+    category: &str,
+    api: &State<ApiClient>,
+) -> Result<RawHtml<Template>, ApiError> {
+    let category = lifespan_category(category)?;
+    let url = format!("/{}/{}", category, id);
+    let subject: LifespanSubject = api.get_protected(&url, guard.access_token(), None).await?;
+    let lifespan = Lifespan::fetch(api, guard.access_token(), id).await?;
+    let fragments: Option<Vec<StoryFragment>> = api
+        .get_protected("/fragments/", guard.access_token(), None)
+        .await?;
+    let mut fragments = fragments.unwrap_or_default();
+    fragments.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(RawHtml(Template::render(
+        "timelines/lifespan",
+        context! { title: format!("lifespan of {}", subject.name), subject, category, lifespan, fragments },
+    )))
+}
+
+#[derive(Debug, Clone, FromForm)]
+struct LifespanForm {
+    category: String,
+    /// left empty for an open end.
+    begins: Option<Uuid>,
+    ends: Option<Uuid>,
+}
+
+#[post("/lifespans/<id>", data = "<form>")]
+async fn set_lifespan(
+    guard: Guard,
+    id: Uuid,
+    api: &State<ApiClient>,
+    form: Form<LifespanForm>,
+) -> Result<Redirect, ApiError> {
+    let form = form.into_inner();
+    let category = lifespan_category(&form.category)?;
+    let lifespan = Lifespan {
+        entity: id,
+        begins: form.begins,
+        ends: form.ends,
+    };
+    lifespan.save(api, guard.access_token()).await?;
+    Ok(Redirect::to(format!("/{}/{}", category, id)))
+}
+
 pub fn get_routes() -> Vec<Route> {
-    routes![get_timeline, list_timelines, create_html, create_timeline]
+    routes![
+        get_timeline,
+        list_timelines,
+        create_html,
+        create_timeline,
+        set_moment_date,
+        add_moment,
+        move_moment,
+        remove_moment,
+        reorder_moments,
+        create_branch_html,
+        create_branch,
+        compare_branches,
+        timeline_continuity,
+        export_ics,
+        import_ics_html,
+        import_ics,
+        import_photos_html,
+        import_photos,
+        lifespan_html,
+        set_lifespan
+    ]
 }
@@ -0,0 +1,437 @@
+//! iCalendar (RFC 5545) export and import of dated moments.
+//!
+//! every dated moment becomes an all-day `VEVENT` named after its fragment. importing goes the
+//! other way, each event becomes a fragment and a moment covering the days of the event. events
+//! with a time of day keep only the date as written, time zones are not converted, which keeps a
+//! journal entry on the day it was written wherever the calendar was exported from.
+use crate::ApiClient;
+use crate::calendars::api::{CalendarDate, CalendarDefinition};
+use crate::errors::ApiError;
+use crate::fragments::api::FragmentBuilder;
+use crate::model::StoryFragment;
+use crate::timelines::api::MomentDate;
+use crate::timelines::editing::MomentBuilder;
+use uuid::Uuid;
+
+/// lines longer than this many octets are folded.
+const LINE_LIMIT: usize = 75;
+/// how much of a fragment is kept as the description of its event.
+const SUMMARY_LIMIT: usize = 280;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// empty when an imported event has none.
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub categories: Vec<String>,
+    /// days since 1970-01-01.
+    pub start: i64,
+    /// the last day the event covers, inclusive.
+    pub until: i64,
+    /// when the event last changed, seconds since the epoch.
+    pub stamp: Option<i64>,
+}
+
+/// the first paragraph of a fragment, cut short at a word boundary.
+pub fn summary(content: &str) -> Option<String> {
+    let paragraph = crate::normalize_newlines(content);
+    let paragraph = paragraph
+        .split("\n\n")
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|paragraph| !paragraph.is_empty())?;
+    if paragraph.chars().count() <= SUMMARY_LIMIT {
+        return Some(paragraph);
+    }
+    let cut: String = paragraph.chars().take(SUMMARY_LIMIT).collect();
+    let cut = cut.rsplit_once(' ').map(|(head, _)| head).unwrap_or(&cut);
+    Some(format!("{}…", cut.trim_end()))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// appends a content line, folded so no line exceeds [`LINE_LIMIT`] octets.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn gregorian(day: i64) -> CalendarDate {
+    CalendarDefinition::gregorian().from_day(day)
+}
+
+fn format_date(day: i64) -> String {
+    let date = gregorian(day);
+    format!("{:04}{:02}{:02}", date.year, date.month, date.day)
+}
+
+fn format_stamp(epoch: i64) -> String {
+    let seconds = epoch.rem_euclid(86_400);
+    format!(
+        "{}T{:02}{:02}{:02}Z",
+        format_date(epoch.div_euclid(86_400)),
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+/// a calendar holding the given events, `name` is shown by clients that support it.
+pub fn write(name: &str, events: &[Event]) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//storyteller//timelines//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}", event.uid));
+        push_line(
+            &mut out,
+            &format!("DTSTAMP:{}", format_stamp(event.stamp.unwrap_or_default())),
+        );
+        push_line(
+            &mut out,
+            &format!("DTSTART;VALUE=DATE:{}", format_date(event.start)),
+        );
+        // the end of an all-day event is exclusive.
+        let end = event.until.max(event.start) + 1;
+        push_line(&mut out, &format!("DTEND;VALUE=DATE:{}", format_date(end)));
+        push_line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(description) = &event.description {
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape(description)));
+        }
+        if !event.categories.is_empty() {
+            let categories: Vec<String> = event.categories.iter().map(|c| escape(c)).collect();
+            push_line(&mut out, &format!("CATEGORIES:{}", categories.join(",")));
+        }
+        if let Some(url) = &event.url {
+            push_line(&mut out, &format!("URL:{}", url));
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// splits a list value on the commas that aren't escaped.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                current.extend(chars.next());
+            }
+            ',' => items.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    items.push(current);
+    items
+        .iter()
+        .map(|item| unescape(item).trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// joins folded lines back together.
+fn unfold(source: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in source.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// a content line, names and parameter keys are upper cased.
+struct ContentLine<'a> {
+    name: String,
+    params: Vec<(String, String)>,
+    value: &'a str,
+}
+
+fn content_line(line: &str) -> Option<ContentLine<'_>> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| {
+            (
+                key.trim().to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+    Some(ContentLine {
+        name,
+        params,
+        value,
+    })
+}
+
+/// a DATE or DATE-TIME value as a day number and, for DATE-TIME, seconds into that day.
+fn parse_date(value: &str) -> Result<(i64, Option<i64>), ApiError> {
+    let invalid = || ApiError::UnprocessableEntity(format!("{} is not a valid date", value));
+    let value = value.trim();
+    let (date, time) = match value.split_once(['T', 't']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    if date.len() != 8 || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let number = |range: std::ops::Range<usize>| date[range].parse::<i64>().map_err(|_| invalid());
+    let date = CalendarDate {
+        year: number(0..4)?,
+        month: number(4..6)? as u32,
+        day: number(6..8)? as u32,
+    };
+    let day = CalendarDefinition::gregorian().to_day(date)?;
+    let seconds = match time {
+        Some(time) => {
+            let time = time.trim_end_matches(['Z', 'z']);
+            if time.len() != 6 || !time.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            let number =
+                |range: std::ops::Range<usize>| time[range].parse::<i64>().map_err(|_| invalid());
+            Some(number(0..2)? * 3_600 + number(2..4)? * 60 + number(4..6)?)
+        }
+        None => None,
+    };
+    Ok((day, seconds))
+}
+
+/// whole days in a DURATION value, a part of a day doesn't reach into the next one.
+fn parse_duration(value: &str) -> Result<i64, ApiError> {
+    let invalid = || ApiError::UnprocessableEntity(format!("{} is not a valid duration", value));
+    let value = value.trim().trim_start_matches('+');
+    if value.starts_with('-') {
+        return Err(invalid());
+    }
+    let value = value.strip_prefix(['P', 'p']).ok_or_else(invalid)?;
+    let date = value.split(['T', 't']).next().unwrap_or_default();
+    let mut days = 0;
+    let mut digits = String::new();
+    for c in date.chars() {
+        match c.to_ascii_uppercase() {
+            c if c.is_ascii_digit() => digits.push(c),
+            'W' => days += digits.parse::<i64>().map_err(|_| invalid())? * 7,
+            'D' => days += digits.parse::<i64>().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        }
+        if !c.is_ascii_digit() {
+            digits.clear();
+        }
+    }
+    Ok(days)
+}
+
+#[derive(Default)]
+struct Draft {
+    uid: String,
+    summary: Option<String>,
+    description: Option<String>,
+    url: Option<String>,
+    categories: Vec<String>,
+    start: Option<(i64, Option<i64>)>,
+    end: Option<(i64, Option<i64>)>,
+    duration: Option<i64>,
+    stamp: Option<i64>,
+}
+
+impl Draft {
+    fn finish(self) -> Option<Event> {
+        let (start, _) = self.start?;
+        let until = match (self.end, self.duration) {
+            // a DATE end, or a DATE-TIME end at midnight, is exclusive.
+            (Some((end, None | Some(0))), _) => end - 1,
+            (Some((end, Some(_))), _) => end,
+            (None, Some(days)) => start + days - 1,
+            (None, None) => start,
+        };
+        Some(Event {
+            uid: self.uid,
+            summary: self
+                .summary
+                .filter(|summary| !summary.trim().is_empty())
+                .unwrap_or_else(|| "untitled event".to_string()),
+            description: self.description.filter(|d| !d.trim().is_empty()),
+            url: self.url,
+            categories: self.categories,
+            start,
+            until: until.max(start),
+            stamp: self.stamp,
+        })
+    }
+}
+
+/// the events of a calendar in the order they start, events without a start are skipped.
+/// properties of components nested in an event, such as alarms, are ignored.
+pub fn parse(source: &str) -> Result<Vec<Event>, ApiError> {
+    let mut events = Vec::new();
+    let mut components: Vec<String> = Vec::new();
+    let mut draft: Option<Draft> = None;
+    for line in unfold(source) {
+        let Some(ContentLine {
+            name,
+            params,
+            value,
+        }) = content_line(&line)
+        else {
+            continue;
+        };
+        match name.as_str() {
+            "BEGIN" => {
+                let component = value.trim().to_ascii_uppercase();
+                if component == "VEVENT" {
+                    draft = Some(Draft::default());
+                }
+                components.push(component);
+                continue;
+            }
+            "END" => {
+                if components.pop().as_deref() == Some("VEVENT") {
+                    events.extend(draft.take().and_then(Draft::finish));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        let (Some("VEVENT"), Some(draft)) = (components.last().map(String::as_str), &mut draft)
+        else {
+            continue;
+        };
+        let is_date = params
+            .iter()
+            .any(|(key, value)| key == "VALUE" && value.eq_ignore_ascii_case("DATE"));
+        match name.as_str() {
+            "UID" => draft.uid = value.trim().to_string(),
+            "SUMMARY" => draft.summary = Some(unescape(value)),
+            "DESCRIPTION" => draft.description = Some(unescape(value)),
+            "URL" => draft.url = Some(value.trim().to_string()),
+            "CATEGORIES" => draft.categories.extend(split_list(value)),
+            "DTSTART" => {
+                let (day, time) = parse_date(value)?;
+                draft.start = Some((day, time.filter(|_| !is_date)));
+            }
+            "DTEND" => {
+                let (day, time) = parse_date(value)?;
+                draft.end = Some((day, time.filter(|_| !is_date)));
+            }
+            "DURATION" => draft.duration = Some(parse_duration(value)?.max(1)),
+            "DTSTAMP" => {
+                let (day, time) = parse_date(value)?;
+                draft.stamp = Some(day * 86_400 + time.unwrap_or_default());
+            }
+            _ => {}
+        }
+    }
+    if events.is_empty() {
+        return Err(ApiError::UnprocessableEntity(
+            "the calendar holds no dated events".to_string(),
+        ));
+    }
+    events.sort_by_key(|event| (event.start, event.until));
+    Ok(events)
+}
+
+/// creates a fragment for every event under the timeline and appends them to `branch` as
+/// moments, the trunk when `None`. returns how many moments were added.
+pub async fn import(
+    api: &ApiClient,
+    access_token: &str,
+    timeline: Uuid,
+    branch: Option<Uuid>,
+    lane: Option<String>,
+    events: &[Event],
+) -> Result<usize, ApiError> {
+    for event in events {
+        let content = event.description.clone().unwrap_or_default();
+        let fragment = FragmentBuilder {
+            id: None,
+            parent: Some(timeline),
+            category: Some("timelines"),
+            name: &event.summary,
+            content: &content,
+            tags: &event.categories,
+            idx: None,
+        };
+        let fragment: StoryFragment = fragment.build(api, access_token).await?;
+        let moment = MomentBuilder {
+            timeline,
+            fragment: fragment.id,
+            branch,
+            position: None,
+        };
+        let moment = moment.build(api, access_token).await?;
+        let date = MomentDate {
+            day: Some(event.start),
+            calendar: None,
+            until: (event.until > event.start).then_some(event.until),
+            duration: None,
+            lane: lane.clone(),
+        };
+        let url = format!("/timelines/moments/{}", moment);
+        let _: serde_json::Value = api.put(&url, access_token, &date).await?;
+    }
+    Ok(events.len())
+}
@@ -0,0 +1,613 @@
+//! multi lane timeline charts, drawn straight to svg.
+//!
+//! every moment becomes a [`Span`] on a shared axis. dated moments sit at their day numbers and
+//! undated ones are placed between the dated moments around them in timeline order, when nothing
+//! is dated at all the axis is the order of the moments. spans are grouped into lanes by
+//! character, location or sub-plot, and spans overlapping within a lane are stacked into rows.
+//! the chart is laid out here rather than by graphviz so lanes and the time axis line up.
+use crate::calendars::api::{CalendarDate, CalendarDefinition, MAX_DAY};
+use crate::render::escape_html;
+use crate::timelines::continuity::{Beat, FragmentRef};
+use rocket::FromFormField;
+use std::fmt::Write;
+use uuid::Uuid;
+
+const GUTTER: f64 = 160.0;
+const PLOT_WIDTH: f64 = 1000.0;
+const AXIS_HEIGHT: f64 = 40.0;
+const ROW_HEIGHT: f64 = 22.0;
+const LANE_PADDING: f64 = 8.0;
+/// ticks are thinned out past this many so labels don't run into each other.
+const MAX_TICKS: i64 = 20;
+/// windows longer than a million years, or past the days a calendar can show, are ignored and
+/// the chart covers every span instead.
+const MAX_WINDOW: i64 = 365_250_000;
+/// the lane moments without a character, location or sub-plot end up in.
+const UNASSIGNED: &str = "unassigned";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LaneKind {
+    Characters,
+    Locations,
+    #[default]
+    Subplots,
+}
+
+impl LaneKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Characters => "characters",
+            Self::Locations => "locations",
+            Self::Subplots => "subplots",
+        }
+    }
+}
+
+/// how much of the axis a tick covers, clicking a tick zooms into the next finer level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum Zoom {
+    Eras,
+    Years,
+    Months,
+    Days,
+}
+
+impl Zoom {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Eras => "eras",
+            Self::Years => "years",
+            Self::Months => "months",
+            Self::Days => "days",
+        }
+    }
+
+    /// the coarsest level that still puts a few ticks across `days`.
+    pub fn fit(calendar: &CalendarDefinition, days: f64) -> Self {
+        let year = calendar.year_length(1).max(1) as f64;
+        if days > year * 200.0 && !calendar.eras.is_empty() {
+            Self::Eras
+        } else if days > year * 2.0 {
+            Self::Years
+        } else if days > 62.0 {
+            Self::Months
+        } else {
+            Self::Days
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Axis {
+    /// positions are day numbers.
+    Days,
+    /// nothing is dated, positions are the rank of each moment in the timeline.
+    Order,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Span {
+    pub moment: Uuid,
+    pub fragment: FragmentRef,
+    pub idx: i64,
+    pub start: f64,
+    pub length: f64,
+    /// false when the position was inferred from the surrounding moments.
+    pub dated: bool,
+    pub subplot: Option<String>,
+    /// the dates covered, in the calendar the chart is drawn in.
+    pub date: Option<String>,
+    /// set when the span shares time with another span in its lane.
+    #[serde(default)]
+    pub overlaps: bool,
+}
+
+impl Span {
+    pub fn end(&self) -> f64 {
+        self.start + self.length
+    }
+}
+
+/// fills in the positions of undated spans, `spans` must be in timeline order.
+pub fn place(spans: &mut [Span]) -> Axis {
+    if !spans.iter().any(|span| span.dated) {
+        let mut ranks: Vec<i64> = spans.iter().map(|span| span.idx).collect();
+        ranks.dedup();
+        for span in spans.iter_mut() {
+            span.start = ranks.iter().position(|idx| *idx == span.idx).unwrap_or(0) as f64;
+            span.length = 1.0;
+        }
+        return Axis::Order;
+    }
+
+    let dated: Vec<(i64, f64, f64)> = spans
+        .iter()
+        .filter(|span| span.dated)
+        .map(|span| (span.idx, span.start, span.end()))
+        .collect();
+    let undated: Vec<i64> = spans
+        .iter()
+        .filter(|span| !span.dated)
+        .map(|span| span.idx)
+        .collect();
+    for span in spans.iter_mut().filter(|span| !span.dated) {
+        // moments sharing an idx with a dated moment happen alongside it.
+        if let Some((_, start, end)) = dated.iter().find(|(idx, ..)| *idx == span.idx) {
+            span.start = *start;
+            span.length = end - start;
+            continue;
+        }
+        let previous = dated.iter().rev().find(|(idx, ..)| *idx < span.idx);
+        let next = dated.iter().find(|(idx, ..)| *idx > span.idx);
+        let mut run: Vec<i64> = undated
+            .iter()
+            .copied()
+            .filter(|idx| {
+                previous.is_none_or(|(before, ..)| idx > before)
+                    && next.is_none_or(|(after, ..)| idx < after)
+            })
+            .collect();
+        run.dedup();
+        let count = run.len() as f64;
+        let rank = run.iter().position(|idx| *idx == span.idx).unwrap_or(0) as f64;
+        match (previous, next) {
+            (Some((_, _, from)), Some((_, to, _))) => {
+                let step = (to - from).max(0.0) / (count + 1.0);
+                span.start = from + step * (rank + 1.0);
+                if step > 0.0 {
+                    span.length = span.length.min(step);
+                }
+            }
+            (Some((_, _, from)), None) => span.start = from + rank,
+            (None, Some((_, to, _))) => span.start = to - (count - rank),
+            (None, None) => span.start = rank,
+        }
+    }
+    Axis::Days
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lane {
+    pub name: String,
+    pub href: Option<String>,
+    /// spans that don't overlap share a row.
+    pub rows: Vec<Vec<Span>>,
+}
+
+impl Lane {
+    pub fn new(name: &str, href: Option<String>, mut spans: Vec<Span>) -> Self {
+        spans.sort_by(|a, b| a.start.total_cmp(&b.start));
+        for i in 0..spans.len() {
+            let overlaps = spans.iter().enumerate().any(|(j, other)| {
+                j != i && other.start < spans[i].end() && spans[i].start < other.end()
+            });
+            spans[i].overlaps = overlaps;
+        }
+        let mut rows: Vec<Vec<Span>> = Vec::new();
+        for span in spans {
+            let row = rows
+                .iter_mut()
+                .find(|row| row.last().is_none_or(|last| last.end() <= span.start));
+            match row {
+                Some(row) => row.push(span),
+                None => rows.push(vec![span]),
+            }
+        }
+        Self {
+            name: name.to_string(),
+            href,
+            rows,
+        }
+    }
+
+    fn spans(&self) -> impl Iterator<Item = &Span> {
+        self.rows.iter().flatten()
+    }
+}
+
+/// one lane per sub-plot in order of first appearance.
+pub fn by_subplot(spans: &[Span]) -> Vec<Lane> {
+    let mut names: Vec<Option<&str>> = Vec::new();
+    for span in spans {
+        let name = span
+            .subplot
+            .as_deref()
+            .filter(|name| !name.trim().is_empty());
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    // moments outside any sub-plot go last.
+    names.sort_by_key(|name| name.is_none());
+    names
+        .into_iter()
+        .map(|name| {
+            let members = spans
+                .iter()
+                .filter(|span| {
+                    span.subplot
+                        .as_deref()
+                        .filter(|name| !name.trim().is_empty())
+                        == name
+                })
+                .cloned()
+                .collect();
+            Lane::new(name.unwrap_or(UNASSIGNED), None, members)
+        })
+        .collect()
+}
+
+/// one lane per character or location related to a moment's fragment, a moment shows up in the
+/// lane of every entity it involves. `beats` are fetched beats of the same timeline.
+pub fn by_entity(spans: &[Span], beats: &[Beat], kind: LaneKind) -> Vec<Lane> {
+    let targets = |fragment: Uuid| {
+        beats
+            .iter()
+            .filter(move |beat| beat.fragment.id == fragment)
+            .flat_map(move |beat| match kind {
+                LaneKind::Locations => beat.locations.iter(),
+                _ => beat.characters.iter(),
+            })
+    };
+    let mut lanes: Vec<(String, Option<String>, Vec<Span>)> = Vec::new();
+    let mut unassigned = Vec::new();
+    for span in spans {
+        let mut placed = false;
+        for target in targets(span.fragment.id) {
+            let href = format!("/{}/{}", target.category, target.id);
+            match lanes
+                .iter_mut()
+                .find(|(_, link, _)| link.as_ref() == Some(&href))
+            {
+                Some((_, _, members)) if members.iter().any(|s| s.moment == span.moment) => {}
+                Some((_, _, members)) => members.push(span.clone()),
+                None => lanes.push((target.name.clone(), Some(href), vec![span.clone()])),
+            }
+            placed = true;
+        }
+        if !placed {
+            unassigned.push(span.clone());
+        }
+    }
+    lanes.sort_by(|a, b| a.0.cmp(&b.0));
+    if !unassigned.is_empty() {
+        lanes.push((UNASSIGNED.to_string(), None, unassigned));
+    }
+    lanes
+        .into_iter()
+        .map(|(name, href, members)| Lane::new(&name, href, members))
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tick {
+    pub position: f64,
+    pub label: String,
+    /// the level and window `[from, to)` a click on the tick zooms into.
+    pub zoom: Option<(Zoom, i64, i64)>,
+}
+
+/// the smallest 1, 2 or 5 times a power of ten that keeps `count` units under [`MAX_TICKS`].
+fn nice_step(count: i64) -> i64 {
+    let mut magnitude: i64 = 1;
+    loop {
+        for multiple in [1, 2, 5] {
+            let step = multiple * magnitude;
+            if count / step < MAX_TICKS {
+                return step;
+            }
+        }
+        magnitude = magnitude.saturating_mul(10);
+    }
+}
+
+fn year_start(calendar: &CalendarDefinition, year: i64) -> Option<i64> {
+    calendar
+        .to_day(CalendarDate {
+            year,
+            month: 1,
+            day: 1,
+        })
+        .ok()
+}
+
+/// ticks across the window `[from, to)` at the given level.
+pub fn ticks(calendar: &CalendarDefinition, zoom: Zoom, from: i64, to: i64) -> Vec<Tick> {
+    let first = calendar.from_day(from);
+    let last = calendar.from_day((to - 1).max(from));
+    match zoom {
+        Zoom::Eras if calendar.eras.is_empty() => ticks(calendar, Zoom::Years, from, to),
+        Zoom::Eras => {
+            let mut eras: Vec<_> = calendar.eras.iter().collect();
+            eras.sort_by_key(|era| era.start);
+            let mut starts: Vec<(i64, String)> = Vec::new();
+            if let (Some(era), _) = calendar.era(first.year) {
+                starts.push((from, era.name.clone()));
+            }
+            for era in eras {
+                if era.start > first.year
+                    && era.start <= last.year
+                    && let Some(start) = year_start(calendar, era.start)
+                {
+                    starts.push((start, era.name.clone()));
+                }
+            }
+            let ends: Vec<i64> = starts.iter().skip(1).map(|(start, _)| *start).collect();
+            starts
+                .into_iter()
+                .enumerate()
+                .map(|(i, (start, label))| Tick {
+                    position: start as f64,
+                    label,
+                    zoom: Some((Zoom::Years, start, ends.get(i).copied().unwrap_or(to))),
+                })
+                .collect()
+        }
+        Zoom::Years => {
+            let step = nice_step(last.year - first.year + 1);
+            let mut year = first.year.div_euclid(step) * step;
+            let mut ticks = Vec::new();
+            while year <= last.year {
+                if let (Some(start), Some(end)) = (
+                    year_start(calendar, year),
+                    year_start(calendar, year + step),
+                ) {
+                    ticks.push(Tick {
+                        position: start as f64,
+                        label: calendar.year_label(year),
+                        zoom: Some((Zoom::Months, start, end)),
+                    });
+                }
+                year += step;
+            }
+            ticks
+        }
+        Zoom::Months => {
+            let per_year = calendar.months.len() as i64;
+            let offset = first.month as i64 - 1;
+            let count =
+                (last.year - first.year) * per_year + last.month as i64 - first.month as i64 + 1;
+            let step = nice_step(count);
+            let date = |index: i64| CalendarDate {
+                year: first.year + (offset + index).div_euclid(per_year),
+                month: ((offset + index).rem_euclid(per_year) + 1) as u32,
+                day: 1,
+            };
+            (0..count)
+                .step_by(step as usize)
+                .filter_map(|index| {
+                    let month = date(index);
+                    let start = calendar.to_day(month).ok()?;
+                    let end = calendar.to_day(date(index + step)).ok()?;
+                    Some(Tick {
+                        position: start as f64,
+                        label: format!(
+                            "{} {}",
+                            calendar.months[month.month as usize - 1].name,
+                            calendar.year_label(month.year)
+                        ),
+                        zoom: Some((Zoom::Days, start, end)),
+                    })
+                })
+                .collect()
+        }
+        Zoom::Days => {
+            let step = nice_step(to - from);
+            (from..to)
+                .step_by(step as usize)
+                .map(|day| {
+                    let date = calendar.from_day(day);
+                    Tick {
+                        position: day as f64,
+                        label: format!(
+                            "{} {}",
+                            date.day,
+                            calendar.months[date.month as usize - 1].name
+                        ),
+                        zoom: None,
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chart {
+    pub axis: Axis,
+    pub zoom: Option<Zoom>,
+    pub from: f64,
+    pub to: f64,
+    pub lanes: Vec<Lane>,
+    pub ticks: Vec<Tick>,
+}
+
+impl Chart {
+    /// lays out `lanes` over `window`, or over every span when no window is given. on a day axis
+    /// the zoom level defaults to whatever fits the window.
+    pub fn new(
+        axis: Axis,
+        lanes: Vec<Lane>,
+        calendar: &CalendarDefinition,
+        zoom: Option<Zoom>,
+        window: Option<(i64, i64)>,
+    ) -> Self {
+        let spans = || lanes.iter().flat_map(|lane| lane.spans());
+        let days = -MAX_DAY..=MAX_DAY;
+        let window = window.filter(|(from, to)| {
+            days.contains(from) && days.contains(to) && from < to && to - from <= MAX_WINDOW
+        });
+        let (from, to) = match window {
+            Some((from, to)) => (from as f64, to as f64),
+            None => {
+                let from = spans().map(|span| span.start).fold(f64::INFINITY, f64::min);
+                let to = spans()
+                    .map(|span| span.end())
+                    .fold(f64::NEG_INFINITY, f64::max);
+                if from.is_finite() && to > from {
+                    (from.floor(), to.ceil())
+                } else {
+                    (0.0, 1.0)
+                }
+            }
+        };
+        let (zoom, ticks) = match axis {
+            Axis::Days => {
+                let zoom = zoom.unwrap_or_else(|| Zoom::fit(calendar, to - from));
+                (Some(zoom), ticks(calendar, zoom, from as i64, to as i64))
+            }
+            Axis::Order => (None, Vec::new()),
+        };
+        Self {
+            axis,
+            zoom,
+            from,
+            to,
+            lanes,
+            ticks,
+        }
+    }
+
+    fn x(&self, position: f64) -> f64 {
+        GUTTER + (position - self.from) / (self.to - self.from) * PLOT_WIDTH
+    }
+
+    /// the chart as a standalone svg document. `base` is the page the chart is shown on, tick
+    /// labels link back to it with a narrower window.
+    pub fn svg(&self, base: &str) -> String {
+        let lane_heights: Vec<f64> = self
+            .lanes
+            .iter()
+            .map(|lane| lane.rows.len().max(1) as f64 * ROW_HEIGHT + LANE_PADDING * 2.0)
+            .collect();
+        let height = AXIS_HEIGHT + lane_heights.iter().sum::<f64>();
+        let width = GUTTER + PLOT_WIDTH + 20.0;
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" id="svg" class="timeline-chart" viewBox="0 0 {} {}">"#,
+            width, height
+        );
+        svg.push_str(concat!(
+            "<style>",
+            ".lane-band{fill:#f4f1ec}.lane-band.odd{fill:#ffffff}",
+            ".tick{stroke:#d5d0c8;stroke-width:1}",
+            ".span{fill:#e8896b;stroke:#9c4a31}.span.undated{fill:#f3c9b8;stroke-dasharray:3 2}",
+            ".span.overlap{stroke:#5b2a9c;stroke-width:2}",
+            "text{font-family:sans-serif;font-size:11px;fill:#333}",
+            ".lane-name{font-weight:bold}",
+            "</style>"
+        ));
+
+        for tick in self.ticks.iter() {
+            if tick.position < self.from || tick.position >= self.to {
+                continue;
+            }
+            let x = self.x(tick.position);
+            let _ = write!(
+                svg,
+                r#"<line class="tick" x1="{x:.1}" y1="{}" x2="{x:.1}" y2="{height}"/>"#,
+                AXIS_HEIGHT - 6.0
+            );
+            let label = format!(
+                r#"<text x="{:.1}" y="{}">{}</text>"#,
+                x + 3.0,
+                AXIS_HEIGHT - 12.0,
+                escape_html(&tick.label)
+            );
+            match tick.zoom {
+                Some((zoom, from, to)) => {
+                    let href = format!("{}&zoom={}&from={}&to={}", base, zoom.as_str(), from, to);
+                    let _ = write!(svg, r#"<a href="{}">{}</a>"#, escape_html(&href), label);
+                }
+                None => svg.push_str(&label),
+            }
+        }
+
+        let mut y = AXIS_HEIGHT;
+        for (index, (lane, lane_height)) in self.lanes.iter().zip(lane_heights).enumerate() {
+            let parity = if index % 2 == 1 { " odd" } else { "" };
+            let _ = write!(
+                svg,
+                r#"<rect class="lane-band{}" x="0" y="{y:.1}" width="{width}" height="{lane_height:.1}"/>"#,
+                parity
+            );
+            let name = format!(
+                r#"<text class="lane-name" x="8" y="{:.1}">{}</text>"#,
+                y + LANE_PADDING + ROW_HEIGHT * 0.7,
+                escape_html(&truncate(&lane.name, GUTTER - 16.0))
+            );
+            match &lane.href {
+                Some(href) => {
+                    let _ = write!(svg, r#"<a href="{}">{}</a>"#, escape_html(href), name);
+                }
+                None => svg.push_str(&name),
+            }
+            for (row, spans) in lane.rows.iter().enumerate() {
+                let top = y + LANE_PADDING + row as f64 * ROW_HEIGHT;
+                for span in spans {
+                    svg.push_str(&self.span(span, top));
+                }
+            }
+            y += lane_height;
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    fn span(&self, span: &Span, top: f64) -> String {
+        let start = span.start.max(self.from);
+        let end = span.end().min(self.to);
+        if end <= start {
+            return String::new();
+        }
+        let x = self.x(start);
+        let width = (self.x(end) - x).max(3.0);
+        let mut class = String::from("span");
+        if !span.dated {
+            class.push_str(" undated");
+        }
+        if span.overlaps {
+            class.push_str(" overlap");
+        }
+        let title = match &span.date {
+            Some(date) => format!("{}, {}", span.fragment.name, date),
+            None => format!("{}, undated", span.fragment.name),
+        };
+        let mut out = format!(
+            r#"<a href="/fragments/{}"><rect class="{}" x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="3"><title>{}</title></rect>"#,
+            span.fragment.id,
+            class,
+            x,
+            top + 2.0,
+            width,
+            ROW_HEIGHT - 4.0,
+            escape_html(&title)
+        );
+        if width > 30.0 {
+            let _ = write!(
+                out,
+                r#"<text x="{:.1}" y="{:.1}">{}</text>"#,
+                x + 4.0,
+                top + ROW_HEIGHT * 0.7,
+                escape_html(&truncate(&span.fragment.name, width - 8.0))
+            );
+        }
+        out.push_str("</a>");
+        out
+    }
+}
+
+/// cuts `text` down to roughly what fits in `width` pixels of 11px text.
+pub(crate) fn truncate(text: &str, width: f64) -> String {
+    let fits = (width / 6.5).max(1.0) as usize;
+    if text.chars().count() <= fits {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(fits.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
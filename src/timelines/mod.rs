@@ -1,3 +1,9 @@
 pub mod api;
+pub mod branches;
+pub mod continuity;
+pub mod editing;
 pub mod frontend;
+pub mod ical;
+pub mod lanes;
+pub mod photos;
 pub use frontend::get_routes;
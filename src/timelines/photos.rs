@@ -0,0 +1,732 @@
+//! timelines imported from a photo library.
+//!
+//! photos are listed by a [`PhotoSource`] with the time they were taken and where, read from
+//! their EXIF tags or from the library's own metadata. sorted by time they're split into events
+//! wherever the gap between two photos, in time or in distance, grows too large. each event
+//! becomes a fragment of a new story with its photos attached, a moment on the dates it covers
+//! and a relation to the location it happened at, an existing location when one is close enough
+//! and a new one otherwise.
+//!
+//! times are kept on the clock of the camera, the same as iCalendar imports, so a photo taken
+//! in the evening abroad isn't moved to the next day.
+use crate::ApiClient;
+use crate::assets::images::{self, ExifTag, ImageBuilder, ImageProcessor};
+use crate::calendars::api::{CalendarDate, CalendarDefinition};
+use crate::errors::ApiError;
+use crate::fragments::api::FragmentBuilder;
+use crate::locations::{Coordinates, LocationBuilder, LocationRender};
+use crate::model::{Location, Story, StoryFragment};
+use crate::relations::Relation;
+use crate::render::SupportedRender;
+use crate::stories::StoryBuilder;
+use crate::timelines::api::{MomentDate, TimelineBuilder};
+use crate::timelines::editing::MomentBuilder;
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use walkdir::WalkDir;
+
+const GPS_LATITUDE_REF: u16 = 0x0001;
+const GPS_LATITUDE: u16 = 0x0002;
+const GPS_LONGITUDE_REF: u16 = 0x0003;
+const GPS_LONGITUDE: u16 = 0x0004;
+const DATE_TIME: u16 = 0x0132;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+const DAY: i64 = 86_400;
+
+/// the largest photo read from an archive, in bytes.
+pub const MAX_PHOTO_SIZE: u64 = 64 * 1024 * 1024;
+/// the most read from a single archive while listing its photos, in bytes.
+pub const MAX_ARCHIVE_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Photo {
+    /// what the source knows the photo by, a path or an asset id.
+    pub id: String,
+    pub name: String,
+    /// seconds since 1970-01-01 on the camera's clock, see [`wall_clock`].
+    pub taken: Option<i64>,
+    pub position: Option<Coordinates>,
+    /// a place name the source already knows, such as the city the photo was taken in.
+    pub place: Option<String>,
+}
+
+/// seconds since 1970-01-01 from `2023:07:09 20:36:33`, `2023-07-09 20:36:33` or an RFC 3339
+/// time, any offset is dropped.
+pub fn wall_clock(text: &str) -> Option<i64> {
+    let numbers: Vec<i64> = text
+        .trim()
+        .get(..19)?
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|number| number.parse().ok())
+        .collect::<Option<_>>()?;
+    let [year, month, day, hour, minute, second] = numbers[..] else {
+        return None;
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let date = CalendarDate {
+        year,
+        month: u32::try_from(month).ok()?,
+        day: u32::try_from(day).ok()?,
+    };
+    let day = CalendarDefinition::gregorian().to_day(date).ok()?;
+    Some(day * DAY + hour * 3_600 + minute * 60 + second)
+}
+
+/// degrees from a GPS value, either decimal or the degrees, minutes and seconds `nom_exif`
+/// formats as `URationalArray[38/1 (38.0000), 42/1 (42.0000), 2040/100 (20.4000)]`.
+fn degrees(value: &str) -> Option<f64> {
+    let list = match value.split_once('[') {
+        Some((_, rest)) => rest.trim_end().trim_end_matches(']'),
+        None => value,
+    };
+    let parts: Vec<f64> = list
+        .split(',')
+        .map(|part| {
+            let number = part.split_whitespace().next()?;
+            match number.split_once('/') {
+                Some((numerator, denominator)) => {
+                    let denominator: f64 = denominator.parse().ok()?;
+                    let numerator: f64 = numerator.parse().ok()?;
+                    (denominator != 0.0).then(|| numerator / denominator)
+                }
+                None => number.parse().ok(),
+            }
+        })
+        .collect::<Option<_>>()?;
+    match parts[..] {
+        [degrees] => Some(degrees),
+        [degrees, minutes] => Some(degrees + minutes / 60.0),
+        [degrees, minutes, seconds] => Some(degrees + minutes / 60.0 + seconds / 3_600.0),
+        _ => None,
+    }
+}
+
+impl Photo {
+    /// a photo dated by its DateTimeOriginal tag and placed by its GPS tags.
+    pub fn from_exif(id: &str, name: &str, tags: &[ExifTag]) -> Self {
+        let find = |code: u16| {
+            tags.iter()
+                .filter(move |tag| tag.tag() == code)
+                .map(|tag| tag.value().trim())
+        };
+        let taken = find(DATE_TIME_ORIGINAL)
+            .chain(find(DATE_TIME))
+            .find_map(wall_clock);
+        // the interoperability index shares its code with the latitude reference.
+        let sign = |code: u16, negative: &str, positive: &str| {
+            find(code).find_map(|value| match value {
+                value if value.eq_ignore_ascii_case(negative) => Some(-1.0),
+                value if value.eq_ignore_ascii_case(positive) => Some(1.0),
+                _ => None,
+            })
+        };
+        let coordinate = |code: u16, limit: f64| {
+            find(code)
+                .find_map(degrees)
+                .filter(|value| value.abs() <= limit)
+        };
+        let position = (|| {
+            Some(Coordinates {
+                latitude: coordinate(GPS_LATITUDE, 90.0)? * sign(GPS_LATITUDE_REF, "S", "N")?,
+                longitude: coordinate(GPS_LONGITUDE, 180.0)? * sign(GPS_LONGITUDE_REF, "W", "E")?,
+            })
+        })();
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            taken,
+            position,
+            place: None,
+        }
+    }
+}
+
+/// where photos are imported from.
+#[rocket::async_trait]
+pub trait PhotoSource: Send + Sync {
+    /// every photo with its metadata, in no particular order.
+    async fn photos(&self) -> Result<Vec<Photo>, ApiError>;
+    /// the original file of a photo listed by [`PhotoSource::photos`].
+    async fn read(&self, photo: &Photo) -> Result<Vec<u8>, ApiError>;
+}
+
+/// whether the image pipeline can decode a file, going by its extension.
+fn is_photo(path: &str) -> bool {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension);
+    extension.is_some_and(|extension| {
+        ["jpg", "jpeg", "png", "tif", "tiff", "webp"]
+            .iter()
+            .any(|supported| extension.eq_ignore_ascii_case(supported))
+    })
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl PhotoSource for DirectorySource {
+    async fn photos(&self) -> Result<Vec<Photo>, ApiError> {
+        let mut photos = Vec::new();
+        for entry in WalkDir::new(&self.root) {
+            let entry = entry.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+            let relative = entry
+                .path()
+                .strip_prefix(&self.root)
+                .unwrap_or(entry.path());
+            let path = relative.to_string_lossy().replace('\\', "/");
+            if entry.file_type().is_file() && is_photo(&path) {
+                let tags = images::exif_tags(&std::fs::read(entry.path())?)?;
+                photos.push(Photo::from_exif(&path, file_name(&path), &tags));
+            }
+        }
+        Ok(photos)
+    }
+
+    async fn read(&self, photo: &Photo) -> Result<Vec<u8>, ApiError> {
+        Ok(std::fs::read(self.root.join(&photo.id))?)
+    }
+}
+
+/// reads at most [`MAX_PHOTO_SIZE`] bytes, the size in the entry's header can't be trusted.
+fn read_photo(reader: impl Read, size: u64, path: &str) -> Result<Vec<u8>, ApiError> {
+    let too_large = || {
+        ApiError::UnprocessableEntity(format!(
+            "{} is larger than {} MiB",
+            path,
+            MAX_PHOTO_SIZE / (1024 * 1024)
+        ))
+    };
+    if size > MAX_PHOTO_SIZE {
+        return Err(too_large());
+    }
+    let mut bytes = Vec::new();
+    reader.take(MAX_PHOTO_SIZE + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_PHOTO_SIZE {
+        return Err(too_large());
+    }
+    Ok(bytes)
+}
+
+/// a zip archive of photos, held in memory. photos over [`MAX_PHOTO_SIZE`] and archives
+/// holding more than [`MAX_ARCHIVE_SIZE`] of photos are rejected.
+pub struct ZipSource {
+    bytes: Vec<u8>,
+}
+
+impl ZipSource {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+}
+
+#[rocket::async_trait]
+impl PhotoSource for ZipSource {
+    async fn photos(&self) -> Result<Vec<Photo>, ApiError> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(&self.bytes))?;
+        let mut photos = Vec::new();
+        let mut total = 0;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            // enclosed_name rejects absolute paths and `..` components.
+            let Some(path) = entry.enclosed_name() else {
+                continue;
+            };
+            let path = path.to_string_lossy().replace('\\', "/");
+            if entry.is_file() && is_photo(&path) {
+                let size = entry.size();
+                let bytes = read_photo(&mut entry, size, &path)?;
+                total += bytes.len() as u64;
+                if total > MAX_ARCHIVE_SIZE {
+                    return Err(ApiError::UnprocessableEntity(format!(
+                        "the archive holds more than {} GiB of photos",
+                        MAX_ARCHIVE_SIZE / (1024 * 1024 * 1024)
+                    )));
+                }
+                let tags = images::exif_tags(&bytes)?;
+                photos.push(Photo::from_exif(&path, file_name(&path), &tags));
+            }
+        }
+        Ok(photos)
+    }
+
+    async fn read(&self, photo: &Photo) -> Result<Vec<u8>, ApiError> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(&self.bytes))?;
+        let mut entry = archive.by_name(&photo.id)?;
+        let size = entry.size();
+        read_photo(&mut entry, size, &photo.id)
+    }
+}
+
+/// a server speaking the immich api, either a single album or every image in the library.
+pub struct ImmichSource {
+    client: reqwest::Client,
+    /// the server's address, without the `/api` suffix.
+    url: String,
+    key: String,
+    album: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImmichAsset {
+    id: String,
+    original_file_name: String,
+    #[serde(rename = "type", default)]
+    kind: String,
+    /// the camera's clock, written as if it were utc.
+    local_date_time: Option<String>,
+    exif_info: Option<ImmichExif>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImmichExif {
+    date_time_original: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    city: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImmichAlbum {
+    assets: Vec<ImmichAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImmichSearch {
+    assets: ImmichPage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImmichPage {
+    items: Vec<ImmichAsset>,
+    next_page: Option<String>,
+}
+
+impl ImmichAsset {
+    fn into_photo(self) -> Photo {
+        let exif = self.exif_info;
+        let taken = self
+            .local_date_time
+            .as_deref()
+            .or(exif
+                .as_ref()
+                .and_then(|exif| exif.date_time_original.as_deref()))
+            .and_then(wall_clock);
+        let position = exif.as_ref().and_then(|exif| {
+            Some(Coordinates {
+                latitude: exif.latitude?,
+                longitude: exif.longitude?,
+            })
+        });
+        Photo {
+            id: self.id,
+            name: self.original_file_name,
+            taken,
+            position,
+            place: exif.and_then(|exif| exif.city),
+        }
+    }
+}
+
+impl ImmichSource {
+    const PAGE_SIZE: usize = 250;
+
+    pub fn new(url: &str, key: &str, album: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim().trim_end_matches('/').to_string(),
+            key: key.trim().to_string(),
+            album,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        let response = request.header("x-api-key", &self.key).send().await?;
+        Ok(response.error_for_status()?)
+    }
+
+    async fn assets(&self) -> Result<Vec<ImmichAsset>, ApiError> {
+        if let Some(album) = &self.album {
+            let url = format!("{}/api/albums/{}", self.url, album);
+            let album: ImmichAlbum = self.send(self.client.get(url)).await?.json().await?;
+            return Ok(album.assets);
+        }
+        let url = format!("{}/api/search/metadata", self.url);
+        let mut assets = Vec::new();
+        let mut page = 1;
+        loop {
+            let query = serde_json::json!({
+                "page": page,
+                "size": Self::PAGE_SIZE,
+                "type": "IMAGE",
+                "withExif": true,
+            });
+            let request = self.client.post(&url).json(&query);
+            let search: ImmichSearch = self.send(request).await?.json().await?;
+            assets.extend(search.assets.items);
+            match search.assets.next_page.and_then(|next| next.parse().ok()) {
+                Some(next) if next > page => page = next,
+                _ => return Ok(assets),
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl PhotoSource for ImmichSource {
+    async fn photos(&self) -> Result<Vec<Photo>, ApiError> {
+        Ok(self
+            .assets()
+            .await?
+            .into_iter()
+            .filter(|asset| asset.kind.is_empty() || asset.kind == "IMAGE")
+            .map(ImmichAsset::into_photo)
+            .collect())
+    }
+
+    async fn read(&self, photo: &Photo) -> Result<Vec<u8>, ApiError> {
+        let url = format!("{}/api/assets/{}/original", self.url, photo.id);
+        let response = self.send(self.client.get(url)).await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+/// when consecutive photos stop belonging to the same event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grouping {
+    /// seconds between two photos.
+    pub gap: i64,
+    /// kilometres between two photos that both have a position.
+    pub distance: f64,
+}
+
+impl Default for Grouping {
+    fn default() -> Self {
+        Self {
+            gap: 6 * 3_600,
+            distance: 25.0,
+        }
+    }
+}
+
+/// photos taken close together, undated photos all end up in a single event of their own.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhotoEvent {
+    pub photos: Vec<Photo>,
+}
+
+fn iso_date(day: i64) -> String {
+    let date = CalendarDefinition::gregorian().from_day(day);
+    format!("{:04}-{:02}-{:02}", date.year, date.month, date.day)
+}
+
+impl PhotoEvent {
+    pub fn start(&self) -> Option<i64> {
+        self.photos.iter().filter_map(|photo| photo.taken).min()
+    }
+
+    pub fn end(&self) -> Option<i64> {
+        self.photos.iter().filter_map(|photo| photo.taken).max()
+    }
+
+    /// the centre of the photos that have a position.
+    pub fn position(&self) -> Option<Coordinates> {
+        let positions: Vec<Coordinates> = self
+            .photos
+            .iter()
+            .filter_map(|photo| photo.position)
+            .collect();
+        if positions.is_empty() {
+            return None;
+        }
+        let count = positions.len() as f64;
+        Some(Coordinates {
+            latitude: positions.iter().map(|p| p.latitude).sum::<f64>() / count,
+            longitude: positions.iter().map(|p| p.longitude).sum::<f64>() / count,
+        })
+    }
+
+    /// the first place name a photo has.
+    pub fn place(&self) -> Option<&str> {
+        self.photos.iter().find_map(|photo| photo.place.as_deref())
+    }
+
+    /// the place and days of the event, `Lisbon, 2023-07-09 to 2023-07-11`.
+    pub fn name(&self) -> String {
+        let (Some(start), Some(end)) = (self.start(), self.end()) else {
+            return "Undated photos".to_string();
+        };
+        let place = self.place().unwrap_or("Photos");
+        let (first, last) = (start.div_euclid(DAY), end.div_euclid(DAY));
+        if first == last {
+            format!("{}, {}", place, iso_date(first))
+        } else {
+            format!("{}, {} to {}", place, iso_date(first), iso_date(last))
+        }
+    }
+
+    /// a markdown list of the photos and the time each was taken.
+    pub fn content(&self) -> String {
+        self.photos
+            .iter()
+            .map(|photo| match photo.taken {
+                Some(taken) => {
+                    let seconds = taken.rem_euclid(DAY);
+                    format!(
+                        "- {} ({:02}:{:02})\n",
+                        photo.name,
+                        seconds / 3_600,
+                        seconds % 3_600 / 60
+                    )
+                }
+                None => format!("- {}\n", photo.name),
+            })
+            .collect()
+    }
+
+    fn admits(&self, photo: &Photo, grouping: Grouping) -> bool {
+        let Some(last) = self.photos.last() else {
+            return true;
+        };
+        let close_in_time = match (last.taken, photo.taken) {
+            (Some(last), Some(taken)) => taken - last <= grouping.gap,
+            (None, None) => return true,
+            _ => false,
+        };
+        let previous = self.photos.iter().rev().find_map(|photo| photo.position);
+        let close_in_space = match (previous, photo.position) {
+            (Some(previous), Some(position)) => previous.distance(&position) <= grouping.distance,
+            _ => true,
+        };
+        close_in_time && close_in_space
+    }
+}
+
+/// splits photos into events in the order they were taken, undated photos go last.
+pub fn group(mut photos: Vec<Photo>, grouping: Grouping) -> Vec<PhotoEvent> {
+    photos.sort_by(|a, b| {
+        (a.taken.is_none(), a.taken, &a.name).cmp(&(b.taken.is_none(), b.taken, &b.name))
+    });
+    let mut events: Vec<PhotoEvent> = Vec::new();
+    for photo in photos {
+        match events.last_mut() {
+            Some(event) if event.admits(&photo, grouping) => event.photos.push(photo),
+            _ => events.push(PhotoEvent {
+                photos: vec![photo],
+            }),
+        }
+    }
+    events
+}
+
+/// the closest location within `radius` kilometres.
+pub fn nearest(locations: &[(Uuid, Coordinates)], at: Coordinates, radius: f64) -> Option<Uuid> {
+    locations
+        .iter()
+        .map(|(id, position)| (*id, position.distance(&at)))
+        .filter(|(_, distance)| *distance <= radius)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(id, _)| id)
+}
+
+/// an event that couldn't be imported, the import carries on with the events after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedEvent {
+    pub name: String,
+    pub error: String,
+}
+
+/// what [`PhotoImport::commit`] created. nothing is rolled back, so an event that failed part
+/// way through may have left a fragment or location behind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhotoReport {
+    pub story: Uuid,
+    pub timeline: Uuid,
+    /// the number of events imported.
+    pub imported: usize,
+    pub failed: Vec<FailedEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhotoImport {
+    /// the title of the story and timeline created.
+    pub title: String,
+    pub grouping: Grouping,
+    /// how close in kilometres an existing location has to be for an event to use it.
+    pub radius: f64,
+}
+
+impl PhotoImport {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            grouping: Grouping::default(),
+            radius: 1.0,
+        }
+    }
+
+    /// lists and groups the photos of a source without creating anything.
+    pub async fn plan<S: PhotoSource + ?Sized>(
+        &self,
+        source: &S,
+    ) -> Result<Vec<PhotoEvent>, ApiError> {
+        let photos = source.photos().await?;
+        if photos.is_empty() {
+            return Err(ApiError::BadRequest(
+                "no photos found to import".to_string(),
+            ));
+        }
+        Ok(group(photos, self.grouping))
+    }
+
+    /// creates a story with a fragment for every event and a timeline of those fragments. an
+    /// event that fails is reported and skipped rather than ending the import.
+    pub async fn commit<S: PhotoSource + ?Sized>(
+        &self,
+        source: &S,
+        events: &[PhotoEvent],
+        api: &ApiClient,
+        access_token: &str,
+        processor: &ImageProcessor,
+    ) -> Result<PhotoReport, ApiError> {
+        let description = Some(format!("{} events imported from photos", events.len()));
+        let builder = StoryBuilder::new(&self.title, description, SupportedRender::Markdown);
+        let story: Story = api.post("/stories", access_token, None, &builder).await?;
+        // created before the story has fragments, so the moments are added in event order.
+        let timeline = TimelineBuilder::new(&self.title, None, story.id, "stories")
+            .build(api, access_token)
+            .await?;
+
+        let existing: Option<Vec<LocationRender>> =
+            api.get_protected("/locations/", access_token, None).await?;
+        let mut locations: Vec<(Uuid, Coordinates)> = existing
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|location| Some((location.id, location.coordinates()?)))
+            .collect();
+        let mut linked = HashSet::new();
+        let tags = Vec::new();
+        let mut report = PhotoReport {
+            story: story.id,
+            timeline: timeline.id(),
+            imported: 0,
+            failed: Vec::new(),
+        };
+
+        for (idx, event) in events.iter().enumerate() {
+            let name = event.name();
+            let result: Result<(), ApiError> = async {
+                let content = event.content();
+                let fragment = FragmentBuilder {
+                    id: None,
+                    parent: Some(story.id),
+                    category: Some("stories"),
+                    name: &name,
+                    content: &content,
+                    tags: &tags,
+                    idx: Some(idx as i32),
+                };
+                let fragment: StoryFragment = fragment.build(api, access_token).await?;
+
+                let mut entries = Vec::with_capacity(event.photos.len());
+                for photo in event.photos.iter() {
+                    let bytes = source.read(photo).await?;
+                    entries.push(processor.store(&bytes).await?);
+                }
+                let images = ImageBuilder::new(
+                    entries,
+                    None,
+                    Vec::new(),
+                    "fragments".to_string(),
+                    Some(fragment.id),
+                );
+                images.build(api, access_token).await?;
+
+                if let Some(position) = event.position() {
+                    let location = match nearest(&locations, position, self.radius) {
+                        Some(location) => location,
+                        None => {
+                            let place = event.place().map(str::to_string).unwrap_or_else(|| {
+                                format!("{:.4}, {:.4}", position.latitude, position.longitude)
+                            });
+                            let description = Some(format!("where {} took place", name));
+                            let location: Location =
+                                LocationBuilder::at(&place, description, position)
+                                    .build(api, access_token)
+                                    .await?;
+                            locations.push((location.id, position));
+                            location.id
+                        }
+                    };
+                    let mut relations = vec![(fragment.id, "fragments")];
+                    if linked.insert(location) {
+                        relations.push((story.id, "stories"));
+                    }
+                    for (parent, category) in relations {
+                        let relation = Relation {
+                            parent,
+                            child: location,
+                            parent_category: category.to_string(),
+                            child_category: "locations".to_string(),
+                            description: None,
+                            relation_type: None,
+                        };
+                        let _: Relation = api
+                            .post("/relations/", access_token, None, relation)
+                            .await?;
+                    }
+                }
+
+                let moment = MomentBuilder {
+                    timeline: timeline.id(),
+                    fragment: fragment.id,
+                    branch: None,
+                    position: None,
+                };
+                let moment = moment.build(api, access_token).await?;
+                if let (Some(start), Some(end)) = (event.start(), event.end()) {
+                    let (day, until) = (start.div_euclid(DAY), end.div_euclid(DAY));
+                    let date = MomentDate {
+                        day: Some(day),
+                        calendar: None,
+                        until: (until > day).then_some(until),
+                        duration: None,
+                        lane: None,
+                    };
+                    let url = format!("/timelines/moments/{}", moment);
+                    let _: serde_json::Value = api.put(&url, access_token, &date).await?;
+                }
+                Ok(())
+            }
+            .await;
+            match result {
+                Ok(()) => report.imported += 1,
+                Err(err) => report.failed.push(FailedEvent {
+                    name,
+                    error: err.to_string(),
+                }),
+            }
+        }
+        Ok(report)
+    }
+}